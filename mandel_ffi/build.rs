@@ -0,0 +1,17 @@
+// build.rs
+//
+// Generate the C header include/mandel.h from the extern "C" functions in src/lib.rs
+
+extern crate cbindgen;
+
+use std::env;
+
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    cbindgen::generate(&crate_dir).expect("Unable to generate the C header")
+        .write_to_file("include/mandel.h");
+}
@@ -0,0 +1,40 @@
+#ifndef MANDEL_H
+#define MANDEL_H
+
+/* Generated by cbindgen from mandel_ffi/src/lib.rs, don't edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define MANDEL_OK 0
+
+#define MANDEL_ERROR_NULL_POINTER -1
+
+#define MANDEL_ERROR_INVALID_PARAMS -2
+
+#define MANDEL_ERROR_BUFFER_SIZE -3
+
+#define MANDEL_ERROR_THREAD_POOL -4
+
+#define MANDEL_ERROR_PANIC -5
+
+typedef struct CMandelParams {
+  double re1;
+  double re2;
+  double img1;
+  double img2;
+  double z0_re;
+  double z0_im;
+  uint32_t max_iter;
+  uint32_t img_size;
+  uint32_t num_threads;
+  uint32_t flip_y;
+} CMandelParams;
+
+int32_t mandel_render(const struct CMandelParams *params, uint32_t *out, uintptr_t len);
+
+const char *mandel_version(void);
+
+#endif  /* MANDEL_H */
@@ -0,0 +1,126 @@
+// C interface for mandel-rust, so that the renderer can be used from other languages
+// (for example from Python via ctypes).
+//
+// The C header is generated by build.rs: include/mandel.h
+//
+// Example (Python):
+//
+//     lib = ctypes.CDLL("target/release/libmandel_ffi.so")
+//     image = (ctypes.c_uint32 * (1024 * 1024))()
+//     lib.mandel_render(ctypes.byref(params), image, len(image))
+//
+// Written by Willi Kappler, grandor@gmx.de
+//
+// License: MIT
+
+// External crates
+extern crate rayon;
+
+// Internal crates
+extern crate mandel_util;
+extern crate mandel_method;
+
+// Rust modules
+use std::os::raw::c_char;
+use std::panic;
+use std::slice;
+use std::sync::atomic::AtomicBool;
+
+// Internal modules
+use mandel_util::MandelConfig;
+use mandel_method::rayon_join;
+
+// Return values of mandel_render
+pub const MANDEL_OK: i32 = 0;
+// params or out is a null pointer
+pub const MANDEL_ERROR_NULL_POINTER: i32 = -1;
+// The parameters are not valid, for example img_size is not a power of two
+pub const MANDEL_ERROR_INVALID_PARAMS: i32 = -2;
+// len is not img_size * img_size
+pub const MANDEL_ERROR_BUFFER_SIZE: i32 = -3;
+// The thread pool could not be created
+pub const MANDEL_ERROR_THREAD_POOL: i32 = -4;
+// Unexpected internal error (panic)
+pub const MANDEL_ERROR_PANIC: i32 = -5;
+
+// Parameters of one render, mirrors the relevant part of MandelConfig
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CMandelParams {
+    pub re1: f64,
+    pub re2: f64,
+    pub img1: f64,
+    pub img2: f64,
+    // Starting value of the iteration, 0 for the normal mandelbrot set
+    pub z0_re: f64,
+    pub z0_im: f64,
+    pub max_iter: u32,
+    // Width and height in pixel, must be a power of two
+    pub img_size: u32,
+    // 0 means use all cores
+    pub num_threads: u32,
+    // 1: row 0 is img1 instead of img2
+    pub flip_y: u32,
+}
+
+impl CMandelParams {
+    fn to_config(&self) -> MandelConfig {
+        MandelConfig {
+            re1: self.re1,
+            re2: self.re2,
+            img1: self.img1,
+            img2: self.img2,
+            z0_re: self.z0_re,
+            z0_im: self.z0_im,
+            max_iter: self.max_iter,
+            img_size: self.img_size,
+            num_threads: if self.num_threads == 0 { rayon::current_num_threads() as u32 } else { self.num_threads },
+            flip_y: self.flip_y != 0,
+            ..MandelConfig::default()
+        }
+    }
+}
+
+// Compute the iteration counts of the image into out (row by row, img_size * img_size values).
+// Returns MANDEL_OK or one of the MANDEL_ERROR_* codes.
+//
+// # Safety
+//
+// params must point to a valid CMandelParams and out to at least len writable u32 values.
+#[no_mangle]
+pub unsafe extern "C" fn mandel_render(params: *const CMandelParams, out: *mut u32, len: usize) -> i32 {
+    if params.is_null() || out.is_null() {
+        return MANDEL_ERROR_NULL_POINTER;
+    }
+
+    let config = (*params).to_config();
+
+    if config.validate().is_err() {
+        return MANDEL_ERROR_INVALID_PARAMS;
+    }
+
+    if (config.img_size as usize).checked_mul(config.img_size as usize) != Some(len) {
+        return MANDEL_ERROR_BUFFER_SIZE;
+    }
+
+    let image = slice::from_raw_parts_mut(out, len);
+
+    // A panic must not unwind into the caller
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        match rayon::ThreadPoolBuilder::from(&config).build() {
+            Ok(pool) => {
+                pool.install(|| rayon_join(&config, image, &AtomicBool::new(false)));
+                MANDEL_OK
+            }
+            Err(_) => MANDEL_ERROR_THREAD_POOL,
+        }
+    }));
+
+    result.unwrap_or(MANDEL_ERROR_PANIC)
+}
+
+// Version of the library, a static null terminated string
+#[no_mangle]
+pub extern "C" fn mandel_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
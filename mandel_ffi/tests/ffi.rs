@@ -0,0 +1,86 @@
+// Call the exported functions through the shared library, like a C program would
+extern crate libloading;
+extern crate mandel_ffi;
+extern crate mandel_method;
+extern crate mandel_util;
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+
+use libloading::{Library, Symbol};
+use mandel_ffi::{CMandelParams, MANDEL_ERROR_BUFFER_SIZE, MANDEL_ERROR_INVALID_PARAMS, MANDEL_ERROR_NULL_POINTER, MANDEL_OK};
+use mandel_util::MandelConfig;
+
+type RenderFn = unsafe extern "C" fn(*const CMandelParams, *mut u32, usize) -> i32;
+type VersionFn = unsafe extern "C" fn() -> *const c_char;
+
+// The cdylib is built into the same folder as this test executable (target/debug/deps)
+fn library() -> Library {
+    let mut path = PathBuf::from(std::env::current_exe().unwrap());
+    path.pop();
+    path.push(libloading::library_filename("mandel_ffi"));
+
+    unsafe { Library::new(&path) }.unwrap_or_else(|e| panic!("Can't load '{}': {}", path.display(), e))
+}
+
+fn params(img_size: u32, num_threads: u32) -> CMandelParams {
+    CMandelParams {
+        re1: -2.0,
+        re2: 1.0,
+        img1: -1.5,
+        img2: 1.5,
+        z0_re: 0.0,
+        z0_im: 0.0,
+        max_iter: 64,
+        img_size,
+        num_threads,
+        flip_y: 0,
+    }
+}
+
+#[test]
+fn render_through_shared_library() {
+    let library = library();
+    let render: Symbol<RenderFn> = unsafe { library.get(b"mandel_render") }.unwrap();
+    let version: Symbol<VersionFn> = unsafe { library.get(b"mandel_version") }.unwrap();
+
+    let version = unsafe { CStr::from_ptr(version()) }.to_str().unwrap();
+    assert_eq!(version, env!("CARGO_PKG_VERSION"));
+
+    let expected_config = MandelConfig {
+        img_size: 32,
+        max_iter: 64,
+        ..MandelConfig::default()
+    };
+    let mut expected = vec![0; 32 * 32];
+    mandel_method::serial(&expected_config, &mut expected, &AtomicBool::new(false));
+
+    for &num_threads in &[0, 1, 3] {
+        let mut image = vec![0; 32 * 32];
+        let result = unsafe { render(&params(32, num_threads), image.as_mut_ptr(), image.len()) };
+
+        assert_eq!(result, MANDEL_OK);
+        assert!(image == expected, "differs from serial with {} threads", num_threads);
+    }
+}
+
+#[test]
+fn error_codes() {
+    let library = library();
+    let render: Symbol<RenderFn> = unsafe { library.get(b"mandel_render") }.unwrap();
+
+    let mut image = vec![0; 32 * 32];
+
+    unsafe {
+        assert_eq!(render(std::ptr::null(), image.as_mut_ptr(), image.len()), MANDEL_ERROR_NULL_POINTER);
+        assert_eq!(render(&params(32, 1), std::ptr::null_mut(), image.len()), MANDEL_ERROR_NULL_POINTER);
+        assert_eq!(render(&params(32, 1), image.as_mut_ptr(), 31 * 32), MANDEL_ERROR_BUFFER_SIZE);
+        assert_eq!(render(&params(30, 1), image.as_mut_ptr(), 30 * 30), MANDEL_ERROR_INVALID_PARAMS);
+
+        let mut swapped = params(32, 1);
+        swapped.re1 = 2.0;
+        assert_eq!(render(&swapped, image.as_mut_ptr(), image.len()), MANDEL_ERROR_INVALID_PARAMS);
+    }
+}
@@ -0,0 +1,215 @@
+// Pin worker threads to CPU cores (--pin_threads, --cpu_affinity).
+//
+// The thread pools don't tell us which worker runs a job, so each worker pins itself
+// when it executes its first job: the first worker gets core 0, the second worker core 1, ...
+// With --cpu_affinity=0,2,4,6 the first worker gets core 0, the second core 2, ...
+// If there are more threads than cores the cores are used again (wrap around).
+//
+// The pinning ends with the method: when the ThreadPinner is dropped the threads that outlive the method get
+// their affinity from before back (on Linux, elsewhere they stay pinned). These are the calling thread, which is
+// also a worker of job_steal, and the threads of the rayon pool. The threads of the other pools end with the method.
+
+// External modules
+use core_affinity;
+
+// Rust modules
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Internal modules
+use mandel_util::MandelConfig;
+
+// Each pinner gets a unique id, so a thread that is reused by the next method gets pinned again.
+static NEXT_PINNER_ID: AtomicUsize = AtomicUsize::new(1);
+
+thread_local!(static PINNED_BY: Cell<usize> = Cell::new(0));
+// The affinity of the thread before it was pinned
+thread_local!(static ORIGINAL_AFFINITY: Cell<Option<Affinity>> = Cell::new(None));
+
+#[cfg(target_os = "linux")]
+type Affinity = libc::cpu_set_t;
+#[cfg(not(target_os = "linux"))]
+type Affinity = ();
+
+// The cores the current thread may run on
+#[cfg(target_os = "linux")]
+fn current_affinity() -> Option<Affinity> {
+    unsafe {
+        let mut mask: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut mask) == 0 {
+            Some(mask)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn current_affinity() -> Option<Affinity> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn set_affinity(mask: &Affinity) {
+    unsafe {
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), mask);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_affinity(_mask: &Affinity) {
+}
+
+// Give the current thread its affinity back if it was pinned by the pinner with this id
+fn unpin_current_thread(id: usize) {
+    PINNED_BY.with(|pinned_by| {
+        if pinned_by.get() == id {
+            if let Some(original) = ORIGINAL_AFFINITY.with(|original| original.take()) {
+                set_affinity(&original);
+            }
+            pinned_by.set(0);
+        }
+    });
+}
+
+pub struct ThreadPinner {
+    id: usize,
+    next_worker: AtomicUsize,
+    core_ids: Vec<core_affinity::CoreId>
+}
+
+impl ThreadPinner {
+    // Returns None if pinning is disabled or the core ids are not available on this platform
+    pub fn new(mandel_config: &MandelConfig) -> Option<ThreadPinner> {
+        if !mandel_config.pins_threads() {
+            return None;
+        }
+
+        match core_affinity::get_core_ids() {
+            Some(available) => {
+                let core_ids = if mandel_config.cpu_affinity.is_empty() {
+                    available
+                } else {
+                    for id in mandel_config.cpu_affinity.iter().filter(|&&id| !available.iter().any(|core| core.id == id)) {
+                        println!("Warning: core {} from --cpu_affinity is not available, pinning to it may fail", id);
+                    }
+
+                    mandel_config.cpu_affinity.iter().map(|&id| core_affinity::CoreId{id: id}).collect()
+                };
+
+                if core_ids.is_empty() {
+                    return None;
+                }
+
+                if (mandel_config.num_threads as usize) > core_ids.len() {
+                    println!("Warning: more threads ({}) than cores ({}), some threads share a core",
+                        mandel_config.num_threads, core_ids.len());
+                }
+
+                Some(ThreadPinner{
+                    id: NEXT_PINNER_ID.fetch_add(1, Ordering::SeqCst),
+                    next_worker: AtomicUsize::new(0),
+                    core_ids: core_ids
+                })
+            },
+            None => {
+                println!("Warning: can't get core ids, threads are not pinned");
+                None
+            }
+        }
+    }
+
+    // Pin the current thread to the next free core, does nothing if the thread is already pinned
+    pub fn pin_current_thread(&self) {
+        PINNED_BY.with(|pinned_by| {
+            if pinned_by.get() != self.id {
+                // A thread that is still pinned by another pinner keeps its original affinity from before
+                if pinned_by.get() == 0 {
+                    ORIGINAL_AFFINITY.with(|original| original.set(current_affinity()));
+                }
+                let worker = self.next_worker.fetch_add(1, Ordering::SeqCst);
+                core_affinity::set_for_current(self.core_ids[worker % self.core_ids.len()]);
+                pinned_by.set(self.id);
+            }
+        });
+    }
+}
+
+impl Drop for ThreadPinner {
+    fn drop(&mut self) {
+        let id = self.id;
+        unpin_current_thread(id);
+        // The current pool, the one of the method (the global pool or the one of pool.install)
+        rayon::broadcast(move |_| unpin_current_thread(id));
+    }
+}
+
+// Pin the current thread if pinning is enabled
+#[inline]
+pub fn pin(pinner: &Option<ThreadPinner>) {
+    if let Some(ref pinner) = *pinner {
+        pinner.pin_current_thread();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_affinity_selects_cores() {
+        assert!(ThreadPinner::new(&MandelConfig::default()).is_none());
+
+        let config = MandelConfig{cpu_affinity: vec![0, 0], .. MandelConfig::default()};
+
+        if let Some(pinner) = ThreadPinner::new(&config) {
+            assert_eq!(pinner.core_ids.iter().map(|core| core.id).collect::<Vec<_>>(), vec![0, 0]);
+        }
+    }
+
+    // The current thread is not pinned anymore and has the affinity original
+    #[cfg(target_os = "linux")]
+    fn is_unpinned(original: &Affinity) -> bool {
+        PINNED_BY.with(|pinned_by| pinned_by.get()) == 0 && unsafe { libc::CPU_EQUAL(&current_affinity().unwrap(), original) }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn pinned_thread_is_unpinned() {
+        let original = current_affinity().unwrap();
+        let core = core_affinity::get_core_ids().unwrap()[0].id;
+        let config = MandelConfig{cpu_affinity: vec![core], .. MandelConfig::default()};
+
+        {
+            let pinner = ThreadPinner::new(&config).unwrap();
+            pinner.pin_current_thread();
+
+            let pinned = current_affinity().unwrap();
+            assert!(unsafe { libc::CPU_COUNT(&pinned) == 1 && libc::CPU_ISSET(core, &pinned) });
+        }
+
+        assert!(is_unpinned(&original));
+    }
+
+    // Neither the calling thread nor the threads of the rayon pool stay pinned after a method
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn methods_unpin_their_threads() {
+        use std::sync::atomic::AtomicBool;
+        use crate::registry::{get_method, method_names};
+
+        let original = current_affinity().unwrap();
+        let core = core_affinity::get_core_ids().unwrap()[0].id;
+        let config = MandelConfig{img_size: 16, max_iter: 64, num_threads: 3, cpu_affinity: vec![core],
+            .. MandelConfig::default()};
+
+        for name in method_names() {
+            let mut image = vec![0; config.num_of_pixels()];
+            get_method(name).unwrap()(&config, &mut image, &AtomicBool::new(false));
+
+            assert!(is_unpinned(&original), "method '{}'", name);
+            assert!(rayon::broadcast(|_| is_unpinned(&original)).iter().all(|&equal| equal),
+                "method '{}'", name);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+// Pick the threading backend at run time (--thread_model) instead of calling a method directly.
+//
+// Every parallel method belongs to exactly one thread model. render uses the first method of the
+// model in config.thread_model, the main program runs serial and all methods of the model.
+// A model without methods is not available in this build (tokio without the feature, os on wasm32).
+
+// Rust modules
+use std::sync::atomic::AtomicBool;
+
+// Internal modules
+use mandel_util::{mirror_rows, to_rows, MandelConfig, ThreadModel};
+use crate::registry::get_method;
+
+// Methods of each thread model, the first one is used by render
+static OS_METHOD_NAMES: &'static [&'static str] = &[
+    #[cfg(not(target_arch = "wasm32"))]
+    "scoped_thread_pool",
+    #[cfg(not(target_arch = "wasm32"))]
+    "rust_scoped_pool",
+    #[cfg(not(target_arch = "wasm32"))]
+    "job_steal",
+    #[cfg(not(target_arch = "wasm32"))]
+    "job_steal_join",
+    #[cfg(not(target_arch = "wasm32"))]
+    "kirk_crossbeam",
+    #[cfg(not(target_arch = "wasm32"))]
+    "crossbeam_pipeline",
+];
+
+static RAYON_METHOD_NAMES: &'static [&'static str] = &[
+    "rayon_join",
+    #[cfg(not(target_arch = "wasm32"))]
+    "rayon_join_dynamic",
+    "rayon_par_iter",
+    "rayon_par_iter_collect",
+    "rayon_par_iter_no_div",
+    "rayon_par_bridge",
+    "rayon_adaptive_weighted",
+    "rayon_perturbation",
+    #[cfg(feature = "portable_simd")]
+    "rayon_portable_simd",
+];
+
+static TOKIO_METHOD_NAMES: &'static [&'static str] = &[
+    #[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+    "tokio_spawn_blocking",
+    #[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+    "tokio_spawn",
+];
+
+// Returns the names of the methods of the thread model, empty if the model is not available
+pub fn method_names(thread_model: ThreadModel) -> &'static [&'static str] {
+    match thread_model {
+        ThreadModel::Os => OS_METHOD_NAMES,
+        ThreadModel::Rayon => RAYON_METHOD_NAMES,
+        ThreadModel::Tokio => TOKIO_METHOD_NAMES
+    }
+}
+
+// Returns true if the thread model has at least one method in this build
+pub fn is_available(thread_model: ThreadModel) -> bool {
+    !method_names(thread_model).is_empty()
+}
+
+// Returns the thread model of a method, None for serial and null_method
+pub fn thread_model_of(name: &str) -> Option<ThreadModel> {
+    [ThreadModel::Os, ThreadModel::Rayon, ThreadModel::Tokio].iter().cloned()
+        .find(|&thread_model| method_names(thread_model).contains(&name))
+}
+
+// Compute the image with the first method of config.thread_model (rayon if none is set), row by row.
+// Panics if the thread model is not available.
+pub fn render(mandel_config: &MandelConfig, image: &mut [u32]) {
+    let thread_model = mandel_config.thread_model.unwrap_or(ThreadModel::Rayon);
+    let name = method_names(thread_model).first()
+        .unwrap_or_else(|| panic!("Thread model '{}' is not available in this build", thread_model));
+
+    get_method(name).unwrap()(mandel_config, image, &AtomicBool::new(false));
+    mirror_rows(mandel_config, image);
+    to_rows(mandel_config, image);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::{is_parallel, method_names as all_method_names};
+    use crate::serial;
+
+    #[test]
+    fn every_parallel_method_has_one_thread_model() {
+        for name in all_method_names().iter().filter(|&&name| is_parallel(name) && name != "null_method") {
+            assert!(thread_model_of(name).is_some(), "method '{}'", name);
+        }
+
+        for &thread_model in &[ThreadModel::Os, ThreadModel::Rayon, ThreadModel::Tokio] {
+            assert_eq!(thread_model.to_string().parse::<ThreadModel>(), Ok(thread_model));
+
+            for name in method_names(thread_model) {
+                assert!(get_method(name).is_some(), "method '{}'", name);
+            }
+        }
+
+        assert_eq!(thread_model_of("serial"), None);
+        assert!("fibers".parse::<ThreadModel>().is_err());
+    }
+
+    #[test]
+    fn render_matches_serial() {
+        let default = MandelConfig::default();
+        let config = MandelConfig{
+            img_size: 16,
+            max_iter: 64,
+            .. default
+        };
+
+        let mut expected = vec![0; 16 * 16];
+        serial(&config, &mut expected, &AtomicBool::new(false));
+
+        for &thread_model in &[ThreadModel::Os, ThreadModel::Rayon, ThreadModel::Tokio] {
+            if !is_available(thread_model) {
+                continue;
+            }
+
+            let mut image = vec![0; 16 * 16];
+            render(&MandelConfig{thread_model: Some(thread_model), .. config.clone()}, &mut image);
+            assert!(image == expected, "thread model: {}", thread_model);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+// First touch initialization of the image buffer (--numa_init).
+//
+// On NUMA systems a memory page is placed on the node of the thread that writes it first.
+// vec![0; n] doesn't write the buffer: alloc_zeroed gets lazily zeroed pages from the OS for a large buffer,
+// so the first method that writes the image places all of its pages. By default that is serial on the main thread,
+// and all methods after it write to memory of one node.
+// With --numa_init every method gets a new buffer and runs once on it before it is measured: each page is placed by
+// the worker that renders its rows, with the chunks of the method itself. The dynamic methods (work stealing) can give
+// a row to another worker in the measured runs, then only most of the rows are local.
+// On a single socket machine this makes no difference.
+
+// Rust modules
+use std::sync::atomic::AtomicBool;
+use std::time::Instant;
+
+// Internal modules
+use mandel_util::MandelConfig;
+use crate::registry::MandelFunc;
+
+// Replace the image with a new lazily zeroed buffer and render it once with the method, so that its workers write
+// each page first. The old buffer is freed before, so its pages can be reused. Returns the time of the run in ms.
+pub fn first_touch(mandel_func: MandelFunc, mandel_config: &MandelConfig, image: &mut Vec<u32>) -> f64 {
+    *image = Vec::new();
+    *image = vec![0; mandel_config.num_of_pixels()];
+
+    let start_time = Instant::now();
+    mandel_func(mandel_config, image, &AtomicBool::new(false));
+    start_time.elapsed().as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use mandel_util::MandelConfig;
+    use crate::registry::get_method;
+    use super::first_touch;
+
+    #[test]
+    fn first_touch_renders_a_new_buffer() {
+        let config = MandelConfig{img_size: 16, max_iter: 64, num_threads: 4, .. MandelConfig::default()};
+        let mut expected = vec![0; config.num_of_pixels()];
+        crate::serial(&config, &mut expected, &AtomicBool::new(false));
+
+        for name in &["scoped_thread_pool", "rayon_par_iter", "job_steal", "crossbeam_pipeline"] {
+            let mut image = vec![7; 3];
+            assert!(first_touch(get_method(name).unwrap(), &config, &mut image) >= 0.0);
+            assert!(image == expected, "{}", name);
+        }
+    }
+}
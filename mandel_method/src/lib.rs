@@ -1,182 +1,1127 @@
+// std::simd is nightly only
+#![cfg_attr(feature = "portable_simd", feature(portable_simd))]
+
 // External crates
-extern crate num;
+// The thread pool crates need OS threads, on wasm32 only serial and the rayon methods are available
+#[cfg(not(target_arch = "wasm32"))]
 extern crate scoped_threadpool;
 extern crate rayon;
+#[cfg(not(target_arch = "wasm32"))]
 extern crate scoped_pool;
+#[cfg(not(target_arch = "wasm32"))]
 extern crate jobsteal;
-//extern crate kirk;
+#[cfg(not(target_arch = "wasm32"))]
 extern crate crossbeam;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate crossbeam_channel;
+#[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+extern crate tokio;
+extern crate core_affinity;
+extern crate num_complex;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
 
 // Internal crates
 extern crate mandel_util;
 
 // External modules
-use num::complex::Complex64;
-use rayon::par_iter::*;
-//use kirk::crew::deque::Options;
+use rayon::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use crossbeam::sync::chase_lev::{self, Steal};
 
 // Internal modules
-use mandel_util::{mandel_iter, MandelConfig};
+use mandel_util::{complexity_map, compute_ref_orbit, mandel_iter_perturbation, pixel_to_complex, render_line, render_pixel,
+    AccessOrder, MandelConfig, Workload};
+use mandel_util::denormal::FlushDenormals;
+use num_complex::Complex64;
+
+// Look up methods by name
+pub mod registry;
+// Pick the methods by threading backend (--thread_model)
+pub mod dispatch;
+// Pin worker threads to cores
+pub mod affinity;
+// Record and replay the order of the jobs of the dynamic methods
+#[cfg(not(target_arch = "wasm32"))]
+pub mod schedule;
+// NUMA friendly initialization of the image buffer
+#[cfg(not(target_arch = "wasm32"))]
+pub mod first_touch;
+// Streaming access to the pixels without an image buffer
+pub mod pixels;
+// Rendering of a rectangle of the image
+pub mod rect;
+// Progressive rendering within a time limit (std::time::Instant is not available on wasm32)
+#[cfg(not(target_arch = "wasm32"))]
+pub mod progressive;
+// JavaScript interface
+#[cfg(feature = "wasm")]
+pub mod wasm;
+// Portable SIMD version (nightly)
+#[cfg(feature = "portable_simd")]
+pub mod portable_simd;
+
+#[cfg(feature = "portable_simd")]
+pub use portable_simd::rayon_portable_simd;
+
+use crate::affinity::{ThreadPinner, pin};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::schedule::{record, write_trace, ScheduleRecorder};
+
+// Rust modules
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Mutex;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicU64, AtomicUsize};
+#[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::BTreeMap;
+
+// All methods take a cancel flag, it is set when the method ran longer than --method_timeout_ms.
+// The methods check it before each row (rayon_par_iter before each pixel) and return early,
+// the image is incomplete then.
+fn cancelled(cancel: &AtomicBool) -> bool {
+    cancel.load(Ordering::Relaxed)
+}
+
+// The methods write the image buffer in the layout of --layout (see mandel_util::BufferLayout).
+// The buffer is split into lines of img_size values, a line is a row of the image with --layout=rows
+// and one row of img_size / 64 tiles with --layout=tiles64 (see mandel_util::render_line).
+// A "row" of the jobs below is a line.
+
+// Number of values in one job: --chunk_rows lines of the image buffer
+fn chunk_len(mandel_config: &MandelConfig) -> usize {
+    (mandel_config.chunk_rows.max(1) as usize) * (mandel_config.img_size as usize)
+}
+
+// Compute a chunk of whole lines, first_row is the line of the first value.
+// Rows that are mirrored with use_symmetry are skipped, see mandel_util::mirror_rows.
+// This is one job of a worker, it has FTZ and DAZ with --detect_denormals.
+fn compute_chunk(mandel_config: &MandelConfig, chunk: &mut [u32], first_row: u32, cancel: &AtomicBool) {
+    let _denormals = FlushDenormals::new(mandel_config);
+
+    for (n, line) in chunk.chunks_mut(mandel_config.img_size as usize).enumerate() {
+        if cancelled(cancel) {
+            return;
+        }
+        render_line(mandel_config, first_row + (n as u32), line);
+    }
+}
+
+// --normalize_access=rows: every method computes whole rows in their natural order
+fn rows_only(mandel_config: &MandelConfig) -> bool {
+    mandel_config.normalize_access == AccessOrder::Rows
+}
+
+// The rows of the image for the jobs of the pools with --normalize_access=rows: each job takes the next row,
+// so the rows are computed from top to bottom whatever order the pool runs its jobs in (LIFO for jobsteal).
+#[cfg(not(target_arch = "wasm32"))]
+struct RowQueue<'a> {
+    rows: Mutex<std::iter::Enumerate<std::slice::ChunksMut<'a, u32>>>
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<'a> RowQueue<'a> {
+    fn new(mandel_config: &MandelConfig, image: &'a mut [u32]) -> RowQueue<'a> {
+        RowQueue{rows: Mutex::new(image.chunks_mut(mandel_config.img_size as usize).enumerate())}
+    }
+
+    // The next row and its index, None when all rows are taken
+    fn next(&self) -> Option<(u32, &'a mut [u32])> {
+        self.rows.lock().expect("Row queue poisoned").next().map(|(y, row)| (y as u32, row))
+    }
+
+    // Take the next row and compute it, the row is recorded with --trace_schedule
+    fn compute_next(&self, mandel_config: &MandelConfig, recorder: &Option<ScheduleRecorder>, cancel: &AtomicBool) {
+        if let Some((y, row)) = self.next() {
+            record(recorder, y, 1);
+            compute_chunk(mandel_config, row, y, cancel);
+        }
+    }
+}
+
+// compute_chunk as one job of a dynamic method, the job is recorded with --trace_schedule
+#[cfg(not(target_arch = "wasm32"))]
+fn compute_job(mandel_config: &MandelConfig, recorder: &Option<ScheduleRecorder>, chunk: &mut [u32], first_row: u32, cancel: &AtomicBool) {
+    record(recorder, first_row, (chunk.len() / (mandel_config.img_size as usize)) as u32);
+    compute_chunk(mandel_config, chunk, first_row, cancel);
+}
+
+// The rayon_par_iter methods with --normalize_access=rows: an indexed parallel iterator over the rows
+// instead of the pixels, one task per row.
+fn rayon_par_rows(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
+    let pinner = ThreadPinner::new(mandel_config);
+
+    image.par_chunks_mut(mandel_config.img_size as usize).enumerate().for_each(|(y, row)| {
+        pin(&pinner);
+        compute_chunk(mandel_config, row, y as u32, cancel);
+    });
+}
 
 // The serial version of the mandelbrot set calculation.
-pub fn serial(mandel_config: &MandelConfig, image: &mut [u32]) {
+pub fn serial(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
+    let img_size = mandel_config.img_size as usize;
+    let _denormals = FlushDenormals::new(mandel_config);
+
     for y in 0..mandel_config.img_size {
-        for x in 0..mandel_config.img_size {
-            image[((y * mandel_config.img_size) + x) as usize] =
-                mandel_iter(mandel_config.max_iter,
-                    Complex64{re: mandel_config.re1 + ((x as f64) * mandel_config.x_step),
-                              im: mandel_config.img1 + ((y as f64) * mandel_config.y_step)}
-                );
+        if cancelled(cancel) {
+            return;
         }
+        let start = (y as usize) * img_size;
+        render_line(mandel_config, y, &mut image[start..(start + img_size)]);
     }
 }
 
 // The parallel version of the mandelbrot set calculation, uses scoped_threadpool.
-pub fn scoped_thread_pool_(mandel_config: &MandelConfig, image: &mut [u32]) {
+#[cfg(not(target_arch = "wasm32"))]
+pub fn scoped_thread_pool_(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
     let mut pool = scoped_threadpool::Pool::new(mandel_config.num_threads);
+    let pinner = &ThreadPinner::new(mandel_config);
 
     pool.scoped(|scope| {
-        for (y, slice) in image.chunks_mut(mandel_config.img_size as usize).enumerate() {
+        for (n, chunk) in image.chunks_mut(chunk_len(mandel_config)).enumerate() {
             scope.execute(move || {
-                for x in 0..mandel_config.img_size {
-                    slice[x as usize] =
-                    mandel_iter(mandel_config.max_iter,
-                        Complex64{re: mandel_config.re1 + ((x as f64) * mandel_config.x_step),
-                                  im: mandel_config.img1 + ((y as f64) * mandel_config.y_step)}
-                    );
-                }
+                pin(pinner);
+                compute_chunk(mandel_config, chunk, (n as u32) * mandel_config.chunk_rows, cancel);
             });
         }
     });
 }
 
 // The parallel version of the mandelbrot set calculation, uses rayon join.
-pub fn rayon_join(mandel_config: &MandelConfig, image: &mut [u32]) {
-    rayon_helper(mandel_config, image, 0);
+pub fn rayon_join(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
+    rayon_helper(mandel_config, &ThreadPinner::new(mandel_config), image, 0, cancel);
 }
 
 // Rayon helper function for recursive divide-and-conquer call
-fn rayon_helper(mandel_config: &MandelConfig, slice: &mut [u32], y: u32) {
-    if slice.len() == (mandel_config.img_size as usize) { // just process one scanline of the mandelbrot image
-        for x in 0..mandel_config.img_size {
-            slice[x as usize] =
-            mandel_iter(mandel_config.max_iter,
-                Complex64{re: mandel_config.re1 + ((x as f64) * mandel_config.x_step),
-                          im: mandel_config.img1 + ((y as f64) * mandel_config.y_step)}
-            );
+fn rayon_helper(mandel_config: &MandelConfig, pinner: &Option<ThreadPinner>, slice: &mut [u32], y: u32, cancel: &AtomicBool) {
+    if slice.len() <= chunk_len(mandel_config) { // just process chunk_rows scanlines of the mandelbrot image
+        pin(pinner);
+        compute_chunk(mandel_config, slice, y, cancel);
+    } else {
+        let mid = slice.len() / 2;
+        let (top, bottom) = slice.split_at_mut(mid);
+        rayon::join(
+            || rayon_helper(mandel_config, pinner, top, y, cancel),
+            || rayon_helper(mandel_config, pinner, bottom, y + ((mid / (mandel_config.img_size as usize)) as u32), cancel)
+        );
+    }
+}
+
+// Leaves of rayon_join_dynamic: the imbalance (slowest / fastest leaf) above which the leaves get smaller
+// and below which they get larger
+#[cfg(not(target_arch = "wasm32"))]
+const DYNAMIC_IMBALANCE: u64 = 4;
+#[cfg(not(target_arch = "wasm32"))]
+const DYNAMIC_BALANCE: u64 = 2;
+
+// The split threshold of rayon_join_dynamic and the leaf times it is adjusted with, shared by all leaves of one call
+#[cfg(not(target_arch = "wasm32"))]
+struct DynamicSplit {
+    // Slices with at most this many pixels are computed as one leaf
+    threshold: AtomicUsize,
+    // One row .. one leaf per thread
+    min_threshold: usize,
+    max_threshold: usize,
+    // The threshold is adjusted after every window leaves (one per thread)
+    window: usize,
+    num_of_leaves: AtomicUsize,
+    // Fastest and slowest leaf of the current window in ns
+    fastest_ns: AtomicU64,
+    slowest_ns: AtomicU64
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DynamicSplit {
+    // Start with leaves of --chunk_rows rows like rayon_join
+    fn new(mandel_config: &MandelConfig, num_of_pixels: usize) -> DynamicSplit {
+        let min_threshold = mandel_config.img_size as usize;
+        let max_threshold = (num_of_pixels / (mandel_config.num_threads.max(1) as usize)).max(min_threshold);
+
+        DynamicSplit{
+            threshold: AtomicUsize::new(chunk_len(mandel_config).max(min_threshold).min(max_threshold)),
+            min_threshold: min_threshold,
+            max_threshold: max_threshold,
+            window: mandel_config.num_threads.max(1) as usize,
+            num_of_leaves: AtomicUsize::new(0),
+            fastest_ns: AtomicU64::new(u64::MAX),
+            slowest_ns: AtomicU64::new(0)
+        }
+    }
+
+    fn threshold(&self) -> usize {
+        self.threshold.load(Ordering::Relaxed)
+    }
+
+    // Record the time of a leaf. The last leaf of a window compares the slowest with the fastest leaf:
+    // imbalanced leaves are split finer (half the threshold), balanced ones coarser (twice the threshold)
+    // to save the overhead of the tasks. Then the next window starts.
+    fn record(&self, leaf_ns: u64) {
+        self.fastest_ns.fetch_min(leaf_ns, Ordering::Relaxed);
+        self.slowest_ns.fetch_max(leaf_ns, Ordering::Relaxed);
+
+        if (self.num_of_leaves.fetch_add(1, Ordering::Relaxed) + 1) % self.window != 0 {
+            return;
         }
+
+        let fastest_ns = self.fastest_ns.swap(u64::MAX, Ordering::Relaxed).max(1);
+        let slowest_ns = self.slowest_ns.swap(0, Ordering::Relaxed);
+        let threshold = self.threshold();
+
+        if slowest_ns > DYNAMIC_IMBALANCE.saturating_mul(fastest_ns) {
+            self.threshold.store((threshold / 2).max(self.min_threshold), Ordering::Relaxed);
+        } else if slowest_ns < DYNAMIC_BALANCE.saturating_mul(fastest_ns) {
+            self.threshold.store((threshold * 2).min(self.max_threshold), Ordering::Relaxed);
+        }
+    }
+}
+
+// Like rayon_join, but the slices are not always split down to --chunk_rows rows: each leaf measures its time and
+// the split threshold follows the imbalance of the leaves (closed loop, see DynamicSplit::record).
+// Uniform regions end up with few large leaves, regions with a very different work per row with small ones.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn rayon_join_dynamic(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
+    let split = DynamicSplit::new(mandel_config, image.len());
+    rayon_dynamic_helper(mandel_config, &ThreadPinner::new(mandel_config), &split, image, 0, cancel);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn rayon_dynamic_helper(mandel_config: &MandelConfig, pinner: &Option<ThreadPinner>, split: &DynamicSplit, slice: &mut [u32],
+    y: u32, cancel: &AtomicBool) {
+    // At least one row, the slices are split at row boundaries
+    if slice.len() <= split.threshold() || slice.len() <= (mandel_config.img_size as usize) {
+        pin(pinner);
+        let start = Instant::now();
+        compute_chunk(mandel_config, slice, y, cancel);
+        split.record(start.elapsed().as_nanos() as u64);
     } else {
         let mid = slice.len() / 2;
         let (top, bottom) = slice.split_at_mut(mid);
         rayon::join(
-            || rayon_helper(mandel_config, top, y),
-            || rayon_helper(mandel_config, bottom, y + ((mid as u32) / mandel_config.img_size))
+            || rayon_dynamic_helper(mandel_config, pinner, split, top, y, cancel),
+            || rayon_dynamic_helper(mandel_config, pinner, split, bottom, y + ((mid / (mandel_config.img_size as usize)) as u32), cancel)
         );
     }
 }
 
 // The parallel version of the mandelbrot set calculation, uses rayon par_iter_mut.
-pub fn rayon_par_iter(mandel_config: &MandelConfig, image: &mut [u32]) {
-
-    image.par_iter_mut().enumerate().for_each(
-        |(n, pixel)| {
-            let y = (n as u32) / mandel_config.img_size;
-            let x = (n as u32) - (y * mandel_config.img_size);
-            *pixel = mandel_iter(mandel_config.max_iter,
-                        Complex64{re: mandel_config.re1 + ((x as f64) * mandel_config.x_step),
-                                  im: mandel_config.img1 + ((y as f64) * mandel_config.y_step)}
-            );
+// --rayon_min_len and --rayon_max_len limit the number of pixels per task.
+// The pixels are split at arbitrary positions, with --normalize_access=rows see rayon_par_rows.
+pub fn rayon_par_iter(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
+    if rows_only(mandel_config) {
+        return rayon_par_rows(mandel_config, image, cancel);
+    }
+
+    let pinner = ThreadPinner::new(mandel_config);
+
+    // One FlushDenormals for each part of the image that rayon gives to a worker
+    image.par_iter_mut().enumerate()
+        .with_min_len(mandel_config.rayon_min_len).with_max_len(mandel_config.rayon_max_len).for_each_init(
+        || FlushDenormals::new(mandel_config),
+        |_, (n, pixel)| {
+            pin(&pinner);
+            let (x, y) = mandel_config.buffer_pixel(n);
+            if mandel_config.mirror_source_row(y).is_none() && !cancelled(cancel) {
+                *pixel = render_pixel(mandel_config, x, y);
+            }
+        });
+}
+
+// Like rayon_par_iter, but the pixels are collected into a new Vec first and then copied into the image,
+// to measure the cost of the extra allocation and copy. Skipped pixels (mirrored rows, cancelled) keep their old value.
+pub fn rayon_par_iter_collect(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
+    let pinner = ThreadPinner::new(mandel_config);
+
+    if rows_only(mandel_config) {
+        // One Vec per row
+        let line_len = mandel_config.img_size as usize;
+        let old_image: &[u32] = image;
+        let rows: Vec<Vec<u32>> = old_image.par_chunks(line_len).enumerate().map(|(y, old_row)| {
+            pin(&pinner);
+            let mut row = old_row.to_vec();
+            compute_chunk(mandel_config, &mut row, y as u32, cancel);
+            row
+        }).collect();
+
+        for (chunk, row) in image.chunks_mut(line_len).zip(rows) {
+            chunk.copy_from_slice(&row);
+        }
+        return;
+    }
+
+    let old_image: &[u32] = image;
+    let result: Vec<u32> = (0..old_image.len()).into_par_iter().map_init(|| FlushDenormals::new(mandel_config), |_, n| {
+        pin(&pinner);
+        let (x, y) = mandel_config.buffer_pixel(n);
+        if mandel_config.mirror_source_row(y).is_none() && !cancelled(cancel) {
+            render_pixel(mandel_config, x, y)
+        } else {
+            old_image[n]
+        }
+    }).collect();
+
+    image.copy_from_slice(&result);
+}
+
+// Like rayon_par_iter, but without the division per pixel that recovers (x, y) from the pixel index:
+// the image is split into runs of consecutive pixels of one row first (the rows with --layout=rows)
+// and every run into pixels, both as nested parallel iterators. Only the start of each run is looked up.
+pub fn rayon_par_iter_no_div(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
+    if rows_only(mandel_config) {
+        return rayon_par_rows(mandel_config, image, cancel);
+    }
+
+    let pinner = ThreadPinner::new(mandel_config);
+    let run_len = mandel_config.run_len();
+
+    image.par_chunks_mut(run_len).enumerate()
+        .map(|(n, run)| (mandel_config.buffer_pixel(n * run_len), run))
+        .filter(|&((_, y), _)| mandel_config.mirror_source_row(y).is_none() && !cancelled(cancel))
+        .flat_map(|((x0, y), run)| run.par_iter_mut().enumerate().map(move |(x, pixel)| (x0 + (x as u32), y, pixel)))
+        .for_each_init(|| FlushDenormals::new(mandel_config), |_, (x, y, pixel)| {
+            pin(&pinner);
+            *pixel = render_pixel(mandel_config, x, y);
         });
 }
 
+// The parallel version of the mandelbrot set calculation, uses rayon par_bridge.
+// The serial iterator over the rows is handed to rayon as it is, rayon pulls the rows from it
+// and distributes them with work stealing.
+pub fn rayon_par_bridge(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
+    let pinner = ThreadPinner::new(mandel_config);
+
+    image.chunks_mut(chunk_len(mandel_config)).enumerate().par_bridge().for_each(
+        |(n, chunk)| {
+            pin(&pinner);
+            compute_chunk(mandel_config, chunk, (n as u32) * mandel_config.chunk_rows, cancel);
+        });
+}
+
+// Tile size of the pre-pass of rayon_adaptive_weighted
+const ADAPTIVE_COARSE_FACTOR: u32 = 8;
+
+// The parallel version of the mandelbrot set calculation, uses rayon scope_fifo.
+// A coarse pre-pass (mandel_util::complexity_map) estimates the work of each job of chunk_rows rows,
+// the jobs are spawned hardest first, so that no expensive job is left for the end (shorter tail).
+// The pre-pass is part of the measured time.
+pub fn rayon_adaptive_weighted(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
+    let pinner = &ThreadPinner::new(mandel_config);
+    let estimates = complexity_map(mandel_config, ADAPTIVE_COARSE_FACTOR);
+    let num_of_tiles = ((mandel_config.img_size + ADAPTIVE_COARSE_FACTOR - 1) / ADAPTIVE_COARSE_FACTOR) as usize;
+
+    let img_size = mandel_config.img_size as usize;
+    let run_len = mandel_config.run_len();
+
+    // Estimated work of one line of the image buffer: the tiles of the pre-pass that its runs cover
+    // (the whole tile row of an image row with --layout=rows)
+    let tiles_per_run = (run_len + (ADAPTIVE_COARSE_FACTOR as usize) - 1) / (ADAPTIVE_COARSE_FACTOR as usize);
+    let row_work = |line: u32| -> u64 {
+        (0..(img_size / run_len)).map(|n| {
+            let (x0, y) = mandel_config.buffer_pixel(((line as usize) * img_size) + (n * run_len));
+            let first = (((y / ADAPTIVE_COARSE_FACTOR) as usize) * num_of_tiles) + ((x0 / ADAPTIVE_COARSE_FACTOR) as usize);
+            estimates[first..(first + tiles_per_run)].iter().map(|&estimate| estimate as u64).sum::<u64>()
+        }).sum()
+    };
+
+    let mut jobs: Vec<(u64, u32, &mut [u32])> = image.chunks_mut(chunk_len(mandel_config)).enumerate()
+        .map(|(n, chunk)| {
+            let first_row = (n as u32) * mandel_config.chunk_rows;
+            let num_of_rows = (chunk.len() / (mandel_config.img_size as usize)) as u32;
+            ((first_row..(first_row + num_of_rows)).map(&row_work).sum(), first_row, chunk)
+        }).collect();
+
+    // Hardest first, stable for equal estimates
+    jobs.sort_by(|a, b| b.0.cmp(&a.0));
+
+    rayon::scope_fifo(|scope| {
+        for (_, first_row, chunk) in jobs {
+            scope.spawn_fifo(move |_| {
+                pin(pinner);
+                compute_chunk(mandel_config, chunk, first_row, cancel);
+            });
+        }
+    });
+}
+
+// The parallel version of the mandelbrot set calculation for deep zooms, uses rayon par_chunks_mut.
+// The reference orbit of the center pixel is computed once, every pixel only iterates the difference to it
+// (perturbation, see mandel_util::mandel_iter_perturbation). The pixel offsets to the center are exact,
+// so the precision isn't lost at high zoom levels like in pixel_to_complex.
+// The results can differ from serial in a few pixels close to the boundary (different rounding).
+// The synthetic workload and z0 != 0 are computed like in serial.
+pub fn rayon_perturbation(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
+    let pinner = ThreadPinner::new(mandel_config);
+
+    if mandel_config.workload != Workload::Fractal || mandel_config.has_z0() {
+        image.par_chunks_mut(chunk_len(mandel_config)).enumerate().for_each(|(n, chunk)| {
+            pin(&pinner);
+            compute_chunk(mandel_config, chunk, (n as u32) * mandel_config.chunk_rows, cancel);
+        });
+        return;
+    }
+
+    let img_size = mandel_config.img_size;
+    let center = img_size / 2;
+    let ref_orbit = compute_ref_orbit(mandel_config.max_iter, pixel_to_complex(mandel_config, center, center));
+
+    // Row index from the bottom (img1), see MandelConfig::row_to_im
+    let row = |y: u32| if mandel_config.flip_y { y } else { img_size - 1 - y };
+
+    let run_len = mandel_config.run_len();
+
+    image.par_chunks_mut(chunk_len(mandel_config)).enumerate().for_each(|(n, chunk)| {
+        pin(&pinner);
+        let _denormals = FlushDenormals::new(mandel_config);
+        let start = n * chunk_len(mandel_config);
+
+        // Runs of consecutive pixels of one row, the rows with --layout=rows
+        for (i, run) in chunk.chunks_mut(run_len).enumerate() {
+            if cancelled(cancel) {
+                return;
+            }
+            let (x0, y) = mandel_config.buffer_pixel(start + (i * run_len));
+            if mandel_config.mirror_source_row(y).is_some() {
+                continue;
+            }
+            let delta_im = ((row(y) as f64) - (row(center) as f64)) * mandel_config.y_step();
+
+            for (x, pixel) in run.iter_mut().enumerate() {
+                let delta_re = (((x0 as f64) + (x as f64)) - (center as f64)) * mandel_config.x_step();
+                *pixel = mandel_iter_perturbation(mandel_config.max_iter, Complex64{re: delta_re, im: delta_im}, &ref_orbit);
+            }
+        }
+    });
+}
+
 // The parallel version of the mandelbrot set calculation, uses rust scoped pool.
-pub fn rust_scoped_pool(mandel_config: &MandelConfig, image: &mut [u32]) {
+#[cfg(not(target_arch = "wasm32"))]
+pub fn rust_scoped_pool(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
     let pool = scoped_pool::Pool::new(mandel_config.num_threads as usize);
+    let pinner = &ThreadPinner::new(mandel_config);
 
     pool.scoped(|scope| {
-        for (y, slice) in image.chunks_mut(mandel_config.img_size as usize).enumerate() {
+        for (n, chunk) in image.chunks_mut(chunk_len(mandel_config)).enumerate() {
             scope.execute(move || {
-                for x in 0..mandel_config.img_size {
-                    slice[x as usize] =
-                    mandel_iter(mandel_config.max_iter,
-                        Complex64{re: mandel_config.re1 + ((x as f64) * mandel_config.x_step),
-                                  im: mandel_config.img1 + ((y as f64) * mandel_config.y_step)}
-                    );
-                }
+                pin(pinner);
+                compute_chunk(mandel_config, chunk, (n as u32) * mandel_config.chunk_rows, cancel);
             });
         }
     });
 }
 
 // The parallel version of the mandelbrot set calculation, uses jobsteal.
-pub fn job_steal(mandel_config: &MandelConfig, image: &mut [u32]) {
+#[cfg(not(target_arch = "wasm32"))]
+pub fn job_steal(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
     let mut pool = jobsteal::make_pool((mandel_config.num_threads - 1) as usize).unwrap();
+    let pinner = &ThreadPinner::new(mandel_config);
+    let recorder = &ScheduleRecorder::new(mandel_config);
 
-    pool.scope(|scope| {
-        for (y, slice) in image.chunks_mut(mandel_config.img_size as usize).enumerate() {
-            scope.submit(move || {
-                for x in 0..mandel_config.img_size {
-                    slice[x as usize] =
-                    mandel_iter(mandel_config.max_iter,
-                        Complex64{re: mandel_config.re1 + ((x as f64) * mandel_config.x_step),
-                                  im: mandel_config.img1 + ((y as f64) * mandel_config.y_step)}
-                    );
-                }
-            });
-        }
-    });
+    if rows_only(mandel_config) {
+        let queue = &RowQueue::new(mandel_config, image);
+
+        pool.scope(|scope| {
+            for _ in 0..mandel_config.img_size {
+                scope.submit(move || {
+                    pin(pinner);
+                    queue.compute_next(mandel_config, recorder, cancel);
+                });
+            }
+        });
+    } else {
+        pool.scope(|scope| {
+            for (n, chunk) in image.chunks_mut(chunk_len(mandel_config)).enumerate() {
+                scope.submit(move || {
+                    pin(pinner);
+                    compute_job(mandel_config, recorder, chunk, (n as u32) * mandel_config.chunk_rows, cancel);
+                });
+            }
+        });
+    }
+
+    write_trace(recorder, "job_steal", mandel_config);
 }
 
 // The parallel version of the mandelbrot set calculation, uses jobsteal with divide-and-conquer strategy.
-pub fn job_steal_join(mandel_config: &MandelConfig, image: &mut [u32]) {
+#[cfg(not(target_arch = "wasm32"))]
+pub fn job_steal_join(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
     // Jobsteal uses n + 1 threads (1 main thread + n sub-threads)
     // It is OK to create a Jobsteal pool with zero threads.
     // See https://github.com/willi-kappler/mandel-rust/issues/1
     let mut pool = jobsteal::make_pool((mandel_config.num_threads - 1) as usize).unwrap();
+    let pinner = ThreadPinner::new(mandel_config);
+    let recorder = ScheduleRecorder::new(mandel_config);
+
+    if rows_only(mandel_config) {
+        let queue = RowQueue::new(mandel_config, image);
+        pool.scope(|scope| {
+            job_steal_rows_helper(mandel_config, &pinner, &recorder, scope, &queue, mandel_config.img_size, cancel);
+        });
+    } else {
+        pool.scope(|scope| {
+            job_steal_helper(mandel_config, &pinner, &recorder, scope, image, 0, cancel);
+        });
+    }
 
-    pool.scope(|scope| {
-        job_steal_helper(mandel_config, scope, image, 0);
-    })
+    write_trace(&recorder, "job_steal_join", mandel_config);
+}
+
+// job_steal_join with --normalize_access=rows: the jobs are split the same way, but every leaf is one row
+// that is taken from the queue, so the rows are computed in order.
+#[cfg(not(target_arch = "wasm32"))]
+fn job_steal_rows_helper<'a, 'b>(mandel_config: &MandelConfig, pinner: &Option<ThreadPinner>, recorder: &Option<ScheduleRecorder>,
+                                 spawner: &jobsteal::Spawner<'a, 'b>, queue: &RowQueue, num_of_rows: u32, cancel: &AtomicBool) {
+    if num_of_rows <= 1 {
+        pin(pinner);
+        queue.compute_next(mandel_config, recorder, cancel);
+    } else {
+        let mid = num_of_rows / 2;
+        spawner.join(
+            |inner| job_steal_rows_helper(mandel_config, pinner, recorder, inner, queue, mid, cancel),
+            |inner| job_steal_rows_helper(mandel_config, pinner, recorder, inner, queue, num_of_rows - mid, cancel)
+        );
+    }
 }
 
 // jobsteal helper for divide and conquer version.
-fn job_steal_helper<'a, 'b>(mandel_config: &MandelConfig, spawner: &jobsteal::Spawner<'a, 'b>,
-                            slice: &mut [u32], y: u32) {
-    if slice.len() == (mandel_config.img_size as usize) { // just process one scanline of the mandelbrot image
-        for x in 0..mandel_config.img_size {
-            slice[x as usize] =
-            mandel_iter(mandel_config.max_iter,
-                Complex64{re: mandel_config.re1 + ((x as f64) * mandel_config.x_step),
-                          im: mandel_config.img1 + ((y as f64) * mandel_config.y_step)}
-            );
-        }
+#[cfg(not(target_arch = "wasm32"))]
+fn job_steal_helper<'a, 'b>(mandel_config: &MandelConfig, pinner: &Option<ThreadPinner>, recorder: &Option<ScheduleRecorder>,
+                            spawner: &jobsteal::Spawner<'a, 'b>, slice: &mut [u32], y: u32, cancel: &AtomicBool) {
+    if slice.len() <= chunk_len(mandel_config) { // just process chunk_rows scanlines of the mandelbrot image
+        pin(pinner);
+        compute_job(mandel_config, recorder, slice, y, cancel);
     } else {
         let mid = slice.len() / 2;
         let (top, bottom) = slice.split_at_mut(mid);
         spawner.join(
-            |inner| job_steal_helper(mandel_config, inner, top, y),
-            |inner| job_steal_helper(mandel_config, inner, bottom, y + ((mid as u32) / mandel_config.img_size))
+            |inner| job_steal_helper(mandel_config, pinner, recorder, inner, top, y, cancel),
+            |inner| job_steal_helper(mandel_config, pinner, recorder, inner, bottom, y + ((mid / (mandel_config.img_size as usize)) as u32), cancel)
         );
     }
 }
 
-// The parallel version of the mandelbrot set calculation, uses kirk and crossbeam.
-/*
-pub fn kirk_crossbeam(mandel_config: &MandelConfig, image: &mut [u32]) {
+// The parallel version of the mandelbrot set calculation, uses the blocking thread pool of tokio.
+// spawn_blocking only takes 'static closures, so each job computes its rows into its own buffer
+// and the buffers are copied into the image in order. This copy is part of the runtime overhead.
+// The jobs can't borrow the cancel flag either, they check their own flag that is set on cancel.
+#[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+pub fn tokio_spawn_blocking(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .max_blocking_threads(mandel_config.num_threads as usize)
+        .build()
+        .unwrap_or_else(|e| panic!("Tokio error: can't create runtime: {}", e));
+    let config = Arc::new(mandel_config.clone());
+    let pinner = Arc::new(ThreadPinner::new(mandel_config));
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let tasks: Vec<_> = image.chunks(chunk_len(mandel_config)).enumerate().map(|(n, chunk)| {
+        let (config, pinner, stop, len) = (config.clone(), pinner.clone(), stop.clone(), chunk.len());
+
+        runtime.spawn_blocking(move || {
+            pin(&pinner);
+            let mut rows = vec![0; len];
+            compute_chunk(&config, &mut rows, (n as u32) * config.chunk_rows, &stop);
+            rows
+        })
+    }).collect();
+
+    for (task, chunk) in tasks.into_iter().zip(image.chunks_mut(chunk_len(mandel_config))) {
+        if cancelled(cancel) {
+            // Don't wait for the remaining jobs, they return as soon as they see the flag
+            stop.store(true, Ordering::Relaxed);
+            runtime.shutdown_background();
+            return;
+        }
+        chunk.copy_from_slice(&runtime.block_on(task).expect("Tokio task panicked"));
+    }
+}
+
+// The async version of tokio_spawn_blocking: one spawn_blocking task per line, all tasks are spawned first and
+// then awaited in order (like join_all). Each task computes its line into its own buffer and copies it into
+// the shared image under the lock, so the lock and the copy are part of the task overhead.
+// CPU bound work must not run in a plain tokio::spawn, it would block the worker threads of the async tasks.
+// Must be called within a tokio runtime.
+#[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+pub async fn tokio_spawn(mandel_config: &MandelConfig, image: Arc<Mutex<Vec<u32>>>) {
+    tokio_spawn_lines(mandel_config, image, &AtomicBool::new(false)).await
+}
+
+// tokio_spawn with a cancel flag: no more tasks are spawned once it is set, and like in tokio_spawn_blocking
+// the tasks that are not running yet see their own stop flag and return without computing their line.
+#[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+async fn tokio_spawn_lines(mandel_config: &MandelConfig, image: Arc<Mutex<Vec<u32>>>, cancel: &AtomicBool) {
+    let config = Arc::new(mandel_config.clone());
+    let pinner = Arc::new(ThreadPinner::new(mandel_config));
+    let stop = Arc::new(AtomicBool::new(false));
+    let line_len = mandel_config.img_size as usize;
+
+    let tasks: Vec<_> = (0..mandel_config.img_size).take_while(|_| !cancelled(cancel)).map(|line| {
+        let (config, pinner, stop, image) = (config.clone(), pinner.clone(), stop.clone(), image.clone());
+
+        tokio::task::spawn_blocking(move || {
+            if cancelled(&stop) {
+                return;
+            }
+            pin(&pinner);
+            let _denormals = FlushDenormals::new(&config);
+            let mut values = vec![0; line_len];
+            render_line(&config, line, &mut values);
+
+            let start = (line as usize) * line_len;
+            let mut image = image.lock().expect("Tokio task: image lock poisoned");
+            image[start..(start + line_len)].copy_from_slice(&values);
+        })
+    }).collect();
+
+    for task in tasks {
+        if cancelled(cancel) {
+            stop.store(true, Ordering::Relaxed);
+            return;
+        }
+        task.await.expect("Tokio task panicked");
+    }
+}
+
+// tokio_spawn as a method of the benchmark. The benchmark calls the methods from a plain thread (a #[tokio::main]
+// would make the block_on of tokio_spawn_blocking panic), so each call drives the future on its own runtime
+// with num_threads blocking threads. The image is copied into the shared buffer and back.
+#[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+pub fn tokio_spawn_method(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .max_blocking_threads(mandel_config.num_threads as usize)
+        .build()
+        .unwrap_or_else(|e| panic!("Tokio error: can't create runtime: {}", e));
+    let shared = Arc::new(Mutex::new(image.to_vec()));
+
+    runtime.block_on(tokio_spawn_lines(mandel_config, shared.clone(), cancel));
+    // Don't wait for the tasks of a cancelled run, they return as soon as they see the flag
+    runtime.shutdown_background();
+
+    image.copy_from_slice(&shared.lock().expect("Tokio: image lock poisoned"));
+}
+
+// Baseline for the thread pool overhead: distributes the rows like scoped_thread_pool_
+// but doesn't compute anything, every pixel is set to 0.
+// The time of this method is the cost of the pool and the scheduling alone.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn null_method(mandel_config: &MandelConfig, image: &mut [u32], _cancel: &AtomicBool) {
+    let mut pool = scoped_threadpool::Pool::new(mandel_config.num_threads);
+    let pinner = &ThreadPinner::new(mandel_config);
+
+    pool.scoped(|scope| {
+        for chunk in image.chunks_mut(chunk_len(mandel_config)) {
+            scope.execute(move || {
+                pin(pinner);
+                for pixel in chunk.iter_mut() {
+                    *pixel = 0;
+                }
+            });
+        }
+    });
+}
+
+// The parallel version of the mandelbrot set calculation, uses a crossbeam scope and a work-stealing deque.
+// (The kirk pool that gave this method its name isn't maintained anymore and is not on crates.io.)
+// All jobs are pushed before the workers start, so a worker can stop as soon as the deque is empty.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn kirk_crossbeam(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
+    let (mut jobs, stealer) = chase_lev::deque();
+    let pinner = &ThreadPinner::new(mandel_config);
+    let recorder = &ScheduleRecorder::new(mandel_config);
+    // The workers steal the oldest job first, so with one row per job the rows are taken in order
+    let rows_per_job = if rows_only(mandel_config) { 1 } else { mandel_config.chunk_rows.max(1) };
+
+    for (n, chunk) in image.chunks_mut((rows_per_job as usize) * (mandel_config.img_size as usize)).enumerate() {
+        jobs.push(((n as u32) * rows_per_job, chunk));
+    }
+
     crossbeam::scope(|scope| {
-        let mut pool = kirk::Pool::<kirk::Deque<kirk::Task>>::scoped(scope,
-            Options{ num_workers: mandel_config.num_threads as usize, .. Options::default()});
-        for (y, slice) in image.chunks_mut(mandel_config.img_size as usize).enumerate() {
-            pool.push(move || {
-                for x in 0..mandel_config.img_size {
-                    slice[x as usize] =
-                    mandel_iter(mandel_config.max_iter,
-                        Complex64{re: mandel_config.re1 + ((x as f64) * mandel_config.x_step),
-                                  im: mandel_config.img1 + ((y as f64) * mandel_config.y_step)}
-                    );
+        for _ in 0..mandel_config.num_threads {
+            let stealer = stealer.clone();
+
+            scope.spawn(move || {
+                pin(pinner);
+
+                while !cancelled(cancel) {
+                    match stealer.steal() {
+                        Steal::Data((first_row, chunk)) => compute_job(mandel_config, recorder, chunk, first_row, cancel),
+                        // Lost a race with another worker, try again
+                        Steal::Abort => continue,
+                        Steal::Empty => break
+                    }
+                }
+            });
+        }
+    });
+
+    write_trace(recorder, "kirk_crossbeam", mandel_config);
+}
+
+// A two-stage pipeline with crossbeam channels: the calling thread sends the line numbers on a dispatch channel,
+// num_threads workers take them (multi-producer, multi-consumer) and send each computed line with its number
+// on a completion channel. A single writer thread puts the lines back in order and copies them into the image.
+// Unlike the other methods the workers never write into the image, the copy and the two channels are the overhead
+// of the pipeline. Both channels are bounded, so at most a few lines wait for the writer.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn crossbeam_pipeline(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
+    let num_threads = mandel_config.num_threads.max(1) as usize;
+    let line_len = mandel_config.img_size as usize;
+    let (line_sender, line_receiver) = crossbeam_channel::bounded::<u32>(num_threads);
+    let (row_sender, row_receiver) = crossbeam_channel::bounded::<(u32, Vec<u32>)>(num_threads);
+    let pinner = &ThreadPinner::new(mandel_config);
+
+    std::thread::scope(|scope| {
+        for _ in 0..num_threads {
+            let (line_receiver, row_sender) = (line_receiver.clone(), row_sender.clone());
+
+            scope.spawn(move || {
+                pin(pinner);
+
+                for y in line_receiver {
+                    let mut row = vec![0; line_len];
+                    compute_chunk(mandel_config, &mut row, y, cancel);
+
+                    // A cancelled line may be incomplete, it is not written
+                    if cancelled(cancel) || row_sender.send((y, row)).is_err() {
+                        break;
+                    }
                 }
-            })
+            });
+        }
+        // Only the workers hold the ends, so the channels are closed when the workers are done
+        drop((line_receiver, row_sender));
+
+        scope.spawn(move || {
+            let mut lines = image.chunks_mut(line_len);
+            // The lines that arrived before the lines above them
+            let mut pending = BTreeMap::new();
+            let mut next_y = 0;
+
+            for (y, row) in row_receiver {
+                pending.insert(y, row);
+
+                while let Some(row) = pending.remove(&next_y) {
+                    lines.next().expect("Line outside of the image").copy_from_slice(&row);
+                    next_y += 1;
+                }
+            }
+        });
+
+        for y in 0..mandel_config.img_size {
+            if cancelled(cancel) || line_sender.send(y).is_err() {
+                break;
+            }
         }
+        // No more lines, the workers stop when the dispatch channel is empty
+        drop(line_sender);
     });
 }
-*/
+
+// The configuration with the phoenix workload, all other parameters (region, phoenix_p, phoenix_q, ...) stay the same
+fn phoenix_config(mandel_config: &MandelConfig) -> MandelConfig {
+    MandelConfig{workload: Workload::Phoenix, .. mandel_config.clone()}
+}
+
+// The phoenix fractal (see mandel_util::phoenix_iter), computed like serial.
+// All methods render the phoenix fractal with --workload=phoenix, these two don't need the workload to be set.
+pub fn phoenix_serial(mandel_config: &MandelConfig, image: &mut [u32]) {
+    serial(&phoenix_config(mandel_config), image, &AtomicBool::new(false));
+}
+
+// The phoenix fractal, computed in parallel like rayon_join
+pub fn phoenix_rayon(mandel_config: &MandelConfig, image: &mut [u32]) {
+    rayon_join(&phoenix_config(mandel_config), image, &AtomicBool::new(false));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use mandel_util::{mirror_rows, to_rows, AccessOrder, BufferLayout, MandelConfig, Workload, SyntheticDistribution};
+    use crate::registry::{get_method, method_names, is_approximate, is_baseline};
+
+    fn tiny_config() -> MandelConfig {
+        let default = MandelConfig::default();
+
+        MandelConfig{
+            img_size: 16,
+            max_iter: 64,
+            .. default
+        }
+    }
+
+    fn assert_all_methods_match_serial(config: &MandelConfig) {
+        let num_pixels = config.num_of_pixels();
+
+        let mut expected = vec![0; num_pixels];
+        super::serial(config, &mut expected, &AtomicBool::new(false));
+
+        for name in method_names().iter().filter(|name| !is_baseline(name)) {
+            let mut image = vec![0; num_pixels];
+            get_method(name).unwrap()(config, &mut image, &AtomicBool::new(false));
+
+            if is_approximate(name) {
+                let num_of_mismatches = image.iter().zip(&expected).filter(|&(a, b)| a != b).count();
+                assert!(num_of_mismatches <= num_pixels / 100, "method '{}' differs from serial in {} pixels", name, num_of_mismatches);
+            } else {
+                assert!(image == expected, "method '{}' differs from serial", name);
+            }
+        }
+    }
+
+    #[test]
+    fn all_methods_match_serial() {
+        assert_all_methods_match_serial(&tiny_config());
+    }
+
+    #[test]
+    fn all_methods_match_serial_synthetic() {
+        assert_all_methods_match_serial(&MandelConfig{
+            workload: Workload::Synthetic,
+            synthetic_distribution: SyntheticDistribution::Zipf,
+            synthetic_seed: 7,
+            .. tiny_config()
+        });
+    }
+
+    #[test]
+    fn all_methods_match_serial_phoenix() {
+        let config = MandelConfig{workload: Workload::Phoenix, phoenix_p: 0.56667, phoenix_q: -0.5, .. tiny_config()};
+        assert_all_methods_match_serial(&config);
+
+        let mut serial_image = vec![0; config.num_of_pixels()];
+        let mut rayon_image = vec![0; config.num_of_pixels()];
+        super::phoenix_serial(&tiny_config(), &mut serial_image);
+        super::phoenix_rayon(&tiny_config(), &mut rayon_image);
+
+        assert!(serial_image == rayon_image);
+        assert!(serial_image.contains(&64) && serial_image.iter().any(|&iter| iter < 64));
+
+        // Not the mandelbrot set
+        let mut mandel_image = vec![0; config.num_of_pixels()];
+        super::serial(&tiny_config(), &mut mandel_image, &AtomicBool::new(false));
+        assert!(serial_image != mandel_image);
+    }
+
+    #[test]
+    fn dynamic_split_follows_the_imbalance() {
+        let config = MandelConfig{num_threads: 2, chunk_rows: 4, .. tiny_config()};
+        let split = super::DynamicSplit::new(&config, 16 * 16);
+        assert_eq!((split.threshold(), split.min_threshold, split.max_threshold), (4 * 16, 16, 8 * 16));
+
+        // Balanced: twice the threshold after each window of two leaves, at most one leaf per thread
+        split.record(1000);
+        assert_eq!(split.threshold(), 4 * 16);
+        split.record(1500);
+        assert_eq!(split.threshold(), 8 * 16);
+        split.record(1000);
+        split.record(1000);
+        assert_eq!(split.threshold(), 8 * 16);
+
+        // Imbalanced: half the threshold, at least one row
+        for _ in 0..4 {
+            split.record(100);
+            split.record(1000);
+        }
+        assert_eq!(split.threshold(), 16);
+
+        // In between: unchanged
+        split.record(100);
+        split.record(300);
+        assert_eq!(split.threshold(), 16);
+    }
+
+    #[test]
+    fn all_methods_match_serial_chunked() {
+        for &chunk_rows in &[2, 3, 16, 100] {
+            assert_all_methods_match_serial(&MandelConfig{chunk_rows: chunk_rows, .. tiny_config()});
+        }
+    }
+
+    #[test]
+    fn all_methods_match_serial_rayon_task_lengths() {
+        for &(min_len, max_len) in &[(1, 1), (7, 7), (16, 1000), (1000, usize::MAX)] {
+            assert_all_methods_match_serial(&MandelConfig{rayon_min_len: min_len, rayon_max_len: max_len, .. tiny_config()});
+        }
+    }
+
+    #[test]
+    fn all_methods_match_serial_thread_counts() {
+        // One worker and many more workers than jobs must neither hang nor miss a chunk
+        for &num_threads in &[1, 32] {
+            assert_all_methods_match_serial(&MandelConfig{num_threads: num_threads, .. tiny_config()});
+        }
+    }
+
+    #[test]
+    fn all_methods_match_serial_with_symmetry() {
+        for &(flip_y, inclusive_edges) in &[(false, false), (true, false), (false, true), (true, true)] {
+            let config = MandelConfig{flip_y: flip_y, inclusive_edges: inclusive_edges, .. tiny_config()};
+            let mut expected = vec![0; config.num_of_pixels()];
+            super::serial(&config, &mut expected, &AtomicBool::new(false));
+
+            let symmetric = MandelConfig{use_symmetry: true, .. config};
+            for name in method_names().iter().filter(|name| !is_baseline(name) && !is_approximate(name)) {
+                let mut image = vec![0; symmetric.num_of_pixels()];
+                get_method(name).unwrap()(&symmetric, &mut image, &AtomicBool::new(false));
+                mirror_rows(&symmetric, &mut image);
+
+                assert!(image == expected, "method '{}' with flip_y: {}, inclusive_edges: {}", name, flip_y, inclusive_edges);
+            }
+        }
+    }
+
+    #[test]
+    fn all_methods_match_serial_with_rows_access() {
+        let mut expected = vec![0; tiny_config().num_of_pixels()];
+        super::serial(&tiny_config(), &mut expected, &AtomicBool::new(false));
+
+        for &use_symmetry in &[false, true] {
+            let config = MandelConfig{normalize_access: AccessOrder::Rows, use_symmetry: use_symmetry, chunk_rows: 3, .. tiny_config()};
+
+            for name in method_names().iter().filter(|name| !is_baseline(name)) {
+                let mut image = vec![0; config.num_of_pixels()];
+                get_method(name).unwrap()(&config, &mut image, &AtomicBool::new(false));
+                mirror_rows(&config, &mut image);
+
+                let num_of_mismatches = image.iter().zip(&expected).filter(|&(a, b)| a != b).count();
+                let allowed = if is_approximate(name) { image.len() / 100 } else { 0 };
+                assert!(num_of_mismatches <= allowed, "method '{}' with rows access (use_symmetry: {}) differs from serial in {} pixels",
+                    name, use_symmetry, num_of_mismatches);
+            }
+        }
+    }
+
+    #[test]
+    fn all_methods_match_serial_with_tiles() {
+        // 2 x 2 tiles of 64 x 64 pixels, with and without symmetry
+        let config = MandelConfig{img_size: 128, chunk_rows: 3, .. tiny_config()};
+        let mut expected = vec![0; config.num_of_pixels()];
+        super::serial(&config, &mut expected, &AtomicBool::new(false));
+
+        for &use_symmetry in &[false, true] {
+            let tiled = MandelConfig{layout: BufferLayout::Tiles64, use_symmetry: use_symmetry, .. config.clone()};
+
+            for name in method_names().iter().filter(|name| !is_baseline(name)) {
+                let mut image = vec![0; tiled.num_of_pixels()];
+                get_method(name).unwrap()(&tiled, &mut image, &AtomicBool::new(false));
+                mirror_rows(&tiled, &mut image);
+                to_rows(&tiled, &mut image);
+
+                let num_of_mismatches = image.iter().zip(&expected).filter(|&(a, b)| a != b).count();
+                let allowed = if is_approximate(name) { image.len() / 100 } else { 0 };
+                assert!(num_of_mismatches <= allowed, "method '{}' with tiles (use_symmetry: {}) differs from serial in {} pixels",
+                    name, use_symmetry, num_of_mismatches);
+            }
+        }
+    }
+
+    // The jobs restore the FPU mode: neither the calling thread (a worker of job_steal) nor the threads of the
+    // rayon pool keep FTZ and DAZ after the method
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn flushed_denormals_are_restored() {
+        use mandel_util::denormal::{mxcsr, MXCSR_DAZ, MXCSR_FTZ};
+
+        let config = MandelConfig{detect_denormals: true, num_threads: 3, .. tiny_config()};
+        let flush_bits = |csr: u32| csr & (MXCSR_FTZ | MXCSR_DAZ);
+        assert_eq!(flush_bits(mxcsr()), 0);
+        assert_all_methods_match_serial(&config);
+
+        assert_eq!(flush_bits(mxcsr()), 0);
+        assert!(rayon::broadcast(|_| flush_bits(mxcsr())).iter().all(|&bits| bits == 0));
+    }
+
+    #[test]
+    fn cancelled_methods_stop_early() {
+        let config = tiny_config();
+
+        for name in method_names().iter().filter(|name| !is_baseline(name)) {
+            let mut image = vec![7; config.num_of_pixels()];
+            get_method(name).unwrap()(&config, &mut image, &AtomicBool::new(true));
+
+            assert!(image.iter().all(|&pixel| pixel == 7), "method '{}' didn't stop", name);
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn tokio_spawn_within_a_runtime() {
+        use std::sync::{Arc, Mutex};
+
+        let config = tiny_config();
+        let mut expected = vec![0; config.num_of_pixels()];
+        get_method("serial").unwrap()(&config, &mut expected, &AtomicBool::new(false));
+
+        let image = Arc::new(Mutex::new(vec![0; config.num_of_pixels()]));
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        runtime.block_on(super::tokio_spawn(&config, image.clone()));
+
+        assert_eq!(*image.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn null_method_only_clears_image() {
+        let config = tiny_config();
+        let mut image = vec![1; 16 * 16];
+        get_method("null_method").unwrap()(&config, &mut image, &AtomicBool::new(false));
+
+        assert!(image.iter().all(|&pixel| pixel == 0));
+    }
+
+    // Asymmetric view around the period two bulb (center -1, radius 1/4):
+    // the real axis lies in row 11 counted from the top, 4 counted from the bottom.
+    fn bulb_config(flip_y: bool) -> MandelConfig {
+        MandelConfig{
+            re1: -1.5,
+            re2: -0.5,
+            img1: -0.25,
+            img2: 0.75,
+            flip_y: flip_y,
+            .. tiny_config()
+        }
+    }
+
+    #[test]
+    fn positive_imaginary_axis_points_up() {
+        let config = bulb_config(false);
+        let mut image = vec![0; 16 * 16];
+        super::serial(&config, &mut image, &AtomicBool::new(false));
+
+        // Pixel (8, 11) is c = -1 + 0i
+        assert_eq!(image[(11 * 16) + 8], config.max_iter);
+        // Pixel (8, 4) is c = -1 + 0.4375i, outside of the bulb
+        assert!(image[(4 * 16) + 8] < config.max_iter);
+    }
+
+    #[test]
+    fn flip_y_mirrors_image() {
+        let mut image = vec![0; 16 * 16];
+        super::serial(&bulb_config(false), &mut image, &AtomicBool::new(false));
+
+        let mut flipped = vec![0; 16 * 16];
+        super::serial(&bulb_config(true), &mut flipped, &AtomicBool::new(false));
+
+        assert_eq!(flipped[(4 * 16) + 8], 64);
+
+        for y in 0..16 {
+            assert_eq!(&image[(y * 16)..((y + 1) * 16)], &flipped[((15 - y) * 16)..((16 - y) * 16)]);
+        }
+    }
+
+    #[test]
+    fn perturbation_offsets_follow_flip_y() {
+        for &flip_y in &[false, true] {
+            let config = bulb_config(flip_y);
+            let mut expected = vec![0; 16 * 16];
+            super::serial(&config, &mut expected, &AtomicBool::new(false));
+
+            let mut image = vec![0; 16 * 16];
+            super::rayon_perturbation(&config, &mut image, &AtomicBool::new(false));
+
+            assert!(image == expected, "flip_y: {}", flip_y);
+        }
+    }
+}
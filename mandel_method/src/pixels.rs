@@ -0,0 +1,214 @@
+// Pixel by pixel access to the mandelbrot set without allocating the whole image buffer,
+// for very large images and streaming consumers.
+//
+// - pixels: a plain (serial) iterator over (x, y, iter), row by row
+// - for_each_pixel: calls the callback from the worker threads, the order of the pixels is NOT defined
+//   (the pixels of a row are in order, but the rows come in any order and from different threads)
+// - for_each_pixel_ordered: like for_each_pixel, but the callback runs in the calling thread in row order.
+//   Rows that are finished too early are buffered until all rows before them are done.
+
+// External modules
+use rayon::prelude::*;
+
+// Rust modules
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::BTreeMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc::channel;
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+
+// Internal modules
+use mandel_util::{render_pixel, MandelConfig};
+use mandel_util::denormal::FlushDenormals;
+#[cfg(not(target_arch = "wasm32"))]
+use mandel_util::render_row;
+
+// Methods that can be used with for_each_pixel and for_each_pixel_ordered
+pub static STREAMING_METHOD_NAMES: &'static [&'static str] = &[
+    "serial",
+    #[cfg(not(target_arch = "wasm32"))]
+    "scoped_thread_pool",
+    "rayon_par_iter",
+];
+
+// Iterator over all pixels, row by row: (x, y, number of iterations)
+pub struct PixelIter<'a> {
+    mandel_config: &'a MandelConfig,
+    x: u32,
+    y: u32
+}
+
+pub fn pixels<'a>(mandel_config: &'a MandelConfig) -> PixelIter<'a> {
+    PixelIter{mandel_config: mandel_config, x: 0, y: 0}
+}
+
+impl<'a> Iterator for PixelIter<'a> {
+    type Item = (u32, u32, u32);
+
+    fn next(&mut self) -> Option<(u32, u32, u32)> {
+        if self.y >= self.mandel_config.img_size {
+            return None;
+        }
+
+        let (x, y) = (self.x, self.y);
+
+        self.x += 1;
+        if self.x == self.mandel_config.img_size {
+            self.x = 0;
+            self.y += 1;
+        }
+
+        Some((x, y, render_pixel(self.mandel_config, x, y)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let img_size = self.mandel_config.img_size as usize;
+        let left = if (self.y as usize) < img_size {
+            ((img_size - (self.y as usize)) * img_size) - (self.x as usize)
+        } else {
+            0
+        };
+        (left, Some(left))
+    }
+}
+
+// Call f for every row index, distributed by the given method.
+// Panics if the method is not in STREAMING_METHOD_NAMES.
+fn for_each_row<F>(mandel_config: &MandelConfig, method: &str, f: F) where F: Fn(u32) + Sync {
+    match method {
+        "serial" => {
+            for y in 0..mandel_config.img_size {
+                f(y);
+            }
+        },
+        #[cfg(not(target_arch = "wasm32"))]
+        "scoped_thread_pool" => {
+            let mut pool = ::scoped_threadpool::Pool::new(mandel_config.num_threads);
+            let f = &f;
+
+            pool.scoped(|scope| {
+                for y in 0..mandel_config.img_size {
+                    scope.execute(move || f(y));
+                }
+            });
+        },
+        "rayon_par_iter" => (0..mandel_config.img_size).into_par_iter().for_each(|y| f(y)),
+        _ => panic!("Method '{}' can't stream pixels, use one of: {}", method, STREAMING_METHOD_NAMES.join(", "))
+    }
+}
+
+// Compute all pixels with the given method and call callback(x, y, iter) for each of them
+// from the worker threads, in no particular order.
+// Panics if the method is not in STREAMING_METHOD_NAMES.
+pub fn for_each_pixel<F>(mandel_config: &MandelConfig, method: &str, callback: F) where F: Fn(u32, u32, u32) + Sync {
+    for_each_row(mandel_config, method, |y| {
+        let _denormals = FlushDenormals::new(mandel_config);
+        for x in 0..mandel_config.img_size {
+            callback(x, y, render_pixel(mandel_config, x, y));
+        }
+    });
+}
+
+// Like for_each_pixel, but callback is called in the calling thread, row by row in the same order as pixels().
+// Panics if the method is not in STREAMING_METHOD_NAMES.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn for_each_pixel_ordered<F>(mandel_config: &MandelConfig, method: &str, mut callback: F) where F: FnMut(u32, u32, u32) {
+    let (sender, receiver) = channel();
+
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            for_each_row(mandel_config, method, |y| {
+                let _denormals = FlushDenormals::new(mandel_config);
+                let mut row = vec![0; mandel_config.img_size as usize];
+                render_row(mandel_config, y, &mut row);
+                // The receiver only goes away if the callback panics
+                let _ = sender.send((y, row));
+            });
+        });
+
+        // Rows that arrived before the rows above them
+        let mut waiting = BTreeMap::new();
+        let mut next_row = 0;
+
+        for (y, row) in receiver {
+            waiting.insert(y, row);
+
+            while let Some(row) = waiting.remove(&next_row) {
+                for (x, value) in row.into_iter().enumerate() {
+                    callback(x as u32, next_row, value);
+                }
+                next_row += 1;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::sync::atomic::AtomicBool;
+    use crate::serial;
+
+    fn tiny_config() -> MandelConfig {
+        let default = MandelConfig::default();
+
+        MandelConfig{
+            img_size: 16,
+            max_iter: 64,
+            num_threads: 3,
+            .. default
+        }
+    }
+
+    fn serial_image(config: &MandelConfig) -> Vec<u32> {
+        let mut image = vec![0; config.num_of_pixels()];
+        serial(config, &mut image, &AtomicBool::new(false));
+        image
+    }
+
+    #[test]
+    fn iterator_equals_serial() {
+        let config = tiny_config();
+        let iter = pixels(&config);
+        assert_eq!(iter.size_hint(), (256, Some(256)));
+
+        let collected: Vec<(u32, u32, u32)> = iter.collect();
+        assert_eq!(collected.len(), 256);
+        assert!(collected.iter().enumerate().all(|(n, &(x, y, _))| (n as u32) == (y * 16) + x));
+        assert!(collected.iter().map(|&(_, _, value)| value).collect::<Vec<_>>() == serial_image(&config));
+    }
+
+    #[test]
+    fn for_each_pixel_equals_serial() {
+        let config = tiny_config();
+        let expected = serial_image(&config);
+
+        for method in STREAMING_METHOD_NAMES {
+            let image = Mutex::new(vec![0; 256]);
+            let count = Mutex::new(0);
+
+            for_each_pixel(&config, method, |x, y, value| {
+                image.lock().unwrap()[((y * 16) + x) as usize] = value;
+                *count.lock().unwrap() += 1;
+            });
+
+            assert_eq!(*count.lock().unwrap(), 256, "method '{}'", method);
+            assert!(*image.lock().unwrap() == expected, "method '{}' differs from serial", method);
+        }
+    }
+
+    #[test]
+    fn ordered_adapter_restores_row_order() {
+        let config = tiny_config();
+        let expected: Vec<(u32, u32, u32)> = pixels(&config).collect();
+
+        for method in STREAMING_METHOD_NAMES {
+            let mut collected = Vec::new();
+            for_each_pixel_ordered(&config, method, |x, y, value| collected.push((x, y, value)));
+
+            assert!(collected == expected, "method '{}'", method);
+        }
+    }
+}
@@ -0,0 +1,155 @@
+// Portable SIMD (std::simd, nightly only) version of the iteration: LANES pixels of a row at once.
+//
+// Each lane does exactly the same f64 operations in the same order as mandel_iter_z0, so the
+// results are identical to serial. A lane stops (its z and its count are frozen) as soon as it
+// escapes or reaches max_iter, the loop ends when no lane is active anymore.
+// If the row width is not a multiple of LANES, the last group has unused lanes: they start
+// with the count max_iter, so they are never active and are not written back.
+
+// Rust modules
+use std::simd::cmp::SimdPartialOrd;
+use std::simd::{Select, Simd};
+use std::sync::atomic::AtomicBool;
+
+// External modules
+use rayon::prelude::*;
+
+// Internal modules
+use mandel_util::{render_row_from, MandelConfig, Workload};
+use mandel_util::denormal::FlushDenormals;
+use crate::affinity::{ThreadPinner, pin};
+use crate::{cancelled, chunk_len};
+
+// Number of pixels per vector, f64x4 fits into one AVX register
+pub const LANES: usize = 4;
+
+// mandel_iter_z0 for up to N values of c at once, only the first num_of_values lanes are used
+pub fn mandel_iter_lanes<const N: usize>(max_iter: u32, z0: (f64, f64), c_re: Simd<f64, N>, c_im: Simd<f64, N>,
+    num_of_values: usize) -> [u32; N] {
+    let four = Simd::splat(4.0);
+    let max_iter_lanes = Simd::splat(max_iter as u64);
+
+    // z1 = z0 * z0 + c, see mandel_iter_z0
+    let (z0_re, z0_im) = (Simd::splat(z0.0), Simd::splat(z0.1));
+    let mut z_re = c_re + ((z0_re * z0_re) - (z0_im * z0_im));
+    let mut z_im = c_im + ((z0_re * z0_im) + (z0_im * z0_re));
+
+    let mut iter = Simd::from_array(std::array::from_fn(|lane| if lane < num_of_values { 0 } else { max_iter as u64 }));
+
+    loop {
+        let active = ((z_re * z_re) + (z_im * z_im)).simd_le(four) & iter.simd_lt(max_iter_lanes);
+        if !active.any() {
+            break;
+        }
+
+        let new_re = c_re + ((z_re * z_re) - (z_im * z_im));
+        let new_im = c_im + ((z_re * z_im) + (z_im * z_re));
+
+        z_re = active.select(new_re, z_re);
+        z_im = active.select(new_im, z_im);
+        iter = active.select(iter + Simd::splat(1), iter);
+    }
+
+    iter.to_array().map(|value| value as u32)
+}
+
+// Compute one row with groups of N pixels
+pub fn compute_row_lanes<const N: usize>(mandel_config: &MandelConfig, row: &mut [u32], y: u32) {
+    compute_row_lanes_from::<N>(mandel_config, 0, y, row);
+}
+
+// Compute the pixels x = x0 .. x0 + row.len() of row y with groups of N pixels
+pub fn compute_row_lanes_from<const N: usize>(mandel_config: &MandelConfig, x0: u32, y: u32, row: &mut [u32]) {
+    let z0 = mandel_config.z0();
+    let c_im = Simd::<f64, N>::splat(mandel_config.row_to_im(y));
+
+    for (n, pixels) in row.chunks_mut(N).enumerate() {
+        let first_x = (x0 as usize) + (n * N);
+        let c_re = Simd::from_array(std::array::from_fn(|lane|
+            mandel_config.re1 + (((first_x + lane) as f64) * mandel_config.x_step())));
+
+        let iters = mandel_iter_lanes(mandel_config.max_iter, (z0.re, z0.im), c_re, c_im, pixels.len());
+        pixels.copy_from_slice(&iters[..pixels.len()]);
+    }
+}
+
+// The parallel version of the mandelbrot set calculation, uses rayon par_chunks_mut and std::simd.
+// The synthetic workload is computed like in serial.
+pub fn rayon_portable_simd(mandel_config: &MandelConfig, image: &mut [u32], cancel: &AtomicBool) {
+    let pinner = ThreadPinner::new(mandel_config);
+    let run_len = mandel_config.run_len();
+
+    image.par_chunks_mut(chunk_len(mandel_config)).enumerate().for_each(|(n, chunk)| {
+        pin(&pinner);
+        let _denormals = FlushDenormals::new(mandel_config);
+        let start = n * chunk_len(mandel_config);
+
+        // Runs of consecutive pixels of one row, the rows with --layout=rows
+        for (i, run) in chunk.chunks_mut(run_len).enumerate() {
+            if cancelled(cancel) {
+                return;
+            }
+            let (x0, y) = mandel_config.buffer_pixel(start + (i * run_len));
+            if mandel_config.mirror_source_row(y).is_some() {
+                continue;
+            }
+
+            match mandel_config.workload {
+                Workload::Fractal => compute_row_lanes_from::<LANES>(mandel_config, x0, y, run),
+                Workload::Synthetic | Workload::Phoenix => render_row_from(mandel_config, x0, y, run)
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mandel_util::{pixel_to_complex, mandel_iter, mandel_iter_z0};
+    use num_complex::Complex64;
+
+    fn config(img_size: u32, z0_re: f64) -> MandelConfig {
+        let default = MandelConfig::default();
+
+        MandelConfig{
+            img_size: img_size,
+            max_iter: 200,
+            z0_re: z0_re,
+            .. default
+        }
+    }
+
+    fn assert_rows_equal_scalar<const N: usize>(config: &MandelConfig) {
+        let mut row = vec![0; config.img_size as usize];
+
+        for y in 0..config.img_size {
+            compute_row_lanes::<N>(config, &mut row, y);
+
+            for x in 0..config.img_size {
+                let expected = mandel_iter_z0(config.max_iter, config.z0(), pixel_to_complex(config, x, y));
+                assert_eq!(row[x as usize], expected, "lanes {}, pixel ({}, {}) of {}", N, x, y, config.img_size);
+            }
+        }
+    }
+
+    #[test]
+    fn lanes_equal_scalar() {
+        // Row widths that are and aren't multiples of the lane count, also narrower than one vector
+        for &img_size in &[1, 3, 8, 13, 37] {
+            for &z0_re in &[0.0, 0.3] {
+                assert_rows_equal_scalar::<4>(&config(img_size, z0_re));
+                assert_rows_equal_scalar::<8>(&config(img_size, z0_re));
+            }
+        }
+    }
+
+    #[test]
+    fn unused_lanes_keep_max_iter() {
+        // Only lane 0 is used, lane 1 would escape at once
+        let c_re = Simd::from_array([1.0, -2.5, 0.0, 0.0]);
+        let expected = mandel_iter(50, Complex64{re: 1.0, im: 0.0});
+
+        assert_eq!(mandel_iter_lanes::<4>(50, (0.0, 0.0), c_re, Simd::splat(0.0), 1), [expected, 50, 50, 50]);
+        assert_eq!(mandel_iter_lanes::<4>(0, (0.0, 0.0), c_re, Simd::splat(0.0), 4), [0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,193 @@
+// Progressive rendering within a time limit (--time_budget).
+//
+// The first pass renders all pixels with max_iter / 16. Each further pass doubles the limit and renders only
+// the unresolved pixels again, the ones that reached the limit of the previous pass. The last pass uses max_iter,
+// so a render that finishes in time is exactly the same as a normal one: a pixel that escapes after n iterations
+// escapes after n iterations with every limit above n.
+//
+// The deadline is checked before each row (in parallel with rayon), not for every pixel. A row that has
+// been started is finished, the rows after the deadline keep the values of the previous pass.
+// Each row remembers the limit it was rendered with, that is the effective max_iter of its unresolved pixels.
+
+// External modules
+use rayon::prelude::*;
+
+// Rust modules
+use std::time::Instant;
+
+// Internal modules
+use mandel_util::{render_pixel, MandelConfig};
+use mandel_util::denormal::FlushDenormals;
+
+// Result of render_progressive
+pub struct ProgressiveImage {
+    // The iteration counts, unresolved pixels have the value of their row limit
+    pub image: Vec<u32>,
+    // The limit (effective max_iter) each row was rendered with
+    pub row_limits: Vec<u32>,
+    pub num_of_passes: u32,
+    // True if all passes are done, the image is the same as a normal render
+    pub finished: bool
+}
+
+impl ProgressiveImage {
+    // The image for write_image with max_iter: unresolved pixels count as inside of the set
+    pub fn display_image(&self, max_iter: u32) -> Vec<u32> {
+        let img_size = self.row_limits.len().max(1);
+
+        self.image.chunks(img_size).zip(&self.row_limits)
+            .flat_map(|(row, &limit)| row.iter().map(move |&value| if value >= limit { max_iter } else { value }))
+            .collect()
+    }
+
+    // Number of pixels per effective max_iter, the highest limit first
+    pub fn pixels_per_limit(&self) -> Vec<(u32, u64)> {
+        let img_size = self.row_limits.len() as u64;
+        let mut limits: Vec<(u32, u64)> = Vec::new();
+
+        for &limit in &self.row_limits {
+            match limits.iter_mut().find(|&&mut (other, _)| other == limit) {
+                Some(entry) => entry.1 += img_size,
+                None => limits.push((limit, img_size))
+            }
+        }
+
+        limits.sort_by(|a, b| b.0.cmp(&a.0));
+        limits
+    }
+}
+
+// The limits of all passes: max_iter / 16, max_iter / 8, ..., max_iter
+pub fn pass_limits(max_iter: u32) -> Vec<u32> {
+    let mut limits = vec![(max_iter / 16).max(1)];
+
+    while *limits.last().unwrap() < max_iter {
+        let next = limits.last().unwrap().saturating_mul(2).min(max_iter);
+        limits.push(next);
+    }
+
+    limits
+}
+
+// Render the image progressively until all passes are done or the deadline has passed.
+// The first pass is always done completely, so that every pixel has a value.
+// Uses the current rayon thread pool.
+pub fn render_progressive(mandel_config: &MandelConfig, deadline: Instant) -> ProgressiveImage {
+    let img_size = mandel_config.img_size as usize;
+    let limits = pass_limits(mandel_config.max_iter);
+
+    let mut image = vec![0; img_size * img_size];
+    let mut row_limits = vec![0; img_size];
+    let mut num_of_passes = 0;
+
+    for (pass, &limit) in limits.iter().enumerate() {
+        if pass > 0 && Instant::now() >= deadline {
+            break;
+        }
+
+        let pass_config = MandelConfig{max_iter: limit, .. mandel_config.clone()};
+
+        image.par_chunks_mut(img_size).zip(row_limits.par_iter_mut()).enumerate().for_each(|(y, (row, row_limit))| {
+            if pass > 0 && Instant::now() >= deadline {
+                return;
+            }
+
+            // In the first pass all pixels are 0 and the row limit is 0, so all of them are rendered
+            let _denormals = FlushDenormals::new(&pass_config);
+            for (x, value) in row.iter_mut().enumerate() {
+                if *value >= *row_limit {
+                    *value = render_pixel(&pass_config, x as u32, y as u32);
+                }
+            }
+            *row_limit = limit;
+        });
+
+        num_of_passes += 1;
+    }
+
+    let finished = row_limits.iter().all(|&limit| limit == mandel_config.max_iter);
+
+    ProgressiveImage{
+        image: image,
+        row_limits: row_limits,
+        num_of_passes: num_of_passes,
+        finished: finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial;
+    use std::sync::atomic::AtomicBool;
+    use std::time::Duration;
+
+    fn tiny_config() -> MandelConfig {
+        let default = MandelConfig::default();
+
+        MandelConfig{
+            img_size: 32,
+            max_iter: 256,
+            .. default
+        }
+    }
+
+    #[test]
+    fn limits_double_up_to_max_iter() {
+        assert_eq!(pass_limits(256), vec![16, 32, 64, 128, 256]);
+        assert_eq!(pass_limits(100), vec![6, 12, 24, 48, 96, 100]);
+        assert_eq!(pass_limits(4), vec![1, 2, 4]);
+        assert_eq!(pass_limits(1), vec![1]);
+    }
+
+    #[test]
+    fn generous_budget_equals_normal_render() {
+        let config = tiny_config();
+        let mut expected = vec![0; 32 * 32];
+        serial(&config, &mut expected, &AtomicBool::new(false));
+
+        let result = render_progressive(&config, Instant::now() + Duration::from_secs(3600));
+
+        assert!(result.finished);
+        assert_eq!(result.num_of_passes, 5);
+        assert!(result.image == expected);
+        assert!(result.display_image(config.max_iter) == expected);
+        assert_eq!(result.pixels_per_limit(), vec![(256, 32 * 32)]);
+    }
+
+    #[test]
+    fn expired_budget_keeps_first_pass() {
+        let config = tiny_config();
+        let mut expected = vec![0; 32 * 32];
+        serial(&config, &mut expected, &AtomicBool::new(false));
+
+        let result = render_progressive(&config, Instant::now());
+
+        assert!(!result.finished);
+        assert_eq!(result.num_of_passes, 1);
+        assert_eq!(result.pixels_per_limit(), vec![(16, 32 * 32)]);
+
+        // Pixels that escape early are exact, all others are shown as inside of the set
+        let display = result.display_image(config.max_iter);
+        for (n, (&value, &exact)) in display.iter().zip(&expected).enumerate() {
+            if exact < 16 {
+                assert_eq!(value, exact, "pixel {}", n);
+            } else {
+                assert_eq!(value, 256, "pixel {}", n);
+            }
+        }
+    }
+
+    #[test]
+    fn mixed_row_limits() {
+        let result = ProgressiveImage{
+            image: vec![1, 8, 16, 3],
+            row_limits: vec![8, 16],
+            num_of_passes: 2,
+            finished: false
+        };
+
+        assert_eq!(result.display_image(64), vec![1, 64, 64, 3]);
+        assert_eq!(result.pixels_per_limit(), vec![(16, 2), (8, 2)]);
+    }
+}
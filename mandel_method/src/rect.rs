@@ -0,0 +1,215 @@
+// Rendering of a rectangle of the image (used by --refine, --resume and --refine_from).
+//
+// The methods in lib.rs always compute the whole image starting at row 0. render_rect computes only the pixels
+// of a rectangle into a buffer of rect.width() * rect.height() values, row by row. Value (x, y) of the buffer is
+// pixel (rect.x0 + x, rect.y0 + y) of the whole image, so both have exactly the same value.
+// With previous_max_iter the buffer holds the values of a previous render, the exact ones are kept (see update_pixel).
+
+// External modules
+use rayon::prelude::*;
+
+// Internal modules
+use mandel_util::{render_row_from, update_pixel, MandelConfig};
+use mandel_util::denormal::FlushDenormals;
+use mandel_util::refine::PixelRect;
+
+// Methods that can be used with render_rect
+pub static RECT_METHOD_NAMES: &'static [&'static str] = &[
+    "serial",
+    #[cfg(not(target_arch = "wasm32"))]
+    "scoped_thread_pool",
+    "rayon_join",
+    "rayon_par_iter",
+    "rayon_par_bridge",
+    #[cfg(not(target_arch = "wasm32"))]
+    "rust_scoped_pool",
+    #[cfg(not(target_arch = "wasm32"))]
+    "job_steal",
+];
+
+// Number of values in one job: --chunk_rows rows of the rectangle
+fn rect_chunk_len(mandel_config: &MandelConfig, rect: &PixelRect) -> usize {
+    (mandel_config.chunk_rows.max(1) as usize) * (rect.width() as usize)
+}
+
+// Compute a chunk of whole rows of the rectangle, first_row is the row in the rectangle of the first value
+fn compute_rect_chunk(mandel_config: &MandelConfig, rect: &PixelRect, chunk: &mut [u32], first_row: u32) {
+    let _denormals = FlushDenormals::new(mandel_config);
+
+    for (n, row) in chunk.chunks_mut(rect.width() as usize).enumerate() {
+        render_row_from(mandel_config, rect.x0, rect.y0 + first_row + (n as u32), row);
+    }
+}
+
+// Compute the pixels of the rectangle with the given method into buffer (rect.num_of_pixels() values).
+// Panics if the method is not in RECT_METHOD_NAMES.
+pub fn render_rect(mandel_config: &MandelConfig, method: &str, rect: &PixelRect, buffer: &mut [u32]) {
+    assert_eq!(buffer.len(), rect.num_of_pixels());
+
+    let chunk_len = rect_chunk_len(mandel_config, rect);
+
+    match method {
+        "serial" => compute_rect_chunk(mandel_config, rect, buffer, 0),
+        #[cfg(not(target_arch = "wasm32"))]
+        "scoped_thread_pool" => {
+            let mut pool = ::scoped_threadpool::Pool::new(mandel_config.num_threads);
+
+            pool.scoped(|scope| {
+                for (n, chunk) in buffer.chunks_mut(chunk_len).enumerate() {
+                    scope.execute(move || compute_rect_chunk(mandel_config, rect, chunk, (n as u32) * mandel_config.chunk_rows));
+                }
+            });
+        },
+        "rayon_join" => rayon_rect_helper(mandel_config, rect, buffer, 0),
+        "rayon_par_iter" => {
+            let width = rect.width() as usize;
+
+            buffer.par_iter_mut().enumerate().for_each_init(|| FlushDenormals::new(mandel_config), |_, (n, pixel)| {
+                let (x, y) = ((n % width) as u32, (n / width) as u32);
+                update_pixel(mandel_config, rect.x0 + x, rect.y0 + y, pixel);
+            });
+        },
+        "rayon_par_bridge" => buffer.chunks_mut(chunk_len).enumerate().par_bridge().for_each(|(n, chunk)| {
+            compute_rect_chunk(mandel_config, rect, chunk, (n as u32) * mandel_config.chunk_rows);
+        }),
+        #[cfg(not(target_arch = "wasm32"))]
+        "rust_scoped_pool" => {
+            let pool = ::scoped_pool::Pool::new(mandel_config.num_threads as usize);
+
+            pool.scoped(|scope| {
+                for (n, chunk) in buffer.chunks_mut(chunk_len).enumerate() {
+                    scope.execute(move || compute_rect_chunk(mandel_config, rect, chunk, (n as u32) * mandel_config.chunk_rows));
+                }
+            });
+        },
+        #[cfg(not(target_arch = "wasm32"))]
+        "job_steal" => {
+            let mut pool = ::jobsteal::make_pool((mandel_config.num_threads - 1) as usize).unwrap();
+
+            pool.scope(|scope| {
+                for (n, chunk) in buffer.chunks_mut(chunk_len).enumerate() {
+                    scope.submit(move || compute_rect_chunk(mandel_config, rect, chunk, (n as u32) * mandel_config.chunk_rows));
+                }
+            });
+        },
+        _ => panic!("Method '{}' can't render a rectangle, use one of: {}", method, RECT_METHOD_NAMES.join(", "))
+    }
+}
+
+// Divide-and-conquer like rayon_join, but the height of the rectangle is arbitrary:
+// the slice is split at a row boundary instead of in the middle
+fn rayon_rect_helper(mandel_config: &MandelConfig, rect: &PixelRect, slice: &mut [u32], first_row: u32) {
+    let num_of_rows = slice.len() / (rect.width() as usize);
+
+    if num_of_rows <= (mandel_config.chunk_rows.max(1) as usize) {
+        compute_rect_chunk(mandel_config, rect, slice, first_row);
+    } else {
+        let mid_row = num_of_rows / 2;
+        let (top, bottom) = slice.split_at_mut(mid_row * (rect.width() as usize));
+        rayon::join(
+            || rayon_rect_helper(mandel_config, rect, top, first_row),
+            || rayon_rect_helper(mandel_config, rect, bottom, first_row + (mid_row as u32))
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial;
+    use mandel_util::{mandel_iter_z0, pixel_to_complex};
+    use std::sync::atomic::AtomicBool;
+
+    fn tiny_config() -> MandelConfig {
+        let default = MandelConfig::default();
+
+        MandelConfig{
+            img_size: 16,
+            max_iter: 64,
+            num_threads: 3,
+            .. default
+        }
+    }
+
+    #[test]
+    fn rect_equals_part_of_serial() {
+        let config = tiny_config();
+        let mut image = vec![0; 16 * 16];
+        serial(&config, &mut image, &AtomicBool::new(false));
+
+        // Odd sizes and offsets, a single row and a single column, the whole image
+        let rects = [
+            PixelRect{x0: 3, y0: 5, x1: 10, y1: 12},
+            PixelRect{x0: 0, y0: 7, x1: 16, y1: 8},
+            PixelRect{x0: 15, y0: 0, x1: 16, y1: 16},
+            PixelRect{x0: 0, y0: 0, x1: 16, y1: 16},
+        ];
+
+        for rect in &rects {
+            let expected: Vec<u32> = (rect.y0..rect.y1)
+                .flat_map(|y| (rect.x0..rect.x1).map(move |x| (x, y)))
+                .map(|(x, y)| image[((y * 16) + x) as usize])
+                .collect();
+
+            for chunk_rows in &[1, 2, 5] {
+                let config = MandelConfig{chunk_rows: *chunk_rows, .. config.clone()};
+
+                for method in RECT_METHOD_NAMES {
+                    let mut buffer = vec![0; rect.num_of_pixels()];
+                    render_rect(&config, method, rect, &mut buffer);
+
+                    assert!(buffer == expected, "method '{}', {:?}, chunk_rows: {}", method, rect, chunk_rows);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn previous_image_equals_full_render() {
+        let previous = MandelConfig{max_iter: 16, .. tiny_config()};
+        let mut previous_image = vec![0; 16 * 16];
+        serial(&previous, &mut previous_image, &AtomicBool::new(false));
+
+        let config = MandelConfig{max_iter: 256, .. tiny_config()};
+        let mut expected = vec![0; 16 * 16];
+        serial(&config, &mut expected, &AtomicBool::new(false));
+
+        let rect = PixelRect{x0: 0, y0: 0, x1: 16, y1: 16};
+        let refined = MandelConfig{previous_max_iter: Some(16), .. config.clone()};
+
+        for method in RECT_METHOD_NAMES {
+            let mut image = previous_image.clone();
+            render_rect(&refined, method, &rect, &mut image);
+            assert!(image == expected, "method '{}'", method);
+        }
+
+        // Only the pixels that reached the old max_iter are computed again: the others keep even a wrong value
+        let mut image = previous_image.clone();
+        let escaped = image.iter().position(|&value| value < 16).unwrap();
+        image[escaped] = 1;
+        render_rect(&refined, "serial", &rect, &mut image);
+        assert_eq!(image[escaped], 1);
+        assert!(image.iter().zip(&expected).enumerate().all(|(n, (a, b))| n == escaped || a == b));
+    }
+
+    #[test]
+    fn rows_beyond_u32_indices() {
+        // 131072 * 131072 pixels, only two rows are rendered: their pixels are behind index 2^32 of the whole image.
+        // With max_iter 1 the points outside of the circle of radius 2 are 0, the others 1.
+        let config = MandelConfig{img_size: 131072, max_iter: 1, num_threads: 3, chunk_rows: 1, .. MandelConfig::default()};
+        let rect = PixelRect{x0: 0, y0: 40000, x1: 131072, y1: 40002};
+        assert!(config.pixel_index(rect.x0, rect.y0) as u64 > (u32::MAX as u64));
+
+        let expected: Vec<u32> = (rect.y0..rect.y1)
+            .flat_map(|y| (rect.x0..rect.x1).map(move |x| (x, y)))
+            .map(|(x, y)| mandel_iter_z0(1, config.z0(), pixel_to_complex(&config, x, y)))
+            .collect();
+        assert!(expected.contains(&0) && expected.contains(&1));
+
+        for method in RECT_METHOD_NAMES {
+            let mut buffer = vec![7; rect.num_of_pixels()];
+            render_rect(&config, method, &rect, &mut buffer);
+            assert!(buffer == expected, "method '{}'", method);
+        }
+    }
+}
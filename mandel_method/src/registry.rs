@@ -0,0 +1,116 @@
+// Maps method names to the corresponding function, so that the main program
+// can pick the methods to run at run time instead of hard coding each call.
+//
+// To add a new method: implement it in lib.rs and add its name to METHOD_NAMES
+// and get_method below.
+
+// Internal modules
+use mandel_util::MandelConfig;
+use super::*;
+
+// All methods share the same signature, the flag cancels the computation (see --method_timeout_ms)
+pub type MandelFunc = fn(&MandelConfig, &mut [u32], &AtomicBool);
+
+// Names of all available methods, in the order they are run by default
+// (on wasm32 only the methods that don't need OS threads, rayon_portable_simd only with the feature portable_simd)
+static METHOD_NAMES: &'static [&'static str] = &[
+    "serial",
+    #[cfg(not(target_arch = "wasm32"))]
+    "scoped_thread_pool",
+    "rayon_join",
+    #[cfg(not(target_arch = "wasm32"))]
+    "rayon_join_dynamic",
+    "rayon_par_iter",
+    "rayon_par_iter_collect",
+    "rayon_par_iter_no_div",
+    "rayon_par_bridge",
+    "rayon_adaptive_weighted",
+    "rayon_perturbation",
+    #[cfg(feature = "portable_simd")]
+    "rayon_portable_simd",
+    #[cfg(not(target_arch = "wasm32"))]
+    "rust_scoped_pool",
+    #[cfg(not(target_arch = "wasm32"))]
+    "job_steal",
+    #[cfg(not(target_arch = "wasm32"))]
+    "job_steal_join",
+    #[cfg(not(target_arch = "wasm32"))]
+    "kirk_crossbeam",
+    #[cfg(not(target_arch = "wasm32"))]
+    "crossbeam_pipeline",
+    #[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+    "tokio_spawn_blocking",
+    #[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+    "tokio_spawn",
+    #[cfg(not(target_arch = "wasm32"))]
+    "null_method",
+];
+
+// Returns the names of all available methods
+pub fn method_names() -> &'static [&'static str] {
+    METHOD_NAMES
+}
+
+// Returns true if the method uses more than one thread
+pub fn is_parallel(name: &str) -> bool {
+    name != "serial"
+}
+
+// Returns true if the method pins its worker threads when --pin_threads is set
+pub fn supports_pinning(name: &str) -> bool {
+    is_parallel(name)
+}
+
+// Returns true if the method distributes the image in jobs of --chunk_rows rows
+pub fn supports_chunking(name: &str) -> bool {
+    name != "serial" && name != "rayon_par_iter" && name != "rayon_par_iter_collect" && name != "rayon_par_iter_no_div" &&
+        name != "tokio_spawn" && name != "crossbeam_pipeline"
+}
+
+// Returns true if the method computes the image in a different way than serial,
+// so that a few pixels can differ (rounding)
+pub fn is_approximate(name: &str) -> bool {
+    name == "rayon_perturbation"
+}
+
+// Returns true if the method only measures the overhead and doesn't compute the image
+pub fn is_baseline(name: &str) -> bool {
+    name == "null_method"
+}
+
+// Look up a method by its name, returns None if there is no such method
+pub fn get_method(name: &str) -> Option<MandelFunc> {
+    match name {
+        "serial" => Some(serial),
+        #[cfg(not(target_arch = "wasm32"))]
+        "scoped_thread_pool" => Some(scoped_thread_pool_),
+        "rayon_join" => Some(rayon_join),
+        #[cfg(not(target_arch = "wasm32"))]
+        "rayon_join_dynamic" => Some(rayon_join_dynamic),
+        "rayon_par_iter" => Some(rayon_par_iter),
+        "rayon_par_iter_collect" => Some(rayon_par_iter_collect),
+        "rayon_par_iter_no_div" => Some(rayon_par_iter_no_div),
+        "rayon_par_bridge" => Some(rayon_par_bridge),
+        "rayon_adaptive_weighted" => Some(rayon_adaptive_weighted),
+        "rayon_perturbation" => Some(rayon_perturbation),
+        #[cfg(feature = "portable_simd")]
+        "rayon_portable_simd" => Some(rayon_portable_simd),
+        #[cfg(not(target_arch = "wasm32"))]
+        "rust_scoped_pool" => Some(rust_scoped_pool),
+        #[cfg(not(target_arch = "wasm32"))]
+        "job_steal" => Some(job_steal),
+        #[cfg(not(target_arch = "wasm32"))]
+        "job_steal_join" => Some(job_steal_join),
+        #[cfg(not(target_arch = "wasm32"))]
+        "kirk_crossbeam" => Some(kirk_crossbeam),
+        #[cfg(not(target_arch = "wasm32"))]
+        "crossbeam_pipeline" => Some(crossbeam_pipeline),
+        #[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+        "tokio_spawn_blocking" => Some(tokio_spawn_blocking),
+        #[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+        "tokio_spawn" => Some(tokio_spawn_method),
+        #[cfg(not(target_arch = "wasm32"))]
+        "null_method" => Some(null_method),
+        _ => None
+    }
+}
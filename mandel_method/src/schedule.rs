@@ -0,0 +1,178 @@
+// Recording and replay of the schedule of the dynamic methods (--trace_schedule, --replay_schedule),
+// see mandel_util::schedule for the trace file.
+//
+// Like for pinning (see affinity) the pools don't tell us which worker runs a job: each thread gets the next
+// worker number when it records its first job. Every worker appends to a buffer of its own, only the
+// sequence number of the jobs is shared (one atomic add per job). The buffers are merged when the method is done.
+
+// Rust modules
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+// Internal modules
+use mandel_util::MandelConfig;
+use mandel_util::schedule::{trace_file_name, write_schedule, Schedule, ScheduleEntry};
+use crate::{cancelled, compute_chunk};
+
+// Each recorder gets a unique id, so a thread that is reused by the next method gets a new buffer
+static NEXT_RECORDER_ID: AtomicUsize = AtomicUsize::new(1);
+
+// The jobs of one worker with their sequence numbers
+type WorkerBuffer = Arc<Mutex<Vec<(u64, ScheduleEntry)>>>;
+
+// The recorder id, the worker number and the buffer of the current thread
+thread_local!(static WORKER_BUFFER: RefCell<Option<(usize, u32, WorkerBuffer)>> = RefCell::new(None));
+
+pub struct ScheduleRecorder {
+    id: usize,
+    next_sequence: AtomicU64,
+    buffers: Mutex<Vec<WorkerBuffer>>
+}
+
+impl ScheduleRecorder {
+    // Returns None without --trace_schedule
+    pub fn new(mandel_config: &MandelConfig) -> Option<ScheduleRecorder> {
+        mandel_config.trace_schedule.as_ref().map(|_| ScheduleRecorder{
+            id: NEXT_RECORDER_ID.fetch_add(1, Ordering::SeqCst),
+            next_sequence: AtomicU64::new(0),
+            buffers: Mutex::new(Vec::new())
+        })
+    }
+
+    // The current thread starts the job of num_of_rows lines from first_row
+    pub fn record(&self, first_row: u32, num_of_rows: u32) {
+        let sequence = self.next_sequence.fetch_add(1, Ordering::SeqCst);
+
+        WORKER_BUFFER.with(|worker_buffer| {
+            let mut worker_buffer = worker_buffer.borrow_mut();
+
+            if worker_buffer.as_ref().map_or(true, |&(id, _, _)| id != self.id) {
+                let mut buffers = self.buffers.lock().expect("Schedule recorder poisoned");
+                let buffer = WorkerBuffer::default();
+                buffers.push(buffer.clone());
+                *worker_buffer = Some((self.id, (buffers.len() - 1) as u32, buffer));
+            }
+
+            let &(_, worker, ref buffer) = worker_buffer.as_ref().unwrap();
+            buffer.lock().expect("Schedule buffer poisoned")
+                .push((sequence, ScheduleEntry{worker: worker, first_row: first_row, num_of_rows: num_of_rows}));
+        });
+    }
+
+    // All recorded jobs in the order they were started. Call it after all workers are done.
+    pub fn schedule(&self, method: &str, mandel_config: &MandelConfig) -> Schedule {
+        let mut entries: Vec<(u64, ScheduleEntry)> = self.buffers.lock().expect("Schedule recorder poisoned").iter()
+            .flat_map(|buffer| buffer.lock().expect("Schedule buffer poisoned").clone())
+            .collect();
+        entries.sort_by_key(|&(sequence, _)| sequence);
+
+        Schedule{method: method.to_string(), config: mandel_config.to_string(),
+            entries: entries.into_iter().map(|(_, entry)| entry).collect()}
+    }
+}
+
+// Record the job if the schedule is traced
+pub fn record(recorder: &Option<ScheduleRecorder>, first_row: u32, num_of_rows: u32) {
+    if let Some(ref recorder) = *recorder {
+        recorder.record(first_row, num_of_rows);
+    }
+}
+
+// Write the trace of the method (see trace_file_name) if the schedule is traced.
+// The methods can't return an error, so a trace that can't be written is only reported.
+pub fn write_trace(recorder: &Option<ScheduleRecorder>, method: &str, mandel_config: &MandelConfig) {
+    if let (Some(ref recorder), Some(ref file_name)) = (recorder, &mandel_config.trace_schedule) {
+        let file_name = trace_file_name(file_name, method);
+
+        match write_schedule(&file_name, &recorder.schedule(method, mandel_config)) {
+            Ok(()) => println!("Schedule trace written ({}): {}", method, file_name),
+            Err(e) => println!("Can't write schedule trace '{}': {}", file_name, e)
+        }
+    }
+}
+
+// The deterministic executor of --replay_schedule: computes the jobs of the schedule one after the other
+// on the current thread, in the order they were started in the traced run.
+// The schedule must fit the image, see Schedule::check.
+pub fn replay(mandel_config: &MandelConfig, image: &mut [u32], schedule: &Schedule, cancel: &AtomicBool) {
+    let img_size = mandel_config.img_size as usize;
+
+    for entry in &schedule.entries {
+        if cancelled(cancel) {
+            return;
+        }
+
+        let start = (entry.first_row as usize) * img_size;
+        let end = start + (entry.num_of_rows as usize) * img_size;
+        compute_chunk(mandel_config, &mut image[start..end], entry.first_row, cancel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use mandel_util::schedule::read_schedule;
+    use crate::registry::get_method;
+
+    #[test]
+    fn recorded_schedules_replay_to_the_same_image() {
+        let default = MandelConfig::default();
+        let trace = env::temp_dir().join(format!("mandel_trace_{}.txt", std::process::id()));
+
+        let config = MandelConfig{
+            img_size: 64,
+            max_iter: 256,
+            num_threads: 4,
+            chunk_rows: 2,
+            trace_schedule: Some(trace.to_str().unwrap().to_string()),
+            .. default
+        };
+
+        for method in &["job_steal", "job_steal_join", "kirk_crossbeam"] {
+            let mut image = vec![0; config.num_of_pixels()];
+            get_method(method).unwrap()(&config, &mut image, &AtomicBool::new(false));
+
+            let file_name = trace_file_name(config.trace_schedule.as_ref().unwrap(), method);
+            let schedule = read_schedule(&file_name).unwrap();
+            fs::remove_file(&file_name).unwrap();
+
+            assert_eq!(schedule.method, *method);
+            assert_eq!(schedule.config, config.to_string());
+            assert_eq!(schedule.check(config.img_size), Ok(()), "{}", method);
+            assert!(schedule.entries.iter().all(|entry| entry.num_of_rows == 2), "{}", method);
+
+            let mut replayed = vec![0; config.num_of_pixels()];
+            replay(&config, &mut replayed, &schedule, &AtomicBool::new(false));
+            assert!(replayed == image, "{}", method);
+        }
+
+        // Without --trace_schedule nothing is recorded
+        assert!(ScheduleRecorder::new(&MandelConfig::default()).is_none());
+    }
+
+    #[test]
+    fn workers_have_their_own_numbers() {
+        let config = MandelConfig{trace_schedule: Some("unused".to_string()), .. MandelConfig::default()};
+        let recorder = ScheduleRecorder::new(&config).unwrap();
+
+        recorder.record(0, 1);
+        std::thread::scope(|scope| {
+            scope.spawn(|| recorder.record(1, 1));
+        });
+        recorder.record(2, 1);
+
+        let entries = recorder.schedule("job_steal", &config).entries;
+        assert_eq!(entries.iter().map(|entry| (entry.worker, entry.first_row)).collect::<Vec<_>>(), vec![(0, 0), (1, 1), (0, 2)]);
+
+        // A new recorder numbers the workers again
+        let recorder = ScheduleRecorder::new(&config).unwrap();
+        std::thread::scope(|scope| {
+            scope.spawn(|| recorder.record(5, 1));
+        });
+        recorder.record(6, 1);
+        assert_eq!(recorder.schedule("job_steal", &config).rows_per_worker(), vec![1, 1]);
+    }
+}
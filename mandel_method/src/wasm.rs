@@ -0,0 +1,119 @@
+// JavaScript interface for wasm32-unknown-unknown (feature "wasm").
+//
+// Build with wasm-pack or by hand:
+//
+//     cargo build --release --target wasm32-unknown-unknown --features wasm
+//     wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/mandel_method.wasm
+//
+// render_region computes the whole image in the calling thread. To keep the page responsive
+// the image can be split into strips of rows, each Web Worker calls render_rows for its strip.
+//
+// Example (JavaScript):
+//
+//     const counts = render_region(-2.0, 1.0, -1.5, 1.5, 512, 512, 1024);
+//     const rgba = color_to_rgba(counts, 1024);
+//     ctx.putImageData(new ImageData(new Uint8ClampedArray(rgba.buffer), 512, 512), 0, 0);
+
+// External modules
+use rayon::prelude::*;
+use wasm_bindgen::prelude::*;
+
+// Internal modules
+use mandel_util::{render_row, escape_color, MandelConfig};
+
+// Iteration counts of the given region, width * height values row by row, row 0 is the top (img2).
+#[wasm_bindgen]
+pub fn render_region(re1: f64, re2: f64, img1: f64, img2: f64, width: u32, height: u32, max_iter: u32) -> Vec<u32> {
+    render_rows(re1, re2, img1, img2, width, height, max_iter, 0, height)
+}
+
+// Like render_region, but only the rows first_row .. first_row + num_rows (width * num_rows values).
+// Rows outside of the image are left out.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn render_rows(re1: f64, re2: f64, img1: f64, img2: f64, width: u32, height: u32, max_iter: u32,
+    first_row: u32, num_rows: u32) -> Vec<u32> {
+    let first_row = first_row.min(height);
+    let num_rows = num_rows.min(height - first_row);
+
+    // The image doesn't have to be square: img_size is the height (used for the rows).
+    // The step sizes follow from img_size, so re2 is moved to where height steps of (re2 - re1) / width end.
+    let mandel_config = MandelConfig{
+        re1: re1,
+        re2: re1 + ((re2 - re1) * (height as f64) / (width as f64)),
+        img1: img1,
+        img2: img2,
+        max_iter: max_iter,
+        img_size: height,
+        .. MandelConfig::default()
+    };
+
+    let mut image = vec![0; (width as usize) * (num_rows as usize)];
+
+    // Without thread support rayon runs everything in the calling thread
+    image.par_chunks_mut((width as usize).max(1)).enumerate().for_each(|(y, row)| {
+        render_row(&mandel_config, first_row + (y as u32), row);
+    });
+
+    image
+}
+
+// Colors of the iteration counts as RGBA bytes (4 per pixel) for an ImageData of a canvas.
+// Same colors as the PPM images, the inside of the set is black.
+#[wasm_bindgen]
+pub fn color_to_rgba(counts: &[u32], max_iter: u32) -> Vec<u8> {
+    let mut rgba = Vec::with_capacity(4 * counts.len());
+
+    for &count in counts {
+        let (red, green, blue) = if count == max_iter { (0, 0, 0) } else { escape_color(count) };
+        rgba.extend_from_slice(&[red, green, blue, 255]);
+    }
+
+    rgba
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serial;
+    use std::sync::atomic::AtomicBool;
+
+    // Same checksum as in tests/wasm.rs
+    const REGION_16_CHECKSUM: u32 = 2659287258;
+
+    fn checksum(image: &[u32]) -> u32 {
+        image.iter().fold(0u32, |sum, &value| sum.wrapping_mul(31).wrapping_add(value))
+    }
+
+    #[test]
+    fn region_like_serial() {
+        let mandel_config = MandelConfig{
+            img_size: 16,
+            max_iter: 64,
+            .. MandelConfig::default()
+        };
+        let mut expected = vec![0; 16 * 16];
+        serial(&mandel_config, &mut expected, &AtomicBool::new(false));
+
+        let image = render_region(-2.0, 1.0, -1.5, 1.5, 16, 16, 64);
+
+        assert!(image == expected);
+        assert_eq!(checksum(&image), REGION_16_CHECKSUM);
+    }
+
+    #[test]
+    fn strips_make_up_the_region() {
+        let image = render_region(-2.0, 1.0, -1.0, 1.0, 24, 16, 64);
+        let mut strips = render_rows(-2.0, 1.0, -1.0, 1.0, 24, 16, 64, 0, 5);
+        strips.extend(render_rows(-2.0, 1.0, -1.0, 1.0, 24, 16, 64, 5, 100));
+
+        assert_eq!(image.len(), 24 * 16);
+        assert!(strips == image);
+        assert!(render_rows(-2.0, 1.0, -1.0, 1.0, 24, 16, 64, 20, 5).is_empty());
+    }
+
+    #[test]
+    fn rgba_colors() {
+        assert_eq!(color_to_rgba(&[64, 0, 17], 64), vec![0, 0, 0, 255, 255, 0, 0, 255, 255, 16, 0, 255]);
+    }
+}
@@ -0,0 +1,24 @@
+// Headless test of the JavaScript interface, runs in node with wasm-bindgen-test-runner:
+//
+//     cargo install wasm-bindgen-cli
+//     CARGO_TARGET_WASM32_UNKNOWN_UNKNOWN_RUNNER=wasm-bindgen-test-runner \
+//         cargo test --target wasm32-unknown-unknown --features wasm --test wasm
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+extern crate mandel_method;
+extern crate wasm_bindgen_test;
+
+use mandel_method::wasm::{render_region, color_to_rgba};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+// Same checksum as in src/wasm.rs
+const REGION_16_CHECKSUM: u32 = 2659287258;
+
+#[wasm_bindgen_test]
+fn render_16x16_region() {
+    let image = render_region(-2.0, 1.0, -1.5, 1.5, 16, 16, 64);
+
+    assert_eq!(image.len(), 16 * 16);
+    assert_eq!(image.iter().fold(0u32, |sum, &value| sum.wrapping_mul(31).wrapping_add(value)), REGION_16_CHECKSUM);
+    assert_eq!(color_to_rgba(&image, 64).len(), 4 * 16 * 16);
+}
@@ -0,0 +1,236 @@
+// Jobs file for the batch mode (--batch=jobs.toml).
+//
+// Each job is a [[job]] table, missing keys are taken from the command line options:
+//
+//     [[job]]
+//     file_name = "seahorse.ppm"
+//     re1 = -0.76
+//     re2 = -0.72
+//     img1 = 0.08
+//     img2 = 0.12
+//     max_iter = 8192
+//     img_size = 1024
+//     method = "rayon_join"
+//     interior_coloring = "distance"
+//
+// A jobs file that ends with .csv has one job per line instead, the header names the keys.
+// Empty cells are taken from the command line options like missing keys:
+//
+//     file_name,max_iter,method
+//     iter_256.ppm,256,
+//     iter_512.ppm,512,serial
+//
+// A job with invalid parameters does not invalidate the whole file, it is returned as error
+// so that the batch can report and skip it.
+
+// External modules
+use csv;
+use toml;
+
+// Rust modules
+use std::fs::File;
+use std::io::prelude::Read;
+
+// Internal modules
+use super::{MandelConfig, ConfigError};
+use interior::InteriorColoring;
+use coloring::ColoringMethod;
+
+// Method that is used if a job doesn't specify one
+pub const DEFAULT_BATCH_METHOD: &'static str = "rayon_join";
+
+// One render job of the jobs file
+#[derive(Clone)]
+pub struct BatchJob {
+    // Output PPM image
+    pub file_name: String,
+    pub method: String,
+    pub config: MandelConfig
+}
+
+// Parse all jobs of a jobs file.
+// Returns an error if the file itself is not valid, otherwise one result per job.
+pub fn parse_jobs(content: &str, base_config: &MandelConfig) -> Result<Vec<Result<BatchJob, ConfigError>>, ConfigError> {
+    let table = try!(content.parse::<toml::Table>().map_err(|e| ConfigError::ParseError(format!("{}", e))));
+
+    for key in table.keys() {
+        if key != "job" {
+            return Err(ConfigError::ParseError(format!("unknown key: '{}', expected [[job]] tables", key)));
+        }
+    }
+
+    match table.get("job") {
+        Some(&toml::Value::Array(ref jobs)) => Ok(jobs.iter().enumerate().map(|(n, job)| parse_job(n, job, base_config)).collect()),
+        Some(_) => Err(ConfigError::ParseError("'job' must be an array of tables: [[job]]".to_string())),
+        None => Ok(Vec::new())
+    }
+}
+
+// Parse all jobs of a CSV jobs file, each line is turned into a [[job]] table and parsed like one
+pub fn parse_csv_jobs(content: &str, base_config: &MandelConfig) -> Result<Vec<Result<BatchJob, ConfigError>>, ConfigError> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(content.as_bytes());
+    let header = try!(reader.headers().map_err(|e| ConfigError::ParseError(format!("invalid header: {}", e)))).clone();
+
+    Ok(reader.records().enumerate().map(|(n, record)| {
+        let record = try!(record.map_err(|e| ConfigError::ParseError(format!("job {}: {}", n + 1, e))));
+        let job = header.iter().zip(record.iter())
+            .filter(|&(_, cell)| !cell.is_empty())
+            .map(|(key, cell)| (key.to_string(), csv_value(cell)))
+            .collect::<toml::Table>();
+
+        parse_job(n, &toml::Value::Table(job), base_config)
+    }).collect())
+}
+
+// A CSV cell as TOML value: integer, float or string
+fn csv_value(cell: &str) -> toml::Value {
+    if let Ok(i) = cell.parse::<i64>() {
+        toml::Value::Integer(i)
+    } else if let Ok(f) = cell.parse::<f64>() {
+        toml::Value::Float(f)
+    } else {
+        toml::Value::String(cell.to_string())
+    }
+}
+
+// A TOML number as float, integers are converted (also used by the bookmarks)
+pub fn float(value: &toml::Value) -> Option<f64> {
+    match *value {
+        toml::Value::Float(f) => Some(f),
+        toml::Value::Integer(i) => Some(i as f64),
+        _ => None
+    }
+}
+
+// A TOML integer in 1 .. u32::MAX
+pub fn positive(value: &toml::Value) -> Option<u32> {
+    match *value {
+        toml::Value::Integer(i) if i > 0 && i <= (u32::MAX as i64) => Some(i as u32),
+        _ => None
+    }
+}
+
+// Read and parse a jobs file, TOML or CSV (.csv)
+pub fn read_jobs(file_name: &str, base_config: &MandelConfig) -> Result<Vec<Result<BatchJob, ConfigError>>, ConfigError> {
+    let mut content = String::new();
+
+    try!(File::open(file_name).and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(|e| ConfigError::ParseError(format!("can't read jobs file '{}': {}", file_name, e))));
+
+    if file_name.to_lowercase().ends_with(".csv") {
+        parse_csv_jobs(&content, base_config)
+    } else {
+        parse_jobs(&content, base_config)
+    }
+}
+
+fn parse_job(n: usize, job: &toml::Value, base_config: &MandelConfig) -> Result<BatchJob, ConfigError> {
+    let error = |message: String| ConfigError::ParseError(format!("job {}: {}", n + 1, message));
+
+    let table = match *job {
+        toml::Value::Table(ref table) => table,
+        _ => return Err(error("not a table".to_string()))
+    };
+
+    let mut config = base_config.clone();
+    let mut file_name = None;
+    let mut method = DEFAULT_BATCH_METHOD.to_string();
+
+    for (key, value) in table.iter() {
+        let invalid = || error(format!("invalid value for '{}': {}", key, value));
+
+        match key.as_str() {
+            "re1" => config.re1 = try!(float(value).ok_or_else(&invalid)),
+            "re2" => config.re2 = try!(float(value).ok_or_else(&invalid)),
+            "img1" => config.img1 = try!(float(value).ok_or_else(&invalid)),
+            "img2" => config.img2 = try!(float(value).ok_or_else(&invalid)),
+            "max_iter" => config.max_iter = try!(positive(value).ok_or_else(&invalid)),
+            "img_size" => config.img_size = try!(positive(value).ok_or_else(&invalid)),
+            "file_name" => file_name = Some(try!(value.as_str().ok_or_else(&invalid)).to_string()),
+            "method" => method = try!(value.as_str().ok_or_else(&invalid)).to_string(),
+            "interior_coloring" => config.interior_coloring = try!(value.as_str()
+                .and_then(|s| s.parse::<InteriorColoring>().ok()).ok_or_else(&invalid)),
+            "coloring" => config.coloring_method = try!(value.as_str()
+                .and_then(|s| s.parse::<ColoringMethod>().ok()).ok_or_else(&invalid)),
+            _ => return Err(error(format!("unknown key: '{}'", key)))
+        }
+    }
+
+    let file_name = try!(file_name.ok_or_else(|| error("missing 'file_name'".to_string())));
+
+    try!(config.validate().map_err(|e| error(format!("{}", e))));
+
+    Ok(BatchJob{file_name: file_name, method: method, config: config})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jobs_inherit_base_config() {
+        let base = MandelConfig{max_iter: 100, .. MandelConfig::default()};
+        let jobs = parse_jobs("[[job]]\nfile_name = \"a.ppm\"\nre1 = -1\nre2 = 0.5\nimg_size = 64\n\
+                               [[job]]\nfile_name = \"b.ppm\"\nmethod = \"serial\"\ninterior_coloring = \"period\"\n", &base).unwrap();
+
+        assert_eq!(jobs.len(), 2);
+
+        let a = jobs[0].as_ref().unwrap();
+        assert_eq!(a.file_name, "a.ppm");
+        assert_eq!(a.method, DEFAULT_BATCH_METHOD);
+        assert_eq!(a.config.max_iter, 100);
+        assert_eq!(a.config.img_size, 64);
+        assert_eq!(a.config.re1, -1.0);
+        assert_eq!(a.config.x_step(), 1.5 / 64.0);
+
+        let b = jobs[1].as_ref().unwrap();
+        assert_eq!(b.method, "serial");
+        assert_eq!(b.config.interior_coloring, InteriorColoring::Period);
+        assert_eq!(b.config.img_size, base.img_size);
+    }
+
+    #[test]
+    fn invalid_jobs_are_errors() {
+        let base = MandelConfig::default();
+        let jobs = parse_jobs("[[job]]\nre1 = 0\n\
+                               [[job]]\nfile_name = \"a.ppm\"\nre1 = 2.0\n\
+                               [[job]]\nfile_name = \"b.ppm\"\nmax_iter = -5\n\
+                               [[job]]\nfile_name = \"c.ppm\"\ncolor = \"red\"\n\
+                               [[job]]\nfile_name = \"d.ppm\"\n", &base).unwrap();
+
+        assert_eq!(jobs.len(), 5);
+        assert!(jobs[..4].iter().all(|job| job.is_err()));
+        assert!(jobs[4].is_ok());
+
+        assert!(parse_jobs("job = 1", &base).is_err());
+        assert!(parse_jobs("[[job]\n", &base).is_err());
+        assert!(parse_jobs("[other]\n", &base).is_err());
+    }
+
+    #[test]
+    fn csv_lines_are_jobs() {
+        let base = MandelConfig{max_iter: 100, .. MandelConfig::default()};
+        let jobs = parse_csv_jobs("file_name, max_iter, method, re1\n\
+                                   iter_256.ppm, 256, , \n\
+                                   iter_512.ppm, 512, serial, -1.5\n\
+                                   bad_iter.ppm, 0, , \n\
+                                   bad_re1.ppm, 256, , 3\n\
+                                   too_short.ppm\n", &base).unwrap();
+
+        assert_eq!(jobs.len(), 5);
+
+        let first = jobs[0].as_ref().unwrap();
+        assert_eq!((first.file_name.as_str(), first.method.as_str(), first.config.max_iter), ("iter_256.ppm", DEFAULT_BATCH_METHOD, 256));
+        assert_eq!(first.config.re1, base.re1);
+
+        let second = jobs[1].as_ref().unwrap();
+        assert_eq!((second.method.as_str(), second.config.max_iter, second.config.re1), ("serial", 512, -1.5));
+        assert_eq!(second.config.x_step(), 2.5 / (base.img_size as f64));
+
+        assert!(jobs[2..].iter().all(|job| job.is_err()));
+
+        // Unknown columns are errors of every job
+        assert!(parse_csv_jobs("file_name,color\na.ppm,red\n", &base).unwrap()[0].is_err());
+        assert_eq!(parse_csv_jobs("file_name,max_iter\n", &base).unwrap().len(), 0);
+    }
+}
@@ -0,0 +1,73 @@
+// Benchmark results in an SQLite database (--bench_db), for tracking the results of many runs
+// and experiments over a long time. The CSV files in plot/ are still written for the gnuplot script.
+//
+// All results go into the table benchmark_results, one row per method and run.
+// The table is created on the first write.
+
+// External modules
+use rusqlite::{params, Connection, Result};
+use time::now;
+
+// Internal modules
+use {compiler_version, BenchmarkResult, MandelConfig};
+
+const CREATE_TABLE: &'static str = "CREATE TABLE IF NOT EXISTS benchmark_results (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    method TEXT NOT NULL,
+    num_threads INTEGER NOT NULL,
+    img_size INTEGER NOT NULL,
+    max_iter INTEGER NOT NULL,
+    mean_ms REAL NOT NULL,
+    min_ms REAL NOT NULL,
+    max_ms REAL NOT NULL,
+    stddev_ms REAL NOT NULL,
+    rustc_version TEXT NOT NULL
+)";
+
+// Append the result of one method to the database, the file is created if it doesn't exist.
+// num_threads is taken from the result, it differs from the configuration with --auto_tune.
+pub fn write_benchmark_result_sqlite(db_path: &str, method: &str, config: &MandelConfig, result: &BenchmarkResult) -> Result<()> {
+    let connection = try!(Connection::open(db_path));
+    try!(connection.execute(CREATE_TABLE, []));
+
+    try!(connection.execute(
+        "INSERT INTO benchmark_results (timestamp, method, num_threads, img_size, max_iter, mean_ms, min_ms, max_ms, stddev_ms, rustc_version)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![now().rfc3339().to_string(), method, result.num_threads, config.img_size, config.max_iter,
+            result.mean_ms, result.min_ms, result.max_ms, result.stddev_ms, compiler_version]));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn results_are_appended() {
+        let db_path = env::temp_dir().join(format!("mandel_bench_db_test_{}.sqlite", ::std::process::id()));
+        let db_path = db_path.to_str().unwrap();
+        let _ = fs::remove_file(db_path);
+
+        let config = MandelConfig{img_size: 64, max_iter: 128, .. MandelConfig::default()};
+        let result = BenchmarkResult{method: "rayon_join".to_string(), num_threads: 4, mean_ms: 2.5, min_ms: 2.0, max_ms: 3.0, stddev_ms: 0.5, timed_out: false};
+
+        write_benchmark_result_sqlite(db_path, "serial", &config, &BenchmarkResult{num_threads: 1, .. result.clone()}).unwrap();
+        write_benchmark_result_sqlite(db_path, "rayon_join", &config, &result).unwrap();
+
+        let connection = Connection::open(db_path).unwrap();
+        let count: i64 = connection.query_row("SELECT COUNT(*) FROM benchmark_results", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 2);
+
+        let (method, num_threads, img_size, max_iter, mean_ms, rustc_version): (String, u32, u32, u32, f64, String) = connection.query_row(
+            "SELECT method, num_threads, img_size, max_iter, mean_ms, rustc_version FROM benchmark_results ORDER BY id DESC LIMIT 1", [],
+            |row| Ok((try!(row.get(0)), try!(row.get(1)), try!(row.get(2)), try!(row.get(3)), try!(row.get(4)), try!(row.get(5))))).unwrap();
+        assert_eq!((method.as_str(), num_threads, img_size, max_iter, mean_ms), ("rayon_join", 4, 64, 128, 2.5));
+        assert_eq!(rustc_version, compiler_version);
+
+        fs::remove_file(db_path).unwrap();
+    }
+}
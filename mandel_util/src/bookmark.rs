@@ -0,0 +1,291 @@
+// Named views of the mandelbrot set (--bookmark, --save_bookmark).
+//
+// A bookmark is a table of a TOML file, the name of the table is the name of the bookmark:
+//
+//     [seahorse_valley]
+//     center = [-0.745, 0.113]
+//     zoom = 40.0
+//     max_iter = 1024
+//     color_scale = "log"
+//
+// color_cycle, color_offset, color_scale and coloring are optional, without them the command line options are used.
+// The bookmarks of the user (~/.config/mandel-rust/bookmarks.toml) override the built-in ones (bookmarks.toml
+// of mandel_util) with the same name. --save_bookmark appends to the file of the user.
+
+// External modules
+use toml;
+
+// Rust modules
+use std::env;
+use std::fs;
+use std::io;
+use std::io::prelude::Write;
+use std::path::{Path, PathBuf};
+
+// Internal modules
+use super::{ConfigError, MandelConfig, FULL_VIEW_SIZE};
+use batch::{float, positive};
+use coloring::ColoringMethod;
+use error::MandelError;
+use palette::ColorScale;
+use region::MandelbrotRegion;
+
+// The built-in bookmarks
+pub const BUILTIN_BOOKMARKS: &'static str = include_str!("../bookmarks.toml");
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Bookmark {
+    pub name: String,
+    // RE, IM of the center of the view
+    pub center: (f64, f64),
+    // See MandelConfig::zoom
+    pub zoom: f64,
+    pub max_iter: u32,
+    pub color_cycle: Option<u32>,
+    pub color_offset: Option<u32>,
+    pub color_scale: Option<ColorScale>,
+    pub coloring_method: Option<ColoringMethod>
+}
+
+impl Bookmark {
+    // The view and the colors of the configuration
+    pub fn from_config(name: &str, mandel_config: &MandelConfig) -> Bookmark {
+        Bookmark{
+            name: name.to_string(),
+            center: MandelbrotRegion::from_config(mandel_config).center(),
+            zoom: mandel_config.zoom(),
+            max_iter: mandel_config.max_iter,
+            color_cycle: mandel_config.color_cycle,
+            color_offset: Some(mandel_config.color_offset),
+            color_scale: Some(mandel_config.color_scale),
+            coloring_method: Some(mandel_config.coloring_method)
+        }
+    }
+
+    // Copy of the configuration that shows the square view of the bookmark, the colors that the bookmark
+    // doesn't have are kept
+    pub fn apply_to(&self, mandel_config: &MandelConfig) -> MandelConfig {
+        let size = FULL_VIEW_SIZE / self.zoom;
+        let config = MandelbrotRegion::from_center(self.center, size, size).apply_to(mandel_config);
+
+        MandelConfig{
+            max_iter: self.max_iter,
+            color_cycle: self.color_cycle.or(config.color_cycle),
+            color_offset: self.color_offset.unwrap_or(config.color_offset),
+            color_scale: self.color_scale.unwrap_or(config.color_scale),
+            coloring_method: self.coloring_method.unwrap_or(config.coloring_method),
+            .. config
+        }
+    }
+
+    // The bookmark as TOML table, the floats are written with all digits
+    pub fn to_toml(&self) -> String {
+        let mut table = format!("[{}]\ncenter = [{:?}, {:?}]\nzoom = {:?}\nmax_iter = {}\n",
+            self.name, self.center.0, self.center.1, self.zoom, self.max_iter);
+
+        if let Some(color_cycle) = self.color_cycle {
+            table.push_str(&format!("color_cycle = {}\n", color_cycle));
+        }
+        if let Some(color_offset) = self.color_offset {
+            table.push_str(&format!("color_offset = {}\n", color_offset));
+        }
+        if let Some(color_scale) = self.color_scale {
+            table.push_str(&format!("color_scale = \"{}\"\n", color_scale));
+        }
+        if let Some(coloring_method) = self.coloring_method {
+            table.push_str(&format!("coloring = \"{}\"\n", coloring_method));
+        }
+
+        table
+    }
+}
+
+// The bookmarks file of the user: ~/.config/mandel-rust/bookmarks.toml, None without a home directory
+pub fn user_bookmarks_file() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| Path::new(&home).join(".config").join("mandel-rust").join("bookmarks.toml"))
+}
+
+// Parse all bookmarks of a bookmarks file, source is the name of the file for the error messages
+pub fn parse_bookmarks(content: &str, source: &str) -> Result<Vec<Bookmark>, ConfigError> {
+    let table = try!(content.parse::<toml::Table>().map_err(|e| ConfigError::ParseError(format!("{}: {}", source, e))));
+
+    table.iter().map(|(name, entry)| parse_bookmark(name, entry)
+        .map_err(|message| ConfigError::ParseError(format!("{}: bookmark '{}': {}", source, name, message))))
+        .collect()
+}
+
+fn parse_bookmark(name: &str, entry: &toml::Value) -> Result<Bookmark, String> {
+    let table = try!(entry.as_table().ok_or_else(|| "not a table".to_string()));
+    let mut center = None;
+    let mut zoom = None;
+    let mut max_iter = None;
+    let mut bookmark = Bookmark{name: name.to_string(), center: (0.0, 0.0), zoom: 1.0, max_iter: 1,
+        color_cycle: None, color_offset: None, color_scale: None, coloring_method: None};
+
+    for (key, value) in table.iter() {
+        let invalid = || format!("invalid value for '{}': {}", key, value);
+
+        match key.as_str() {
+            "center" => center = Some(try!(match value.as_array().map(|array| array.as_slice()) {
+                Some(&[ref re, ref im]) => float(re).and_then(|re| float(im).map(|im| (re, im))),
+                _ => None
+            }.ok_or_else(&invalid))),
+            "zoom" => zoom = Some(try!(float(value).filter(|&zoom| zoom > 0.0 && zoom.is_finite()).ok_or_else(&invalid))),
+            "max_iter" => max_iter = Some(try!(positive(value).ok_or_else(&invalid))),
+            "color_cycle" => bookmark.color_cycle = Some(try!(positive(value).ok_or_else(&invalid))),
+            "color_offset" => bookmark.color_offset = Some(try!(value.as_integer()
+                .filter(|&offset| offset >= 0 && offset <= (u32::MAX as i64)).ok_or_else(&invalid)) as u32),
+            "color_scale" => bookmark.color_scale = Some(try!(value.as_str()
+                .and_then(|s| s.parse::<ColorScale>().ok()).ok_or_else(&invalid))),
+            "coloring" => bookmark.coloring_method = Some(try!(value.as_str()
+                .and_then(|s| s.parse::<ColoringMethod>().ok()).ok_or_else(&invalid))),
+            _ => return Err(format!("unknown key: '{}'", key))
+        }
+    }
+
+    bookmark.center = try!(center.ok_or_else(|| "missing 'center'".to_string()));
+    bookmark.zoom = try!(zoom.ok_or_else(|| "missing 'zoom'".to_string()));
+    bookmark.max_iter = try!(max_iter.ok_or_else(|| "missing 'max_iter'".to_string()));
+
+    Ok(bookmark)
+}
+
+// The bookmarks of the user file, none if the file doesn't exist
+fn read_user_bookmarks(user_file: &Path) -> Result<Vec<Bookmark>, MandelError> {
+    match fs::read_to_string(user_file) {
+        Ok(content) => parse_bookmarks(&content, &user_file.display().to_string()).map_err(MandelError::from),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(MandelError::io(&user_file.display().to_string(), e))
+    }
+}
+
+// Look up a bookmark: first in the user file (if there is one), then in the built-in bookmarks
+pub fn find_bookmark(name: &str, user_file: Option<&Path>) -> Result<Bookmark, MandelError> {
+    let mut bookmarks = match user_file {
+        Some(user_file) => try!(read_user_bookmarks(user_file)),
+        None => Vec::new()
+    };
+    bookmarks.extend(try!(parse_bookmarks(BUILTIN_BOOKMARKS, "built-in bookmarks")));
+
+    match bookmarks.iter().find(|bookmark| bookmark.name == name) {
+        Some(bookmark) => Ok(bookmark.clone()),
+        None => {
+            let mut names: Vec<&str> = bookmarks.iter().map(|bookmark| bookmark.name.as_str()).collect();
+            names.sort();
+            names.dedup();
+            Err(MandelError::Config(ConfigError::InvalidValue(
+                format!("unknown bookmark: '{}', available bookmarks: {}", name, names.join(", ")))))
+        }
+    }
+}
+
+// Append the bookmark to the user file, the file and its directory are created if needed.
+// A bookmark with the same name (in the user file or built-in) is only replaced with force.
+// Replacing a bookmark of the user file writes the whole file again, its comments are lost.
+pub fn save_bookmark(user_file: &Path, bookmark: &Bookmark, force: bool) -> Result<(), MandelError> {
+    let file_name = user_file.display().to_string();
+
+    if bookmark.name.is_empty() || !bookmark.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(MandelError::Config(ConfigError::InvalidValue(
+            format!("invalid bookmark name: '{}', only letters, digits, '_' and '-' are allowed", bookmark.name))));
+    }
+
+    let user_bookmarks = try!(read_user_bookmarks(user_file));
+    let in_user_file = user_bookmarks.iter().any(|other| other.name == bookmark.name);
+    let builtin = try!(parse_bookmarks(BUILTIN_BOOKMARKS, "built-in bookmarks")).iter().any(|other| other.name == bookmark.name);
+
+    if (in_user_file || builtin) && !force {
+        return Err(MandelError::Config(ConfigError::InvalidValue(
+            format!("bookmark '{}' already exists, use --force to replace it", bookmark.name))));
+    }
+
+    if let Some(directory) = user_file.parent() {
+        try!(fs::create_dir_all(directory).map_err(|e| MandelError::io(&directory.display().to_string(), e)));
+    }
+
+    if in_user_file {
+        let content = user_bookmarks.iter()
+            .map(|other| if other.name == bookmark.name { bookmark.to_toml() } else { other.to_toml() })
+            .collect::<Vec<String>>()
+            .join("\n");
+        fs::write(user_file, content).map_err(|e| MandelError::io(&file_name, e))
+    } else {
+        let mut file = try!(fs::OpenOptions::new().create(true).append(true).open(user_file)
+            .map_err(|e| MandelError::io(&file_name, e)));
+        let separator = if user_bookmarks.is_empty() { "" } else { "\n" };
+        write!(file, "{}{}", separator, bookmark.to_toml()).map_err(|e| MandelError::io(&file_name, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("mandel_bookmark_test_{}_{}", name, ::std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory.join("mandel-rust").join("bookmarks.toml")
+    }
+
+    #[test]
+    fn builtin_bookmarks() {
+        let bookmarks = parse_bookmarks(BUILTIN_BOOKMARKS, "built-in bookmarks").unwrap();
+        assert!(bookmarks.len() >= 4);
+
+        let seahorse = find_bookmark("seahorse_valley", None).unwrap();
+        assert_eq!((seahorse.center, seahorse.zoom, seahorse.max_iter), ((-0.745, 0.113), 40.0, 1024));
+
+        // The full view is the default region
+        let full = find_bookmark("full", None).unwrap().apply_to(&MandelConfig::default());
+        assert_eq!(full, MandelConfig{max_iter: 256, .. MandelConfig::default()});
+
+        match find_bookmark("atlantis", None) {
+            Err(MandelError::Config(e)) => assert!(e.to_string().contains("seahorse_valley")),
+            other => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn user_file_overrides_builtin() {
+        let user_file = temp_file("override");
+        fs::create_dir_all(user_file.parent().unwrap()).unwrap();
+        fs::write(&user_file, "[seahorse_valley]\ncenter = [-0.75, 0.1]\nzoom = 10\nmax_iter = 500\ncolor_scale = \"log\"\n\n\
+                               [mine]\ncenter = [0, 1]\nzoom = 2.5\nmax_iter = 100\n").unwrap();
+
+        let seahorse = find_bookmark("seahorse_valley", Some(&user_file)).unwrap();
+        assert_eq!((seahorse.center, seahorse.zoom, seahorse.color_scale), ((-0.75, 0.1), 10.0, Some(ColorScale::Log)));
+        assert_eq!(find_bookmark("mine", Some(&user_file)).unwrap().max_iter, 100);
+        assert_eq!(find_bookmark("elephant_valley", Some(&user_file)).unwrap().zoom, 150.0);
+
+        // A missing user file is no error, an invalid one is
+        assert!(find_bookmark("full", Some(&temp_file("missing"))).is_ok());
+        fs::write(&user_file, "[broken]\ncenter = [0]\nzoom = 1\nmax_iter = 10\n").unwrap();
+        assert!(find_bookmark("full", Some(&user_file)).is_err());
+    }
+
+    #[test]
+    fn saved_bookmark_round_trip() {
+        let user_file = temp_file("save");
+        let default = MandelConfig::default();
+        let config = MandelConfig{max_iter: 3000, color_cycle: Some(4), color_scale: ColorScale::Sqrt,
+            coloring_method: ColoringMethod::Smooth, .. default.clone()};
+        let config = Bookmark{name: String::new(), center: (-0.1, 0.651), zoom: 1234.5, max_iter: 3000,
+            color_cycle: None, color_offset: None, color_scale: None, coloring_method: None}.apply_to(&config);
+
+        save_bookmark(&user_file, &Bookmark::from_config("spiral", &config), false).unwrap();
+        save_bookmark(&user_file, &Bookmark::from_config("other", &default), false).unwrap();
+
+        let saved = find_bookmark("spiral", Some(&user_file)).unwrap();
+        assert_eq!(saved, Bookmark::from_config("spiral", &config));
+        assert_eq!(saved.apply_to(&default), config);
+
+        // Existing names need force, also the built-in ones
+        assert!(save_bookmark(&user_file, &Bookmark::from_config("spiral", &default), false).is_err());
+        assert!(save_bookmark(&user_file, &Bookmark::from_config("full", &default), false).is_err());
+        assert!(save_bookmark(&user_file, &Bookmark::from_config("bad name", &default), true).is_err());
+
+        save_bookmark(&user_file, &Bookmark::from_config("spiral", &default), true).unwrap();
+        assert_eq!(find_bookmark("spiral", Some(&user_file)).unwrap().max_iter, default.max_iter);
+        assert!(find_bookmark("other", Some(&user_file)).is_ok());
+    }
+}
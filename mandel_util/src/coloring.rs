@@ -0,0 +1,386 @@
+// Colors of the points outside of the set (--coloring).
+//
+// - iter: the palette by the iteration count, the classic images (see palette)
+// - smooth: the palette by the continuous iteration count, without the bands between the iteration counts
+// - distance: by the estimated distance to the set, the boundary is dark and the far outside is bright
+// - orbit_trap: by the smallest distance of the orbit to a trap (a point, a cross or a circle)
+// - histogram: by the fraction of the exterior pixels with fewer iterations (histogram equalization),
+//   every color gets the same number of pixels
+// - stripe: by the stripe average of the orbit (see mandel_iter_stripe), stripes that follow the shape of the set
+//
+// Only the iteration counts are stored, so smooth, distance, orbit_trap and stripe iterate the point again
+// (like the interior coloring). They need the mandelbrot set, other workloads are colored like iter.
+// The inside of the set is always colored by --interior_coloring.
+//
+// With --auto_levels the palette of iter and smooth covers only the 1st .. 99th percentile of the exterior
+// iteration counts (see auto_levels), so a few pixels close to max_iter don't squeeze all others into the first colors.
+
+// External modules
+use num_complex::Complex64;
+
+// Rust modules
+use std::fmt;
+use std::str::FromStr;
+
+// Internal modules
+use super::{mandel_iter_stripe_z0, pixel_to_complex, MandelConfig, Workload};
+use palette::Palette;
+
+// The escape radius of smooth and distance (squared): far outside of 2, so the orbit has settled
+const LARGE_BAILOUT: f64 = 1.0e6;
+
+// Percentiles of the exterior iteration counts that --auto_levels spreads over the palette
+pub const AUTO_LEVELS_PERCENTILES: (f64, f64) = (1.0, 99.0);
+
+// Distance (in pixels) from the boundary at which distance reaches the brightest color
+const DISTANCE_PIXELS: f64 = 4.0;
+
+// The shape of the orbit trap
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TrapShape {
+    // The origin
+    Point,
+    // The real and the imaginary axis
+    Cross,
+    // The unit circle
+    Circle
+}
+
+impl TrapShape {
+    // Distance of z to the trap
+    pub fn distance(&self, z: Complex64) -> f64 {
+        match *self {
+            TrapShape::Point => z.norm(),
+            TrapShape::Cross => z.re.abs().min(z.im.abs()),
+            TrapShape::Circle => (z.norm() - 1.0).abs()
+        }
+    }
+}
+
+// How the points outside of the set are colored
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ColoringMethod {
+    IterationCount,
+    Smooth,
+    Distance,
+    OrbitTrap(TrapShape),
+    Histogram,
+    StripeAverage
+}
+
+impl fmt::Display for ColoringMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ColoringMethod::IterationCount => write!(f, "iter"),
+            ColoringMethod::Smooth => write!(f, "smooth"),
+            ColoringMethod::Distance => write!(f, "distance"),
+            ColoringMethod::OrbitTrap(TrapShape::Point) => write!(f, "orbit_trap"),
+            ColoringMethod::OrbitTrap(TrapShape::Cross) => write!(f, "orbit_trap:cross"),
+            ColoringMethod::OrbitTrap(TrapShape::Circle) => write!(f, "orbit_trap:circle"),
+            ColoringMethod::Histogram => write!(f, "histogram"),
+            ColoringMethod::StripeAverage => write!(f, "stripe")
+        }
+    }
+}
+
+// The shape of the orbit trap follows the name: orbit_trap (a point), orbit_trap:point, orbit_trap:cross, orbit_trap:circle
+impl FromStr for ColoringMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColoringMethod, String> {
+        match s {
+            "iter" => Ok(ColoringMethod::IterationCount),
+            "smooth" => Ok(ColoringMethod::Smooth),
+            "distance" => Ok(ColoringMethod::Distance),
+            "orbit_trap" | "orbit_trap:point" => Ok(ColoringMethod::OrbitTrap(TrapShape::Point)),
+            "orbit_trap:cross" => Ok(ColoringMethod::OrbitTrap(TrapShape::Cross)),
+            "orbit_trap:circle" => Ok(ColoringMethod::OrbitTrap(TrapShape::Circle)),
+            "histogram" => Ok(ColoringMethod::Histogram),
+            "stripe" => Ok(ColoringMethod::StripeAverage),
+            other => Err(format!("Unknown coloring: '{}', must be iter, smooth, distance, orbit_trap[:point|cross|circle], histogram or stripe", other))
+        }
+    }
+}
+
+// Black (0.0) .. orange .. white (1.0), the colors of distance, orbit_trap, histogram and stripe
+pub fn ramp(t: f64) -> (u8, u8, u8) {
+    let t = t.max(0.0).min(1.0);
+    ((255.0 * (2.0 * t).min(1.0)) as u8, (255.0 * t) as u8, (255.0 * ((2.0 * t) - 1.0).max(0.0)) as u8)
+}
+
+// Gray by the cost of a pixel (--cost_map): black for 0 iterations, white for max_iter (the interior),
+// log scale in between, so the few expensive pixels near the boundary don't make everything else black
+pub fn cost_color(iter: u32, max_iter: u32) -> (u8, u8, u8) {
+    let iter = iter.min(max_iter) as f64;
+    let gray = (255.0 * ((iter + 1.0).ln() / ((max_iter as f64) + 1.0).ln())).round() as u8;
+    (gray, gray, gray)
+}
+
+// The continuous iteration count of c: between n and n + 1 for a point that escapes after n iterations
+// (see mandel_iter_z0), max_iter if it doesn't escape.
+// The orbit is followed beyond |z| = 2 up to the large bailout, there mu = k + 1 - log2(log2(|z_k|)) doesn't depend on k.
+pub fn smooth_iter(max_iter: u32, z0: Complex64, c: Complex64) -> f64 {
+    let mut z: Complex64 = (z0 * z0) + c;
+    let mut iter = 0;
+
+    while (z.norm_sqr() <= 4.0) && (iter < max_iter) {
+        z = c + (z * z);
+        iter = iter + 1;
+    }
+
+    if iter >= max_iter {
+        return max_iter as f64;
+    }
+
+    let mut k = iter;
+    while z.norm_sqr() <= LARGE_BAILOUT {
+        z = c + (z * z);
+        k = k + 1;
+    }
+
+    let mu = (k as f64) + 1.0 - z.norm().log2().log2();
+    mu.max(iter as f64).min((iter as f64) + 0.999)
+}
+
+// Estimated distance of c to the mandelbrot set, 0.0 if it doesn't escape within max_iter iterations.
+// z and its derivative by c are iterated until the large bailout, the estimate is |z| * ln(|z|) / |dz|.
+pub fn exterior_distance(max_iter: u32, z0: Complex64, c: Complex64) -> f64 {
+    let mut z: Complex64 = (z0 * z0) + c;
+    let mut dz = Complex64{re: 1.0, im: 0.0};
+    let mut iter = 0;
+
+    while z.norm_sqr() <= LARGE_BAILOUT {
+        if iter >= max_iter {
+            return 0.0;
+        }
+        dz = (z * dz * 2.0) + 1.0;
+        z = c + (z * z);
+        iter = iter + 1;
+    }
+
+    let norm = z.norm();
+    norm * norm.ln() / dz.norm()
+}
+
+// Smallest distance of the orbit z_1, z_2, ... of c (until |z| > 2 or max_iter) to the trap
+pub fn orbit_trap_distance(max_iter: u32, z0: Complex64, c: Complex64, shape: TrapShape) -> f64 {
+    let mut z: Complex64 = (z0 * z0) + c;
+    let mut distance = shape.distance(z);
+    let mut iter = 0;
+
+    while (z.norm_sqr() <= 4.0) && (iter < max_iter) {
+        z = c + (z * z);
+        distance = distance.min(shape.distance(z));
+        iter = iter + 1;
+    }
+
+    distance
+}
+
+// Fraction of the exterior pixels (iter < max_iter) of the image with at most n iterations at index n
+pub fn cumulative_histogram(image: &[u32], max_iter: u32) -> Vec<f64> {
+    let mut counts = vec![0u64; max_iter as usize];
+    for &iter in image.iter().filter(|&&iter| iter < max_iter) {
+        counts[iter as usize] += 1;
+    }
+
+    let num_of_exterior = counts.iter().sum::<u64>().max(1) as f64;
+    let mut sum = 0;
+    counts.iter().map(|&count| {
+        sum += count;
+        (sum as f64) / num_of_exterior
+    }).collect()
+}
+
+// The iteration counts at the percentiles of AUTO_LEVELS_PERCENTILES of the exterior pixels of the image:
+// the lowest ones with at least this fraction of the exterior pixels at or below them (see cumulative_histogram).
+// None if there are no exterior pixels, low == high if all exterior pixels have the same iteration count.
+pub fn auto_levels(image: &[u32], max_iter: u32) -> Option<(u32, u32)> {
+    let cumulative = cumulative_histogram(image, max_iter);
+    let (low, high) = AUTO_LEVELS_PERCENTILES;
+    let percentile = |percent: f64| cumulative.iter().position(|&fraction| fraction >= percent / 100.0).map(|iter| iter as u32);
+
+    // Without exterior pixels all fractions are 0
+    match (percentile(low), percentile(high)) {
+        (Some(low), Some(high)) => Some((low, high)),
+        _ => None
+    }
+}
+
+// The palette levels of the image: --levels, the ones of auto_levels with --auto_levels, otherwise None
+pub fn image_levels(mandel_config: &MandelConfig, image: &[u32]) -> Option<(u32, u32)> {
+    if mandel_config.auto_levels {
+        auto_levels(image, mandel_config.max_iter)
+    } else {
+        mandel_config.levels
+    }
+}
+
+// Everything the exterior colors of one image need: the palette and for histogram the cumulative histogram
+#[derive(Clone, Debug)]
+pub struct ImageColoring {
+    method: ColoringMethod,
+    palette: Palette,
+    // Fraction of the exterior pixels with at most n iterations at index n (only for histogram)
+    cumulative: Vec<f64>
+}
+
+impl ImageColoring {
+    pub fn new(mandel_config: &MandelConfig, image: &[u32]) -> ImageColoring {
+        let cumulative = if mandel_config.coloring_method == ColoringMethod::Histogram {
+            cumulative_histogram(image, mandel_config.max_iter)
+        } else {
+            Vec::new()
+        };
+
+        ImageColoring{
+            method: mandel_config.coloring_method,
+            palette: match image_levels(mandel_config, image) {
+                Some(levels) => Palette::from_config_with_levels(mandel_config, levels),
+                None => Palette::from_config(mandel_config)
+            },
+            cumulative: cumulative
+        }
+    }
+
+    // Color of the pixel (x, y) that escaped after iter < max_iter iterations
+    pub fn color(&self, mandel_config: &MandelConfig, x: u32, y: u32, iter: u32) -> (u8, u8, u8) {
+        let fractal = mandel_config.workload == Workload::Fractal;
+        let c = || pixel_to_complex(mandel_config, x, y);
+
+        match self.method {
+            ColoringMethod::Histogram => ramp(self.cumulative[iter as usize]),
+            ColoringMethod::Smooth if fractal =>
+                self.palette.color_smooth(smooth_iter(mandel_config.max_iter, mandel_config.z0(), c())),
+            ColoringMethod::Distance if fractal => {
+                let distance = exterior_distance(mandel_config.max_iter, mandel_config.z0(), c());
+                ramp((distance / (DISTANCE_PIXELS * mandel_config.x_step().abs())).sqrt())
+            },
+            ColoringMethod::OrbitTrap(shape) if fractal =>
+                ramp(1.0 - orbit_trap_distance(mandel_config.max_iter, mandel_config.z0(), c(), shape)),
+            ColoringMethod::StripeAverage if fractal =>
+                ramp(mandel_iter_stripe_z0(mandel_config.max_iter, mandel_config.z0(), c(), mandel_config.stripe_density).1),
+            _ => self.palette.color(iter)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{mandel_iter, mandel_iter_stripe, escape_color};
+
+    fn zero() -> Complex64 {
+        Complex64{re: 0.0, im: 0.0}
+    }
+
+    #[test]
+    fn parse_and_print() {
+        for name in &["iter", "smooth", "distance", "orbit_trap", "orbit_trap:cross", "orbit_trap:circle", "histogram", "stripe"] {
+            assert_eq!(name.parse::<ColoringMethod>().unwrap().to_string(), *name);
+        }
+        assert_eq!("orbit_trap:point".parse::<ColoringMethod>(), Ok(ColoringMethod::OrbitTrap(TrapShape::Point)));
+        assert!("orbit_trap:star".parse::<ColoringMethod>().is_err());
+        assert_eq!(MandelConfig::default().coloring_method, ColoringMethod::IterationCount);
+    }
+
+    #[test]
+    fn smooth_iter_is_between_the_iteration_counts() {
+        for &re in &[0.3, 0.5, 1.0, -2.1, -0.75] {
+            let c = Complex64{re: re, im: 0.05};
+            let iter = mandel_iter(1000, c);
+            let mu = smooth_iter(1000, zero(), c);
+
+            assert!(mu >= (iter as f64) && mu < (iter as f64) + 1.0, "{}: {} vs {}", re, mu, iter);
+        }
+        assert_eq!(smooth_iter(100, zero(), Complex64{re: -0.5, im: 0.0}), 100.0);
+    }
+
+    #[test]
+    fn distance_and_orbit_trap() {
+        // The distance of 1 to the set (the cusp at 0.25) is 0.75, the estimate is within a factor of 4
+        let distance = exterior_distance(1000, zero(), Complex64{re: 1.0, im: 0.0});
+        assert!(distance > 0.75 / 4.0 && distance < 0.75 * 4.0, "{}", distance);
+        assert_eq!(exterior_distance(1000, zero(), Complex64{re: -1.0, im: 0.0}), 0.0);
+
+        // The orbit of -1 is 0, -1, 0, ... (z_1 = -1)
+        let c = Complex64{re: -1.0, im: 0.0};
+        assert_eq!(orbit_trap_distance(100, zero(), c, TrapShape::Point), 0.0);
+        assert_eq!(orbit_trap_distance(100, zero(), c, TrapShape::Circle), 0.0);
+        assert_eq!(TrapShape::Cross.distance(Complex64{re: 0.5, im: -0.25}), 0.25);
+    }
+
+    #[test]
+    fn stripe_average_of_the_orbit() {
+        for &re in &[0.3, 0.5, 1.0, -2.1, -0.75] {
+            let c = Complex64{re: re, im: 0.05};
+            let (iter, average) = mandel_iter_stripe(1000, c, 5.0);
+
+            assert_eq!(iter, mandel_iter(1000, c));
+            assert!(average >= 0.0 && average <= 1.0, "{}: {}", re, average);
+        }
+
+        // The orbit of 1 (1, 2, 5) stays on the positive real axis (arg 0): 0.5 + 0.5 * sin(0)
+        assert_eq!(mandel_iter_stripe(100, Complex64{re: 1.0, im: 0.0}, 5.0), (2, 0.5));
+        // The orbit of -1 is -1, 0, -1, ...: arg(-1) = pi, arg(0) = 0
+        let (iter, average) = mandel_iter_stripe(3, Complex64{re: -1.0, im: 0.0}, 0.5);
+        assert_eq!(iter, 3);
+        assert!((average - 0.75).abs() < 1e-12, "{}", average);
+
+        let config = MandelConfig{max_iter: 64, coloring_method: ColoringMethod::StripeAverage, .. MandelConfig::default()};
+        let coloring = ImageColoring::new(&config, &[]);
+        let c = pixel_to_complex(&config, 10, 20);
+        assert_eq!(coloring.color(&config, 10, 20, 0), ramp(mandel_iter_stripe(64, c, 5.0).1));
+    }
+
+    #[test]
+    fn image_colors() {
+        let config = MandelConfig{max_iter: 8, .. MandelConfig::default()};
+        let image = vec![0, 0, 1, 3, 8, 8];
+
+        // The default is the palette
+        let coloring = ImageColoring::new(&config, &image);
+        assert_eq!(coloring.color(&config, 0, 0, 3), escape_color(3));
+
+        // Half of the exterior pixels have 0 iterations
+        let histogram = MandelConfig{coloring_method: ColoringMethod::Histogram, .. config.clone()};
+        let coloring = ImageColoring::new(&histogram, &image);
+        assert_eq!(coloring.color(&histogram, 0, 0, 0), ramp(0.5));
+        assert_eq!(coloring.color(&histogram, 0, 0, 3), ramp(1.0));
+        assert_eq!((ramp(0.0), ramp(1.0)), ((0, 0, 0), (255, 255, 255)));
+    }
+
+    #[test]
+    fn auto_levels_clip_the_percentiles() {
+        // 400 exterior pixels 10 .. 409, 2 outliers close to max_iter and 4 interior pixels:
+        // 1 % of 402 pixels is the 5th one, 99 % the 398th one
+        let mut image: Vec<u32> = (10..410).collect();
+        image.extend_from_slice(&[995, 999, 1000, 1000, 1000, 1000]);
+        assert_eq!(auto_levels(&image, 1000), Some((14, 407)));
+
+        let config = MandelConfig{max_iter: 1000, color_cycle: Some(1), auto_levels: true, .. MandelConfig::default()};
+        let coloring = ImageColoring::new(&config, &image);
+        assert_eq!(coloring.color(&config, 0, 0, 10), escape_color(0));
+        assert_eq!(coloring.color(&config, 0, 0, 999), escape_color(15));
+
+        // --levels instead of the percentiles
+        let fixed = MandelConfig{auto_levels: false, levels: Some((500, 999)), .. config.clone()};
+        assert_eq!(image_levels(&fixed, &image), Some((500, 999)));
+        assert_eq!(ImageColoring::new(&fixed, &image).color(&fixed, 0, 0, 409), escape_color(0));
+
+        // Degenerate images: all pixels inside of the set, all exterior pixels the same
+        assert_eq!(auto_levels(&[1000; 16], 1000), None);
+        assert_eq!(image_levels(&config, &[1000; 16]), None);
+        assert_eq!(auto_levels(&[7, 7, 7, 1000], 1000), Some((7, 7)));
+        assert_eq!(auto_levels(&[], 1000), None);
+    }
+
+    #[test]
+    fn cost_colors_are_log_scaled() {
+        assert_eq!(cost_color(0, 255), (0, 0, 0));
+        // ln(16) / ln(256) = 0.5
+        assert_eq!(cost_color(15, 255), (128, 128, 128));
+        assert_eq!(cost_color(255, 255), (255, 255, 255));
+        assert_eq!(cost_color(300, 255), (255, 255, 255));
+    }
+}
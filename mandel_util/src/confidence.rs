@@ -0,0 +1,91 @@
+// Confidence interval of the mean of the run times (--adaptive_runs): the runs are repeated until
+// the 95% confidence interval is within --ci of the mean or --max_runs is reached.
+//
+// The few runs of a benchmark are a small sample, so the interval uses the t-distribution with
+// n - 1 degrees of freedom instead of the normal distribution: mean +- t * stddev / sqrt(n).
+
+// Two-sided 95% critical values of the t-distribution for 1 to 30 degrees of freedom
+const T_95: [f64; 30] = [12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+                          2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+                          2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042];
+
+// Critical values for more degrees of freedom, the one of the next smaller number is used (slightly wider)
+const T_95_LARGE: [(u32, f64); 3] = [(40, 2.021), (60, 2.000), (120, 1.980)];
+
+// The normal distribution, used for more than 1000 degrees of freedom
+const Z_95: f64 = 1.960;
+
+// Two-sided 95% critical value of the t-distribution, None for 0 degrees of freedom
+pub fn t_critical_95(degrees_of_freedom: u32) -> Option<f64> {
+    match degrees_of_freedom {
+        0 => None,
+        1..=30 => Some(T_95[(degrees_of_freedom - 1) as usize]),
+        _ if degrees_of_freedom > 1000 => Some(Z_95),
+        _ => Some(T_95_LARGE.iter().rev().find(|&&(df, _)| df <= degrees_of_freedom).map_or(T_95[29], |&(_, t)| t))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ConfidenceInterval {
+    pub mean: f64,
+    // The interval is mean - half_width to mean + half_width
+    pub half_width: f64
+}
+
+impl ConfidenceInterval {
+    // The half width relative to the mean, for example 0.02: mean +- 2%. Infinite for a mean of zero.
+    pub fn relative_width(&self) -> f64 {
+        if self.mean == 0.0 { ::std::f64::INFINITY } else { self.half_width / self.mean.abs() }
+    }
+}
+
+// 95% confidence interval of the mean of the samples, None for less than two samples
+pub fn confidence_interval(samples: &[f64]) -> Option<ConfidenceInterval> {
+    let n = samples.len();
+    let t = t_critical_95(n.saturating_sub(1) as u32)?;
+
+    let mean = samples.iter().sum::<f64>() / (n as f64);
+    let variance = samples.iter().map(|sample| (sample - mean) * (sample - mean)).sum::<f64>() / ((n - 1) as f64);
+
+    Some(ConfidenceInterval{mean: mean, half_width: t * variance.sqrt() / (n as f64).sqrt()})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-3, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn critical_values() {
+        assert_eq!(t_critical_95(0), None);
+        assert_eq!(t_critical_95(1), Some(12.706));
+        assert_eq!(t_critical_95(9), Some(2.262));
+        assert_eq!(t_critical_95(30), Some(2.042));
+        assert_eq!(t_critical_95(45), Some(2.021));
+        assert_eq!(t_critical_95(200), Some(1.980));
+        assert_eq!(t_critical_95(100_000), Some(1.960));
+    }
+
+    #[test]
+    fn interval_of_known_samples() {
+        // mean 3, stddev sqrt(2.5), t(4) = 2.776
+        let interval = confidence_interval(&[1.0, 2.0, 3.0, 4.0, 5.0]).unwrap();
+        assert_close(interval.mean, 3.0);
+        assert_close(interval.half_width, 1.963);
+        assert_close(interval.relative_width(), 0.654);
+
+        // Student's sleep data (difference of the two drugs): mean 1.58, 95% CI 0.700 to 2.460
+        let sleep = [1.2, 2.4, 1.3, 1.3, 0.0, 1.0, 1.8, 0.8, 4.6, 1.4];
+        let interval = confidence_interval(&sleep).unwrap();
+        assert_close(interval.mean - interval.half_width, 0.700);
+        assert_close(interval.mean + interval.half_width, 2.460);
+
+        // Equal times have no spread at all
+        assert_eq!(confidence_interval(&[10.0, 10.0]).unwrap().half_width, 0.0);
+        assert_eq!(confidence_interval(&[10.0]), None);
+        assert_eq!(confidence_interval(&[]), None);
+    }
+}
@@ -0,0 +1,352 @@
+// Boundary of the mandelbrot set as polygons (--extract_contour), for vector graphics or topology analysis.
+//
+// Also the vector output format (--output_format=svg with --contours=LEVELS): one group of paths per level.
+//
+// estimate_fractal_dimension estimates the box-counting dimension of the contour points (--fractal_dimension).
+// The boundary of the mandelbrot set has the Hausdorff dimension 2 (Shishikura), but a rendered image
+// only resolves it down to the pixel size, so the estimate for a finite image is lower and grows with the zoom and max_iter.
+//
+// Marching squares over the grid of pixel samples: a pixel is inside if it needs at least
+// threshold iterations. The crossing points on the cell edges are interpolated linearly between
+// the two iteration counts at the level threshold - 0.5 (between the last outside and the first
+// inside count). Saddle cells are resolved with the mean of the four corners.
+
+// Rust modules
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::File;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::{self, BufWriter, Write};
+
+// Internal modules
+use MandelConfig;
+
+// The points (re, im) of one contour. A closed loop ends with its first point again,
+// contours that run into the border of the image are open.
+pub type MandelbrotSetContour = Vec<(f64, f64)>;
+
+// An edge of the grid: between pixel (x, y) and (x + 1, y) or, if vertical, (x, y + 1)
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+struct Edge {
+    x: u32,
+    y: u32,
+    vertical: bool
+}
+
+// The edges of a cell, (x, y) is the upper left pixel
+fn cell_edges(x: u32, y: u32) -> (Edge, Edge, Edge, Edge) {
+    (Edge{x: x, y: y, vertical: false},         // top
+     Edge{x: x + 1, y: y, vertical: true},      // right
+     Edge{x: x, y: y + 1, vertical: false},     // bottom
+     Edge{x: x, y: y, vertical: true})          // left
+}
+
+// Find the contours at iter == threshold in the image
+pub fn extract_contour(mandel_config: &MandelConfig, image: &[u32], threshold: u32) -> Vec<MandelbrotSetContour> {
+    let img_size = mandel_config.img_size;
+    let level = (threshold as f64) - 0.5;
+    let value = |x: u32, y: u32| image[mandel_config.pixel_index(x, y)];
+    let inside = |x: u32, y: u32| value(x, y) >= threshold;
+
+    // Each edge is shared by two cells, so it has at most two neighbours (one on the border)
+    let mut neighbours: BTreeMap<Edge, Vec<Edge>> = BTreeMap::new();
+
+    {
+        let mut connect = |a: Edge, b: Edge| {
+            neighbours.entry(a).or_insert_with(Vec::new).push(b);
+            neighbours.entry(b).or_insert_with(Vec::new).push(a);
+        };
+
+        for y in 0..img_size.saturating_sub(1) {
+            for x in 0..img_size.saturating_sub(1) {
+                let case = ((inside(x, y) as u8) << 3) | ((inside(x + 1, y) as u8) << 2) |
+                    ((inside(x + 1, y + 1) as u8) << 1) | (inside(x, y + 1) as u8);
+                let (top, right, bottom, left) = cell_edges(x, y);
+                let center_inside = || ((value(x, y) as f64) + (value(x + 1, y) as f64) +
+                    (value(x + 1, y + 1) as f64) + (value(x, y + 1) as f64)) / 4.0 >= level;
+
+                match case {
+                    1 | 14 => connect(left, bottom),
+                    2 | 13 => connect(bottom, right),
+                    3 | 12 => connect(left, right),
+                    4 | 11 => connect(top, right),
+                    6 | 9 => connect(top, bottom),
+                    7 | 8 => connect(left, top),
+                    // Upper right and lower left inside
+                    5 => if center_inside() {
+                        connect(left, top);
+                        connect(bottom, right);
+                    } else {
+                        connect(top, right);
+                        connect(left, bottom);
+                    },
+                    // Upper left and lower right inside
+                    10 => if center_inside() {
+                        connect(top, right);
+                        connect(left, bottom);
+                    } else {
+                        connect(left, top);
+                        connect(bottom, right);
+                    },
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // The point where the contour crosses the edge, in the complex plane
+    let crossing = |edge: &Edge| {
+        let (x2, y2) = if edge.vertical { (edge.x, edge.y + 1) } else { (edge.x + 1, edge.y) };
+        let (a, b) = (value(edge.x, edge.y) as f64, value(x2, y2) as f64);
+        let t = (level - a) / (b - a);
+        let (x, y) = if edge.vertical { (edge.x as f64, (edge.y as f64) + t) } else { ((edge.x as f64) + t, edge.y as f64) };
+
+        // See MandelConfig::row_to_im
+        let row = if mandel_config.flip_y { y } else { ((img_size - 1) as f64) - y };
+        (mandel_config.re1 + (x * mandel_config.x_step()), mandel_config.img1 + (row * mandel_config.y_step()))
+    };
+
+    // Open contours start at the border, so they are traced from one end to the other
+    let starts = neighbours.iter().filter(|&(_, edges)| edges.len() == 1)
+        .chain(neighbours.iter()).map(|(&edge, _)| edge);
+
+    let mut visited = BTreeSet::new();
+    let mut contours = Vec::new();
+
+    for start in starts {
+        if !visited.insert(start) {
+            continue;
+        }
+
+        let mut path = vec![start];
+        let mut current = start;
+
+        while let Some(&next) = neighbours[&current].iter().find(|edge| !visited.contains(*edge)) {
+            visited.insert(next);
+            path.push(next);
+            current = next;
+        }
+
+        if path.len() > 2 && neighbours[&current].contains(&start) {
+            path.push(start);
+        }
+
+        contours.push(path.iter().map(&crossing).collect());
+    }
+
+    contours
+}
+
+// The contours of each level (iteration threshold), in the order of the levels
+pub fn extract_contour_levels(mandel_config: &MandelConfig, image: &[u32], levels: &[u32]) -> Vec<(u32, Vec<MandelbrotSetContour>)> {
+    levels.iter().map(|&threshold| (threshold, extract_contour(mandel_config, image, threshold))).collect()
+}
+
+// Number of scales of estimate_fractal_dimension: box sizes of 1/2 .. 1/2^MAX_BOX_SCALES of the extent of the points
+const MAX_BOX_SCALES: u32 = 16;
+
+// The coarse scales with fewer boxes are left out of the fit if possible, a curve that crosses a box boundary
+// occupies one box more than its length needs, which flattens the line
+const MIN_BOXES: usize = 16;
+
+// Box counting: the number N(eps) of eps x eps boxes that contain a point, for eps = extent / 2^k (k = 1, 2, ..),
+// and the slope of the least squares line through (log(1 / eps), log(N(eps))).
+// The points are samples of the contour, finer boxes than their distance would count single points,
+// so the scales stop when there are more than a quarter as many boxes as points (at least two scales are used).
+// Fewer than two different points have the dimension 0.
+pub fn estimate_fractal_dimension(contour: &[(f64, f64)]) -> f64 {
+    let fold = |init: f64, f: fn(f64, f64) -> f64, coord: fn(&(f64, f64)) -> f64| contour.iter().map(coord).fold(init, f);
+    let (min_re, max_re) = (fold(f64::INFINITY, f64::min, |p| p.0), fold(f64::NEG_INFINITY, f64::max, |p| p.0));
+    let (min_im, max_im) = (fold(f64::INFINITY, f64::min, |p| p.1), fold(f64::NEG_INFINITY, f64::max, |p| p.1));
+    let extent = (max_re - min_re).max(max_im - min_im);
+
+    if !(extent > 0.0) {
+        return 0.0;
+    }
+
+    let mut samples: Vec<(usize, f64, f64)> = Vec::new();
+    for k in 1..(MAX_BOX_SCALES + 1) {
+        let num_of_boxes = 1u64 << k;
+        let eps = extent / (num_of_boxes as f64);
+        // A point on the upper edge of the extent belongs to the last box
+        let index = |value: f64, min: f64| (((value - min) / eps) as u64).min(num_of_boxes - 1);
+        let boxes: BTreeSet<(u64, u64)> = contour.iter().map(|&(re, im)| (index(re, min_re), index(im, min_im))).collect();
+
+        if samples.len() >= 2 && (boxes.len() * 4) > contour.len() {
+            break;
+        }
+        samples.push((boxes.len(), (1.0 / eps).ln(), (boxes.len() as f64).ln()));
+    }
+
+    let fine: Vec<(f64, f64)> = samples.iter().filter(|sample| sample.0 >= MIN_BOXES).map(|&(_, x, y)| (x, y)).collect();
+    let samples = if fine.len() >= 2 { fine } else { samples.iter().map(|&(_, x, y)| (x, y)).collect() };
+
+    let n = samples.len() as f64;
+    let mean_x = samples.iter().map(|&(x, _)| x).sum::<f64>() / n;
+    let mean_y = samples.iter().map(|&(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = samples.iter().map(|&(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f64 = samples.iter().map(|&(x, _)| (x - mean_x) * (x - mean_x)).sum();
+
+    covariance / variance
+}
+
+// Write the contours as SVG paths, one group per level. The user space is the complex plane
+// (the imaginary axis points up). The method, the levels and the configuration are in <desc>.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_contour_svg(file_name: &str, mandel_config: &MandelConfig, method: &str,
+    levels: &[(u32, Vec<MandelbrotSetContour>)]) -> io::Result<()> {
+    let mut buffer = BufWriter::new(try!(File::create(file_name)));
+    let level_list: Vec<String> = levels.iter().map(|&(threshold, _)| threshold.to_string()).collect();
+
+    try!(write!(buffer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"));
+    try!(write!(buffer, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{0}\" viewBox=\"{1} {2} {3} {4}\">\n",
+        mandel_config.img_size, mandel_config.re1, -mandel_config.img2,
+        mandel_config.re2 - mandel_config.re1, mandel_config.img2 - mandel_config.img1));
+    try!(write!(buffer, "  <desc>mandelbrot contours, method: {}, iter levels: {}, {}</desc>\n",
+        method, level_list.join(","), mandel_config));
+
+    for &(threshold, ref contours) in levels {
+        try!(write!(buffer, "  <g id=\"iter_{}\" fill=\"none\" stroke=\"black\" stroke-width=\"1\">\n", threshold));
+
+        for contour in contours {
+            let closed = contour.len() > 2 && contour.first() == contour.last();
+            let points = if closed { &contour[..(contour.len() - 1)] } else { &contour[..] };
+
+            try!(buffer.write(b"    <path d=\""));
+            for (n, &(re, im)) in points.iter().enumerate() {
+                try!(write!(buffer, "{}{} {}", if n == 0 { "M" } else { " L" }, re, -im));
+            }
+            if closed {
+                try!(buffer.write(b" Z"));
+            }
+            try!(buffer.write(b"\" vector-effect=\"non-scaling-stroke\"/>\n"));
+        }
+
+        try!(buffer.write(b"  </g>\n"));
+    }
+
+    try!(buffer.write(b"</svg>\n"));
+
+    // Flush explicitly, dropping the BufWriter would silently ignore errors
+    buffer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs;
+
+    // img_size x img_size pixels, each pixel is 1 x 1 with the origin at pixel (0, 0)
+    fn grid_config(img_size: u32, flip_y: bool) -> MandelConfig {
+        MandelConfig{
+            re1: 0.0,
+            re2: img_size as f64,
+            img1: 0.0,
+            img2: img_size as f64,
+            img_size: img_size,
+            max_iter: 10,
+            flip_y: flip_y,
+            .. MandelConfig::default()
+        }
+    }
+
+    #[test]
+    fn single_inside_pixel_gives_closed_loop() {
+        let config = grid_config(3, true);
+        let mut image = vec![0; 9];
+        image[4] = 10;
+
+        let contours = extract_contour(&config, &image, 10);
+        assert_eq!(contours.len(), 1);
+
+        let contour = &contours[0];
+        assert_eq!(contour.len(), 5);
+        assert_eq!(contour.first(), contour.last());
+
+        // The crossings are at 0.95 of the way from the outside to the inside pixel (1, 1)
+        for &(re, im) in &contour[..4] {
+            let distance = (re - 1.0).abs() + (im - 1.0).abs();
+            assert!((distance - 0.05).abs() < 1e-12, "({}, {})", re, im);
+        }
+    }
+
+    #[test]
+    fn box_counting_dimension() {
+        // A line segment and a filled square
+        let line: Vec<(f64, f64)> = (0..4096).map(|n| ((n as f64) * 0.001, (n as f64) * 0.0005)).collect();
+        let square: Vec<(f64, f64)> = (0..(256 * 256)).map(|n| ((n % 256) as f64, (n / 256) as f64)).collect();
+
+        assert!((estimate_fractal_dimension(&line) - 1.0).abs() < 0.05, "{}", estimate_fractal_dimension(&line));
+        assert!((estimate_fractal_dimension(&square) - 2.0).abs() < 0.1, "{}", estimate_fractal_dimension(&square));
+
+        assert_eq!(estimate_fractal_dimension(&[]), 0.0);
+        assert_eq!(estimate_fractal_dimension(&[(1.0, 2.0), (1.0, 2.0)]), 0.0);
+    }
+
+    #[test]
+    fn contour_at_border_is_open() {
+        let config = grid_config(3, false);
+        // Left column inside
+        let image = vec![10, 0, 0, 10, 0, 0, 10, 0, 0];
+
+        let contours = extract_contour(&config, &image, 10);
+        assert_eq!(contours.len(), 1);
+        assert_eq!(contours[0].len(), 3);
+        assert!(contours[0].first() != contours[0].last());
+        assert!(contours[0].iter().all(|&(re, _)| (re - 0.05).abs() < 1e-12));
+
+        // Row 0 is the top (im 2) without flip_y
+        let mut ims: Vec<f64> = contours[0].iter().map(|&(_, im)| im).collect();
+        ims.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(ims, vec![0.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn two_inside_pixels_in_saddle() {
+        let config = grid_config(4, true);
+        let mut image = vec![0; 16];
+        image[config.pixel_index(1, 1)] = 10;
+        image[config.pixel_index(2, 2)] = 10;
+
+        // Mean of the saddle cell is 5, below the level: two separate loops
+        assert_eq!(extract_contour(&config, &image, 10).len(), 2);
+        // Mean 5 is above the level for threshold 5: one loop around both pixels
+        assert_eq!(extract_contour(&config, &image, 5).len(), 1);
+    }
+
+    #[test]
+    fn svg_has_one_group_per_level() {
+        let config = grid_config(4, true);
+        // One inside pixel in the middle, a column at the left border reaches level 5 only
+        let mut image = vec![0; 16];
+        image[config.pixel_index(2, 2)] = 10;
+        for y in 0..4 {
+            image[config.pixel_index(0, y)] = 5;
+        }
+
+        let levels = extract_contour_levels(&config, &image, &[5, 10]);
+        assert_eq!(levels.iter().map(|&(threshold, ref contours)| (threshold, contours.len())).collect::<Vec<_>>(),
+            vec![(5, 2), (10, 1)]);
+
+        let file_name = temp_dir().join("mandel_contour_test.svg").to_str().unwrap().to_string();
+        write_contour_svg(&file_name, &config, "serial", &levels).unwrap();
+        let svg = fs::read_to_string(&file_name).unwrap();
+        fs::remove_file(&file_name).unwrap();
+
+        assert!(svg.contains("<desc>mandelbrot contours, method: serial, iter levels: 5,10, re1=0;"));
+        assert_eq!(svg.matches("<g id=\"iter_5\"").count(), 1);
+        assert_eq!(svg.matches("<g id=\"iter_10\"").count(), 1);
+        assert_eq!(svg.matches("<path").count(), 3);
+        // The loops around the middle pixel are closed, the contour of the border column is open
+        assert_eq!(svg.matches(" Z\"").count(), 2);
+    }
+
+    #[test]
+    fn no_contour_without_boundary() {
+        let config = grid_config(3, false);
+        assert!(extract_contour(&config, &vec![0; 9], 10).is_empty());
+        assert!(extract_contour(&config, &vec![10; 9], 10).is_empty());
+        assert!(extract_contour(&grid_config(1, false), &[10], 10).is_empty());
+    }
+}
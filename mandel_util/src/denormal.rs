@@ -0,0 +1,280 @@
+// Denormal (subnormal) floats during the iteration (--detect_denormals).
+//
+// Values below 2.2e-308 are stored as denormals, most FPUs compute with them 10 - 100 times slower.
+// They appear at extreme zooms near the origin and in the deltas of the perturbation iteration,
+// the rows with them take much longer than their neighbours. There are two tools:
+//
+// - sample_denormals iterates a grid of pixels (plain and perturbation iteration) and counts the steps
+//   with a denormal value in each band of rows. This works everywhere and changes nothing.
+// - On x86_64 FlushDenormals sets FTZ (flush to zero) and DAZ (denormals are zero) in the MXCSR register
+//   of the current thread, so denormals become 0.0. This changes the iteration counts of these pixels,
+//   so it is only done with --detect_denormals and it is written into the PPM header.
+//   The methods create one in each job of their workers, the register is restored when the job is done:
+//   the thread pools keep their threads, the mode must not stay set for the code that runs on them afterwards.
+
+// Rust modules
+use std::fmt;
+use std::marker::PhantomData;
+
+// External modules
+use num_complex::Complex64;
+
+// Internal modules
+use super::{compute_ref_orbit, pixel_to_complex, MandelConfig};
+
+// Bits of the MXCSR register: flush results to zero and treat inputs as zero
+pub const MXCSR_FTZ: u32 = 1 << 15;
+pub const MXCSR_DAZ: u32 = 1 << 6;
+
+// True if FlushDenormals can change the FPU mode on this architecture
+pub const FLUSH_SUPPORTED: bool = cfg!(target_arch = "x86_64");
+
+// Number of sampled pixels in each direction and number of row bands of the report
+pub const DENORMAL_SAMPLES: u32 = 64;
+pub const DENORMAL_BANDS: u32 = 8;
+
+// The MXCSR register of the current thread
+#[cfg(target_arch = "x86_64")]
+pub fn mxcsr() -> u32 {
+    let mut csr: u32 = 0;
+    unsafe {
+        ::std::arch::asm!("stmxcsr [{}]", in(reg) &mut csr, options(nostack, preserves_flags));
+    }
+    csr
+}
+
+// Set the MXCSR register of the current thread
+#[cfg(target_arch = "x86_64")]
+pub fn set_mxcsr(csr: u32) {
+    unsafe {
+        ::std::arch::asm!("ldmxcsr [{}]", in(reg) &csr, options(nostack, readonly, preserves_flags));
+    }
+}
+
+// FTZ and DAZ for the current thread with --detect_denormals, until it is dropped: then the MXCSR register
+// gets the value it had before. Nothing is changed without the option and on other architectures.
+// Not Send, the register belongs to the thread that created it.
+pub struct FlushDenormals {
+    saved: Option<u32>,
+    _thread: PhantomData<*const ()>
+}
+
+impl FlushDenormals {
+    #[inline]
+    pub fn new(mandel_config: &MandelConfig) -> FlushDenormals {
+        FlushDenormals{saved: set_flush_mode(mandel_config.detect_denormals), _thread: PhantomData}
+    }
+
+    // True if FTZ and DAZ are set by this guard
+    pub fn is_active(&self) -> bool {
+        self.saved.is_some()
+    }
+}
+
+impl Drop for FlushDenormals {
+    #[inline]
+    fn drop(&mut self) {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if let Some(saved) = self.saved {
+                set_mxcsr(saved);
+            }
+        }
+    }
+}
+
+// Set FTZ and DAZ if enabled, returns the previous value of the register
+#[cfg(target_arch = "x86_64")]
+fn set_flush_mode(enabled: bool) -> Option<u32> {
+    if !enabled {
+        return None;
+    }
+
+    let saved = mxcsr();
+    set_mxcsr(saved | MXCSR_FTZ | MXCSR_DAZ);
+    Some(saved)
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn set_flush_mode(_enabled: bool) -> Option<u32> {
+    None
+}
+
+// The sampled iteration steps of the rows first_row ..= last_row
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct DenormalBand {
+    pub first_row: u32,
+    pub last_row: u32,
+    pub num_of_steps: u64,
+    // Steps with a denormal value in z (plain iteration) or in the delta (perturbation iteration)
+    pub num_of_denormal: u64
+}
+
+impl DenormalBand {
+    // Fraction of the steps with a denormal value, 0.0 without steps
+    pub fn fraction(&self) -> f64 {
+        if self.num_of_steps == 0 { 0.0 } else { (self.num_of_denormal as f64) / (self.num_of_steps as f64) }
+    }
+}
+
+impl fmt::Display for DenormalBand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rows {} .. {}: {} of {} steps denormal ({:.3} %)", self.first_row, self.last_row,
+            self.num_of_denormal, self.num_of_steps, self.fraction() * 100.0)
+    }
+}
+
+fn is_denormal(z: Complex64) -> bool {
+    z.re.is_subnormal() || z.im.is_subnormal()
+}
+
+// Steps and denormal steps of mandel_iter_z0 for c. Like the iteration count the first step is not counted.
+fn count_plain(max_iter: u32, z0: Complex64, c: Complex64) -> (u64, u64) {
+    let mut z: Complex64 = (z0 * z0) + c;
+    let mut num_of_steps = 0;
+    let mut num_of_denormal = 0;
+
+    while (z.norm_sqr() <= 4.0) && (num_of_steps < (max_iter as u64)) {
+        z = c + (z * z);
+        num_of_steps += 1;
+        if is_denormal(z) {
+            num_of_denormal += 1;
+        }
+    }
+
+    (num_of_steps, num_of_denormal)
+}
+
+// Steps and denormal steps of mandel_iter_perturbation for c_delta, the same recurrence with the same rebasing
+fn count_perturbation(max_iter: u32, c_delta: Complex64, ref_orbit: &[Complex64]) -> (u64, u64) {
+    let mut n = 0;
+    let mut delta = Complex64{re: 0.0, im: 0.0};
+    let mut num_of_steps = 0;
+    let mut num_of_denormal = 0;
+
+    loop {
+        delta = (ref_orbit[n] * delta * 2.0) + (delta * delta) + c_delta;
+        let denormal = is_denormal(delta);
+        n += 1;
+        let z = ref_orbit[n] + delta;
+
+        if ((n + 1) >= ref_orbit.len()) || (z.norm_sqr() < delta.norm_sqr()) {
+            delta = z;
+            n = 0;
+        }
+
+        // The first step is not counted, like in mandel_iter_perturbation
+        if num_of_steps > 0 && denormal {
+            num_of_denormal += 1;
+        }
+        if (z.norm_sqr() > 4.0) || (num_of_steps >= (max_iter as u64)) {
+            break;
+        }
+        num_of_steps += 1;
+    }
+
+    (num_of_steps, num_of_denormal)
+}
+
+// Iterate a grid of DENORMAL_SAMPLES x DENORMAL_SAMPLES pixels with the plain iteration (with z0) and with the
+// perturbation iteration around the center pixel (like rayon_perturbation), and count the denormal steps in
+// num_of_bands bands of rows. Always computes the mandelbrot set, like region::sample_region.
+pub fn sample_denormals(mandel_config: &MandelConfig, num_of_bands: u32) -> Vec<DenormalBand> {
+    let img_size = mandel_config.img_size;
+    let band_rows = ((img_size + num_of_bands - 1) / num_of_bands).max(1);
+    let mut bands: Vec<DenormalBand> = (0..img_size).step_by(band_rows as usize).map(|first_row| DenormalBand{
+        first_row: first_row,
+        last_row: (first_row + band_rows - 1).min(img_size - 1),
+        num_of_steps: 0,
+        num_of_denormal: 0
+    }).collect();
+
+    let center = img_size / 2;
+    let ref_orbit = compute_ref_orbit(mandel_config.max_iter, pixel_to_complex(mandel_config, center, center));
+    let row = |y: u32| if mandel_config.flip_y { y } else { img_size - 1 - y };
+    let step = (img_size / DENORMAL_SAMPLES).max(1) as usize;
+
+    for y in (0..img_size).step_by(step) {
+        let band = &mut bands[(y / band_rows) as usize];
+        let delta_im = ((row(y) as f64) - (row(center) as f64)) * mandel_config.y_step();
+
+        for x in (0..img_size).step_by(step) {
+            let delta_re = ((x as f64) - (center as f64)) * mandel_config.x_step();
+            let plain = count_plain(mandel_config.max_iter, mandel_config.z0(), pixel_to_complex(mandel_config, x, y));
+            let perturbation = count_perturbation(mandel_config.max_iter, Complex64{re: delta_re, im: delta_im}, &ref_orbit);
+
+            band.num_of_steps += plain.0 + perturbation.0;
+            band.num_of_denormal += plain.1 + perturbation.1;
+        }
+    }
+
+    bands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{mandel_iter_z0, mandel_iter_perturbation};
+
+    fn config(re1: f64, re2: f64, img1: f64, img2: f64) -> MandelConfig {
+        MandelConfig{re1: re1, re2: re2, img1: img1, img2: img2, max_iter: 256, img_size: 128,
+            .. MandelConfig::default()}
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn flush_sets_and_restores_the_mxcsr_bits() {
+        // Without the exception flags (bits 0 - 5), the computations below set them
+        let control = || mxcsr() & !0x3f;
+
+        let original = mxcsr();
+        set_mxcsr(original & !(MXCSR_FTZ | MXCSR_DAZ));
+        let before = control();
+        assert_eq!(before & (MXCSR_FTZ | MXCSR_DAZ), 0);
+
+        // A denormal result survives without FTZ and is 0.0 with it
+        let tiny = ::std::hint::black_box(1.0e-300f64);
+        assert!((tiny * 1.0e-10).is_subnormal());
+
+        assert!(!FlushDenormals::new(&MandelConfig::default()).is_active());
+        assert_eq!(control(), before);
+
+        {
+            let flush = FlushDenormals::new(&MandelConfig{detect_denormals: true, .. MandelConfig::default()});
+            assert!(flush.is_active());
+            assert_eq!(mxcsr() & (MXCSR_FTZ | MXCSR_DAZ), MXCSR_FTZ | MXCSR_DAZ);
+            assert_eq!(::std::hint::black_box(tiny) * 1.0e-10, 0.0);
+        }
+
+        // The mode doesn't leak into the code after the guard
+        assert_eq!(control(), before);
+        assert!((::std::hint::black_box(tiny) * 1.0e-10).is_subnormal());
+
+        set_mxcsr(original);
+    }
+
+    #[test]
+    fn denormals_near_the_origin() {
+        // The default region has no denormals
+        let bands = sample_denormals(&config(-2.0, 1.0, -1.5, 1.5), DENORMAL_BANDS);
+        assert_eq!(bands.len(), 8);
+        assert_eq!((bands[0].first_row, bands[0].last_row, bands[7].last_row), (0, 15, 127));
+        assert!(bands.iter().all(|band| band.num_of_steps > 0 && band.num_of_denormal == 0));
+
+        // Around the origin at a zoom of 1e310 all values are denormal: z stays close to c
+        let bands = sample_denormals(&config(-1.0e-310, 1.0e-310, -1.0e-310, 1.0e-310), 4);
+        assert_eq!(bands.len(), 4);
+        assert!(bands.iter().all(|band| band.fraction() > 0.9));
+        assert!(bands[0].to_string().starts_with("rows 0 .. 31: "));
+    }
+
+    #[test]
+    fn counts_the_steps_of_the_iterations() {
+        let c = Complex64{re: -0.75, im: 0.1};
+        let ref_orbit = compute_ref_orbit(1000, Complex64{re: -0.75, im: 0.0});
+        let c_delta = Complex64{re: 0.0, im: 0.1};
+
+        assert_eq!(count_plain(1000, Complex64{re: 0.0, im: 0.0}, c).0, mandel_iter_z0(1000, Complex64{re: 0.0, im: 0.0}, c) as u64);
+        assert_eq!(count_perturbation(1000, c_delta, &ref_orbit).0, mandel_iter_perturbation(1000, c_delta, &ref_orbit) as u64);
+    }
+}
@@ -0,0 +1,344 @@
+// Density renderers, used by the "density" sub command.
+//
+// Instead of coloring each pixel by its escape time, random points c are iterated and every
+// point z of the orbit that lies inside the view increments the counter of its pixel.
+//
+// buddhabrot: orbits of the points that escape
+// anti_buddhabrot: orbits of the points that don't escape
+// nebulabrot: three buddhabrot channels with different max_iter values, mapped to red, green and blue
+//
+// Each thread samples its own share of the points into its own histograms,
+// at the end all histograms are added up, so no synchronization is needed while sampling.
+
+// External modules
+use num_complex::Complex64;
+
+// Rust modules
+use std::fmt;
+use std::error;
+use std::fs::File;
+use std::io;
+use std::io::prelude::Write;
+use std::io::BufWriter;
+use std::str::FromStr;
+use std::thread;
+
+// Internal modules
+use super::{is_likely_interior, MandelConfig, splitmix64};
+
+// Upper limit for the memory of all histograms (per thread and merged)
+pub const MAX_DENSITY_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+
+// The points c are sampled from this rectangle, it contains the whole mandelbrot set
+const SAMPLE_RE1: f64 = -2.0;
+const SAMPLE_RE2: f64 = 0.5;
+const SAMPLE_IMG1: f64 = -1.25;
+const SAMPLE_IMG2: f64 = 1.25;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DensityMode {
+    Buddhabrot,
+    AntiBuddhabrot,
+    Nebulabrot
+}
+
+impl FromStr for DensityMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DensityMode, String> {
+        match s {
+            "buddhabrot" => Ok(DensityMode::Buddhabrot),
+            "anti_buddhabrot" => Ok(DensityMode::AntiBuddhabrot),
+            "nebulabrot" => Ok(DensityMode::Nebulabrot),
+            other => Err(format!("Unknown density mode: '{}', must be buddhabrot, anti_buddhabrot or nebulabrot", other))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DensityError {
+    // The histograms would need more than MAX_DENSITY_BYTES
+    TooLarge{bytes: u64},
+    InvalidIterations(String),
+    Io(io::Error)
+}
+
+impl fmt::Display for DensityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DensityError::TooLarge{bytes} => write!(f,
+                "the histograms need {} MiB, more than the limit of {} MiB: reduce --img_size, --num_threads or the number of channels",
+                bytes / (1024 * 1024), MAX_DENSITY_BYTES / (1024 * 1024)),
+            DensityError::InvalidIterations(ref message) => write!(f, "{}", message),
+            DensityError::Io(ref e) => write!(f, "I/O error: {}", e)
+        }
+    }
+}
+
+impl error::Error for DensityError {}
+
+impl From<io::Error> for DensityError {
+    fn from(e: io::Error) -> DensityError {
+        DensityError::Io(e)
+    }
+}
+
+// Parse the max_iter values of the nebulabrot channels, for example "50,500,5000"
+pub fn parse_channel_iters(iters: &str) -> Result<Vec<u32>, DensityError> {
+    let invalid = || DensityError::InvalidIterations(format!("invalid iterations: '{}', expected three positive numbers: R,G,B", iters));
+    let channel_iters: Vec<u32> = try!(iters.split(',').map(|n| n.trim().parse::<u32>()).collect::<Result<_, _>>().map_err(|_| invalid()));
+
+    if channel_iters.len() != 3 || channel_iters.iter().any(|&n| n == 0) {
+        return Err(invalid());
+    }
+
+    Ok(channel_iters)
+}
+
+// max_iter of each channel for the given mode
+pub fn channel_iters(mode: DensityMode, mandel_config: &MandelConfig, nebula_iters: &[u32]) -> Vec<u32> {
+    match mode {
+        DensityMode::Nebulabrot => nebula_iters.to_vec(),
+        _ => vec![mandel_config.max_iter]
+    }
+}
+
+// Memory needed by all histograms: one per thread and the merged one
+pub fn histogram_bytes(mandel_config: &MandelConfig, num_of_channels: usize) -> u64 {
+    let plane = 4 * (mandel_config.img_size as u64) * (mandel_config.img_size as u64);
+    plane * (num_of_channels as u64) * ((mandel_config.num_threads as u64) + 1)
+}
+
+// The pixel index of the point z or None if it is outside the view
+#[inline]
+fn point_to_pixel(mandel_config: &MandelConfig, z: Complex64) -> Option<usize> {
+    let column = ((z.re - mandel_config.re1) / mandel_config.x_step()).floor();
+    let row = ((z.im - mandel_config.img1) / mandel_config.y_step()).floor();
+    let img_size = mandel_config.img_size as f64;
+
+    if column < 0.0 || column >= img_size || row < 0.0 || row >= img_size {
+        return None;
+    }
+
+    // Same orientation as row_to_im: row 0 of the image is the top unless flip_y is set
+    let row = row as u32;
+    let y = if mandel_config.flip_y { row } else { mandel_config.img_size - 1 - row };
+
+    Some(((y as usize) * (mandel_config.img_size as usize)) + (column as usize))
+}
+
+// Sample the given number of points and count the orbits, returns one histogram per channel.
+// The result only depends on the seed and the number of samples, not on the number of threads.
+pub fn render_density(mandel_config: &MandelConfig, mode: DensityMode, channel_iters: &[u32],
+                      num_of_samples: u64, seed: u64) -> Result<Vec<Vec<u32>>, DensityError> {
+    let bytes = histogram_bytes(mandel_config, channel_iters.len());
+    if bytes > MAX_DENSITY_BYTES {
+        return Err(DensityError::TooLarge{bytes: bytes});
+    }
+
+    let num_of_pixels = (mandel_config.img_size as usize) * (mandel_config.img_size as usize);
+    let num_of_threads = mandel_config.num_threads.max(1) as u64;
+
+    let histograms: Vec<Vec<Vec<u32>>> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..num_of_threads).map(|thread_index| scope.spawn(move || {
+            let mut histogram = vec![vec![0u32; num_of_pixels]; channel_iters.len()];
+            // Thread n takes the samples n, n + num_of_threads, ...
+            let mut sample = thread_index;
+
+            while sample < num_of_samples {
+                accumulate_orbit(mandel_config, mode, channel_iters, sample_point(seed, sample), &mut histogram);
+                sample += num_of_threads;
+            }
+
+            histogram
+        })).collect();
+
+        workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+    });
+
+    // Merge the per thread histograms
+    let mut result = vec![vec![0u32; num_of_pixels]; channel_iters.len()];
+    for histogram in histograms {
+        for (merged, channel) in result.iter_mut().zip(histogram.iter()) {
+            for (count, value) in merged.iter_mut().zip(channel.iter()) {
+                *count = count.saturating_add(*value);
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+// Pseudo random point c in the sample rectangle
+fn sample_point(seed: u64, sample: u64) -> Complex64 {
+    let random_re = splitmix64(seed ^ splitmix64(2 * sample));
+    let random_im = splitmix64(seed ^ splitmix64((2 * sample) + 1));
+    let unit = |random: u64| ((random >> 11) as f64) / ((1u64 << 53) as f64);
+
+    Complex64{re: SAMPLE_RE1 + (unit(random_re) * (SAMPLE_RE2 - SAMPLE_RE1)),
+              im: SAMPLE_IMG1 + (unit(random_im) * (SAMPLE_IMG2 - SAMPLE_IMG1))}
+}
+
+// Iterate c once up to the largest max_iter and add the orbit to every channel that counts it
+fn accumulate_orbit(mandel_config: &MandelConfig, mode: DensityMode, channel_iters: &[u32], c: Complex64, histogram: &mut [Vec<u32>]) {
+    // Only escaping orbits are counted, the points of the cardioid and the large bulbs would run up to max_iter for nothing
+    if mode != DensityMode::AntiBuddhabrot && is_likely_interior(c) {
+        return;
+    }
+
+    let max_iter = channel_iters.iter().cloned().max().unwrap_or(0);
+    let mut z = c;
+    let mut iter = 0;
+
+    while (z.norm_sqr() <= 4.0) && (iter < max_iter) {
+        z = c + (z * z);
+        iter = iter + 1;
+    }
+
+    let counts = |channel_max_iter: u32| match mode {
+        DensityMode::AntiBuddhabrot => iter >= channel_max_iter,
+        _ => iter < channel_max_iter
+    };
+
+    if !channel_iters.iter().any(|&channel_max_iter| counts(channel_max_iter)) {
+        return;
+    }
+
+    // Iterate again and add the orbit, each channel up to its own limit
+    let mut z = c;
+    for n in 0..iter {
+        z = c + (z * z);
+
+        if let Some(pixel) = point_to_pixel(mandel_config, z) {
+            for (channel, &channel_max_iter) in histogram.iter_mut().zip(channel_iters.iter()) {
+                if counts(channel_max_iter) && n < channel_max_iter {
+                    channel[pixel] = channel[pixel].saturating_add(1);
+                }
+            }
+        }
+    }
+}
+
+// Scale a histogram to 0..255, each channel is normalized independently.
+// The square root brings out the faint parts.
+pub fn normalize(channel: &[u32]) -> Vec<u8> {
+    let max_count = channel.iter().cloned().max().unwrap_or(0);
+
+    if max_count == 0 {
+        return vec![0; channel.len()];
+    }
+
+    channel.iter().map(|&count| (((count as f64) / (max_count as f64)).sqrt() * 255.0).round() as u8).collect()
+}
+
+// Write the histograms as color PPM (P6): one channel as gray scale, three channels as red, green and blue
+pub fn write_density_image(file_name: &str, mandel_config: &MandelConfig, histograms: &[Vec<u32>]) -> io::Result<()> {
+    let channels: Vec<Vec<u8>> = histograms.iter().map(|histogram| normalize(histogram)).collect();
+    let mut buffer = BufWriter::new(try!(File::create(file_name)));
+
+    try!(write!(buffer, "P6\n# mandelbrot density\n{0} {0}\n255\n", mandel_config.img_size));
+
+    let num_of_pixels = (mandel_config.img_size as usize) * (mandel_config.img_size as usize);
+    for pixel in 0..num_of_pixels {
+        let rgb = match channels.len() {
+            3 => [channels[0][pixel], channels[1][pixel], channels[2][pixel]],
+            _ => [channels[0][pixel], channels[0][pixel], channels[0][pixel]]
+        };
+        try!(buffer.write_all(&rgb));
+    }
+
+    buffer.flush()
+}
+
+// Render and write the density image, used by the "density" sub command
+pub fn run_density(mandel_config: &MandelConfig, mode: DensityMode, nebula_iters: &[u32],
+                   num_of_samples: u64, file_name: &str) -> Result<(), DensityError> {
+    let channel_iters = channel_iters(mode, mandel_config, nebula_iters);
+    let histograms = try!(render_density(mandel_config, mode, &channel_iters, num_of_samples, 0));
+
+    for (iters, histogram) in channel_iters.iter().zip(histograms.iter()) {
+        println!("Channel max_iter: {}, maximum count: {}", iters, histogram.iter().cloned().max().unwrap_or(0));
+    }
+
+    try!(write_density_image(file_name, mandel_config, &histograms));
+    println!("Density image written: {}", file_name);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use ppm::read_ppm;
+
+    fn small_config(num_threads: u32) -> MandelConfig {
+        MandelConfig{
+            img_size: 32,
+            max_iter: 50,
+            num_threads: num_threads,
+            .. MandelConfig::default()
+        }
+    }
+
+    #[test]
+    fn independent_of_thread_count() {
+        let iters = [10, 20, 40];
+        let one = render_density(&small_config(1), DensityMode::Nebulabrot, &iters, 2000, 1).unwrap();
+        let three = render_density(&small_config(3), DensityMode::Nebulabrot, &iters, 2000, 1).unwrap();
+
+        assert_eq!(one.len(), 3);
+        assert!(one == three);
+        assert!(one[0].iter().any(|&count| count > 0));
+    }
+
+    #[test]
+    fn anti_buddhabrot_stays_inside() {
+        let config = small_config(2);
+        let histograms = render_density(&config, DensityMode::AntiBuddhabrot, &[config.max_iter], 2000, 1).unwrap();
+
+        // Orbits of points that don't escape never leave the disc with radius 2,
+        // the corners of the view (|z| > 2) must be empty
+        assert_eq!(histograms[0][0], 0);
+        assert_eq!(histograms[0][(32 * 32) - 1], 0);
+        assert!(histograms[0].iter().any(|&count| count > 0));
+    }
+
+    #[test]
+    fn guard_against_huge_histograms() {
+        let config = MandelConfig{img_size: 65536, num_threads: 8, .. MandelConfig::default()};
+
+        match render_density(&config, DensityMode::Nebulabrot, &[1, 2, 3], 1, 0) {
+            Err(DensityError::TooLarge{..}) => {},
+            _ => panic!("expected DensityError::TooLarge")
+        }
+    }
+
+    #[test]
+    fn channels_are_normalized_independently() {
+        assert_eq!(normalize(&[0, 4, 16]), vec![0, 128, 255]);
+        assert_eq!(normalize(&[0, 1, 4]), vec![0, 128, 255]);
+        assert_eq!(normalize(&[0, 0]), vec![0, 0]);
+    }
+
+    #[test]
+    fn parse_nebula_iters() {
+        assert_eq!(parse_channel_iters("50, 500,5000").unwrap(), vec![50, 500, 5000]);
+        assert!(parse_channel_iters("50,500").is_err());
+        assert!(parse_channel_iters("50,0,5").is_err());
+        assert!(parse_channel_iters("a,b,c").is_err());
+    }
+
+    #[test]
+    fn write_color_image() {
+        let config = small_config(2);
+        let histograms = vec![vec![1; 32 * 32], vec![0; 32 * 32], vec![2; 32 * 32]];
+        let file_name = temp_dir().join("mandel_density_test.ppm").to_str().unwrap().to_string();
+
+        write_density_image(&file_name, &config, &histograms).unwrap();
+        let image = read_ppm(&file_name).unwrap();
+
+        assert_eq!((image.width, image.height), (32, 32));
+        assert_eq!(&image.data[0..3], &[255, 0, 255]);
+    }
+}
@@ -0,0 +1,175 @@
+// Compare two raw dumps (.mraw) or two PPM images, used by the "diff" sub command.
+//
+// Exit codes of the sub command:
+// 0: images are identical, 1: images differ, 2: images can't be compared (different size, I/O error)
+
+// Rust modules
+use std::fs::File;
+use std::io::prelude::Write;
+use std::io::{BufWriter, Result};
+
+// Internal modules
+use raw::read_raw;
+use ppm::read_ppm;
+
+pub const DIFF_IDENTICAL: i32 = 0;
+pub const DIFF_DIFFERENT: i32 = 1;
+pub const DIFF_MISMATCH: i32 = 2;
+
+// One of the two images to compare
+pub struct DiffInput {
+    pub width: u32,
+    pub height: u32,
+    // Number of values per pixel: 1 for raw dumps, 3 for PPM images
+    pub channels: usize,
+    // Largest possible value, used for the gray scale of the diff image
+    pub max_value: u32,
+    pub values: Vec<u32>
+}
+
+// The outcome of a comparison
+pub struct DiffResult {
+    pub num_of_diffs: usize,
+    // Maximum absolute difference of all values (iteration counts or color values)
+    pub max_diff: u32,
+    // Coordinates of the pixel with the maximum difference, if there is any
+    pub worst_pixel: Option<(u32, u32)>,
+    // One flag per pixel, true if the pixel differs
+    pub mismatches: Vec<bool>
+}
+
+// Load a raw dump if the file name ends with ".mraw", otherwise a PPM image
+pub fn load_diff_input(file_name: &str) -> Result<DiffInput> {
+    if file_name.ends_with(".mraw") {
+        let raw = try!(read_raw(file_name));
+        Ok(DiffInput{width: raw.width, height: raw.height, channels: 1, max_value: raw.max_iter, values: raw.data})
+    } else {
+        let ppm = try!(read_ppm(file_name));
+        Ok(DiffInput{width: ppm.width, height: ppm.height, channels: 3, max_value: ppm.max_value, values: ppm.data})
+    }
+}
+
+// Compare two images pixel by pixel. Returns None if the images have a different structure.
+pub fn diff_images(image_a: &DiffInput, image_b: &DiffInput) -> Option<DiffResult> {
+    if image_a.width != image_b.width || image_a.height != image_b.height || image_a.channels != image_b.channels {
+        return None;
+    }
+
+    let mut num_of_diffs = 0;
+    let mut max_diff = 0;
+    let mut worst_pixel = None;
+    let mut mismatches = Vec::with_capacity((image_a.width as usize) * (image_a.height as usize));
+
+    for (n, (pixel_a, pixel_b)) in image_a.values.chunks(image_a.channels)
+        .zip(image_b.values.chunks(image_b.channels)).enumerate() {
+
+        let diff = pixel_a.iter().zip(pixel_b.iter())
+            .map(|(a, b)| if a > b { a - b } else { b - a }).max().unwrap_or(0);
+
+        if diff > 0 {
+            num_of_diffs += 1;
+
+            if diff > max_diff {
+                max_diff = diff;
+                worst_pixel = Some(((n % (image_a.width as usize)) as u32, (n / (image_a.width as usize)) as u32));
+            }
+        }
+
+        mismatches.push(diff > 0);
+    }
+
+    Some(DiffResult{
+        num_of_diffs: num_of_diffs,
+        max_diff: max_diff,
+        worst_pixel: worst_pixel,
+        mismatches: mismatches
+    })
+}
+
+// Write a PPM image showing the first image in gray and all differing pixels in red
+pub fn write_diff_image(file_name: &str, image: &DiffInput, result: &DiffResult) -> Result<()> {
+    let mut buffer = BufWriter::new(try!(File::create(file_name)));
+
+    try!(write!(buffer, "P3\n# mandelbrot diff, differing pixels: {}\n{} {}\n255\n",
+        result.num_of_diffs, image.width, image.height));
+
+    let max_value = if image.max_value == 0 { 1 } else { image.max_value } as u64;
+
+    for (pixel, mismatch) in image.values.chunks(image.channels).zip(result.mismatches.iter()) {
+        if *mismatch {
+            try!(buffer.write_all(b"255 0 0\n"));
+        } else {
+            let sum = pixel.iter().fold(0, |sum, value| sum + (*value as u64));
+            let gray = (sum * 255) / (max_value * (image.channels as u64));
+            try!(write!(buffer, "{0} {0} {0}\n", gray));
+        }
+    }
+
+    buffer.flush()
+}
+
+// Run the diff sub command, prints a report and returns the exit code
+pub fn run_diff(file_a: &str, file_b: &str, diff_image: Option<&str>) -> i32 {
+    let (image_a, image_b) = match (load_diff_input(file_a), load_diff_input(file_b)) {
+        (Ok(image_a), Ok(image_b)) => (image_a, image_b),
+        (Err(e), _) | (_, Err(e)) => {
+            println!("Could not read image: {}", e);
+            return DIFF_MISMATCH;
+        }
+    };
+
+    let result = match diff_images(&image_a, &image_b) {
+        Some(result) => result,
+        None => {
+            println!("Images have different sizes: {} x {} (channels: {}) and {} x {} (channels: {})",
+                image_a.width, image_a.height, image_a.channels, image_b.width, image_b.height, image_b.channels);
+            return DIFF_MISMATCH;
+        }
+    };
+
+    println!("Number of differing pixels: {} of {}", result.num_of_diffs, result.mismatches.len());
+    println!("Maximum absolute difference: {}", result.max_diff);
+
+    if let Some((x, y)) = result.worst_pixel {
+        println!("Worst pixel: x: {}, y: {}", x, y);
+    }
+
+    if let Some(diff_image) = diff_image {
+        if let Err(e) = write_diff_image(diff_image, &image_a, &result) {
+            println!("Could not write diff image '{}': {}", diff_image, e);
+        }
+    }
+
+    if result.num_of_diffs == 0 { DIFF_IDENTICAL } else { DIFF_DIFFERENT }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_input(width: u32, height: u32, values: Vec<u32>) -> DiffInput {
+        DiffInput{width: width, height: height, channels: 1, max_value: 16, values: values}
+    }
+
+    #[test]
+    fn identical_images() {
+        let result = diff_images(&raw_input(2, 2, vec![1, 2, 3, 4]), &raw_input(2, 2, vec![1, 2, 3, 4])).unwrap();
+        assert_eq!(result.num_of_diffs, 0);
+        assert_eq!(result.max_diff, 0);
+        assert!(result.worst_pixel.is_none());
+    }
+
+    #[test]
+    fn worst_pixel() {
+        let result = diff_images(&raw_input(2, 2, vec![1, 2, 3, 4]), &raw_input(2, 2, vec![1, 5, 13, 4])).unwrap();
+        assert_eq!(result.num_of_diffs, 2);
+        assert_eq!(result.max_diff, 10);
+        assert_eq!(result.worst_pixel, Some((0, 1)));
+        assert_eq!(result.mismatches, vec![false, true, true, false]);
+    }
+
+    #[test]
+    fn different_sizes() {
+        assert!(diff_images(&raw_input(2, 2, vec![1, 2, 3, 4]), &raw_input(4, 1, vec![1, 2, 3, 4])).is_none());
+    }
+}
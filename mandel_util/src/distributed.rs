@@ -0,0 +1,710 @@
+// Rendering of one image on several machines (--worker, --coordinator).
+//
+// A worker (--worker=BIND_ADDR) accepts connections of coordinators and computes the rows that they ask for.
+// The coordinator (--coordinator=HOST:PORT,...) keeps one connection per worker. Each connection takes the next
+// batch of ROWS_PER_JOB rows from a shared queue, so fast workers compute more rows than slow ones.
+// If a worker fails (connection lost, timeout, wrong checksum) its batch goes back into the queue and the
+// other workers compute it, the render fails only if no worker is left.
+//
+// All messages are frames: the length of the payload (u32) followed by the payload, all numbers are little endian.
+// The first byte of the payload is the message type:
+//
+//     1 job:   PROTOCOL_VERSION, RenderParams (re1, re2, img1, img2, z0_re, z0_im as f64, max_iter, img_size as u32,
+//              flags as u8: 1 flip_y, 2 inclusive_edges, 4 detect_denormals, workload as u8: 0 fractal, 1 synthetic,
+//              2 phoenix, synthetic_distribution as u8: 0 uniform, 1 zipf, synthetic_seed as u64,
+//              phoenix_p, phoenix_q as f64), first_row, num_of_rows
+//     2 rows:  first_row, num_of_rows, CRC32 of the values, the values (img_size * num_of_rows u32)
+//     3 error: a UTF-8 message, the worker can't compute the job
+//
+// The worker answers each job with rows or an error.
+
+// Rust modules
+use std::collections::VecDeque;
+use std::io::{self, Error, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// Internal modules
+use super::{MandelConfig, SyntheticDistribution, Workload};
+use refine::PixelRect;
+use tile_cache::crc32;
+
+// Changes with every incompatible change of the messages
+pub const PROTOCOL_VERSION: u32 = 2;
+
+// Rows of one job of the coordinator
+pub const ROWS_PER_JOB: u32 = 16;
+
+// Connecting to a worker
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Sending a job and receiving its rows. A worker that takes longer is treated as failed.
+pub const JOB_TIMEOUT: Duration = Duration::from_secs(120);
+
+// Largest payload that is accepted: one job of an image with 65536 x 65536 pixels
+const MAX_FRAME_LEN: usize = 16 + (4 * 65536 * (ROWS_PER_JOB as usize));
+
+const JOB_MESSAGE: u8 = 1;
+const ROWS_MESSAGE: u8 = 2;
+const ERROR_MESSAGE: u8 = 3;
+
+// Everything that the workers need to compute the same iteration counts as the coordinator.
+// The method and the number of threads are chosen by each worker.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderParams {
+    pub re1: f64,
+    pub re2: f64,
+    pub img1: f64,
+    pub img2: f64,
+    pub z0_re: f64,
+    pub z0_im: f64,
+    pub max_iter: u32,
+    pub img_size: u32,
+    pub flip_y: bool,
+    pub inclusive_edges: bool,
+    pub detect_denormals: bool,
+    pub workload: Workload,
+    pub synthetic_distribution: SyntheticDistribution,
+    pub synthetic_seed: u64,
+    pub phoenix_p: f64,
+    pub phoenix_q: f64
+}
+
+impl RenderParams {
+    pub fn from_config(mandel_config: &MandelConfig) -> RenderParams {
+        RenderParams{
+            re1: mandel_config.re1,
+            re2: mandel_config.re2,
+            img1: mandel_config.img1,
+            img2: mandel_config.img2,
+            z0_re: mandel_config.z0_re,
+            z0_im: mandel_config.z0_im,
+            max_iter: mandel_config.max_iter,
+            img_size: mandel_config.img_size,
+            flip_y: mandel_config.flip_y,
+            inclusive_edges: mandel_config.inclusive_edges,
+            detect_denormals: mandel_config.detect_denormals,
+            workload: mandel_config.workload,
+            synthetic_distribution: mandel_config.synthetic_distribution,
+            synthetic_seed: mandel_config.synthetic_seed,
+            phoenix_p: mandel_config.phoenix_p,
+            phoenix_q: mandel_config.phoenix_q
+        }
+    }
+
+    // Copy of the configuration of the worker with the parameters of the coordinator
+    pub fn apply_to(&self, mandel_config: &MandelConfig) -> MandelConfig {
+        MandelConfig{
+            re1: self.re1,
+            re2: self.re2,
+            img1: self.img1,
+            img2: self.img2,
+            z0_re: self.z0_re,
+            z0_im: self.z0_im,
+            max_iter: self.max_iter,
+            img_size: self.img_size,
+            flip_y: self.flip_y,
+            inclusive_edges: self.inclusive_edges,
+            detect_denormals: self.detect_denormals,
+            workload: self.workload,
+            synthetic_distribution: self.synthetic_distribution,
+            synthetic_seed: self.synthetic_seed,
+            phoenix_p: self.phoenix_p,
+            phoenix_q: self.phoenix_q,
+            .. mandel_config.clone()
+        }
+    }
+}
+
+// Rows first_row .. first_row + num_of_rows of the image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RowJob {
+    pub first_row: u32,
+    pub num_of_rows: u32
+}
+
+impl RowJob {
+    // The rows as rectangle of the whole width of the image
+    pub fn rect(&self, img_size: u32) -> PixelRect {
+        PixelRect{x0: 0, y0: self.first_row, x1: img_size, y1: self.first_row + self.num_of_rows}
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    Job(RenderParams, RowJob),
+    Rows{job: RowJob, checksum: u32, values: Vec<u32>},
+    Error(String)
+}
+
+// CRC32 of the values as little endian bytes
+pub fn rows_checksum(values: &[u32]) -> u32 {
+    let bytes: Vec<u8> = values.iter().flat_map(|value| value.to_le_bytes()).collect();
+    crc32(&bytes)
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+// Reads the numbers of a payload one after another
+struct Payload<'a> {
+    bytes: &'a [u8]
+}
+
+impl<'a> Payload<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(invalid_data("message is too short".to_string()));
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(try!(self.take(1))[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(try!(self.take(4)));
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(try!(self.take(8)));
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn f64(&mut self) -> io::Result<f64> {
+        Ok(f64::from_bits(try!(self.u64())))
+    }
+
+    fn job(&mut self) -> io::Result<RowJob> {
+        Ok(RowJob{first_row: try!(self.u32()), num_of_rows: try!(self.u32())})
+    }
+}
+
+impl Message {
+    // The payload of the frame
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        match *self {
+            Message::Job(ref params, ref job) => {
+                bytes.push(JOB_MESSAGE);
+                bytes.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+                for value in &[params.re1, params.re2, params.img1, params.img2, params.z0_re, params.z0_im] {
+                    bytes.extend_from_slice(&value.to_bits().to_le_bytes());
+                }
+                bytes.extend_from_slice(&params.max_iter.to_le_bytes());
+                bytes.extend_from_slice(&params.img_size.to_le_bytes());
+                bytes.push((params.flip_y as u8) | ((params.inclusive_edges as u8) << 1) | ((params.detect_denormals as u8) << 2));
+                bytes.push(match params.workload {
+                    Workload::Fractal => 0,
+                    Workload::Synthetic => 1,
+                    Workload::Phoenix => 2
+                });
+                bytes.push(match params.synthetic_distribution {
+                    SyntheticDistribution::Uniform => 0,
+                    SyntheticDistribution::Zipf => 1
+                });
+                bytes.extend_from_slice(&params.synthetic_seed.to_le_bytes());
+                bytes.extend_from_slice(&params.phoenix_p.to_bits().to_le_bytes());
+                bytes.extend_from_slice(&params.phoenix_q.to_bits().to_le_bytes());
+                bytes.extend_from_slice(&job.first_row.to_le_bytes());
+                bytes.extend_from_slice(&job.num_of_rows.to_le_bytes());
+            },
+            Message::Rows{ref job, checksum, ref values} => {
+                bytes.reserve(13 + (4 * values.len()));
+                bytes.push(ROWS_MESSAGE);
+                bytes.extend_from_slice(&job.first_row.to_le_bytes());
+                bytes.extend_from_slice(&job.num_of_rows.to_le_bytes());
+                bytes.extend_from_slice(&checksum.to_le_bytes());
+                for value in values {
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+            },
+            Message::Error(ref message) => {
+                bytes.push(ERROR_MESSAGE);
+                bytes.extend_from_slice(message.as_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<Message> {
+        let mut payload = Payload{bytes: bytes};
+
+        let message = match try!(payload.u8()) {
+            JOB_MESSAGE => {
+                let version = try!(payload.u32());
+                if version != PROTOCOL_VERSION {
+                    return Err(invalid_data(format!("protocol version {} is not supported, expected {}", version, PROTOCOL_VERSION)));
+                }
+
+                let (re1, re2, img1, img2) = (try!(payload.f64()), try!(payload.f64()), try!(payload.f64()), try!(payload.f64()));
+                let (z0_re, z0_im) = (try!(payload.f64()), try!(payload.f64()));
+                let (max_iter, img_size) = (try!(payload.u32()), try!(payload.u32()));
+                let flags = try!(payload.u8());
+                let workload = match try!(payload.u8()) {
+                    0 => Workload::Fractal,
+                    1 => Workload::Synthetic,
+                    2 => Workload::Phoenix,
+                    other => return Err(invalid_data(format!("unknown workload {}", other)))
+                };
+                let synthetic_distribution = match try!(payload.u8()) {
+                    0 => SyntheticDistribution::Uniform,
+                    1 => SyntheticDistribution::Zipf,
+                    other => return Err(invalid_data(format!("unknown synthetic distribution {}", other)))
+                };
+                let synthetic_seed = try!(payload.u64());
+                let (phoenix_p, phoenix_q) = (try!(payload.f64()), try!(payload.f64()));
+
+                let params = RenderParams{re1: re1, re2: re2, img1: img1, img2: img2, z0_re: z0_re, z0_im: z0_im,
+                    max_iter: max_iter, img_size: img_size, flip_y: flags & 1 != 0, inclusive_edges: flags & 2 != 0,
+                    detect_denormals: flags & 4 != 0, workload: workload, synthetic_distribution: synthetic_distribution,
+                    synthetic_seed: synthetic_seed, phoenix_p: phoenix_p, phoenix_q: phoenix_q};
+
+                Message::Job(params, try!(payload.job()))
+            },
+            ROWS_MESSAGE => {
+                let job = try!(payload.job());
+                let checksum = try!(payload.u32());
+
+                if payload.bytes.len() % 4 != 0 {
+                    return Err(invalid_data(format!("{} bytes of values are not whole u32 values", payload.bytes.len())));
+                }
+
+                let values = payload.bytes.chunks(4).map(|value| u32::from_le_bytes([value[0], value[1], value[2], value[3]])).collect();
+                payload.bytes = &[];
+
+                Message::Rows{job: job, checksum: checksum, values: values}
+            },
+            ERROR_MESSAGE => {
+                let message = String::from_utf8_lossy(payload.bytes).into_owned();
+                payload.bytes = &[];
+
+                Message::Error(message)
+            },
+            other => return Err(invalid_data(format!("unknown message type {}", other)))
+        };
+
+        if !payload.bytes.is_empty() {
+            return Err(invalid_data(format!("{} bytes after the end of the message", payload.bytes.len())));
+        }
+
+        Ok(message)
+    }
+}
+
+pub fn write_message<W: Write>(writer: &mut W, message: &Message) -> io::Result<()> {
+    let payload = message.encode();
+
+    try!(writer.write_all(&(payload.len() as u32).to_le_bytes()));
+    try!(writer.write_all(&payload));
+    writer.flush()
+}
+
+// Returns None if the connection was closed before the frame
+pub fn read_message<R: Read>(reader: &mut R) -> io::Result<Option<Message>> {
+    let mut len = [0; 4];
+
+    match reader.read_exact(&mut len) {
+        Ok(()) => (),
+        Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e)
+    }
+
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(invalid_data(format!("message of {} bytes is too large", len)));
+    }
+
+    let mut payload = vec![0; len];
+    try!(reader.read_exact(&mut payload));
+
+    Message::decode(&payload).map(Some)
+}
+
+// Answer the jobs of all coordinators that connect to the listener, each one in its own thread.
+// render computes the pixels of a rectangle with the configuration of the worker and the parameters of the job
+// (the method of the worker). Returns only if the listener fails.
+pub fn serve<F>(listener: TcpListener, worker_config: &MandelConfig, render: F) -> io::Result<()>
+    where F: Fn(&MandelConfig, &PixelRect, &mut [u32]) + Sync {
+    let render = &render;
+
+    thread::scope(|scope| {
+        for stream in listener.incoming() {
+            let stream = try!(stream);
+
+            scope.spawn(move || {
+                let peer = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_else(|_| "unknown".to_string());
+
+                match serve_connection(stream, worker_config, render) {
+                    Ok(num_of_rows) => println!("Coordinator {} done, {} rows computed", peer, num_of_rows),
+                    Err(e) => println!("Coordinator {}: connection lost: {}", peer, e)
+                }
+            });
+        }
+
+        Ok(())
+    })
+}
+
+// Answer the jobs of one connection until the coordinator closes it, returns the number of computed rows
+fn serve_connection<F>(mut stream: TcpStream, worker_config: &MandelConfig, render: &F) -> io::Result<u32>
+    where F: Fn(&MandelConfig, &PixelRect, &mut [u32]) {
+    let mut num_of_rows = 0;
+
+    while let Some(message) = try!(read_message(&mut stream)) {
+        let answer = match message {
+            Message::Job(params, job) => {
+                let config = params.apply_to(worker_config);
+
+                // The rows come from the network, first_row + num_of_rows may overflow
+                let in_image = job.first_row.checked_add(job.num_of_rows).map_or(false, |end| end <= config.img_size);
+
+                match config.validate() {
+                    Ok(()) if job.num_of_rows > 0 && in_image => {
+                        let mut values = vec![0; (job.num_of_rows as usize) * (config.img_size as usize)];
+                        render(&config, &job.rect(config.img_size), &mut values);
+                        num_of_rows += job.num_of_rows;
+
+                        Message::Rows{job: job, checksum: rows_checksum(&values), values: values}
+                    },
+                    Ok(()) => Message::Error(format!("{} rows from row {} are outside of the image", job.num_of_rows, job.first_row)),
+                    Err(e) => Message::Error(format!("{}", e))
+                }
+            },
+            _ => Message::Error("only jobs can be answered".to_string())
+        };
+
+        try!(write_message(&mut stream, &answer));
+    }
+
+    Ok(num_of_rows)
+}
+
+// The rows of each worker of a distributed render
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkerStats {
+    pub address: String,
+    pub num_of_rows: u32,
+    // The error that stopped the worker, its rows were computed by the others
+    pub error: Option<String>
+}
+
+// Result of render_distributed
+pub struct DistributedRender {
+    // The iteration counts, row by row
+    pub image: Vec<u32>,
+    pub workers: Vec<WorkerStats>
+}
+
+// Shared state of the connections of the coordinator
+struct Progress {
+    queue: VecDeque<RowJob>,
+    rows_left: u32
+}
+
+struct JobQueue {
+    progress: Mutex<Progress>,
+    changed: Condvar
+}
+
+impl JobQueue {
+    fn new(img_size: u32) -> JobQueue {
+        let queue = (0..img_size).step_by(ROWS_PER_JOB as usize)
+            .map(|first_row| RowJob{first_row: first_row, num_of_rows: ROWS_PER_JOB.min(img_size - first_row)})
+            .collect();
+
+        JobQueue{progress: Mutex::new(Progress{queue: queue, rows_left: img_size}), changed: Condvar::new()}
+    }
+
+    // The next job, waits while the queue is empty but other workers may still fail and give their jobs back.
+    // None when all rows are computed.
+    fn next(&self) -> Option<RowJob> {
+        let mut progress = self.progress.lock().unwrap();
+
+        loop {
+            if let Some(job) = progress.queue.pop_front() {
+                return Some(job);
+            }
+
+            if progress.rows_left == 0 {
+                return None;
+            }
+
+            progress = self.changed.wait(progress).unwrap();
+        }
+    }
+
+    fn done(&self, job: RowJob) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.rows_left -= job.num_of_rows;
+
+        if progress.rows_left == 0 {
+            self.changed.notify_all();
+        }
+    }
+
+    fn give_back(&self, job: RowJob) {
+        self.progress.lock().unwrap().queue.push_back(job);
+        self.changed.notify_all();
+    }
+
+    fn rows_left(&self) -> u32 {
+        self.progress.lock().unwrap().rows_left
+    }
+}
+
+fn connect(address: &str) -> io::Result<TcpStream> {
+    let mut last_error = Error::new(ErrorKind::NotFound, format!("no address for '{}'", address));
+
+    for addr in try!(address.to_socket_addrs()) {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => {
+                try!(stream.set_read_timeout(Some(JOB_TIMEOUT)));
+                try!(stream.set_write_timeout(Some(JOB_TIMEOUT)));
+                try!(stream.set_nodelay(true));
+                return Ok(stream);
+            },
+            Err(e) => last_error = e
+        }
+    }
+
+    Err(last_error)
+}
+
+// Send one job and check the answer: the same rows, all values and the right checksum
+fn compute_job(stream: &mut TcpStream, params: &RenderParams, job: RowJob) -> io::Result<Vec<u32>> {
+    try!(write_message(stream, &Message::Job(params.clone(), job)));
+
+    match try!(read_message(stream)) {
+        Some(Message::Rows{job: rows_job, checksum, values}) => {
+            if rows_job != job {
+                Err(invalid_data(format!("rows {} .. {} instead of {} .. {}", rows_job.first_row,
+                    rows_job.first_row + rows_job.num_of_rows, job.first_row, job.first_row + job.num_of_rows)))
+            } else if values.len() != (job.num_of_rows as usize) * (params.img_size as usize) {
+                Err(invalid_data(format!("{} values instead of {}", values.len(), (job.num_of_rows as usize) * (params.img_size as usize))))
+            } else if rows_checksum(&values) != checksum {
+                Err(invalid_data(format!("wrong checksum of rows {} .. {}", job.first_row, job.first_row + job.num_of_rows)))
+            } else {
+                Ok(values)
+            }
+        },
+        Some(Message::Error(message)) => Err(Error::other(message)),
+        Some(Message::Job(..)) => Err(invalid_data("job instead of rows".to_string())),
+        None => Err(Error::new(ErrorKind::UnexpectedEof, "connection closed"))
+    }
+}
+
+// Compute the rows of jobs from the queue with one worker until all rows are done or the worker fails
+fn run_connection(address: &str, params: &RenderParams, queue: &JobQueue, image: &Mutex<Vec<u32>>) -> WorkerStats {
+    let mut stats = WorkerStats{address: address.to_string(), num_of_rows: 0, error: None};
+    let row_len = params.img_size as usize;
+
+    let mut stream = match connect(address) {
+        Ok(stream) => stream,
+        Err(e) => {
+            stats.error = Some(format!("can't connect: {}", e));
+            return stats;
+        }
+    };
+
+    while let Some(job) = queue.next() {
+        match compute_job(&mut stream, params, job) {
+            Ok(values) => {
+                let start = (job.first_row as usize) * row_len;
+                image.lock().unwrap()[start .. start + values.len()].copy_from_slice(&values);
+                stats.num_of_rows += job.num_of_rows;
+                queue.done(job);
+            },
+            Err(e) => {
+                queue.give_back(job);
+                stats.error = Some(format!("rows {} .. {}: {}", job.first_row, job.first_row + job.num_of_rows, e));
+                break;
+            }
+        }
+    }
+
+    let _ = stream.shutdown(Shutdown::Both);
+    stats
+}
+
+// Render the image of the configuration on the workers. Returns an error if no worker is left before all rows are done.
+pub fn render_distributed(mandel_config: &MandelConfig, workers: &[String]) -> io::Result<DistributedRender> {
+    let params = RenderParams::from_config(mandel_config);
+    let queue = JobQueue::new(mandel_config.img_size);
+    let image = Mutex::new(vec![0; mandel_config.num_of_pixels()]);
+
+    let stats: Vec<WorkerStats> = thread::scope(|scope| {
+        let connections: Vec<_> = workers.iter().map(|address| {
+            let (params, queue, image) = (&params, &queue, &image);
+            scope.spawn(move || {
+                let stats = run_connection(address, params, queue, image);
+
+                if let Some(ref e) = stats.error {
+                    println!("Worker {} failed after {} rows: {}", address, stats.num_of_rows, e);
+                }
+
+                stats
+            })
+        }).collect();
+
+        connections.into_iter().map(|connection| connection.join().unwrap()).collect()
+    });
+
+    let rows_left = queue.rows_left();
+    if rows_left > 0 {
+        return Err(Error::other(format!("all workers failed, {} rows are missing", rows_left)));
+    }
+
+    Ok(DistributedRender{image: image.into_inner().unwrap(), workers: stats})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::render_row_from;
+
+    fn small_config() -> MandelConfig {
+        let default = MandelConfig::default();
+
+        MandelConfig{
+            img_size: 64,
+            max_iter: 128,
+            .. default
+        }
+    }
+
+    fn render_rows(mandel_config: &MandelConfig, rect: &PixelRect, values: &mut [u32]) {
+        for (n, row) in values.chunks_mut(rect.width() as usize).enumerate() {
+            render_row_from(mandel_config, rect.x0, rect.y0 + (n as u32), row);
+        }
+    }
+
+    fn start_worker() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        thread::spawn(move || serve(listener, &MandelConfig::default(), render_rows));
+        address
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let params = RenderParams{z0_re: 0.25, flip_y: true, inclusive_edges: true, detect_denormals: true,
+            workload: Workload::Phoenix, synthetic_distribution: SyntheticDistribution::Zipf, synthetic_seed: 7,
+            phoenix_p: 0.5, phoenix_q: -0.25, .. RenderParams::from_config(&small_config())};
+        let job = RowJob{first_row: 16, num_of_rows: 2};
+        let messages = vec![
+            Message::Job(params.clone(), job),
+            Message::Rows{job: job, checksum: rows_checksum(&[1, 2, 3]), values: vec![1, 2, 3]},
+            Message::Error("unknown method".to_string())
+        ];
+
+        for message in &messages {
+            let mut frame = Vec::new();
+            write_message(&mut frame, message).unwrap();
+            assert_eq!(read_message(&mut &frame[..]).unwrap().as_ref(), Some(message));
+        }
+
+        // The worker applies the parameters, the rest is its own configuration
+        let config = params.apply_to(&MandelConfig{num_threads: 8, .. MandelConfig::default()});
+        assert_eq!(config, MandelConfig{num_threads: 8, z0_re: 0.25, flip_y: true, inclusive_edges: true,
+            detect_denormals: true, workload: Workload::Phoenix, synthetic_distribution: SyntheticDistribution::Zipf,
+            synthetic_seed: 7, phoenix_p: 0.5, phoenix_q: -0.25, .. small_config()});
+
+        assert_eq!(read_message(&mut &[][..]).unwrap(), None);
+        assert!(Message::decode(&[]).is_err());
+        assert!(Message::decode(&[9]).is_err());
+        assert!(Message::decode(&[ROWS_MESSAGE, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 7]).is_err());
+
+        let mut wrong_version = messages[0].encode();
+        wrong_version[1] = 99;
+        assert!(Message::decode(&wrong_version).is_err());
+
+        // The workload follows the 6 coordinates, max_iter, img_size and the flags
+        let mut wrong_workload = messages[0].encode();
+        wrong_workload[1 + 4 + 48 + 8 + 1] = 3;
+        assert!(Message::decode(&wrong_workload).is_err());
+    }
+
+    #[test]
+    fn failed_worker_rows_are_computed_by_the_others() {
+        let config = small_config();
+        let mut expected = vec![0; config.num_of_pixels()];
+        render_rows(&config, &RowJob{first_row: 0, num_of_rows: 64}.rect(64), &mut expected);
+
+        // Takes the first job and drops the connection
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let broken = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            read_message(&mut stream).unwrap();
+        });
+
+        let render = render_distributed(&config, &[broken.clone(), start_worker()]).unwrap();
+
+        assert!(render.image == expected);
+        assert_eq!(render.workers[0].num_of_rows, 0);
+        assert!(render.workers[0].error.is_some());
+        assert_eq!((render.workers[1].num_of_rows, render.workers[1].error.clone()), (64, None));
+
+        // Nobody listens on the port
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+        assert!(render_distributed(&config, &[closed]).is_err());
+    }
+
+    #[test]
+    fn rows_outside_of_the_image_are_an_error() {
+        let params = RenderParams::from_config(&small_config());
+        let mut stream = TcpStream::connect(start_worker()).unwrap();
+
+        for &(first_row, num_of_rows) in &[(u32::MAX, 16), (60, u32::MAX), (60, 5), (0, 0)] {
+            let job = Message::Job(params.clone(), RowJob{first_row: first_row, num_of_rows: num_of_rows});
+            let mut frame = Vec::new();
+            write_message(&mut frame, &job).unwrap();
+            assert_eq!(read_message(&mut &frame[..]).unwrap(), Some(job.clone()));
+
+            write_message(&mut stream, &job).unwrap();
+            match read_message(&mut stream).unwrap() {
+                Some(Message::Error(message)) => assert!(message.contains("outside of the image"), "{}", message),
+                other => panic!("rows {} + {}: {:?}", first_row, num_of_rows, other)
+            }
+        }
+
+        // The connection still answers jobs
+        let job = RowJob{first_row: 48, num_of_rows: 16};
+        write_message(&mut stream, &Message::Job(params, job)).unwrap();
+        match read_message(&mut stream).unwrap() {
+            Some(Message::Rows{job: answered, ..}) => assert_eq!(answered, job),
+            other => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn workers_render_the_workload_of_the_coordinator() {
+        let configs = [
+            MandelConfig{workload: Workload::Phoenix, phoenix_p: 0.56667, phoenix_q: -0.5, .. small_config()},
+            MandelConfig{workload: Workload::Synthetic, synthetic_distribution: SyntheticDistribution::Zipf, synthetic_seed: 3,
+                .. small_config()}
+        ];
+
+        for config in &configs {
+            let mut expected = vec![0; config.num_of_pixels()];
+            render_rows(config, &RowJob{first_row: 0, num_of_rows: 64}.rect(64), &mut expected);
+
+            // The worker has the default workload
+            assert!(render_distributed(config, &[start_worker()]).unwrap().image == expected, "{:?}", config.workload);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+// Errors of the program and the exit codes of main.rs.
+//
+// Scripts that run mandel-rust can tell from the exit code what went wrong:
+//
+//       0: success
+//       1: other errors, for example some jobs of --batch failed
+//       2: invalid command line options, configuration or jobs file (Config)
+//       3: a file can't be read or written (Io)
+//       4: an output is wrong: a corrupt image (--verify_output) or a method whose pixels differ from serial
+//          (--compare_parallel_outputs) (CorruptOutput, Verification)
+//       5: a method didn't finish within --method_timeout_ms (Timeout)
+//     101: internal error (panic)
+//
+// The diff sub command has its own exit codes, see diff.
+
+// Rust modules
+use std::error;
+use std::fmt;
+use std::io;
+
+// Internal modules
+use ConfigError;
+
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_CONFIG: i32 = 2;
+pub const EXIT_IO: i32 = 3;
+pub const EXIT_VERIFICATION: i32 = 4;
+pub const EXIT_TIMEOUT: i32 = 5;
+
+// The list of the exit codes at the end of --help
+pub const EXIT_CODES_HELP: &'static str = "EXIT CODES:
+    0    success
+    1    other errors (for example failed jobs of --batch)
+    2    invalid options, configuration or jobs file
+    3    a file can't be read or written
+    4    corrupt image (--verify_output) or output differs from serial (--compare_parallel_outputs)
+    5    a method timed out (--method_timeout_ms)
+    101  internal error";
+
+#[derive(Debug)]
+pub enum MandelError {
+    // Invalid options, configuration or jobs file
+    Config(ConfigError),
+    // Reading or writing the file path failed
+    Io{path: String, source: io::Error},
+    // The file path was written but does not contain what it should (--verify_output)
+    CorruptOutput{path: String, message: String},
+    // The output of the method differs from serial in this many pixels (--compare_parallel_outputs)
+    Verification{method: String, mismatches: usize},
+    // The method was cancelled after --method_timeout_ms
+    Timeout{method: String}
+}
+
+impl MandelError {
+    pub fn io(path: &str, source: io::Error) -> MandelError {
+        MandelError::Io{path: path.to_string(), source: source}
+    }
+
+    // The exit code of the program for this error, see the list at the top
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            MandelError::Config(_) => EXIT_CONFIG,
+            MandelError::Io{..} => EXIT_IO,
+            MandelError::CorruptOutput{..} | MandelError::Verification{..} => EXIT_VERIFICATION,
+            MandelError::Timeout{..} => EXIT_TIMEOUT
+        }
+    }
+}
+
+impl fmt::Display for MandelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MandelError::Config(ref e) => write!(f, "{}", e),
+            MandelError::Io{ref path, ref source} => write!(f, "I/O error: '{}': {}", path, source),
+            MandelError::CorruptOutput{ref path, ref message} => write!(f, "corrupt output: '{}': {}", path, message),
+            MandelError::Verification{ref method, mismatches} => write!(f, "output of {} differs from serial in {} pixels", method, mismatches),
+            MandelError::Timeout{ref method} => write!(f, "method {} timed out", method)
+        }
+    }
+}
+
+impl error::Error for MandelError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            MandelError::Config(ref e) => Some(e),
+            MandelError::Io{ref source, ..} => Some(source),
+            _ => None
+        }
+    }
+}
+
+impl From<ConfigError> for MandelError {
+    fn from(e: ConfigError) -> MandelError {
+        MandelError::Config(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn exit_codes_and_messages() {
+        let config = MandelError::from(ConfigError::InvalidValue("max_iter must be greater than 0".to_string()));
+        assert_eq!(config.exit_code(), EXIT_CONFIG);
+        assert_eq!(config.to_string(), "invalid configuration: max_iter must be greater than 0");
+        assert!(config.source().is_some());
+
+        let io = MandelError::io("plot/serial.csv", io::Error::new(io::ErrorKind::PermissionDenied, "permission denied"));
+        assert_eq!(io.exit_code(), EXIT_IO);
+        assert_eq!(io.to_string(), "I/O error: 'plot/serial.csv': permission denied");
+
+        let verification = MandelError::Verification{method: "rayon_join".to_string(), mismatches: 3};
+        assert_eq!(verification.exit_code(), EXIT_VERIFICATION);
+        assert_eq!(verification.to_string(), "output of rayon_join differs from serial in 3 pixels");
+        assert_eq!(MandelError::CorruptOutput{path: "a.ppm".to_string(), message: "empty file".to_string()}.exit_code(), EXIT_VERIFICATION);
+        assert_eq!(MandelError::Timeout{method: "serial".to_string()}.exit_code(), EXIT_TIMEOUT);
+
+        // All codes are different and none is the code of success
+        let mut codes = vec![EXIT_SUCCESS, EXIT_FAILURE, EXIT_CONFIG, EXIT_IO, EXIT_VERIFICATION, EXIT_TIMEOUT];
+        codes.dedup();
+        assert_eq!(codes.len(), 6);
+    }
+}
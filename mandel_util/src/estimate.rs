@@ -0,0 +1,148 @@
+// Runtime estimate of a method from a small probe render (--skip_slow).
+//
+// The probe renders the same region with the same max_iter at PROBE_IMG_SIZE x PROBE_IMG_SIZE pixels,
+// only once. Its time is scaled by the number of pixels and the number of runs. The work per pixel
+// doesn't depend on the image size, so this is a good estimate as long as the probe is not dominated
+// by the start up of the thread pool.
+
+// External modules
+use serde_json::Value;
+
+// Rust modules
+use std::fs;
+use std::io;
+use std::sync::atomic::AtomicBool;
+
+// Internal modules
+use {time_runs, BenchmarkResult, MandelConfig};
+
+// Width and height of the probe image
+pub const PROBE_IMG_SIZE: u32 = 128;
+
+// A method that was not run because of --skip_slow
+#[derive(Clone, Debug)]
+pub struct SkippedMethod {
+    pub method: String,
+    // Estimated time of all runs in seconds
+    pub estimate_s: f64
+}
+
+// The probe: same region and max_iter, at most PROBE_IMG_SIZE x PROBE_IMG_SIZE pixels, a single run and no output
+pub fn probe_config(mandel_config: &MandelConfig) -> MandelConfig {
+    let img_size = mandel_config.img_size.min(PROBE_IMG_SIZE);
+
+    MandelConfig{
+        img_size: img_size,
+        num_of_runs: 1,
+        adaptive_runs: false,
+        no_ppm: true,
+        raw_output: false,
+        stats: false,
+        bench_db: None,
+        .. mandel_config.clone()
+    }
+}
+
+// Estimated time of all num_of_runs runs in seconds from the time of one probe run
+pub fn scale_estimate(probe_ms: f64, probe: &MandelConfig, mandel_config: &MandelConfig) -> f64 {
+    let pixel_ratio = (mandel_config.num_of_pixels() as f64) / (probe.num_of_pixels() as f64);
+    (probe_ms * pixel_ratio * (mandel_config.num_of_runs as f64)) / 1000.0
+}
+
+// Render the probe with the method and return the estimated time of all runs in seconds
+pub fn estimate_runtime(mandel_func: &dyn Fn(&MandelConfig, &mut [u32], &AtomicBool) -> (), mandel_config: &MandelConfig) -> f64 {
+    let probe = probe_config(mandel_config);
+    let mut image = vec![0; probe.num_of_pixels()];
+    let times = time_runs(mandel_func, &probe, &mut image, &AtomicBool::new(false));
+
+    scale_estimate(times.mean_ms, &probe, mandel_config)
+}
+
+// Report of a benchmark with --skip_slow: the results of the methods that ran and
+// the skipped methods with their estimate, so that a missing method is not mistaken for a failed one
+pub fn report_json(run_id: &str, skip_slow: f64, results: &[BenchmarkResult], skipped: &[SkippedMethod]) -> Value {
+    let mut methods: Vec<Value> = results.iter().map(|result| json!({
+        "method": result.method,
+        "skipped": false,
+        "num_threads": result.num_threads,
+        "mean_ms": result.mean_ms,
+        "min_ms": result.min_ms,
+        "max_ms": result.max_ms,
+        "stddev_ms": result.stddev_ms,
+        "timed_out": result.timed_out
+    })).collect();
+
+    methods.extend(skipped.iter().map(|skipped| json!({
+        "method": skipped.method,
+        "skipped": true,
+        "estimate_s": skipped.estimate_s
+    })));
+
+    json!({
+        "run_id": run_id,
+        "skip_slow_s": skip_slow,
+        "methods": methods
+    })
+}
+
+pub fn write_report(file_name: &str, report: &Value) -> io::Result<()> {
+    let content = try!(::serde_json::to_string_pretty(report).map_err(|e| io::Error::new(io::ErrorKind::Other, e)));
+    fs::write(file_name, content + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probe_keeps_region_and_max_iter() {
+        let config = MandelConfig{
+            re1: -0.75,
+            re2: -0.7,
+            img1: 0.1,
+            img2: 0.15,
+            img_size: 4096,
+            max_iter: 100000,
+            num_of_runs: 5,
+            .. MandelConfig::default()
+        };
+        let probe = probe_config(&config);
+
+        assert_eq!((probe.re1, probe.re2, probe.img1, probe.img2), (config.re1, config.re2, config.img1, config.img2));
+        assert_eq!(probe.max_iter, 100000);
+        assert_eq!(probe.img_size, PROBE_IMG_SIZE);
+        assert_eq!(probe.x_step() * (PROBE_IMG_SIZE as f64), config.re2 - config.re1);
+        assert_eq!(probe.y_step() * (PROBE_IMG_SIZE as f64), config.img2 - config.img1);
+        assert_eq!((probe.num_threads, probe.workload, probe.flip_y), (config.num_threads, config.workload, config.flip_y));
+        assert_eq!(probe.num_of_runs, 1);
+        assert!(probe.no_ppm);
+
+        // A small image is probed as it is
+        let small = MandelConfig{img_size: 64, .. MandelConfig::default()};
+        assert_eq!(probe_config(&small).img_size, 64);
+        assert_eq!(probe_config(&small).x_step(), small.x_step());
+    }
+
+    #[test]
+    fn estimate_scales_with_pixels_and_runs() {
+        let config = MandelConfig{img_size: 1024, num_of_runs: 3, .. MandelConfig::default()};
+        let probe = probe_config(&config);
+
+        // 64 times more pixels, 3 runs
+        assert_eq!(scale_estimate(10.0, &probe, &config), 1.92);
+        assert_eq!(scale_estimate(10.0, &probe, &probe), 0.01);
+    }
+
+    #[test]
+    fn skipped_methods_in_report() {
+        let results = [BenchmarkResult{method: "rayon_join".to_string(), num_threads: 4, mean_ms: 2.0, min_ms: 1.0, max_ms: 3.0, stddev_ms: 0.5, timed_out: false}];
+        let skipped = [SkippedMethod{method: "serial".to_string(), estimate_s: 7200.0}];
+        let report = report_json("run", 60.0, &results, &skipped);
+
+        assert_eq!(report["methods"].as_array().unwrap().len(), 2);
+        assert_eq!(report["methods"][0]["skipped"], false);
+        assert_eq!(report["methods"][1]["method"], "serial");
+        assert_eq!(report["methods"][1]["skipped"], true);
+        assert_eq!(report["methods"][1]["estimate_s"], 7200.0);
+    }
+}
@@ -0,0 +1,68 @@
+// OpenEXR output (--output_format=exr): the iteration counts as 32 bit floats without loss of precision,
+// for post-processing and color grading in other tools.
+//
+// The image has one float channel "Y" with the value iter / max_iter for every pixel,
+// 1.0 is the inside of the set. Row 0 is the top of the image like in the PPM images.
+
+// External modules
+use exr::prelude::{AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes, SmallVec, Text,
+    WritableImage};
+use exr::error::Error;
+
+// Rust modules
+use std::io;
+
+// Internal modules
+use MandelConfig;
+
+// Name of the only channel, viewers show it as gray scale
+pub const EXR_CHANNEL: &'static str = "Y";
+
+// Write the iteration counts of a square image as OpenEXR image
+pub fn write_image_exr(file_name: &str, mandel_config: &MandelConfig, image: &[u32]) -> io::Result<()> {
+    let max_iter = mandel_config.max_iter as f64;
+    let values: Vec<f32> = image.iter().map(|&value| ((value as f64) / max_iter) as f32).collect();
+
+    let channel = AnyChannel::new(EXR_CHANNEL, FlatSamples::F32(values));
+    let mut attributes = LayerAttributes::named("mandelbrot");
+    attributes.comments = Some(Text::from(format!("mandelbrot, max_iter: {}, {}", mandel_config.max_iter, mandel_config).as_str()));
+
+    let size = mandel_config.img_size as usize;
+    let layer = Layer::new((size, size), attributes, Encoding::FAST_LOSSLESS, AnyChannels::sort(SmallVec::from_vec(vec![channel])));
+
+    Image::from_layer(layer).write().to_file(file_name).map_err(|e| match e {
+        Error::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::Other, e.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exr::prelude::read_first_flat_layer_from_file;
+    use std::env::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn float_values_round_trip() {
+        let file_name = temp_dir().join("mandel_exr_test.exr");
+        let file_name = file_name.to_str().unwrap();
+        let config = MandelConfig{img_size: 2, max_iter: 4096, .. MandelConfig::default()};
+
+        write_image_exr(file_name, &config, &[0, 1, 4095, 4096]).unwrap();
+
+        let exr_image = read_first_flat_layer_from_file(file_name).unwrap();
+        let layer = &exr_image.layer_data;
+        assert_eq!((layer.size.width(), layer.size.height()), (2, 2));
+        assert_eq!(layer.channel_data.list.len(), 1);
+        assert_eq!(layer.channel_data.list[0].name, Text::from(EXR_CHANNEL));
+
+        match layer.channel_data.list[0].sample_data {
+            // Single iterations are not lost like in the 8 bit PPM colors
+            FlatSamples::F32(ref values) => assert_eq!(*values, vec![0.0, 1.0 / 4096.0, 4095.0 / 4096.0, 1.0]),
+            ref other => panic!("expected f32 samples, got {:?}", other)
+        }
+
+        fs::remove_file(file_name).unwrap();
+    }
+}
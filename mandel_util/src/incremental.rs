@@ -0,0 +1,308 @@
+// Rendering that can be interrupted and continued later (--incremental).
+//
+// The finished rows are tracked in an atomic bitset (one bit per row) and each finished row is
+// appended to a progress file next to the output ("<output>.progress"). If the program is
+// interrupted, the next run reads the progress file, restores the finished rows and only computes
+// the missing ones. The progress file is deleted when the render is complete.
+//
+// File layout (all values are u32 little endian):
+// magic "MPRG", version, length of the key, key (UTF-8), then one record per finished row:
+// row index, img_size iteration counts.
+// The key describes everything that changes the pixels, a progress file with a different key is rejected.
+// An incomplete record at the end (interrupted while writing) is dropped.
+
+// Rust modules
+use std::fs::{self, File, OpenOptions};
+use std::io::prelude::{Read, Write};
+use std::io::{BufReader, Error, ErrorKind, Result, Seek, SeekFrom};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+// Internal modules
+use super::{MandelConfig, render_row};
+use raw::{read_u32, write_u32};
+
+const PROGRESS_MAGIC: &'static [u8; 4] = b"MPRG";
+const PROGRESS_VERSION: u32 = 1;
+
+pub struct IncrementalRenderer {
+    progress_file: String,
+    img_size: u32,
+    // One bit per row, set when the row is finished and written to the progress file
+    rows_done: Vec<AtomicU64>,
+    image: Vec<u32>,
+    writer: Mutex<File>
+}
+
+// Everything that changes the pixels of the image
+fn progress_key(mandel_config: &MandelConfig) -> String {
+    format!("re1={};re2={};img1={};img2={};max_iter={};img_size={};flip_y={};workload={};z0={},{}",
+        mandel_config.re1, mandel_config.re2, mandel_config.img1, mandel_config.img2,
+        mandel_config.max_iter, mandel_config.img_size, mandel_config.flip_y, mandel_config.workload_name(),
+        mandel_config.z0_re, mandel_config.z0_im)
+}
+
+impl IncrementalRenderer {
+    // Open the progress file for the given output file and restore the finished rows,
+    // or start a new progress file if there is none.
+    pub fn open(output_file: &str, mandel_config: &MandelConfig) -> Result<IncrementalRenderer> {
+        let progress_file = format!("{}.progress", output_file);
+        let img_size = mandel_config.img_size;
+        let key = progress_key(mandel_config);
+
+        let mut renderer = IncrementalRenderer{
+            progress_file: progress_file.clone(),
+            img_size: img_size,
+            rows_done: (0..((img_size + 63) / 64)).map(|_| AtomicU64::new(0)).collect(),
+            image: vec![0; (img_size as usize) * (img_size as usize)],
+            writer: Mutex::new(try!(OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&progress_file)))
+        };
+
+        let file_len = try!(renderer.writer.lock().unwrap().metadata()).len();
+
+        if file_len == 0 {
+            let mut writer = renderer.writer.lock().unwrap();
+            try!(writer.write_all(PROGRESS_MAGIC));
+            try!(write_u32(&mut *writer, PROGRESS_VERSION));
+            try!(write_u32(&mut *writer, key.len() as u32));
+            try!(writer.write_all(key.as_bytes()));
+            try!(writer.flush());
+        } else {
+            let valid_len = try!(renderer.restore(&key));
+            let mut writer = renderer.writer.lock().unwrap();
+            // Drop an incomplete record, so that the next record starts at the right place
+            try!(writer.set_len(valid_len));
+            try!(writer.seek(SeekFrom::Start(valid_len)));
+        }
+
+        Ok(renderer)
+    }
+
+    // Read the finished rows from the progress file, returns the length of the valid part of the file
+    fn restore(&mut self, key: &str) -> Result<u64> {
+        let invalid = |message: String| Error::new(ErrorKind::InvalidData, message);
+        let mut reader = BufReader::new(try!(File::open(&self.progress_file)));
+
+        let mut magic = [0; 4];
+        try!(reader.read_exact(&mut magic));
+        if &magic != PROGRESS_MAGIC {
+            return Err(invalid(format!("'{}' is not a progress file", self.progress_file)));
+        }
+
+        let version = try!(read_u32(&mut reader));
+        if version != PROGRESS_VERSION {
+            return Err(invalid(format!("'{}': unsupported progress file version {}", self.progress_file, version)));
+        }
+
+        let mut file_key = vec![0; try!(read_u32(&mut reader)) as usize];
+        try!(reader.read_exact(&mut file_key));
+        if file_key != key.as_bytes() {
+            return Err(invalid(format!("'{}' belongs to a different configuration: '{}', delete it to start over",
+                self.progress_file, String::from_utf8_lossy(&file_key))));
+        }
+
+        let row_len = self.img_size as usize;
+        let record_len = 4 * (row_len + 1);
+        let mut valid_len = (12 + file_key.len()) as u64;
+        let mut record = vec![0; record_len];
+
+        // Stop at the end of the file or at an incomplete record
+        while reader.read_exact(&mut record).is_ok() {
+            let mut values = record.chunks(4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+            let y = values.next().unwrap();
+
+            if y >= self.img_size || self.is_row_done(y) {
+                return Err(invalid(format!("'{}': invalid row {}", self.progress_file, y)));
+            }
+
+            for (pixel, value) in self.image[((y as usize) * row_len)..(((y as usize) + 1) * row_len)].iter_mut().zip(values) {
+                *pixel = value;
+            }
+
+            self.mark_row_done(y);
+            valid_len += record_len as u64;
+        }
+
+        Ok(valid_len)
+    }
+
+    pub fn is_row_done(&self, y: u32) -> bool {
+        is_bit_set(&self.rows_done, y)
+    }
+
+    fn mark_row_done(&self, y: u32) {
+        set_bit(&self.rows_done, y);
+    }
+
+    pub fn num_of_rows_done(&self) -> u32 {
+        self.rows_done.iter().map(|bits| bits.load(Ordering::SeqCst).count_ones()).sum()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.num_of_rows_done() == self.img_size
+    }
+
+    // The image, rows that are not finished yet are zero
+    pub fn image(&self) -> &[u32] {
+        &self.image
+    }
+
+    // Compute all missing rows with num_threads threads, each finished row is appended to the progress file
+    pub fn render(&mut self, mandel_config: &MandelConfig) -> Result<()> {
+        self.render_rows(mandel_config, self.img_size)
+    }
+
+    // Like render, but stops after max_rows new rows
+    pub fn render_rows(&mut self, mandel_config: &MandelConfig, max_rows: u32) -> Result<()> {
+        assert!(mandel_config.img_size == self.img_size);
+
+        let row_len = self.img_size as usize;
+        let num_of_threads = mandel_config.num_threads.max(1);
+        let rows_left = AtomicUsize::new(max_rows as usize);
+
+        // Workers take the next missing row from here
+        let rows = &Mutex::new(self.image.chunks_mut(row_len).enumerate());
+        let rows_left = &rows_left;
+        let rows_done = &self.rows_done;
+        let writer = &self.writer;
+
+        let results: Vec<Result<()>> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..num_of_threads).map(|_| scope.spawn(move || -> Result<()> {
+                loop {
+                    let (y, row) = match rows.lock().unwrap().find(|&(y, _)| !is_bit_set(rows_done, y as u32)) {
+                        Some((y, row)) => (y as u32, row),
+                        None => return Ok(())
+                    };
+
+                    // Take one row from the budget, stop if there is nothing left
+                    if rows_left.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)).is_err() {
+                        return Ok(());
+                    }
+
+                    render_row(mandel_config, y, row);
+
+                    try!(append_row(writer, y, row));
+                    set_bit(rows_done, y);
+                }
+            })).collect();
+
+            workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+        });
+
+        results.into_iter().collect()
+    }
+
+    // Delete the progress file, call this after the output has been written.
+    // Returns an error if the render is not complete yet.
+    pub fn finish(self) -> Result<Vec<u32>> {
+        if !self.is_complete() {
+            return Err(Error::new(ErrorKind::Other, format!("render is not complete: {} of {} rows done",
+                self.num_of_rows_done(), self.img_size)));
+        }
+
+        drop(self.writer);
+        try!(fs::remove_file(&self.progress_file));
+
+        Ok(self.image)
+    }
+}
+
+fn is_bit_set(bits: &[AtomicU64], n: u32) -> bool {
+    (bits[(n / 64) as usize].load(Ordering::SeqCst) & (1 << (n % 64))) != 0
+}
+
+fn set_bit(bits: &[AtomicU64], n: u32) {
+    bits[(n / 64) as usize].fetch_or(1 << (n % 64), Ordering::SeqCst);
+}
+
+// Append a finished row to the progress file
+fn append_row(writer: &Mutex<File>, y: u32, row: &[u32]) -> Result<()> {
+    let mut record = Vec::with_capacity(4 * (row.len() + 1));
+    try!(write_u32(&mut record, y));
+    for value in row {
+        try!(write_u32(&mut record, *value));
+    }
+
+    let mut writer = writer.lock().unwrap();
+    try!(writer.write_all(&record));
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use render_pixel;
+    use std::fs::{self, OpenOptions};
+    use std::path::Path;
+
+    fn test_config() -> MandelConfig {
+        let default = MandelConfig::default();
+
+        MandelConfig{
+            img_size: 16,
+            max_iter: 64,
+            num_threads: 3,
+            .. default
+        }
+    }
+
+    fn serial_image(config: &MandelConfig) -> Vec<u32> {
+        let mut image = Vec::new();
+        for y in 0..config.img_size {
+            for x in 0..config.img_size {
+                image.push(render_pixel(config, x, y));
+            }
+        }
+        image
+    }
+
+    fn output_file(name: &str) -> String {
+        let output = temp_dir().join(name).to_str().unwrap().to_string();
+        let _ = fs::remove_file(format!("{}.progress", output));
+        output
+    }
+
+    #[test]
+    fn continue_interrupted_render() {
+        let config = test_config();
+        let output = output_file("mandel_incremental_test.ppm");
+
+        {
+            let mut renderer = IncrementalRenderer::open(&output, &config).unwrap();
+            renderer.render_rows(&config, 5).unwrap();
+            assert_eq!(renderer.num_of_rows_done(), 5);
+        }
+
+        // Simulate a crash while the sixth row was written
+        let progress_file = format!("{}.progress", output);
+        let file = OpenOptions::new().append(true).open(&progress_file).unwrap();
+        file.set_len(file.metadata().unwrap().len() + 10).unwrap();
+
+        let mut renderer = IncrementalRenderer::open(&output, &config).unwrap();
+        assert_eq!(renderer.num_of_rows_done(), 5);
+        assert!(!renderer.is_complete());
+
+        renderer.render(&config).unwrap();
+        assert!(renderer.is_complete());
+
+        let image = renderer.finish().unwrap();
+        assert_eq!(image, serial_image(&config));
+        assert!(!Path::new(&progress_file).exists());
+    }
+
+    #[test]
+    fn reject_other_configuration() {
+        let config = test_config();
+        let output = output_file("mandel_incremental_other.ppm");
+
+        let renderer = IncrementalRenderer::open(&output, &config).unwrap();
+        assert!(renderer.finish().is_err());
+
+        let other = MandelConfig{max_iter: 128, .. test_config()};
+        assert!(IncrementalRenderer::open(&output, &other).is_err());
+
+        fs::remove_file(format!("{}.progress", output)).unwrap();
+    }
+}
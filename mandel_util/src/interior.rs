@@ -0,0 +1,246 @@
+// Coloring of the inside of the mandelbrot set (--interior_coloring).
+//
+// Points inside the set are attracted by a cycle. After max_iter iterations z is (nearly)
+// on that cycle, so the period can be found by iterating further until z comes back.
+// The derivatives along the cycle give the interior distance estimate, see
+// https://en.wikipedia.org/wiki/Plotting_algorithms_for_the_Mandelbrot_set#Interior_distance_estimation
+
+// External modules
+use num_complex::Complex64;
+
+// Rust modules
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::fmt;
+
+// Maximum period that is detected
+const MAX_PERIOD: u32 = 1024;
+
+// Period of the pixels without a stored result in InteriorResults, larger than MAX_PERIOD
+const NOT_STORED: u32 = u32::MAX;
+
+// z is back on the cycle if it is closer than this
+const PERIOD_EPSILON: f64 = 1e-10;
+
+// How to color the points inside the set
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum InteriorColoring {
+    Black,
+    // Color by the period of the attracting cycle
+    Period,
+    // Color by the estimated distance to the boundary
+    Distance
+}
+
+impl FromStr for InteriorColoring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<InteriorColoring, String> {
+        match s {
+            "black" => Ok(InteriorColoring::Black),
+            "period" => Ok(InteriorColoring::Period),
+            "distance" => Ok(InteriorColoring::Distance),
+            other => Err(format!("Unknown interior coloring: '{}', must be black, period or distance", other))
+        }
+    }
+}
+
+// Result of the iteration of one point
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PixelResult {
+    // Escaped after iter iterations
+    Escaped{iter: u32},
+    // Did not escape within max_iter iterations.
+    // period is 0 and distance is 0.0 if no cycle was found (for example near the boundary).
+    Interior{period: u32, distance: f64}
+}
+
+// The period and the distance of the interior pixels of one image, row by row in two parallel buffers.
+// render_pixel stores them while the methods render the image, so that the writer only maps them to colors.
+// The methods share only the configuration with their workers, each pixel is stored by one of them.
+// Pixels that weren't rendered (for example taken from the tile cache) have no result.
+pub struct InteriorResults {
+    periods: Vec<AtomicU32>,
+    // f64::to_bits of the distance
+    distances: Vec<AtomicU64>
+}
+
+impl InteriorResults {
+    pub fn new(num_of_pixels: usize) -> InteriorResults {
+        InteriorResults {
+            periods: (0..num_of_pixels).map(|_| AtomicU32::new(NOT_STORED)).collect(),
+            distances: (0..num_of_pixels).map(|_| AtomicU64::new(0)).collect()
+        }
+    }
+
+    // Escaped pixels are not stored, their color only depends on the number of iterations
+    pub fn store(&self, index: usize, result: PixelResult) {
+        if let PixelResult::Interior{period, distance} = result {
+            self.periods[index].store(period, Ordering::Relaxed);
+            self.distances[index].store(distance.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    // The stored result of an interior pixel, None if none was stored
+    pub fn get(&self, index: usize) -> Option<PixelResult> {
+        match self.periods[index].load(Ordering::Relaxed) {
+            NOT_STORED => None,
+            period => Some(PixelResult::Interior {
+                period: period,
+                distance: f64::from_bits(self.distances[index].load(Ordering::Relaxed))
+            })
+        }
+    }
+}
+
+// Not the values, they would fill the output of a MandelConfig
+impl fmt::Debug for InteriorResults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "InteriorResults({} pixels)", self.periods.len())
+    }
+}
+
+// Like mandel_iter_z0, but for points that don't escape the period and the interior distance are computed as well
+pub fn mandel_iter_full(max_iter: u32, z0: Complex64, c: Complex64) -> PixelResult {
+    let mut z: Complex64 = (z0 * z0) + c;
+
+    let mut iter = 0;
+
+    while (z.norm_sqr() <= 4.0) && (iter < max_iter) {
+        z = c + (z * z);
+        iter = iter + 1;
+    }
+
+    if iter < max_iter {
+        return PixelResult::Escaped{iter: iter};
+    }
+
+    let period = find_period(z, c);
+
+    if period == 0 {
+        return PixelResult::Interior{period: 0, distance: 0.0};
+    }
+
+    PixelResult::Interior{period: period, distance: interior_distance(z, c, period)}
+}
+
+// Smallest p with f^p(z) == z, 0 if there is none up to MAX_PERIOD
+fn find_period(z0: Complex64, c: Complex64) -> u32 {
+    let mut z = z0;
+
+    for period in 1..(MAX_PERIOD + 1) {
+        z = c + (z * z);
+
+        if (z - z0).norm_sqr() < PERIOD_EPSILON * PERIOD_EPSILON {
+            return period;
+        }
+    }
+
+    0
+}
+
+// Interior distance estimate for the point z0 on an attracting cycle with the given period
+fn interior_distance(z0: Complex64, c: Complex64, period: u32) -> f64 {
+    let one = Complex64{re: 1.0, im: 0.0};
+    let two = Complex64{re: 2.0, im: 0.0};
+
+    let mut z = z0;
+    // Derivatives of f^p with respect to z and c
+    let mut dz = one;
+    let mut dc = Complex64{re: 0.0, im: 0.0};
+    let mut dzdz = Complex64{re: 0.0, im: 0.0};
+    let mut dcdz = Complex64{re: 0.0, im: 0.0};
+
+    for _ in 0..period {
+        dcdz = two * ((z * dcdz) + (dz * dc));
+        dzdz = two * ((z * dzdz) + (dz * dz));
+        dc = (two * z * dc) + one;
+        dz = two * z * dz;
+        z = (z * z) + c;
+    }
+
+    let denominator = (dcdz + ((dzdz * dc) / (one - dz))).norm();
+
+    if denominator == 0.0 {
+        0.0
+    } else {
+        ((1.0 - dz.norm_sqr()) / denominator).max(0.0)
+    }
+}
+
+// Color for a point inside the set. pixel_size is used to scale the distance.
+pub fn interior_color(coloring: InteriorColoring, result: PixelResult, pixel_size: f64) -> (u8, u8, u8) {
+    // A few dark colors, so that the inside is still clearly different from the outside
+    static PERIOD_COLORS: [(u8, u8, u8); 8] = [
+        (0, 0, 96), (0, 64, 128), (0, 96, 64), (64, 0, 128),
+        (96, 96, 0), (128, 0, 64), (0, 128, 128), (96, 48, 0)];
+
+    match (coloring, result) {
+        (InteriorColoring::Period, PixelResult::Interior{period, ..}) if period > 0 =>
+            PERIOD_COLORS[((period - 1) as usize) % PERIOD_COLORS.len()],
+        (InteriorColoring::Distance, PixelResult::Interior{distance, ..}) if distance > 0.0 => {
+            // Brighter the further away from the boundary, measured in pixels on a log scale
+            let pixels = distance / pixel_size;
+            let level = ((pixels.max(1.0).ln() / 8.0).min(1.0) * 255.0) as u8;
+            (0, level / 2, level)
+        },
+        _ => (0, 0, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZERO: Complex64 = Complex64{re: 0.0, im: 0.0};
+
+    fn period_of(re: f64, im: f64) -> u32 {
+        match mandel_iter_full(1000, ZERO, Complex64{re: re, im: im}) {
+            PixelResult::Interior{period, ..} => period,
+            PixelResult::Escaped{..} => panic!("point escaped")
+        }
+    }
+
+    #[test]
+    fn main_cardioid_has_period_one() {
+        assert_eq!(period_of(0.0, 0.0), 1);
+        assert_eq!(period_of(-0.1, 0.1), 1);
+        assert_eq!(period_of(0.2, 0.0), 1);
+    }
+
+    #[test]
+    fn main_bulb_has_period_two() {
+        assert_eq!(period_of(-1.0, 0.0), 2);
+        assert_eq!(period_of(-1.1, 0.1), 2);
+    }
+
+    #[test]
+    fn escaped_point() {
+        assert_eq!(mandel_iter_full(100, ZERO, Complex64{re: 3.0, im: 0.0}), PixelResult::Escaped{iter: 0});
+    }
+
+    #[test]
+    fn stored_results() {
+        let results = InteriorResults::new(3);
+        results.store(0, PixelResult::Interior{period: 2, distance: 0.25});
+        results.store(1, PixelResult::Escaped{iter: 5});
+        results.store(2, PixelResult::Interior{period: 0, distance: 0.0});
+
+        assert_eq!(results.get(0), Some(PixelResult::Interior{period: 2, distance: 0.25}));
+        assert_eq!(results.get(1), None);
+        assert_eq!(results.get(2), Some(PixelResult::Interior{period: 0, distance: 0.0}));
+    }
+
+    #[test]
+    fn distance_shrinks_towards_boundary() {
+        let distance_of = |re: f64| match mandel_iter_full(10000, ZERO, Complex64{re: re, im: 0.0}) {
+            PixelResult::Interior{distance, ..} => distance,
+            PixelResult::Escaped{..} => panic!("point escaped")
+        };
+
+        // The main cardioid ends at 0.25
+        assert!(distance_of(0.0) > distance_of(0.2));
+        assert!(distance_of(0.2) > 0.0);
+        assert!(distance_of(0.0) < 1.0);
+    }
+}
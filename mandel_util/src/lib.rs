@@ -1,217 +1,4038 @@
 #![allow(non_upper_case_globals)]
 
 // External crates
+// The command line, the file output and the timing are not available on wasm32,
+// all items that need them are marked with #[cfg(not(target_arch = "wasm32"))]
+#[cfg(not(target_arch = "wasm32"))]
 #[macro_use]
 extern crate clap;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate csv;
+#[cfg(not(target_arch = "wasm32"))]
 extern crate num_cpus;
-extern crate num;
+extern crate num_complex;
+#[cfg(not(target_arch = "wasm32"))]
 extern crate time;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate toml;
+#[cfg(not(target_arch = "wasm32"))]
+#[macro_use]
+extern crate serde_json;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate exr;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate webp;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate flate2;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate rusqlite;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate rayon;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate rustfft;
+#[cfg(test)]
+#[macro_use]
+extern crate quickcheck;
 
 // External modules
-use clap::App;
-use num::complex::Complex64;
-use time::{precise_time_ns};
+#[cfg(not(target_arch = "wasm32"))]
+use clap::{App, SubCommand};
+use num_complex::Complex64;
+#[cfg(not(target_arch = "wasm32"))]
+use time::{now, precise_time_ns};
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use flate2::write::GzEncoder;
+#[cfg(not(target_arch = "wasm32"))]
+use flate2::Compression;
 
 // Rust modules
+#[cfg(not(target_arch = "wasm32"))]
 use std::fs::File;
-use std::io::prelude::Write;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::prelude::{Read, Write};
+#[cfg(not(target_arch = "wasm32"))]
 use std::io::Result;
+#[cfg(not(target_arch = "wasm32"))]
 use std::io::BufWriter;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io;
+use std::fmt;
+use std::str::FromStr;
+use std::mem;
+#[cfg(not(target_arch = "wasm32"))]
 use std::fs::OpenOptions;
+#[cfg(not(target_arch = "wasm32"))]
 use std::path::Path;
+#[cfg(not(target_arch = "wasm32"))]
 use std::fs;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc::{self, RecvTimeoutError};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::TryLockError;
+
+// Internal modules
+#[cfg(not(target_arch = "wasm32"))]
+pub mod raw;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ppm;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod diff;
+pub mod interior;
+pub mod palette;
+pub mod coloring;
+pub mod pipeline;
+pub mod contour;
+pub mod mesh;
+pub mod random_view;
+pub mod probe;
+pub mod region;
+pub mod refine;
+pub mod stats;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod spectrum;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod batch;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bookmark;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod incremental;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod density;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tuning;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod exr_image;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod webp_image;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bench_db;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod estimate;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod matrix;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tile_cache;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod distributed;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod schedule;
+pub mod summary;
+pub mod sweep;
+pub mod confidence;
+pub mod denormal;
+pub mod error;
+
+#[cfg(not(target_arch = "wasm32"))]
+use raw::write_raw;
+#[cfg(not(target_arch = "wasm32"))]
+use exr_image::write_image_exr;
+#[cfg(not(target_arch = "wasm32"))]
+use webp_image::write_image_webp;
+use summary::ColorChoice;
+#[cfg(not(target_arch = "wasm32"))]
+use summary::{paint, use_color, Style};
+#[cfg(not(target_arch = "wasm32"))]
+use bench_db::write_benchmark_result_sqlite;
+#[cfg(not(target_arch = "wasm32"))]
+use contour::{estimate_fractal_dimension, extract_contour, extract_contour_levels, write_contour_svg};
+#[cfg(not(target_arch = "wasm32"))]
+use mesh::{height_field, mesh_step, write_obj};
+use interior::InteriorColoring;
+use region::Tile;
+use palette::ColorScale;
+use coloring::ColoringMethod;
+#[cfg(not(target_arch = "wasm32"))]
+use error::MandelError;
+#[cfg(not(target_arch = "wasm32"))]
+use coloring::{cost_color, image_levels, ImageColoring};
+#[cfg(not(target_arch = "wasm32"))]
+use palette::Palette;
+use refine::RefineJob;
+use interior::{mandel_iter_full, InteriorResults, PixelResult};
+#[cfg(not(target_arch = "wasm32"))]
+use interior::interior_color;
+
+// What each pixel computes
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Workload {
+    // The mandelbrot set
+    Fractal,
+    // A pseudo random number of dummy iterations per pixel, see synthetic_iter
+    Synthetic,
+    // The phoenix fractal with the parameters phoenix_p and phoenix_q, see phoenix_iter
+    Phoenix
+}
+
+// Distribution of the number of iterations for the synthetic workload
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SyntheticDistribution {
+    // Every number of iterations between 0 and max_iter is equally likely
+    Uniform,
+    // Few expensive pixels and many cheap ones: P(n) ~ 1 / n
+    Zipf
+}
+
+// File format of the images written by the benchmark
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum OutputFormat {
+    // 8 bit colors, see write_image
+    Ppm,
+    // The same colors as binary PPM (P6), much smaller and faster to write
+    PpmBinary,
+    // 32 bit float iteration counts, see exr_image
+    Exr,
+    // The same colors as lossy WebP (--webp_quality), see webp_image
+    Webp,
+    // Contours at the --contours levels as vector graphics, see contour
+    Svg,
+    // The iteration counts as height field (Wavefront OBJ), see mesh
+    Obj
+}
+
+// Largest width and height of a WebP image
+pub const WEBP_MAX_SIZE: u32 = 16383;
+
+impl OutputFormat {
+    // File name extension
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            OutputFormat::Ppm | OutputFormat::PpmBinary => "ppm",
+            OutputFormat::Exr => "exr",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Svg => "svg",
+            OutputFormat::Obj => "obj"
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<OutputFormat, String> {
+        match s {
+            "ppm" => Ok(OutputFormat::Ppm),
+            "ppm_binary" => Ok(OutputFormat::PpmBinary),
+            "exr" => Ok(OutputFormat::Exr),
+            "webp" => Ok(OutputFormat::Webp),
+            "svg" => Ok(OutputFormat::Svg),
+            "obj" => Ok(OutputFormat::Obj),
+            other => Err(format!("Unknown output format: '{}', must be ppm, ppm_binary, exr, webp, svg or obj", other))
+        }
+    }
+}
+
+// Threading backend of the parallel methods (--thread_model), see mandel_method::dispatch
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ThreadModel {
+    // Thread pools with OS threads: scoped_threadpool, scoped-pool, jobsteal, crossbeam
+    Os,
+    Rayon,
+    // The blocking thread pool of the tokio runtime (feature tokio of mandel_method)
+    Tokio
+}
+
+impl fmt::Display for ThreadModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            ThreadModel::Os => "os",
+            ThreadModel::Rayon => "rayon",
+            ThreadModel::Tokio => "tokio"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ThreadModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<ThreadModel, String> {
+        match s {
+            "os" => Ok(ThreadModel::Os),
+            "rayon" => Ok(ThreadModel::Rayon),
+            "tokio" => Ok(ThreadModel::Tokio),
+            other => Err(format!("Unknown thread model: '{}', must be os, rayon or tokio", other))
+        }
+    }
+}
+
+// Order of the pixels in the image buffer of the methods (--layout), see MandelConfig::buffer_index.
+// The methods of mandel_method::registry write through the layout, do_run converts the buffer to rows (to_rows)
+// before anything is written, so all outputs are row by row.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BufferLayout {
+    // Row by row, the same as the image
+    Rows,
+    // Tiles of TILE_SIZE x TILE_SIZE pixels row by row, each tile row by row.
+    // A thread that computes a job of whole tiles doesn't share a cache line with its neighbours.
+    Tiles64
+}
+
+// Width and height of the tiles of BufferLayout::Tiles64
+pub const TILE_SIZE: u32 = 64;
+
+impl fmt::Display for BufferLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            BufferLayout::Rows => "rows",
+            BufferLayout::Tiles64 => "tiles64"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for BufferLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<BufferLayout, String> {
+        match s {
+            "rows" => Ok(BufferLayout::Rows),
+            "tiles64" => Ok(BufferLayout::Tiles64),
+            other => Err(format!("Unknown layout: '{}', must be rows or tiles64", other))
+        }
+    }
+}
+
+// The order in which the methods access the pixels (--normalize_access). With rows every method computes
+// whole rows and hands them out in their natural order (top to bottom), so a comparison of the methods
+// measures the scheduling and not the memory access pattern, see mandel_method::rayon_par_iter.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum AccessOrder {
+    // The order of each method: pixel by pixel for rayon_par_iter, the order of the pool for the others
+    Native,
+    Rows
+}
+
+impl fmt::Display for AccessOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            AccessOrder::Native => "native",
+            AccessOrder::Rows => "rows"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for AccessOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<AccessOrder, String> {
+        match s {
+            "native" => Ok(AccessOrder::Native),
+            "rows" => Ok(AccessOrder::Rows),
+            other => Err(format!("Unknown access order: '{}', must be native or rows", other))
+        }
+    }
+}
 
 // Configuration file, reflects command line options
-#[derive(Copy, Clone)]
+//
+// The pixel grid is half-open: pixel x samples re1 + x * x_step() with
+// x_step() = (re2 - re1) / img_size, so re2 itself is never sampled
+// (the same is true for img2). Each pixel represents the lower left
+// corner of its cell, which makes adjacent regions tile without overlap.
+// With inclusive_edges x_step() = (re2 - re1) / (img_size - 1), the last pixel samples re2.
+// The step sizes are always derived from the region, see with_pixel_size to give the step size instead.
+#[derive(Clone, Debug)]
 pub struct MandelConfig {
     pub re1: f64,
     pub re2: f64,
     pub img1: f64,
     pub img2: f64,
-    pub x_step: f64,
-    pub y_step: f64,
     pub max_iter: u32,
     pub img_size: u32,
     pub write_metadata: bool,
+    // No image file at all, whatever the output format is
     pub no_ppm: bool,
+    pub output_format: OutputFormat,
     pub num_threads: u32,
-    pub num_of_runs: u32
+    // Run only the methods of this threading backend, None: the methods of all backends
+    pub thread_model: Option<ThreadModel>,
+    // Order of the pixels in the image buffer of the methods
+    pub layout: BufferLayout,
+    // Order in which the methods access the pixels
+    pub normalize_access: AccessOrder,
+    pub num_of_runs: u32,
+    // Repeat the runs until the 95% confidence interval of the mean is within ci of the mean (see confidence),
+    // at least num_of_runs and at most max_runs runs
+    pub adaptive_runs: bool,
+    pub max_runs: u32,
+    pub ci: f64,
+    // Names of the methods to run, empty means run all methods
+    pub select_methods: Vec<String>,
+    // Row 0 is img1 instead of img2, so the image is upside down (old behaviour)
+    pub flip_y: bool,
+    // The last pixel of a row samples re2 and the first row img2, see step_divisor
+    pub inclusive_edges: bool,
+    // The region is the whole image of a grid of tiles, only this tile is rendered (see region::Tile)
+    pub tile: Option<Tile>,
+    // Compute only the upper half of a region that is symmetric to the real axis and mirror it
+    pub use_symmetry: bool,
+    // Read back each image after writing it and check that it is complete
+    pub verify_output: bool,
+    // Also write the iteration counts as raw dump (.mraw)
+    pub raw_output: bool,
+    pub workload: Workload,
+    pub synthetic_distribution: SyntheticDistribution,
+    pub synthetic_seed: u64,
+    // Parameters of the phoenix workload: z_{n+1} = z_n^2 + p + q * z_{n-1}
+    pub phoenix_p: f64,
+    pub phoenix_q: f64,
+    // Pin each worker thread to its own core
+    pub pin_threads: bool,
+    // Pin the worker threads to these cores (thread i to cpu_affinity[i % len]), empty: no affinity
+    pub cpu_affinity: Vec<usize>,
+    // Run serial first and compare the output of every other method with it
+    pub compare_parallel_outputs: bool,
+    // The dynamic methods write the order of their jobs to this file (with the method name), see schedule
+    pub trace_schedule: Option<String>,
+    // Compute the jobs of this trace in their order on one thread instead of running the benchmark
+    pub replay_schedule: Option<String>,
+    // Each method renders a new image buffer once before it is measured, so that its workers place the pages
+    // on their NUMA nodes (first touch, see mandel_method::first_touch)
+    pub numa_init: bool,
+    pub interior_coloring: InteriorColoring,
+    // Colors outside of the set, see palette: number of palette repetitions between 0 and max_iter
+    // (None: one repetition every 16 iterations), rotation of the palette and mapping of the iteration counts
+    pub color_cycle: Option<u32>,
+    pub color_offset: u32,
+    pub color_scale: ColorScale,
+    // Spread the palette over the 1st .. 99th percentile of the exterior iteration counts of each image,
+    // or over these iteration counts (low, high), see coloring::image_levels
+    pub auto_levels: bool,
+    pub levels: Option<(u32, u32)>,
+    // How the points outside of the set are colored, see coloring
+    pub coloring_method: ColoringMethod,
+    // Number of stripes per turn around the origin of --coloring=stripe, see mandel_iter_stripe
+    pub stripe_density: f64,
+    // Render the jobs of this file instead of running the benchmark
+    pub batch_file: Option<String>,
+    // File with the compact configuration (--config_file), see MandelConfig::apply_config
+    pub config_file: Option<String>,
+    // Render again whenever config_file changes
+    pub watch: bool,
+    // Changes of config_file within this many milliseconds are one change (editors write a file in several steps)
+    pub watch_debounce_ms: u64,
+    // Compute the rows that coordinators ask for instead of running the benchmark, listening on this address
+    pub worker: Option<String>,
+    // Render the image on these workers (HOST:PORT) instead of running the benchmark
+    pub coordinator: Vec<String>,
+    // Batch jobs keep a progress file, so that an interrupted batch continues where it stopped
+    pub incremental: bool,
+    // Identifies all outputs of one invocation (image file names, benchmark results, PPM metadata).
+    // None until the application generates it, unless it is given with --run_id.
+    pub run_id: Option<String>,
+    // Starting value of the iteration (generalized mandelbrot set), 0 + 0i is the normal mandelbrot set
+    pub z0_re: f64,
+    pub z0_im: f64,
+    // max_iter is computed from the zoom level (auto_max_iter) instead of --max_iter
+    pub max_iter_auto: bool,
+    // Number of image rows per job of the parallel methods
+    pub chunk_rows: u32,
+    // Minimum and maximum number of pixels per rayon task of rayon_par_iter (with_min_len, with_max_len)
+    pub rayon_min_len: usize,
+    pub rayon_max_len: usize,
+    // Find the fastest num_threads and chunk_rows for each method before the benchmark
+    pub auto_tune: bool,
+    // Ignore the cached results of --auto_tune and calibrate again
+    pub retune: bool,
+    // Print statistics of the escape times after each method and write them as JSON
+    pub stats: bool,
+    // Also write the cost (number of iterations) of each pixel as gray image to this file after each method
+    pub cost_map: Option<String>,
+    // Write the radially averaged power spectrum of the iteration counts as CSV to this file after each method
+    pub power_spectrum: Option<String>,
+    // Render progressively for this many seconds instead of running the benchmark
+    pub time_budget: Option<f64>,
+    // Re-render a rectangle of a raw dump instead of running the benchmark
+    pub refine: Option<RefineJob>,
+    // Compute the missing rows of this raw dump (created if it doesn't exist) instead of running the benchmark, see raw::PartialRaw
+    pub resume: Option<String>,
+    // Compute the image again from this raw dump with a higher max_iter instead of running the benchmark,
+    // only the pixels that reached the max_iter of the dump are iterated again
+    pub refine_from: Option<String>,
+    // Values below this in the image buffer are exact from a render with this max_iter and are kept
+    // by the methods (see update_pixel). Set by --refine_from, not a command line option.
+    pub previous_max_iter: Option<u32>,
+    // The methods store the period and distance of the interior pixels here (see render_pixel and pixel_color).
+    // Set by with_interior_results for one image, not a command line option.
+    pub interior_results: Option<Arc<InteriorResults>>,
+    // Also append the benchmark results to this SQLite database
+    pub bench_db: Option<String>,
+    // Skip the methods that would take longer than this many seconds (estimated from a small probe)
+    pub skip_slow: Option<f64>,
+    // Cancel a method that hasn't finished all its runs after this many milliseconds
+    pub method_timeout_ms: Option<u64>,
+    // Replace the region by a random one that is derived from this seed, see random_view
+    pub random_view: Option<u64>,
+    // Write the contour at this number of iterations as SVG for each method
+    pub extract_contour: Option<u32>,
+    // Print the box-counting dimension of the boundary of the set (the contour at max_iter) for each method
+    pub fractal_dimension: bool,
+    // Iteration levels of the contours of --output_format=svg, empty: max_iter (the boundary of the set)
+    pub contours: Vec<u32>,
+    // Quality of --output_format=webp: 0 (smallest file) .. 100 (best colors)
+    pub webp_quality: f32,
+    // Height field of --output_format=obj: every mesh_step'th pixel (None: at most mesh::MESH_MAX_SIDE vertices per side),
+    // the height of the points of the set and log(1 + iter) instead of iter as height, see mesh
+    pub mesh_step: Option<u32>,
+    pub mesh_height: f64,
+    pub mesh_log: bool,
+    // Write the PPM images gzip compressed (<file name>.gz) with this level: 0 (fastest) .. 9 (smallest file)
+    pub compress: bool,
+    pub compress_level: u32,
+    // Colors of the console output (summary table, timeouts)
+    pub color: ColorChoice,
+    // Report the denormal floats of the region and flush them to zero in the worker threads, see denormal
+    pub detect_denormals: bool,
+    // Add the largest change of the resident memory during a run to the benchmark results, see get_rss_bytes
+    pub print_memory: bool,
+    // Directory of the image cache of the batch jobs, see tile_cache
+    pub tile_cache: Option<String>,
+    // Delete the tile_cache directory instead of rendering
+    pub clear_cache: bool,
+    // Write the palette as a strip into this PPM image and exit, see write_palette_preview
+    pub palette_preview: Option<String>,
+    // Run the selected methods for all combinations of size_list and iter_list, see matrix
+    // (empty lists: img_size and max_iter)
+    pub benchmark_matrix: bool,
+    // Run the selected methods with every number of threads START, START + STEP, ... END, see sweep
+    pub num_threads_sweep: Option<(u32, u32, u32)>,
+    pub size_list: Vec<u32>,
+    pub iter_list: Vec<u32>,
+    // Don't ask for confirmation before a long benchmark
+    pub yes: bool
 }
 
-include!(concat!(env!("OUT_DIR"), "/compiler_version.rs"));
+// Timing results of one method
+#[derive(Clone, Debug)]
+pub struct BenchmarkResult {
+    pub method: String,
+    pub num_threads: u32,
+    pub mean_ms: f64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    pub stddev_ms: f64,
+    // The method didn't finish within --method_timeout_ms, the times are not valid
+    pub timed_out: bool
+}
 
-// Parse command line options via clap and returns the responding configuration
-pub fn parse_arguments() -> MandelConfig {
-    let matches = App::new("mandel_rust")
-        .version("0.3")
-        .author("Willi Kappler <grandor@gmx.de>")
-        .about("Simple mandelbrot written in pure rust")
-        .args_from_usage(
-            "--re1=[REAL1] 'left real part (default: -2.0)'
-             --re2=[REAL2] 'right real part (default: 1.0)'
-             --img1=[IMAGINARY1] 'lower part (default: -1.50)'
-             --img2=[IMAGINARY2] 'upper part (default: 1.50)'
-             --write_metadata 'write metadata like run time into the ppm file (default: off)'
-             --no_ppm 'disable creation of the ppm file, just run the calculation (default: off)'
-             --bench 'use all available CPUs (default: off), will change in the future'
-             --max_iter=[MAX_ITER] 'maximum number of iterations (default: 4096)'
-             --img_size=[IMAGE_SIZE] 'size of image in pixel (square, default: 2048, must be a power of two)'
-             --num_of_runs=[NUM_OF_RUNS] 'number of repetitive runs (default: 2)'
-             --num_threads=[NUMBER_OF_THREADS] 'number of threads to use (default: 2)'")
-        .get_matches();
+impl Default for MandelConfig {
+    fn default() -> MandelConfig {
+        let re1 = -2.0;
+        let re2 = 1.0;
+        let img1 = -1.5;
+        let img2 = 1.5;
+        let img_size = 2048;
 
-    let re1 = value_t!(matches.value_of("REAL1"), f64).unwrap_or(-2.0);
-    let re2 = value_t!(matches.value_of("REAL2"), f64).unwrap_or(1.0);
-    let img1 = value_t!(matches.value_of("IMAGINARY1"), f64).unwrap_or(-1.5);
-    let img2 = value_t!(matches.value_of("IMAGINARY2"), f64).unwrap_or(1.5);
-    let metadata = matches.is_present("write_metadata");
-    let bench = matches.is_present("bench");
-    let no_ppm = matches.is_present("no_ppm");
-    let max_iter = value_t!(matches.value_of("MAX_ITER"), u32).unwrap_or(4096);
-    let img_size = value_t!(matches.value_of("IMAGE_SIZE"), u32).unwrap_or(2048);
-    let num_of_runs = value_t!(matches.value_of("NUM_OF_RUNS"), u32).unwrap_or(2);
-    let num_threads = if bench { num_cpus::get() as u32 } else {
-        value_t!(matches.value_of("NUMBER_OF_THREADS"), u32).unwrap_or(2) };
+        MandelConfig{
+            re1: re1,
+            re2: re2,
+            img1: img1,
+            img2: img2,
+            max_iter: 4096,
+            img_size: img_size,
+            write_metadata: false,
+            no_ppm: false,
+            output_format: OutputFormat::Ppm,
+            num_threads: 2,
+            thread_model: None,
+            layout: BufferLayout::Rows,
+            normalize_access: AccessOrder::Native,
+            num_of_runs: 2,
+            adaptive_runs: false,
+            max_runs: 100,
+            ci: 0.02,
+            select_methods: Vec::new(),
+            flip_y: false,
+            inclusive_edges: false,
+            tile: None,
+            use_symmetry: false,
+            verify_output: false,
+            raw_output: false,
+            workload: Workload::Fractal,
+            synthetic_distribution: SyntheticDistribution::Uniform,
+            synthetic_seed: 0,
+            phoenix_p: -0.5,
+            phoenix_q: 0.0,
+            pin_threads: false,
+            cpu_affinity: Vec::new(),
+            compare_parallel_outputs: false,
+            trace_schedule: None,
+            replay_schedule: None,
+            numa_init: false,
+            interior_coloring: InteriorColoring::Black,
+            color_cycle: None,
+            color_offset: 0,
+            color_scale: ColorScale::Linear,
+            auto_levels: false,
+            levels: None,
+            coloring_method: ColoringMethod::IterationCount,
+            stripe_density: 5.0,
+            batch_file: None,
+            config_file: None,
+            watch: false,
+            watch_debounce_ms: 200,
+            worker: None,
+            coordinator: Vec::new(),
+            incremental: false,
+            run_id: None,
+            z0_re: 0.0,
+            z0_im: 0.0,
+            max_iter_auto: false,
+            chunk_rows: 1,
+            rayon_min_len: 1,
+            rayon_max_len: usize::MAX,
+            auto_tune: false,
+            retune: false,
+            stats: false,
+            cost_map: None,
+            power_spectrum: None,
+            time_budget: None,
+            refine: None,
+            resume: None,
+            refine_from: None,
+            previous_max_iter: None,
+            interior_results: None,
+            bench_db: None,
+            skip_slow: None,
+            method_timeout_ms: None,
+            random_view: None,
+            extract_contour: None,
+            fractal_dimension: false,
+            contours: Vec::new(),
+            webp_quality: 80.0,
+            mesh_step: None,
+            mesh_height: 0.25,
+            mesh_log: false,
+            compress: false,
+            compress_level: 6,
+            color: ColorChoice::Auto,
+            detect_denormals: false,
+            print_memory: false,
+            tile_cache: None,
+            clear_cache: false,
+            palette_preview: None,
+            benchmark_matrix: false,
+            num_threads_sweep: None,
+            size_list: Vec::new(),
+            iter_list: Vec::new(),
+            yes: false
+        }
+    }
+}
 
-    assert!(re1 < re2);
-    assert!(img1 < img2);
-    assert!(max_iter > 0);
-    assert!(img_size > 0);
-    assert!(num_threads > 0);
+impl MandelConfig {
+    // Number of steps of img_size pixels between re1 and re2 (img1 and img2):
+    // img_size for the half-open grid, img_size - 1 with inclusive_edges
+    pub fn step_divisor(&self) -> f64 {
+        if self.inclusive_edges { (self.img_size.max(2) - 1) as f64 } else { self.img_size as f64 }
+    }
 
-    println!("Configuration: re1: {:.2}, re2: {:.2}, img1: {:.2}, img2: {:.2}, max_iter: {}, img_size: {}, num_threads: {}",
-        re1, re2, img1, img2, max_iter, img_size, num_threads);
+    // Distance of two neighbouring pixels on the real axis
+    pub fn x_step(&self) -> f64 {
+        (self.re2 - self.re1) / self.step_divisor()
+    }
+
+    // Distance of two neighbouring rows on the imaginary axis
+    pub fn y_step(&self) -> f64 {
+        (self.img2 - self.img1) / self.step_divisor()
+    }
 
-    let x_step = (re2 - re1) / (img_size as f64);
-    let y_step = (img2 - img1) / (img_size as f64);
+    // Copy that stores the period and distance of the interior pixels while an image is rendered with it, if
+    // --interior_coloring needs them. Each image needs its own copy.
+    pub fn with_interior_results(&self) -> MandelConfig {
+        let interior_results = if self.interior_coloring != InteriorColoring::Black && self.workload == Workload::Fractal {
+            Some(Arc::new(InteriorResults::new(self.num_of_pixels())))
+        } else {
+            None
+        };
 
-    MandelConfig{
-        re1: re1,
-        re2: re2,
-        img1: img1,
-        img2: img2,
-        x_step: x_step,
-        y_step: y_step,
-        max_iter: max_iter,
-        img_size: img_size,
-        write_metadata: metadata,
-        no_ppm: no_ppm,
-        num_threads: num_threads,
-        num_of_runs: num_of_runs
+        MandelConfig{interior_results: interior_results, .. self.clone()}
     }
-}
 
-// The inner iteration loop of the mandelbrot calculation
-// See https://en.wikipedia.org/wiki/Mandelbrot_set
-pub fn mandel_iter(max_iter: u32, c: Complex64) -> u32 {
-    let mut z: Complex64 = c;
+    // Copy with the given step size (--pixel_size): re1 and img1 stay, re2 and img2 are moved so that
+    // the pixels are pixel_size apart. Tiling pipelines give the step size, so that all tiles have the same grid.
+    pub fn with_pixel_size(&self, pixel_size: f64) -> MandelConfig {
+        MandelConfig{
+            re2: self.re1 + (pixel_size * self.step_divisor()),
+            img2: self.img1 + (pixel_size * self.step_divisor()),
+            .. self.clone()
+        }
+    }
 
-    let mut iter = 0;
+    // Imaginary part of the given image row.
+    // Row 0 is the top of the image, so the imaginary axis points up like in the complex plane.
+    // If flip_y is set row 0 is at img1 instead.
+    pub fn row_to_im(&self, y: u32) -> f64 {
+        let row = if self.flip_y { y } else { self.img_size - 1 - y };
+        self.img1 + ((row as f64) * self.y_step())
+    }
 
-    while (z.norm_sqr() <= 4.0) && (iter < max_iter) {
-        z = c + (z * z);
-        iter = iter + 1;
+    // With use_symmetry: the row that is copied to row y, None if row y is computed.
+    // Row r counted from img1 has the imaginary part img1 + r * y_step(), which is -(img1 + (img_size - r) * y_step())
+    // because img1 == -img2. Of each pair of rows the upper one in the image is computed.
+    // Row 0 (img1, its mirror img2 is not sampled) and the real axis (r = img_size / 2) have no partner.
+    // With inclusive_edges img2 is sampled and the partner of row r is img_size - 1 - r.
+    pub fn mirror_source_row(&self, y: u32) -> Option<u32> {
+        if !self.use_symmetry {
+            return None;
+        }
+
+        let to_row = |y: u32| if self.flip_y { y } else { self.img_size - 1 - y };
+        let row = to_row(y);
+
+        let partner = if self.inclusive_edges {
+            self.img_size - 1 - row
+        } else if row == 0 {
+            return None;
+        } else {
+            self.img_size - row
+        };
+
+        if partner == row {
+            return None;
+        }
+
+        // to_row is its own inverse
+        let source = to_row(partner);
+        if source < y { Some(source) } else { None }
     }
 
-    iter
-}
+    // Number of pixels of the image. All pixel indices are computed in usize,
+    // img_size * img_size doesn't fit into u32 for img_size >= 65536.
+    pub fn num_of_pixels(&self) -> usize {
+        (self.img_size as usize) * (self.img_size as usize)
+    }
 
-// Write calculated mandelbrot set as PPM image.
-// Add run time information as comment.
-fn write_image(file_name: &str, mandel_config: &MandelConfig, time_in_ms: f64, image: &[u32]) -> Result<()> {
-    let mut buffer = BufWriter::new(try!(File::create(file_name)));
+    // Index of pixel (x, y) in the image buffer
+    pub fn pixel_index(&self, x: u32, y: u32) -> usize {
+        ((y as usize) * (self.img_size as usize)) + (x as usize)
+    }
 
-    try!(buffer.write(b"P3\n"));
-    try!(write!(buffer, "# mandelbrot, max_iter: {}\n", mandel_config.max_iter));
-    if mandel_config.write_metadata {
-        // TODO: add more meta data: date and time, method, ...
-        try!(write!(buffer, "# computation time: {} ms\n", time_in_ms));
+    // Pixel (x, y) at the given index of the image buffer
+    pub fn pixel_at(&self, index: usize) -> (u32, u32) {
+        let img_size = self.img_size as usize;
+        ((index % img_size) as u32, (index / img_size) as u32)
     }
-    try!(write!(buffer, "{0} {0}\n", mandel_config.img_size));
-    try!(buffer.write(b"255\n"));
 
-    let mut img_value: u32;
+    // Width and height of the tiles of the layout, the whole image for rows.
+    // Smaller images are a single tile. Larger ones are a power of two (see validate), so the tiles fit exactly.
+    pub fn tile_size(&self) -> u32 {
+        match self.layout {
+            BufferLayout::Rows => self.img_size,
+            BufferLayout::Tiles64 => TILE_SIZE.min(self.img_size)
+        }
+    }
 
-    for y in 0..mandel_config.img_size {
-        for x in 0..mandel_config.img_size {
+    // Number of values of the image buffer of the methods that are consecutive pixels of one row (a run):
+    // a row of the image for rows, a row of a tile for tiles64
+    pub fn run_len(&self) -> usize {
+        self.tile_size() as usize
+    }
 
-            img_value = image[((y * mandel_config.img_size) + x) as usize];
-            if img_value == mandel_config.max_iter {
-                try!(buffer.write(b"0 0 0 "));
-            } else {
-                try!(write!(buffer, "255 {} 0 ", (img_value % 16) * 16));
+    // Index of pixel (x, y) in the image buffer of the methods, pixel_index for rows
+    pub fn buffer_index(&self, x: u32, y: u32) -> usize {
+        match self.layout {
+            BufferLayout::Rows => self.pixel_index(x, y),
+            BufferLayout::Tiles64 => {
+                let tile_size = self.tile_size() as usize;
+                let (x, y) = (x as usize, y as usize);
+                let tile = ((y / tile_size) * ((self.img_size as usize) / tile_size)) + (x / tile_size);
+                (tile * tile_size * tile_size) + ((y % tile_size) * tile_size) + (x % tile_size)
             }
+        }
+    }
 
+    // Pixel (x, y) at the given index of the image buffer of the methods, pixel_at for rows
+    pub fn buffer_pixel(&self, index: usize) -> (u32, u32) {
+        match self.layout {
+            BufferLayout::Rows => self.pixel_at(index),
+            BufferLayout::Tiles64 => {
+                let tile_size = self.tile_size() as usize;
+                let tiles_per_row = (self.img_size as usize) / tile_size;
+                let (tile, offset) = (index / (tile_size * tile_size), index % (tile_size * tile_size));
+                ((((tile % tiles_per_row) * tile_size) + (offset % tile_size)) as u32,
+                 (((tile / tiles_per_row) * tile_size) + (offset / tile_size)) as u32)
+            }
         }
-        try!(buffer.write(b"\n"));
     }
 
-    Ok(())
-}
+    // Check that all values are in range.
+    // img_size must be a power of two: the divide-and-conquer methods (rayon_join, job_steal_join)
+    // split the image in halves and compute wrong rows otherwise.
+    pub fn validate(&self) -> std::result::Result<(), ConfigError> {
+        let invalid = |message: String| Err(ConfigError::InvalidValue(message));
 
-fn write_benchmark_result(method: &str, num_threads: u32,
-     time_in_ms: f64, min_time: f64, max_time: f64) -> Result<()> {
+        if !(self.re1 < self.re2) {
+            return invalid(format!("re1 ({}) must be less than re2 ({})", self.re1, self.re2));
+        }
 
-    // Check if output folder "plot" is available:
+        if !(self.img1 < self.img2) {
+            return invalid(format!("img1 ({}) must be less than img2 ({})", self.img1, self.img2));
+        }
 
-    if !Path::new("plot").exists() {
-        // If not, create it!
-        println!("Folder 'plot' does not exist, creating it...");
-        try!(fs::create_dir("plot"));
-    
-    }
+        if self.max_iter == 0 {
+            return invalid("max_iter must be greater than 0".to_string());
+        }
 
-    let mut buffer = BufWriter::new(try!(
-        OpenOptions::new()
-            .write(true)
-            .append(true)
-            .create(true)
-            .open(format!("plot{}{}.txt", std::path::MAIN_SEPARATOR, method))));
+        if self.num_threads == 0 {
+            return invalid("num_threads must be greater than 0".to_string());
+        }
 
-    try!(write!(buffer, "{} {} {} {}\n", num_threads, time_in_ms, min_time, max_time));
+        if self.chunk_rows == 0 {
+            return invalid("chunk_rows must be greater than 0".to_string());
+        }
 
-    Ok(())
-}
+        if self.rayon_min_len == 0 || self.rayon_min_len > self.rayon_max_len {
+            return invalid(format!("rayon_min_len ({}) must be between 1 and rayon_max_len ({})", self.rayon_min_len, self.rayon_max_len));
+        }
 
-// Prepares and runs one version of the mandelbrot set calculation.
-pub fn do_run(method: &str, mandel_func: &Fn(&MandelConfig, &mut [u32]) -> (),
-    mandel_config: &MandelConfig, image: &mut [u32], time_now: &str) {
+        if let Some(threshold) = self.extract_contour {
+            if threshold == 0 || threshold > self.max_iter {
+                return invalid(format!("extract_contour ({}) must be between 1 and max_iter ({})", threshold, self.max_iter));
+            }
+        }
 
-    let mut repetitive_times = Vec::new();
-    let mut min_time = std::f64::MAX;
-    let mut max_time = 0.0;
+        if let Some(&level) = self.contours.iter().find(|&&level| level == 0 || level > self.max_iter) {
+            return invalid(format!("contour level {} must be between 1 and max_iter ({})", level, self.max_iter));
+        }
 
-    for _ in 0..mandel_config.num_of_runs {
-        let start_time = precise_time_ns();
+        if self.clear_cache && self.tile_cache.is_none() {
+            return invalid("clear_cache needs tile_cache".to_string());
+        }
+
+        if !(self.webp_quality >= 0.0 && self.webp_quality <= 100.0) {
+            return invalid(format!("webp_quality ({}) must be between 0 and 100", self.webp_quality));
+        }
 
-        mandel_func(mandel_config, image);
+        if self.output_format == OutputFormat::Webp && self.img_size > WEBP_MAX_SIZE {
+            return invalid(format!("img_size ({}) must be at most {} for output_format webp", self.img_size, WEBP_MAX_SIZE));
+        }
 
-        let end_time = precise_time_ns();
-        let total_time_in_ms = ((end_time - start_time) as f64) / (1000.0 * 1000.0);
+        if self.mesh_step == Some(0) {
+            return invalid("mesh_step must be greater than 0".to_string());
+        }
 
-        if total_time_in_ms > max_time {
-            max_time = total_time_in_ms;
+        if self.mesh_step.is_some() && self.output_format != OutputFormat::Obj {
+            return invalid("mesh_step needs output_format obj".to_string());
         }
 
-        if total_time_in_ms < min_time {
-            min_time = total_time_in_ms;
+        if !self.mesh_height.is_finite() {
+            return invalid(format!("mesh_height ({}) must be a finite number", self.mesh_height));
         }
 
-        repetitive_times.push(total_time_in_ms);
+        if self.compress_level > 9 {
+            return invalid(format!("compress_level ({}) must be between 0 and 9", self.compress_level));
+        }
+
+        if self.compress && self.output_format != OutputFormat::Ppm && self.output_format != OutputFormat::PpmBinary {
+            return invalid("compress only works with output_format ppm or ppm_binary".to_string());
+        }
+
+        if !self.contours.is_empty() && self.output_format != OutputFormat::Svg {
+            return invalid("contours needs output_format svg".to_string());
+        }
+
+        if self.normalize_access == AccessOrder::Rows && self.layout != BufferLayout::Rows {
+            return invalid("normalize_access rows needs layout rows, the lines of tiles64 are not rows of the image".to_string());
+        }
+
+        if self.inclusive_edges && self.img_size < 2 {
+            return invalid("inclusive_edges needs an img_size of at least 2".to_string());
+        }
+
+        if let Some(tile) = self.tile {
+            if !(tile.row < tile.of_n && tile.col < tile.of_n) {
+                return invalid(format!("tile {} must be inside of the grid of {} x {} tiles", tile, tile.of_n, tile.of_n));
+            }
+
+            if self.img_size.checked_mul(tile.of_n).is_none() {
+                return invalid(format!("the {} x {} tiles of img_size {} are too large", tile.of_n, tile.of_n, self.img_size));
+            }
+        }
+
+        if self.color_cycle == Some(0) {
+            return invalid("color_cycle must be greater than 0".to_string());
+        }
+
+        if let Some((low, high)) = self.levels {
+            if self.auto_levels {
+                return invalid("only one of auto_levels and levels can be given".to_string());
+            }
+
+            if !(low <= high && high < self.max_iter) {
+                return invalid(format!("levels ({}..{}) must be ordered and less than max_iter ({})", low, high, self.max_iter));
+            }
+        }
+
+        if !(self.stripe_density > 0.0 && self.stripe_density.is_finite()) {
+            return invalid(format!("stripe_density ({}) must be greater than 0", self.stripe_density));
+        }
+
+        if let Some(time_budget) = self.time_budget {
+            if !(time_budget > 0.0) {
+                return invalid(format!("time_budget ({}) must be greater than 0", time_budget));
+            }
+
+            if self.workload != Workload::Fractal {
+                return invalid("time_budget only works with the fractal workload".to_string());
+            }
+        }
+
+        if let Some(skip_slow) = self.skip_slow {
+            if !(skip_slow > 0.0) {
+                return invalid(format!("skip_slow ({}) must be greater than 0", skip_slow));
+            }
+        }
+
+        if self.method_timeout_ms == Some(0) {
+            return invalid("method_timeout_ms must be greater than 0".to_string());
+        }
+
+        // The first touch run has no timeout and the timed runs use their own buffer
+        if self.numa_init && self.method_timeout_ms.is_some() {
+            return invalid("numa_init can't be used with method_timeout_ms".to_string());
+        }
+
+        if self.use_symmetry {
+            if self.img1 != -self.img2 {
+                return invalid(format!("use_symmetry needs a region that is symmetric to the real axis: img1 ({}) must be -img2 ({})",
+                    self.img1, self.img2));
+            }
+
+            if self.workload != Workload::Fractal || self.z0_im != 0.0 {
+                return invalid("use_symmetry only works with the fractal workload and a real z0".to_string());
+            }
+        }
+
+        if (!self.size_list.is_empty() || !self.iter_list.is_empty()) && !self.benchmark_matrix {
+            return invalid("size_list and iter_list need benchmark_matrix".to_string());
+        }
+
+        if let Some((start, end, step)) = self.num_threads_sweep {
+            if !(start > 0 && start <= end && step > 0) {
+                return invalid(format!("num_threads_sweep ({},{},{}) needs 0 < START <= END and STEP > 0", start, end, step));
+            }
+
+            if self.benchmark_matrix {
+                return invalid("only one of num_threads_sweep and benchmark_matrix can be given".to_string());
+            }
+        }
+
+        if self.adaptive_runs {
+            if !(self.ci > 0.0 && self.ci < 1.0) {
+                return invalid(format!("ci ({}) must be greater than 0 and less than 1", self.ci));
+            }
+
+            if self.max_runs < self.num_of_runs.max(2) {
+                return invalid(format!("max_runs ({}) must be at least num_of_runs ({}) and 2", self.max_runs, self.num_of_runs));
+            }
+        }
+
+        if let Some(&size) = self.size_list.iter().find(|&&size| !is_power_of_two(size)) {
+            return invalid(format!("img_size {} in size_list must be a power of two", size));
+        }
+
+        if self.iter_list.contains(&0) {
+            return invalid("max_iter in iter_list must be greater than 0".to_string());
+        }
+
+        if self.watch {
+            if self.config_file.is_none() {
+                return invalid("watch needs config_file".to_string());
+            }
+
+            if self.batch_file.is_some() || self.refine.is_some() || self.resume.is_some() || self.refine_from.is_some() ||
+                self.time_budget.is_some() || self.benchmark_matrix {
+                return invalid("watch renders all methods again, it can't be combined with batch, refine, resume, refine_from, time_budget or benchmark_matrix".to_string());
+            }
+        }
+
+        if self.worker.is_some() && !self.coordinator.is_empty() {
+            return invalid("only one of worker and coordinator can be given".to_string());
+        }
+
+        if !self.coordinator.is_empty() && self.workload != Workload::Fractal {
+            return invalid("coordinator only works with the fractal workload".to_string());
+        }
+
+        if self.trace_schedule.is_some() && self.replay_schedule.is_some() {
+            return invalid("only one of trace_schedule and replay_schedule can be given".to_string());
+        }
+
+        if [self.refine.is_some(), self.resume.is_some(), self.refine_from.is_some()].iter().filter(|&&given| given).count() > 1 {
+            return invalid("only one of refine, resume and refine_from can be given".to_string());
+        }
+
+        if self.refine.as_ref().map_or(false, |refine| refine.max_iter == 0) {
+            return invalid("refine_iter must be greater than 0".to_string());
+        }
+
+        if !is_power_of_two(self.img_size) {
+            return invalid(match nearest_powers_of_two(self.img_size) {
+                (Some(lower), Some(upper)) => format!("img_size ({}) must be a power of two, for example {} or {}", self.img_size, lower, upper),
+                (Some(lower), None) | (None, Some(lower)) => format!("img_size ({}) must be a power of two, for example {}", self.img_size, lower),
+                (None, None) => format!("img_size ({}) must be a power of two", self.img_size)
+            });
+        }
+
+        // Only possible where usize has 32 bits (wasm32)
+        let image_bytes = (self.img_size as usize).checked_mul(self.img_size as usize)
+            .and_then(|num_of_pixels| num_of_pixels.checked_mul(mem::size_of::<u32>()));
+        if image_bytes.is_none() {
+            return invalid(format!("img_size ({}) is too large, the image doesn't fit into memory", self.img_size));
+        }
+
+        Ok(())
     }
 
-    let mean_time = repetitive_times.iter().fold(0.0, |sum, t| sum + t) /
-        (mandel_config.num_of_runs as f64);
+    // Zoom level of the region: 1.0 for the full view (3.0 x 3.0, the default region),
+    // 2.0 for a region half as wide, ...
+    pub fn zoom(&self) -> f64 {
+        FULL_VIEW_SIZE / (self.re2 - self.re1).max(self.img2 - self.img1)
+    }
 
-    println!("Time taken for this run ({}): {:.5} ms", method, mean_time);
+    // True if the iteration doesn't start from 0 + 0i
+    pub fn has_z0(&self) -> bool {
+        self.z0_re != 0.0 || self.z0_im != 0.0
+    }
 
-    write_benchmark_result(&method, mandel_config.num_threads, mean_time,
-        min_time, max_time).expect("I/O error while writing benchmark results");
+    pub fn z0(&self) -> Complex64 {
+        Complex64{re: self.z0_re, im: self.z0_im}
+    }
 
-    if !mandel_config.no_ppm {
-        let file_name = format!("{}_{}.ppm", method, &time_now);
+    // True if the worker threads are pinned to cores (--pin_threads or --cpu_affinity)
+    pub fn pins_threads(&self) -> bool {
+        self.pin_threads || !self.cpu_affinity.is_empty()
+    }
+
+    // Short description of the workload for the benchmark results
+    pub fn workload_name(&self) -> String {
+        match self.workload {
+            Workload::Fractal => "fractal".to_string(),
+            Workload::Synthetic => format!("synthetic:{}:{}",
+                match self.synthetic_distribution {
+                    SyntheticDistribution::Uniform => "uniform",
+                    SyntheticDistribution::Zipf => "zipf"
+                }, self.synthetic_seed),
+            Workload::Phoenix => format!("phoenix:{}:{}", self.phoenix_p, self.phoenix_q)
+        }
+    }
+}
+
+// Width and height of the full view of the mandelbrot set (zoom level 1.0)
+const FULL_VIEW_SIZE: f64 = 3.0;
+
+// Smallest and largest value of auto_max_iter
+const AUTO_MAX_ITER_MIN: f64 = 256.0;
+const AUTO_MAX_ITER_MAX: f64 = 65536.0;
+
+// A max_iter that shows enough details for the given zoom level (--max_iter_auto).
+//
+// Empirical: 256 is enough for the full view, 65536 for a zoom of 1e10 and in between
+// max_iter grows like a power of the zoom (it doubles for every zoom factor of 10^1.25 ~ 17.8):
+//
+//     max_iter = 256 * zoom^(0.8 * log10(2)),  clamped to 256 .. 65536
+//
+// zoom 1: 256, zoom 1e3: 1351, zoom 1e6: 7132, zoom 1e10: 65536
+pub fn auto_max_iter(zoom: f64) -> u32 {
+    let max_iter = AUTO_MAX_ITER_MIN * zoom.powf(0.8 * 2.0f64.log10());
+    max_iter.round().max(AUTO_MAX_ITER_MIN).min(AUTO_MAX_ITER_MAX) as u32
+}
+
+fn is_power_of_two(n: u32) -> bool {
+    n != 0 && (n & (n - 1)) == 0
+}
+
+// The largest power of two below n and the smallest one above n
+fn nearest_powers_of_two(n: u32) -> (Option<u32>, Option<u32>) {
+    if n == 0 {
+        return (None, Some(1));
+    }
+
+    let lower = 1 << (31 - n.leading_zeros());
+    (Some(lower), lower.checked_mul(2))
+}
+
+// Errors in the configuration
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    // The configuration string could not be parsed
+    ParseError(String),
+    // A value is out of range, see MandelConfig::validate
+    InvalidValue(String)
+}
 
-        write_image(&file_name, &mandel_config, mean_time, &image).expect(
-            &format!("I/O error while writing image: '{}'", file_name));
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::ParseError(ref message) => write!(f, "parse error: {}", message),
+            ConfigError::InvalidValue(ref message) => write!(f, "invalid configuration: {}", message)
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// Compact form of the configuration: "re1=-2;re2=1;img1=-1.5;img2=1.5;max_iter=4096;img_size=1024;num_threads=4"
+// The result can be parsed again with str::parse().
+impl fmt::Display for MandelConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "re1={};re2={};img1={};img2={};max_iter={};img_size={};num_threads={}",
+            self.re1, self.re2, self.img1, self.img2, self.max_iter, self.img_size, self.num_threads));
+
+        if self.has_z0() {
+            try!(write!(f, ";z0_re={};z0_im={}", self.z0_re, self.z0_im));
+        }
+
+        Ok(())
+    }
+}
+
+// Relative tolerance of the region in PartialEq
+const CONFIG_EPSILON: f64 = 1e-15;
+
+// True if a and b differ by at most CONFIG_EPSILON relative to the larger of them.
+// Relative, so that the tolerance is the rounding error of the value, whatever its magnitude.
+fn nearly_equal(a: f64, b: f64) -> bool {
+    (a - b).abs() <= CONFIG_EPSILON * a.abs().max(b.abs())
+}
+
+// The region is compared with a tolerance: the same region computed in a different way
+// (for example re2 from --pixel_size and from the corners) differs in the last bits.
+// The step sizes follow from the region, so they are not compared by themselves.
+// All other fields are compared exactly.
+impl PartialEq for MandelConfig {
+    fn eq(&self, other: &MandelConfig) -> bool {
+        nearly_equal(self.re1, other.re1) &&
+        nearly_equal(self.re2, other.re2) &&
+        nearly_equal(self.img1, other.img1) &&
+        nearly_equal(self.img2, other.img2) &&
+        self.max_iter == other.max_iter &&
+        self.img_size == other.img_size &&
+        self.write_metadata == other.write_metadata &&
+        self.no_ppm == other.no_ppm &&
+        self.output_format == other.output_format &&
+        self.num_threads == other.num_threads &&
+        self.thread_model == other.thread_model &&
+        self.layout == other.layout &&
+        self.normalize_access == other.normalize_access &&
+        self.num_of_runs == other.num_of_runs &&
+        self.adaptive_runs == other.adaptive_runs &&
+        self.max_runs == other.max_runs &&
+        self.ci == other.ci &&
+        self.select_methods == other.select_methods &&
+        self.flip_y == other.flip_y &&
+        self.inclusive_edges == other.inclusive_edges &&
+        self.tile == other.tile &&
+        self.use_symmetry == other.use_symmetry &&
+        self.verify_output == other.verify_output &&
+        self.raw_output == other.raw_output &&
+        self.workload == other.workload &&
+        self.synthetic_distribution == other.synthetic_distribution &&
+        self.synthetic_seed == other.synthetic_seed &&
+        self.phoenix_p == other.phoenix_p &&
+        self.phoenix_q == other.phoenix_q &&
+        self.pin_threads == other.pin_threads &&
+        self.cpu_affinity == other.cpu_affinity &&
+        self.compare_parallel_outputs == other.compare_parallel_outputs &&
+        self.trace_schedule == other.trace_schedule &&
+        self.replay_schedule == other.replay_schedule &&
+        self.numa_init == other.numa_init &&
+        self.interior_coloring == other.interior_coloring &&
+        self.color_cycle == other.color_cycle &&
+        self.color_offset == other.color_offset &&
+        self.color_scale == other.color_scale &&
+        self.auto_levels == other.auto_levels &&
+        self.levels == other.levels &&
+        self.coloring_method == other.coloring_method &&
+        self.stripe_density == other.stripe_density &&
+        self.batch_file == other.batch_file &&
+        self.config_file == other.config_file &&
+        self.watch == other.watch &&
+        self.watch_debounce_ms == other.watch_debounce_ms &&
+        self.worker == other.worker &&
+        self.coordinator == other.coordinator &&
+        self.incremental == other.incremental &&
+        self.run_id == other.run_id &&
+        self.z0_re == other.z0_re &&
+        self.z0_im == other.z0_im &&
+        self.max_iter_auto == other.max_iter_auto &&
+        self.chunk_rows == other.chunk_rows &&
+        self.rayon_min_len == other.rayon_min_len &&
+        self.rayon_max_len == other.rayon_max_len &&
+        self.auto_tune == other.auto_tune &&
+        self.retune == other.retune &&
+        self.stats == other.stats &&
+        self.cost_map == other.cost_map &&
+        self.power_spectrum == other.power_spectrum &&
+        self.time_budget == other.time_budget &&
+        self.refine == other.refine &&
+        self.resume == other.resume &&
+        self.refine_from == other.refine_from &&
+        self.previous_max_iter == other.previous_max_iter &&
+        self.bench_db == other.bench_db &&
+        self.skip_slow == other.skip_slow &&
+        self.method_timeout_ms == other.method_timeout_ms &&
+        self.random_view == other.random_view &&
+        self.extract_contour == other.extract_contour &&
+        self.fractal_dimension == other.fractal_dimension &&
+        self.contours == other.contours &&
+        self.webp_quality == other.webp_quality &&
+        self.mesh_step == other.mesh_step &&
+        self.mesh_height == other.mesh_height &&
+        self.mesh_log == other.mesh_log &&
+        self.compress == other.compress &&
+        self.compress_level == other.compress_level &&
+        self.color == other.color &&
+        self.detect_denormals == other.detect_denormals &&
+        self.print_memory == other.print_memory &&
+        self.tile_cache == other.tile_cache &&
+        self.clear_cache == other.clear_cache &&
+        self.palette_preview == other.palette_preview &&
+        self.benchmark_matrix == other.benchmark_matrix &&
+        self.num_threads_sweep == other.num_threads_sweep &&
+        self.size_list == other.size_list &&
+        self.iter_list == other.iter_list &&
+        self.yes == other.yes
+    }
+}
+
+// Parse the compact form of the configuration, see Display above.
+// Missing keys keep their default value, the step sizes are computed from the region.
+impl FromStr for MandelConfig {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> std::result::Result<MandelConfig, ConfigError> {
+        MandelConfig::default().apply_config(s)
+    }
+}
+
+// Thread pool for the rayon methods with the threads of the configuration:
+// rayon::ThreadPoolBuilder::from(&config).build() or .build_global()
+#[cfg(not(target_arch = "wasm32"))]
+impl<'a> From<&'a MandelConfig> for rayon::ThreadPoolBuilder {
+    fn from(mandel_config: &'a MandelConfig) -> rayon::ThreadPoolBuilder {
+        rayon::ThreadPoolBuilder::new().num_threads(mandel_config.num_threads as usize)
+    }
+}
+
+impl MandelConfig {
+    // Copy of the configuration with the keys of a compact configuration ("re1=-2.0;re2=1.0;max_iter=1024"),
+    // the other values are kept
+    pub fn apply_config(&self, s: &str) -> std::result::Result<MandelConfig, ConfigError> {
+        let mut config = self.clone();
+
+        for pair in s.split(';').map(|pair| pair.trim()).filter(|pair| !pair.is_empty()) {
+            let mut key_value = pair.splitn(2, '=');
+            let key = key_value.next().unwrap().trim();
+            let value = match key_value.next() {
+                Some(value) => value.trim(),
+                None => return Err(ConfigError::ParseError(format!("missing value for '{}'", key)))
+            };
+
+            fn parse_value<T: FromStr>(key: &str, value: &str) -> std::result::Result<T, ConfigError> {
+                value.parse::<T>().map_err(|_| ConfigError::ParseError(format!("invalid value for '{}': '{}'", key, value)))
+            }
+
+            match key {
+                "re1" => config.re1 = try!(parse_value(key, value)),
+                "re2" => config.re2 = try!(parse_value(key, value)),
+                "img1" => config.img1 = try!(parse_value(key, value)),
+                "img2" => config.img2 = try!(parse_value(key, value)),
+                "max_iter" => config.max_iter = try!(parse_value(key, value)),
+                "img_size" => config.img_size = try!(parse_value(key, value)),
+                "num_threads" => config.num_threads = try!(parse_value(key, value)),
+                "z0_re" => config.z0_re = try!(parse_value(key, value)),
+                "z0_im" => config.z0_im = try!(parse_value(key, value)),
+                _ => return Err(ConfigError::ParseError(format!("unknown key: '{}'", key)))
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+// Read the compact configuration of --config_file and apply it to the configuration.
+// The file has one key=value per line (or several separated by ;), # starts a comment.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_config_file(file_name: &str, mandel_config: &MandelConfig) -> std::result::Result<MandelConfig, ConfigError> {
+    let mut content = String::new();
+
+    try!(File::open(file_name).and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(|e| ConfigError::ParseError(format!("can't read config file '{}': {}", file_name, e))));
+
+    let compact = content.lines().map(|line| line.split('#').next().unwrap()).collect::<Vec<&str>>().join(";");
+
+    mandel_config.apply_config(&compact)
+}
+
+include!(concat!(env!("OUT_DIR"), "/compiler_version.rs"));
+
+// Grid size of sample_region for --auto_check
+#[cfg(not(target_arch = "wasm32"))]
+const AUTO_CHECK_SAMPLES: u32 = 64;
+
+// Available memory in bytes (MemAvailable of /proc/meminfo), None if it is not known
+#[cfg(not(target_arch = "wasm32"))]
+fn available_memory() -> Option<u64> {
+    let mut content = String::new();
+    if File::open("/proc/meminfo").and_then(|mut file| file.read_to_string(&mut content)).is_err() {
+        return None;
+    }
+
+    content.lines().find(|line| line.starts_with("MemAvailable:"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|kib| kib.parse::<u64>().ok())
+        .map(|kib| kib * 1024)
+}
+
+// Resident memory of the process in bytes (VmRSS of /proc/self/status on Linux, the working set on Windows),
+// None if it is not known
+#[cfg(all(not(target_arch = "wasm32"), target_os = "linux"))]
+pub fn get_rss_bytes() -> Option<u64> {
+    let mut content = String::new();
+    if File::open("/proc/self/status").and_then(|mut file| file.read_to_string(&mut content)).is_err() {
+        return None;
+    }
+
+    content.lines().find(|line| line.starts_with("VmRSS:"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|kib| kib.parse::<u64>().ok())
+        .map(|kib| kib * 1024)
+}
+
+#[cfg(windows)]
+pub fn get_rss_bytes() -> Option<u64> {
+    // PROCESS_MEMORY_COUNTERS of psapi.h
+    #[repr(C)]
+    struct ProcessMemoryCounters {
+        cb: u32,
+        page_fault_count: u32,
+        peak_working_set_size: usize,
+        working_set_size: usize,
+        quota_peak_paged_pool_usage: usize,
+        quota_paged_pool_usage: usize,
+        quota_peak_non_paged_pool_usage: usize,
+        quota_non_paged_pool_usage: usize,
+        pagefile_usage: usize,
+        peak_pagefile_usage: usize
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcess() -> *mut std::os::raw::c_void;
+        fn K32GetProcessMemoryInfo(process: *mut std::os::raw::c_void, counters: *mut ProcessMemoryCounters, cb: u32) -> i32;
+    }
+
+    let mut counters: ProcessMemoryCounters = unsafe { mem::zeroed() };
+    counters.cb = mem::size_of::<ProcessMemoryCounters>() as u32;
+
+    if unsafe { K32GetProcessMemoryInfo(GetCurrentProcess(), &mut counters, counters.cb) } == 0 {
+        None
+    } else {
+        Some(counters.working_set_size as u64)
+    }
+}
+
+#[cfg(all(not(target_arch = "wasm32"), not(target_os = "linux"), not(windows)))]
+pub fn get_rss_bytes() -> Option<u64> {
+    None
+}
+
+// Memory in bytes needed for the image buffers of the benchmark:
+// one image and the reference image of --compare_parallel_outputs
+pub fn image_memory(mandel_config: &MandelConfig) -> u64 {
+    let num_of_images = if mandel_config.compare_parallel_outputs { 2 } else { 1 };
+    (mandel_config.num_of_pixels() as u64) * (mem::size_of::<u32>() as u64) * num_of_images
+}
+
+// Print the error of an invalid option or configuration and exit with error::EXIT_CONFIG
+#[cfg(not(target_arch = "wasm32"))]
+fn exit_config<T: fmt::Display>(message: T) -> ! {
+    println!("Error: {}", message);
+    std::process::exit(error::EXIT_CONFIG);
+}
+
+// The iteration counts LOW..HIGH of --levels, None if it isn't two numbers
+pub fn parse_levels(levels: &str) -> Option<(u32, u32)> {
+    let mut bounds = levels.splitn(2, "..").map(|bound| bound.trim().parse::<u32>().ok());
+
+    match (bounds.next(), bounds.next()) {
+        (Some(Some(low)), Some(Some(high))) => Some((low, high)),
+        _ => None
+    }
+}
+
+// Parse command line options via clap and returns the responding configuration
+#[cfg(not(target_arch = "wasm32"))]
+pub fn parse_arguments() -> MandelConfig {
+    let matches = App::new("mandel_rust")
+        .version("0.3")
+        .author("Willi Kappler <grandor@gmx.de>")
+        .about("Simple mandelbrot written in pure rust")
+        .after_help(error::EXIT_CODES_HELP)
+        .args_from_usage(
+            "--config=[CONFIG] 'compact configuration, for example: \"re1=-2.0;re2=1.0;max_iter=1024\", other options override it'
+             --config_file=[CONFIG_FILE] 'like --config, but read from this file with one key=value per line, # starts a comment'
+             --watch 'with --config_file: run the methods again and write new images whenever the file changes, until Ctrl-C (default: off)'
+             --watch_debounce_ms=[DEBOUNCE_MS] 'with --watch: changes of the file within this many milliseconds are one change (default: 200)'
+             --bookmark=[BOOKMARK] 'start from the view, max_iter and colors of this bookmark (~/.config/mandel-rust/bookmarks.toml or built-in), other options override it'
+             --save_bookmark=[SAVE_BOOKMARK] 'append the view, max_iter and colors to ~/.config/mandel-rust/bookmarks.toml under this name and exit'
+             --force 'with --save_bookmark: replace a bookmark with the same name (default: off)'
+             --re1=[REAL1] 'left real part (default: -2.0)'
+             --re2=[REAL2] 'right real part (default: 1.0)'
+             --img1=[IMAGINARY1] 'lower part (default: -1.50)'
+             --img2=[IMAGINARY2] 'upper part (default: 1.50)'
+             --pixel_size=[PIXEL_SIZE] 'distance of the sampled points instead of --re2 and --img2, they follow from re1, img1 and img_size'
+             --write_metadata 'write metadata like run time, method, date, number of threads and compiler version into the ppm file (default: off)'
+             --no_ppm 'disable creation of the ppm file, just run the calculation (default: off)'
+             --output_format=[OUTPUT_FORMAT] 'file format of the images: ppm, ppm_binary (P6), exr (32 bit float iteration counts), webp (lossy, see --webp_quality), svg (contours, see --contours) or obj (3D height field, see --mesh_step) (default: ppm)'
+             --webp_quality=[QUALITY] 'quality of --output_format=webp between 0 (smallest file) and 100 (best colors) (default: 80)'
+             --mesh_step=[MESH_STEP] 'the height field of --output_format=obj samples every MESH_STEP-th pixel (default: at most 1025 vertices per side)'
+             --mesh_height=[MESH_HEIGHT] 'height of the points of the set in the height field, the image is 1 wide (default: 0.25)'
+             --mesh_log 'log(1 + iter) instead of iter as height of the height field (default: off)'
+             --color=[WHEN] 'colors of the console output: always, never or auto (only on a terminal and if NO_COLOR is not set) (default: auto)'
+             --compress 'write the PPM images gzip compressed, .gz is appended to the file names (default: off)'
+             --compress_level=[COMPRESS_LEVEL] 'gzip level of --compress between 0 (fastest) and 9 (smallest file) (default: 6)'
+             --print_memory 'add the largest change of the resident memory (RSS) during a run to the benchmark results (default: off)'
+             --detect_denormals 'report the denormal floats of the region per band of rows and flush them to zero (FTZ/DAZ, x86_64 only) (default: off)'
+             --bench 'use all available CPUs (default: off), will change in the future'
+             --max_iter=[MAX_ITER] 'maximum number of iterations (default: 4096)'
+             --max_iter_auto 'compute max_iter from the zoom level of the region, overrides --max_iter (default: off)'
+             --img_size=[IMAGE_SIZE] 'size of image in pixel (square, default: 2048, must be a power of two)'
+             --num_of_runs=[NUM_OF_RUNS] 'number of repetitive runs (default: 2)'
+             --adaptive_runs 'repeat the runs of each method until the 95% confidence interval of the mean is within --ci, at least --num_of_runs (and 2) and at most --max_runs runs (default: off)'
+             --max_runs=[MAX_RUNS] 'maximum number of runs of --adaptive_runs (default: 100)'
+             --ci=[CI] 'relative half width of the confidence interval of --adaptive_runs, for example 0.02: mean +- 2% (default: 0.02)'
+             --num_threads=[NUMBER_OF_THREADS] 'number of threads to use (default: 2)'
+             --thread_model=[THREAD_MODEL] 'run only serial and the methods of this threading backend: os, rayon or tokio (default: all)'
+             --layout=[LAYOUT] 'order of the pixels in the image buffer of the methods: rows or tiles64 (tiles of 64 x 64 pixels) (default: rows)'
+             --normalize_access=[ACCESS] 'order in which the methods access the pixels: native (the order of each method) or rows (whole rows from top to bottom, for comparing the scheduling alone) (default: native)'
+             --select_methods=[METHODS] 'comma separated list of methods to run (default: all)'
+             --method=[METHOD] 'run only this method, the same as --select_methods=METHOD'
+             --raw_output 'also write the iteration counts as raw dump (.mraw) for each method (default: off)'
+             --verify_output 'read back each image after writing it and check the header and size (default: off)'
+             --workload=[WORKLOAD] 'fractal, synthetic: dummy iterations with the same total work for every method or phoenix: the phoenix fractal (default: fractal)'
+             --phoenix_p=[PHOENIX_P] 'parameter p of the phoenix workload: z_{n+1} = z_n^2 + p + q * z_{n-1} (default: -0.5)'
+             --phoenix_q=[PHOENIX_Q] 'parameter q of the phoenix workload (default: 0.0)'
+             --synthetic_dist=[DISTRIBUTION] 'distribution of iterations for the synthetic workload: uniform or zipf (default: uniform)'
+             --synthetic_seed=[SEED] 'seed for the synthetic workload (default: 0)'
+             --compare_parallel_outputs 'run serial first and check that every other method computes exactly the same pixels (default: off)'
+             --trace_schedule=[TRACE_FILE] 'job_steal, job_steal_join and kirk_crossbeam write the order of their jobs and the worker of each job to this file, with the method name before the extension'
+             --replay_schedule=[REPLAY_FILE] 'compute the jobs of a --trace_schedule file in their order on one thread, compare the image with serial, write replay_<method>_<run_id>.ppm and exit'
+             --interior_coloring=[INTERIOR_COLORING] 'color of the inside of the set: black, period or distance (default: black)'
+             --color_cycle=[COLOR_CYCLE] 'number of palette repetitions between 0 and max_iter (default: one every 16 iterations)'
+             --color_offset=[COLOR_OFFSET] 'rotate the palette by this many colors (default: 0)'
+             --color_scale=[COLOR_SCALE] 'mapping of the iteration counts into the palette: linear, log or sqrt (default: linear)'
+             --auto_levels 'spread the palette over the 1st to 99th percentile of the iteration counts outside of the set, print these levels and store them in the image (default: off)'
+             --levels=[PALETTE_LEVELS] 'spread the palette over the iteration counts LOW..HIGH, for example the ones of --auto_levels (default: off)'
+             --coloring=[COLORING] 'colors of the outside of the set: iter (palette), smooth (blended palette), distance (to the set), orbit_trap[:point|cross|circle], histogram (equalized) or stripe (stripe average of the orbit) (default: iter)'
+             --stripe_density=[STRIPE_DENSITY] 'number of stripes per turn around the origin of --coloring=stripe (default: 5.0)'
+             --numa_init 'each method renders a new image buffer once before it is measured, so its workers place the pages (first touch) for NUMA systems (default: off)'
+             --refine=[RECT] 'recompute the pixels X0,Y0,X1,Y1 (X1 and Y1 not included) of the raw dump given with --base, the region options must be the same as for the dump'
+             --refine_iter=[REFINE_ITER] 'maximum number of iterations inside of the --refine rectangle'
+             --base=[BASE_DUMP] 'raw dump (.mraw) for --refine'
+             --resume=[PARTIAL_RAW_FILE] 'compute the missing rows of this raw dump and write them into it after each row, so an interrupted render continues there (created if it does not exist), then write it as .ppm'
+             --refine_from=[PREVIOUS_DUMP] 'compute the image of this raw dump again with the higher --max_iter, only the pixels that reached the max_iter of the dump are iterated again'
+             --time_budget=[SECONDS] 'render progressively with more and more iterations for this many seconds and write the best image so far'
+             --skip_slow=[SKIP_SECONDS] 'skip methods whose runtime, estimated from a 128 x 128 probe, is longer than this many seconds and write report_<run_id>.json'
+             --random_view=[VIEW_SEED] 'render a random region with pixels inside and outside of the set, the same seed always gives the same region'
+             --method_timeout_ms=[TIMEOUT_MS] 'cancel a method that has not finished all runs after this many milliseconds and continue with the next one'
+             --method_timeout=[TIMEOUT_SECONDS] 'the same as --method_timeout_ms, in seconds'
+             --extract_contour=[ITER_THRESHOLD] 'write the contour where the iteration count reaches ITER_THRESHOLD as <method>_<run_id>_contour.svg'
+             --fractal_dimension 'estimate the box-counting dimension of the boundary of the set (the contour at max_iter) after each method and print it (default: off)'
+             --contours=[LEVELS] 'comma separated iteration levels of the contours of --output_format=svg, for example: 100,500,1000 (default: max_iter)'
+             --bench_db=[PATH] 'also append the benchmark results to this SQLite database, it is created if it does not exist'
+             --batch=[JOBS_FILE] 'render all jobs of a TOML jobs file (or a CSV file with one job per line) instead of running the benchmark'
+             --worker=[BIND_ADDR] 'compute the rows that coordinators ask for with --method (default: rayon_join) and --num_threads, listening on this address, for example: 0.0.0.0:7878'
+             --coordinator=[WORKERS] 'render the image on these comma separated workers, for example: host1:7878,host2:7878, and write distributed_<run_id>.ppm'
+             --tile_cache=[CACHE_DIR] 'batch jobs read the iteration counts from this directory if they were computed before with the same region, img_size and max_iter and store them there otherwise'
+             --clear_cache 'delete the --tile_cache directory and exit'
+             --benchmark_matrix 'run the selected methods for every combination of --size_list and --iter_list and write plot/matrix_<run_id>.csv and report_<run_id>.json (default: off)'
+             --size_list=[SIZE_LIST] 'comma separated image sizes of --benchmark_matrix, for example: 512,1024,2048 (default: img_size)'
+             --iter_list=[ITER_LIST] 'comma separated max_iter values of --benchmark_matrix, for example: 1024,4096,16384 (default: max_iter)'
+             --num_threads_sweep=[SWEEP] 'run the selected methods with every number of threads from START to END in steps of STEP, for example: 1,16,1, and print the fastest one of each method (overrides --num_threads)'
+             --yes 'start long benchmarks (--benchmark_matrix) without asking for confirmation (default: off)'
+             --palette_preview=[PREVIEW_FILE] 'print the palette of --max_iter, --color_cycle, --color_offset and --color_scale, write it as a strip of img_size x 64 pixels into this PPM image and exit'
+             --incremental 'batch jobs write their progress to <output>.progress and continue interrupted renders (default: off)'
+             --run_id=[RUN_ID] 'use this run id instead of a generated one, for reproducible file names (default: date, time and a random suffix)'
+             --z0_re=[Z0_REAL] 'real part of the starting value z0 of the iteration (default: 0.0)'
+             --z0_im=[Z0_IMAGINARY] 'imaginary part of the starting value z0 of the iteration (default: 0.0)'
+             --pin_threads 'pin each worker thread to its own CPU core (default: off)'
+             --chunk_rows=[CHUNK_ROWS] 'number of image rows per job of the parallel methods (default: 1)'
+             --rayon_min_len=[MIN_LEN] 'rayon_par_iter splits the image into tasks of at least MIN_LEN pixels (default: 1)'
+             --rayon_max_len=[MAX_LEN] 'rayon_par_iter splits the image into tasks of at most MAX_LEN pixels (default: no limit)'
+             --auto_tune 'find the fastest number of threads and chunk_rows for each method with a short calibration run first, cached in plot/autotune_<cpu_model>.json (default: off)'
+             --retune 'ignore the cached results of --auto_tune and calibrate again (default: off)'
+             --cpu_affinity=[CORE_LIST] 'comma separated list of cores, worker thread i is pinned to the i-th core (wraps around), for example: 0,2,4,6 (default: off)'
+             --skip_memory_check 'run even if the image buffer needs more memory than is available (default: off)'
+             --auto_check 'sample the region first and warn if it is almost completely inside of the set (default: off)'
+             --stats 'print statistics and a histogram of the escape times after each method and write them to <method>_<run_id>_stats.json (default: off)'
+             --cost_map=[COST_MAP] 'also write the number of iterations of each pixel as gray PPM image (log scale, the inside of the set is white) to this file after each method'
+             --power_spectrum=[OUTPUT_CSV] 'write the log-power spectrum of the iteration counts (2D FFT, averaged over rings of the same frequency) as CSV to this file after each method'
+             --use_symmetry 'compute only the upper half of a region with img1 = -img2 and mirror it (default: off)'
+             --inclusive_edges 'the last column samples re2 and the first row img2: step = range / (img_size - 1) (default: off, the grid is half-open)'
+             --tile=[TILE] 'render only tile ROW,COL of an OF_N x OF_N grid of tiles of img_size pixels each, the region is the whole image of all tiles, for example: 0,1,4'
+             --flip_y 'flip the image vertically, row 0 is the lower part (old behaviour, default: off)'")
+        .subcommand(SubCommand::with_name("diff")
+            .about("compare two raw dumps (.mraw) or two PPM images, exit code: 0 identical, 1 different, 2 not comparable")
+            .args_from_usage(
+                "<FILE_A> 'first raw dump or PPM image'
+                 <FILE_B> 'second raw dump or PPM image'
+                 --diff_image=[DIFF_IMAGE] 'write a PPM image with all differing pixels in red'"))
+        .subcommand(SubCommand::with_name("zoom")
+            .about("render a zoom animation from the region given by the main options into a target point, one PPM image per frame")
+            .args_from_usage(
+                "--center=<CENTER> 'center of the last frame: RE,IM, for example: -0.75,0.1'
+                 --zoom_factor=[ZOOM_FACTOR] 'the last frame is this many times smaller than the first one (default: 1000)'
+                 --frames=[FRAMES] 'number of frames (default: 100)'"))
+        .subcommand(SubCommand::with_name("density")
+            .about("render a density image of the orbits (buddhabrot) of the region given by the main options")
+            .args_from_usage(
+                "--mode=[MODE] 'buddhabrot: orbits of escaping points, anti_buddhabrot: orbits of points inside the set, nebulabrot: three buddhabrot channels (default: buddhabrot)'
+                 --nebula_iters=[ITERS] 'max_iter of the red, green and blue channel for nebulabrot (default: 50,500,5000)'
+                 --samples=[SAMPLES] 'number of sampled points (default: 16 per pixel)'
+                 --output=[OUTPUT] 'name of the PPM image (default: density.ppm)'"))
+        .subcommand(SubCommand::with_name("probe")
+            .about("print the point, the iteration count and the orbit of one pixel of the region given by the main options")
+            .args_from_usage(
+                "--x=[X] 'column of the pixel'
+                 --y=[Y] 'row of the pixel'
+                 --probe_point=[POINT] 'the point RE,IM instead of a pixel, for example: -0.75,0.1'
+                 --orbit=[ORBIT_LEN] 'print the first ORBIT_LEN points of the orbit (default: 0)'"))
+        .subcommand(SubCommand::with_name("orbit")
+            .about("write the orbit of one point with max_iter and z0 of the main options as CSV: iteration, Re(z), Im(z), |z|")
+            .args_from_usage(
+                "--export_orbit=<POINT> 'the point RE,IM, for example: -1,0'
+                 --orbit_file=[ORBIT_FILE] 'name of the CSV file (default: orbit.csv)'"))
+        .get_matches_safe().unwrap_or_else(|e| {
+            // The help and the version are printed by clap, it exits with 0 after them
+            eprintln!("{}", e);
+            std::process::exit(error::EXIT_CONFIG);
+        });
+
+    if let Some(diff_matches) = matches.subcommand_matches("diff") {
+        let exit_code = diff::run_diff(diff_matches.value_of("FILE_A").unwrap(),
+            diff_matches.value_of("FILE_B").unwrap(), diff_matches.value_of("DIFF_IMAGE"));
+        std::process::exit(exit_code);
+    }
+
+    let default = match (matches.value_of("CONFIG"), matches.value_of("CONFIG_FILE")) {
+        (Some(config), None) => config.parse::<MandelConfig>().unwrap_or_else(|e| exit_config(format!("Invalid configuration: '{}': {}", config, e))),
+        (None, Some(file_name)) => read_config_file(file_name, &MandelConfig::default())
+            .unwrap_or_else(|e| exit_config(format!("Invalid configuration file: '{}': {}", file_name, e))),
+        (None, None) => MandelConfig::default(),
+        _ => exit_config("Only one of --config and --config_file can be given")
+    };
+
+    let default = match matches.value_of("BOOKMARK") {
+        Some(name) => match bookmark::find_bookmark(name, bookmark::user_bookmarks_file().as_ref().map(|file| file.as_path())) {
+            Ok(bookmark) => bookmark.apply_to(&default),
+            Err(e) => {
+                println!("Error: {}", e);
+                std::process::exit(e.exit_code());
+            }
+        },
+        None => default
+    };
+
+    let re1 = value_t!(matches.value_of("REAL1"), f64).unwrap_or(default.re1);
+    let re2 = value_t!(matches.value_of("REAL2"), f64).unwrap_or(default.re2);
+    let img1 = value_t!(matches.value_of("IMAGINARY1"), f64).unwrap_or(default.img1);
+    let img2 = value_t!(matches.value_of("IMAGINARY2"), f64).unwrap_or(default.img2);
+    let pixel_size = matches.value_of("PIXEL_SIZE").map(|pixel_size| pixel_size.parse::<f64>().ok()
+        .filter(|pixel_size| pixel_size.is_finite() && *pixel_size > 0.0)
+        .unwrap_or_else(|| exit_config(format!("Invalid --pixel_size: '{}', must be a positive number", pixel_size))));
+    if pixel_size.is_some() && (matches.is_present("REAL2") || matches.is_present("IMAGINARY2") || matches.is_present("VIEW_SEED")) {
+        exit_config("--pixel_size can't be combined with --re2, --img2 or --random_view");
+    }
+    let metadata = matches.is_present("write_metadata");
+    let bench = matches.is_present("bench");
+    let no_ppm = matches.is_present("no_ppm");
+    let flip_y = matches.is_present("flip_y");
+    let inclusive_edges = matches.is_present("inclusive_edges");
+    let tile = matches.value_of("TILE").map(|tile| tile.parse::<Tile>().unwrap_or_else(|e| exit_config(e)));
+    let use_symmetry = matches.is_present("use_symmetry");
+    let verify_output = matches.is_present("verify_output");
+    let raw_output = matches.is_present("raw_output");
+    let pin_threads = matches.is_present("pin_threads");
+    let compare_parallel_outputs = matches.is_present("compare_parallel_outputs");
+    let trace_schedule = matches.value_of("TRACE_FILE").map(|file_name| file_name.to_string());
+    let replay_schedule = matches.value_of("REPLAY_FILE").map(|file_name| file_name.to_string());
+    let numa_init = matches.is_present("numa_init");
+    let incremental = matches.is_present("incremental");
+    let max_iter_auto = matches.is_present("max_iter_auto");
+    let auto_tune = matches.is_present("auto_tune");
+    let retune = matches.is_present("retune");
+    let stats = matches.is_present("stats");
+    let cost_map = matches.value_of("COST_MAP").map(|file_name| file_name.to_string());
+    let power_spectrum = matches.value_of("OUTPUT_CSV").map(|file_name| file_name.to_string());
+    let output_format = matches.value_of("OUTPUT_FORMAT").unwrap_or("ppm")
+        .parse::<OutputFormat>().unwrap_or_else(|e| exit_config(e));
+    let thread_model = matches.value_of("THREAD_MODEL")
+        .map(|model| model.parse::<ThreadModel>().unwrap_or_else(|e| exit_config(e)));
+    let layout = matches.value_of("LAYOUT").unwrap_or("rows")
+        .parse::<BufferLayout>().unwrap_or_else(|e| exit_config(e));
+    let normalize_access = matches.value_of("ACCESS").unwrap_or("native")
+        .parse::<AccessOrder>().unwrap_or_else(|e| exit_config(e));
+    let interior_coloring = matches.value_of("INTERIOR_COLORING").unwrap_or("black")
+        .parse::<InteriorColoring>().unwrap_or_else(|e| exit_config(e));
+    let coloring_method = matches.value_of("COLORING")
+        .map_or(default.coloring_method, |coloring| coloring.parse::<ColoringMethod>().unwrap_or_else(|e| exit_config(e)));
+    let stripe_density = value_t!(matches.value_of("STRIPE_DENSITY"), f64).unwrap_or(default.stripe_density);
+    let color_scale = matches.value_of("COLOR_SCALE")
+        .map_or(default.color_scale, |color_scale| color_scale.parse::<ColorScale>().unwrap_or_else(|e| exit_config(e)));
+    let auto_levels = matches.is_present("auto_levels");
+    let levels = matches.value_of("PALETTE_LEVELS").map(|levels| parse_levels(levels)
+        .unwrap_or_else(|| exit_config(format!("Invalid --levels: '{}', must be LOW..HIGH", levels))));
+    let workload = match matches.value_of("WORKLOAD").unwrap_or("fractal") {
+        "fractal" => Workload::Fractal,
+        "synthetic" => Workload::Synthetic,
+        "phoenix" => Workload::Phoenix,
+        other => exit_config(format!("Unknown workload: '{}', must be fractal, synthetic or phoenix", other))
+    };
+    let synthetic_distribution = match matches.value_of("DISTRIBUTION").unwrap_or("uniform") {
+        "uniform" => SyntheticDistribution::Uniform,
+        "zipf" => SyntheticDistribution::Zipf,
+        other => exit_config(format!("Unknown distribution: '{}', must be uniform or zipf", other))
+    };
+    let z0_re = value_t!(matches.value_of("Z0_REAL"), f64).unwrap_or(default.z0_re);
+    let z0_im = value_t!(matches.value_of("Z0_IMAGINARY"), f64).unwrap_or(default.z0_im);
+    let synthetic_seed = value_t!(matches.value_of("SEED"), u64).unwrap_or(default.synthetic_seed);
+    let phoenix_p = value_t!(matches.value_of("PHOENIX_P"), f64).unwrap_or(default.phoenix_p);
+    let phoenix_q = value_t!(matches.value_of("PHOENIX_Q"), f64).unwrap_or(default.phoenix_q);
+    let max_iter = value_t!(matches.value_of("MAX_ITER"), u32).unwrap_or(default.max_iter);
+    let img_size = value_t!(matches.value_of("IMAGE_SIZE"), u32).unwrap_or(default.img_size);
+    let num_of_runs = value_t!(matches.value_of("NUM_OF_RUNS"), u32).unwrap_or(default.num_of_runs);
+    let adaptive_runs = matches.is_present("adaptive_runs");
+    let max_runs = value_t!(matches.value_of("MAX_RUNS"), u32).unwrap_or(default.max_runs);
+    let ci = value_t!(matches.value_of("CI"), f64).unwrap_or(default.ci);
+    let chunk_rows = value_t!(matches.value_of("CHUNK_ROWS"), u32).unwrap_or(default.chunk_rows);
+    let rayon_min_len = value_t!(matches.value_of("MIN_LEN"), usize).unwrap_or(default.rayon_min_len);
+    let rayon_max_len = value_t!(matches.value_of("MAX_LEN"), usize).unwrap_or(default.rayon_max_len);
+    let color_offset = value_t!(matches.value_of("COLOR_OFFSET"), u32).unwrap_or(default.color_offset);
+    let color_cycle = matches.value_of("COLOR_CYCLE").map(|color_cycle| color_cycle.parse::<u32>()
+        .unwrap_or_else(|_| exit_config(format!("Invalid --color_cycle: '{}'", color_cycle)))).or(default.color_cycle);
+    let num_threads = if bench { num_cpus::get() as u32 } else {
+        value_t!(matches.value_of("NUMBER_OF_THREADS"), u32).unwrap_or(default.num_threads) };
+    let time_budget = matches.value_of("SECONDS").map(|seconds| seconds.parse::<f64>()
+        .unwrap_or_else(|_| exit_config(format!("Invalid --time_budget: '{}'", seconds))));
+    let skip_slow = matches.value_of("SKIP_SECONDS").map(|seconds| seconds.parse::<f64>()
+        .unwrap_or_else(|_| exit_config(format!("Invalid --skip_slow: '{}'", seconds))));
+    let random_view = matches.value_of("VIEW_SEED").map(|seed| seed.parse::<u64>()
+        .unwrap_or_else(|_| exit_config(format!("Invalid --random_view: '{}'", seed))));
+    let method_timeout_ms = match (matches.value_of("TIMEOUT_MS"), matches.value_of("TIMEOUT_SECONDS")) {
+        (None, None) => None,
+        (Some(timeout_ms), None) => Some(timeout_ms.parse::<u64>()
+            .unwrap_or_else(|_| exit_config(format!("Invalid --method_timeout_ms: '{}'", timeout_ms)))),
+        (None, Some(seconds)) => Some((seconds.parse::<f64>()
+            .unwrap_or_else(|_| exit_config(format!("Invalid --method_timeout: '{}'", seconds))) * 1000.0).round() as u64),
+        _ => exit_config("Only one of --method_timeout_ms and --method_timeout can be given")
+    };
+    let fractal_dimension = matches.is_present("fractal_dimension");
+    let extract_contour = matches.value_of("ITER_THRESHOLD").map(|threshold| threshold.parse::<u32>()
+        .unwrap_or_else(|_| exit_config(format!("Invalid --extract_contour: '{}'", threshold))));
+    let run_id = matches.value_of("RUN_ID").map(|run_id| run_id.to_string());
+    let batch_file = matches.value_of("JOBS_FILE").map(|file_name| file_name.to_string());
+    let config_file = matches.value_of("CONFIG_FILE").map(|file_name| file_name.to_string());
+    let watch = matches.is_present("watch");
+    let worker = matches.value_of("BIND_ADDR").map(|address| address.to_string());
+    let coordinator = matches.value_of("WORKERS").map_or(Vec::new(), |workers| workers.split(',')
+        .map(|address| address.trim().to_string()).filter(|address| !address.is_empty()).collect());
+    let watch_debounce_ms = value_t!(matches.value_of("DEBOUNCE_MS"), u64).unwrap_or(default.watch_debounce_ms);
+    let tile_cache = matches.value_of("CACHE_DIR").map(|cache_dir| cache_dir.to_string());
+    let clear_cache = matches.is_present("clear_cache");
+    let palette_preview = matches.value_of("PREVIEW_FILE").map(|file_name| file_name.to_string());
+    let benchmark_matrix = matches.is_present("benchmark_matrix");
+    let num_threads_sweep = matches.value_of("SWEEP").map(|sweep| sweep::parse_sweep(sweep)
+        .unwrap_or_else(|| exit_config(format!("Invalid --num_threads_sweep: '{}', must be START,END,STEP", sweep))));
+    let size_list = match matches.value_of("SIZE_LIST") {
+        Some(sizes) => sizes.split(',').map(|size| size.trim().parse::<u32>()
+            .unwrap_or_else(|_| exit_config(format!("Invalid size in --size_list: '{}'", size)))).collect(),
+        None => Vec::new()
+    };
+    let iter_list = match matches.value_of("ITER_LIST") {
+        Some(iters) => iters.split(',').map(|iter| iter.trim().parse::<u32>()
+            .unwrap_or_else(|_| exit_config(format!("Invalid max_iter in --iter_list: '{}'", iter)))).collect(),
+        None => Vec::new()
+    };
+    let yes = matches.is_present("yes");
+    let bench_db = matches.value_of("PATH").map(|db_path| db_path.to_string());
+    let webp_quality = value_t!(matches.value_of("QUALITY"), f32).unwrap_or(default.webp_quality);
+    let mesh_step = matches.value_of("MESH_STEP").map(|step| step.parse::<u32>()
+        .unwrap_or_else(|_| exit_config(format!("Invalid --mesh_step: '{}'", step))));
+    let mesh_height = value_t!(matches.value_of("MESH_HEIGHT"), f64).unwrap_or(default.mesh_height);
+    let mesh_log = matches.is_present("mesh_log");
+    let compress = matches.is_present("compress");
+    let detect_denormals = matches.is_present("detect_denormals");
+    let print_memory = matches.is_present("print_memory");
+    let color = matches.value_of("WHEN").unwrap_or("auto")
+        .parse::<ColorChoice>().unwrap_or_else(|e| exit_config(e));
+    let compress_level = value_t!(matches.value_of("COMPRESS_LEVEL"), u32).unwrap_or(default.compress_level);
+    let contours = match matches.value_of("LEVELS") {
+        Some(levels) => levels.split(',').map(|level| level.trim().parse::<u32>()
+            .unwrap_or_else(|_| exit_config(format!("Invalid level in --contours: '{}'", level)))).collect(),
+        None => Vec::new()
+    };
+    let cpu_affinity = match matches.value_of("CORE_LIST") {
+        Some(cores) => cores.split(',').map(|core| core.trim().parse::<usize>()
+            .unwrap_or_else(|_| exit_config(format!("Invalid core in --cpu_affinity: '{}'", core)))).collect(),
+        None => Vec::new()
+    };
+    let refine = match (matches.value_of("RECT"), matches.value_of("REFINE_ITER"), matches.value_of("BASE_DUMP")) {
+        (None, None, None) => None,
+        (Some(rect), Some(refine_iter), Some(base)) => Some(RefineJob{
+            rect: refine::parse_rect(rect).unwrap_or_else(|e| exit_config(e)),
+            max_iter: refine_iter.parse::<u32>().unwrap_or_else(|_| exit_config(format!("Invalid --refine_iter: '{}'", refine_iter))),
+            base: base.to_string()
+        }),
+        _ => exit_config("--refine, --refine_iter and --base must be given together")
+    };
+    let resume = matches.value_of("PARTIAL_RAW_FILE").map(|file_name| file_name.to_string());
+    let refine_from = matches.value_of("PREVIOUS_DUMP").map(|file_name| file_name.to_string());
+    let select_methods = match (matches.value_of("METHODS"), matches.value_of("METHOD")) {
+        (Some(methods), None) => methods.split(',').map(|m| m.trim().to_string()).collect(),
+        (None, Some(method)) => vec![method.trim().to_string()],
+        (None, None) => Vec::new(),
+        _ => exit_config("Only one of --select_methods and --method can be given")
+    };
+
+    let mut mandel_config = MandelConfig{
+        re1: re1,
+        re2: re2,
+        img1: img1,
+        img2: img2,
+        max_iter: max_iter,
+        img_size: img_size,
+        write_metadata: metadata,
+        no_ppm: no_ppm,
+        output_format: output_format,
+        num_threads: num_threads,
+        thread_model: thread_model,
+        layout: layout,
+        normalize_access: normalize_access,
+        num_of_runs: num_of_runs,
+        adaptive_runs: adaptive_runs,
+        max_runs: max_runs,
+        ci: ci,
+        select_methods: select_methods,
+        flip_y: flip_y,
+        inclusive_edges: inclusive_edges,
+        tile: tile,
+        use_symmetry: use_symmetry,
+        verify_output: verify_output,
+        raw_output: raw_output,
+        workload: workload,
+        synthetic_distribution: synthetic_distribution,
+        synthetic_seed: synthetic_seed,
+        phoenix_p: phoenix_p,
+        phoenix_q: phoenix_q,
+        pin_threads: pin_threads,
+        cpu_affinity: cpu_affinity,
+        compare_parallel_outputs: compare_parallel_outputs,
+        trace_schedule: trace_schedule,
+        replay_schedule: replay_schedule,
+        numa_init: numa_init,
+        interior_coloring: interior_coloring,
+        color_cycle: color_cycle,
+        coloring_method: coloring_method,
+        stripe_density: stripe_density,
+        color_offset: color_offset,
+        color_scale: color_scale,
+        auto_levels: auto_levels,
+        levels: levels,
+        batch_file: batch_file,
+        config_file: config_file,
+        watch: watch,
+        watch_debounce_ms: watch_debounce_ms,
+        worker: worker,
+        coordinator: coordinator,
+        incremental: incremental,
+        run_id: run_id,
+        z0_re: z0_re,
+        z0_im: z0_im,
+        max_iter_auto: max_iter_auto,
+        chunk_rows: chunk_rows,
+        rayon_min_len: rayon_min_len,
+        rayon_max_len: rayon_max_len,
+        auto_tune: auto_tune,
+        retune: retune,
+        stats: stats,
+        cost_map: cost_map,
+        power_spectrum: power_spectrum,
+        time_budget: time_budget,
+        refine: refine,
+        resume: resume,
+        refine_from: refine_from,
+        previous_max_iter: None,
+        interior_results: None,
+        bench_db: bench_db,
+        skip_slow: skip_slow,
+        method_timeout_ms: method_timeout_ms,
+        random_view: random_view,
+        extract_contour: extract_contour,
+        fractal_dimension: fractal_dimension,
+        contours: contours,
+        webp_quality: webp_quality,
+        mesh_step: mesh_step,
+        mesh_height: mesh_height,
+        mesh_log: mesh_log,
+        compress: compress,
+        compress_level: compress_level,
+        detect_denormals: detect_denormals,
+        print_memory: print_memory,
+        color: color,
+        tile_cache: tile_cache,
+        clear_cache: clear_cache,
+        palette_preview: palette_preview,
+        benchmark_matrix: benchmark_matrix,
+        num_threads_sweep: num_threads_sweep,
+        size_list: size_list,
+        iter_list: iter_list,
+        yes: yes
+    };
+
+    if let Some(pixel_size) = pixel_size {
+        mandel_config = mandel_config.with_pixel_size(pixel_size);
+    }
+
+    if let Some(seed) = mandel_config.random_view {
+        mandel_config = random_view::random_view(&mandel_config, seed).unwrap_or_else(||
+            exit_config(format!("no region with pixels inside and outside of the set found for --random_view={}", seed)));
+        println!("random_view {}: --re1={} --re2={} --img1={} --img2={}", seed,
+            mandel_config.re1, mandel_config.re2, mandel_config.img1, mandel_config.img2);
+    }
+
+    if mandel_config.max_iter_auto {
+        mandel_config.max_iter = auto_max_iter(mandel_config.zoom());
+        println!("max_iter_auto: zoom: {:.3e}, max_iter: {}", mandel_config.zoom(), mandel_config.max_iter);
+    }
+
+    // After max_iter_auto, so that all tiles have the max_iter of the whole image
+    if let Some(tile) = tile {
+        mandel_config = tile.apply_to(&mandel_config);
+        println!("Tile {} of {} x {} tiles: --re1={} --re2={} --img1={} --img2={}", tile, tile.of_n, tile.of_n,
+            mandel_config.re1, mandel_config.re2, mandel_config.img1, mandel_config.img2);
+    }
+
+    println!("Configuration: re1: {:.2}, re2: {:.2}, img1: {:.2}, img2: {:.2}, max_iter: {}, img_size: {}, num_threads: {}",
+        mandel_config.re1, mandel_config.re2, mandel_config.img1, mandel_config.img2, mandel_config.max_iter, img_size, num_threads);
+
+    if let Err(e) = mandel_config.validate() {
+        exit_config(e);
+    }
+
+    if let Some(name) = matches.value_of("SAVE_BOOKMARK") {
+        let user_file = bookmark::user_bookmarks_file().unwrap_or_else(|| exit_config("no home directory for the bookmarks file"));
+        let saved = bookmark::Bookmark::from_config(name, &mandel_config);
+
+        if let Err(e) = bookmark::save_bookmark(&user_file, &saved, matches.is_present("force")) {
+            println!("Error: {}", e);
+            std::process::exit(e.exit_code());
+        }
+        println!("Bookmark '{}' saved to '{}': center: {}, {}, zoom: {:.3e}, max_iter: {}", name, user_file.display(),
+            saved.center.0, saved.center.1, saved.zoom, saved.max_iter);
+        std::process::exit(0);
+    }
+
+    if !matches.is_present("skip_memory_check") {
+        if let Some(available) = available_memory() {
+            // The matrix allocates one image at a time, the largest one counts
+            let largest_size = mandel_config.size_list.iter().cloned().max().unwrap_or(mandel_config.img_size);
+            let needed = image_memory(&MandelConfig{img_size: largest_size, .. mandel_config.clone()});
+
+            if needed > available {
+                exit_config(format!("the image needs {} MiB, but only {} MiB of memory are available, reduce --img_size or use --skip_memory_check",
+                    needed / (1024 * 1024), available / (1024 * 1024)));
+            }
+        }
+    }
+
+    // Only the mandelbrot set can be uninteresting, the synthetic workload doesn't depend on the region
+    if matches.is_present("auto_check") && mandel_config.workload == Workload::Fractal {
+        let sample = region::sample_region(&mandel_config, AUTO_CHECK_SAMPLES);
+        println!("Region check: interior: {:.1} %, exterior: {:.1} %, mean iterations of the exterior: {:.1}",
+            sample.interior_fraction * 100.0, sample.exterior_fraction * 100.0, sample.mean_exterior_iter);
+
+        if sample.interior_fraction > 0.99 {
+            println!("Warning: the region is almost completely inside of the set, the image will probably be black");
+        }
+    }
+
+    if mandel_config.detect_denormals {
+        println!("Denormal floats ({0} x {0} sampled pixels):", denormal::DENORMAL_SAMPLES);
+        for band in denormal::sample_denormals(&mandel_config, denormal::DENORMAL_BANDS) {
+            println!("  {}", band);
+        }
+        if !denormal::FLUSH_SUPPORTED {
+            println!("Warning: denormals can't be flushed to zero on this architecture, only reported");
+        }
+    }
+
+    if let Some(zoom_matches) = matches.subcommand_matches("zoom") {
+        let center = zoom_matches.value_of("CENTER").unwrap();
+        let target_center = region::parse_center(center).unwrap_or_else(|e| exit_config(e));
+        let zoom_factor = value_t!(zoom_matches.value_of("ZOOM_FACTOR"), f64).unwrap_or(1000.0);
+        let frames = value_t!(zoom_matches.value_of("FRAMES"), u32).unwrap_or(100);
+
+        if !(zoom_factor > 0.0) {
+            exit_config(format!("the zoom factor must be greater than 0, got {}", zoom_factor));
+        }
+
+        if let Err(e) = region::run_zoom(&mandel_config, target_center, zoom_factor, frames) {
+            println!("Error while writing zoom frames: {}", e);
+            std::process::exit(e.exit_code());
+        }
+        std::process::exit(0);
+    }
+
+    if let Some(probe_matches) = matches.subcommand_matches("probe") {
+        let orbit_len = value_t!(probe_matches.value_of("ORBIT_LEN"), usize).unwrap_or(0);
+
+        let result = match (probe_matches.value_of("POINT"), probe_matches.value_of("X"), probe_matches.value_of("Y")) {
+            (Some(point), None, None) => {
+                let (re, im) = region::parse_center(point).unwrap_or_else(|e| exit_config(e));
+                probe::probe_point(&mandel_config, Complex64{re: re, im: im}, orbit_len)
+            },
+            (None, Some(_), Some(_)) => {
+                let x = value_t!(probe_matches.value_of("X"), u32).unwrap_or_else(|e| exit_config(format!("Invalid --x: {}", e)));
+                let y = value_t!(probe_matches.value_of("Y"), u32).unwrap_or_else(|e| exit_config(format!("Invalid --y: {}", e)));
+                if x >= mandel_config.img_size || y >= mandel_config.img_size {
+                    exit_config(format!("pixel ({}, {}) is outside of the image ({} x {})", x, y, mandel_config.img_size, mandel_config.img_size));
+                }
+                probe::probe_pixel(&mandel_config, x, y, orbit_len)
+            },
+            _ => exit_config("probe needs either --x and --y or --probe_point")
+        };
+
+        print!("{}", result);
+        std::process::exit(0);
+    }
+
+    if let Some(orbit_matches) = matches.subcommand_matches("orbit") {
+        let (re, im) = region::parse_center(orbit_matches.value_of("POINT").unwrap()).unwrap_or_else(|e| exit_config(e));
+        let orbit_file = orbit_matches.value_of("ORBIT_FILE").unwrap_or("orbit.csv");
+
+        if let Err(e) = probe::write_orbit_csv(orbit_file, mandel_config.max_iter, mandel_config.z0(), Complex64{re: re, im: im}) {
+            println!("Error while writing orbit file '{}': {}", orbit_file, e);
+            std::process::exit(error::EXIT_IO);
+        }
+        println!("Orbit written to {}", orbit_file);
+        std::process::exit(0);
+    }
+
+    if let Some(density_matches) = matches.subcommand_matches("density") {
+        let mode = density_matches.value_of("MODE").unwrap_or("buddhabrot")
+            .parse::<density::DensityMode>().unwrap_or_else(|e| exit_config(e));
+        let nebula_iters = density::parse_channel_iters(density_matches.value_of("ITERS").unwrap_or("50,500,5000"))
+            .unwrap_or_else(|e| exit_config(e));
+        let samples = value_t!(density_matches.value_of("SAMPLES"), u64)
+            .unwrap_or(16 * (mandel_config.img_size as u64) * (mandel_config.img_size as u64));
+        let output = density_matches.value_of("OUTPUT").unwrap_or("density.ppm");
+
+        if let Err(e) = density::run_density(&mandel_config, mode, &nebula_iters, samples, output) {
+            println!("Error: {}", e);
+            std::process::exit(match e {
+                density::DensityError::Io(_) => error::EXIT_IO,
+                _ => error::EXIT_CONFIG
+            });
+        }
+        std::process::exit(0);
+    }
+
+    mandel_config
+}
+
+// The inner iteration loop of the mandelbrot calculation
+// See https://en.wikipedia.org/wiki/Mandelbrot_set
+//
+// Returns the number of iterations until |z| > 2, at most max_iter.
+// Points with |z| == 2 count as not escaped yet.
+pub fn mandel_iter(max_iter: u32, c: Complex64) -> u32 {
+    mandel_iter_z0(max_iter, Complex64{re: 0.0, im: 0.0}, c)
+}
+
+// Like mandel_iter, but the iteration starts from z0 instead of 0 (generalized mandelbrot set).
+//
+// Convention: the first step z1 = z0 * z0 + c is done before the loop and is not counted,
+// so for z0 = 0 the loop starts from z1 = c exactly like the classic version and the
+// iteration counts (and all images) stay the same.
+#[inline]
+pub fn mandel_iter_z0(max_iter: u32, z0: Complex64, c: Complex64) -> u32 {
+    let mut z: Complex64 = (z0 * z0) + c;
+
+    let mut iter = 0;
+
+    while (z.norm_sqr() <= 4.0) && (iter < max_iter) {
+        z = c + (z * z);
+        iter = iter + 1;
+    }
+
+    iter
+}
+
+// Like mandel_iter, but also returns the stripe average of the orbit (--coloring=stripe):
+// the mean of 0.5 + 0.5 * sin(stripe_density * arg(z_n)) over all z_n of the orbit, between 0.0 and 1.0.
+// The average depends on the angles of the orbit, not on the iteration count.
+pub fn mandel_iter_stripe(max_iter: u32, c: Complex64, stripe_density: f64) -> (u32, f64) {
+    mandel_iter_stripe_z0(max_iter, Complex64{re: 0.0, im: 0.0}, c, stripe_density)
+}
+
+// Like mandel_iter_stripe, but the iteration starts from z0 (see mandel_iter_z0 for the convention).
+// The iteration count is the same as the one of mandel_iter_z0, the average includes z1 and the first escaped z.
+pub fn mandel_iter_stripe_z0(max_iter: u32, z0: Complex64, c: Complex64, stripe_density: f64) -> (u32, f64) {
+    let stripe = |z: Complex64| 0.5 + 0.5 * (stripe_density * z.arg()).sin();
+
+    let mut z: Complex64 = (z0 * z0) + c;
+    let mut sum = stripe(z);
+
+    let mut iter = 0;
+
+    while (z.norm_sqr() <= 4.0) && (iter < max_iter) {
+        z = c + (z * z);
+        sum = sum + stripe(z);
+        iter = iter + 1;
+    }
+
+    (iter, sum / ((iter + 1) as f64))
+}
+
+// The bulbs of period 3 and 4 (centers and radii) that are checked by is_likely_interior: the two period 3 bulbs
+// and the two period 4 bulbs on the main cardioid and the period 4 bulb on the main bulb.
+// The bulbs are not exactly circles, so the circles around their centers are a bit smaller than the bulbs and
+// don't contain points outside of the set: the multiplier of the cycle is below 1 on the whole circle.
+const PERIOD_BULBS: [(f64, f64, f64); 5] = [
+    (-0.12256116687665361, 0.74486176661974423, 0.09),
+    (-0.12256116687665361, -0.74486176661974423, 0.09),
+    (0.28227139076691, 0.53006061757852, 0.04),
+    (0.28227139076691, -0.53006061757852, 0.04),
+    (-1.3107026413368329, 0.0, 0.055)
+];
+
+// The main cardioid (period 1), see
+// https://en.wikipedia.org/wiki/Plotting_algorithms_for_the_Mandelbrot_set#Cardioid_/_bulb_checking
+pub fn is_inside_main_cardioid(c: Complex64) -> bool {
+    let q = ((c.re - 0.25) * (c.re - 0.25)) + (c.im * c.im);
+    q * (q + (c.re - 0.25)) <= 0.25 * c.im * c.im
+}
+
+// The main bulb (period 2): the circle of radius 1/4 around -1
+pub fn is_inside_main_bulb(c: Complex64) -> bool {
+    ((c.re + 1.0) * (c.re + 1.0)) + (c.im * c.im) <= 0.0625
+}
+
+// True if c is in the main cardioid, the main bulb or one of the bulbs of period 3 and 4 (PERIOD_BULBS).
+// These points never escape, so they don't have to be iterated. False doesn't mean that c is outside of the set.
+pub fn is_likely_interior(c: Complex64) -> bool {
+    is_inside_main_cardioid(c) || is_inside_main_bulb(c) ||
+        PERIOD_BULBS.iter().any(|&(re, im, radius)| ((c.re - re) * (c.re - re)) + ((c.im - im) * (c.im - im)) < radius * radius)
+}
+
+// The inner iteration loop of the phoenix fractal: z_{n+1} = z_n^2 + p + q * z_{n-1}
+// See https://en.wikipedia.org/wiki/Phoenix_fractal (c = p + qi)
+//
+// Like a julia set the iteration starts at z_0 = pixel with z_{-1} = 0, the parameters are the same for all pixels.
+// Returns the number of iterations until |z| > 2, at most max_iter.
+#[inline]
+pub fn phoenix_iter(max_iter: u32, pixel: Complex64, p: f64, q: f64) -> u32 {
+    let mut z = pixel;
+    let mut z_prev = Complex64{re: 0.0, im: 0.0};
+
+    let mut iter = 0;
+
+    while (z.norm_sqr() <= 4.0) && (iter < max_iter) {
+        let z_next = (z * z) + p + (z_prev * q);
+        z_prev = z;
+        z = z_next;
+        iter = iter + 1;
+    }
+
+    iter
+}
+
+// The points z1, z2, ... that mandel_iter visits for c: n + 1 points for the iteration count n.
+// If n < max_iter the last point is the first one with |z| > 2 (escaped).
+pub fn orbit(c: Complex64, max_iter: u32) -> Vec<Complex64> {
+    orbit_z0(max_iter, Complex64{re: 0.0, im: 0.0}, c)
+}
+
+// Like orbit, but the iteration starts from z0 (see mandel_iter_z0)
+pub fn orbit_z0(max_iter: u32, z0: Complex64, c: Complex64) -> Vec<Complex64> {
+    let mut z: Complex64 = (z0 * z0) + c;
+    let mut points = vec![z];
+
+    while (z.norm_sqr() <= 4.0) && ((points.len() as u64) <= (max_iter as u64)) {
+        z = c + (z * z);
+        points.push(z);
+    }
+
+    points
+}
+
+// Reference orbit for mandel_iter_perturbation: Z_0 = 0, Z_1 = c_ref, Z_(n+1) = Z_n * Z_n + c_ref.
+// Contains at most max_iter + 2 points (enough for max_iter iterations), the orbit stops before the first
+// point with |Z| > 2. Z_0 and Z_1 are always in it.
+//
+// The reference orbit is computed with f64 like everything else. For a reference point that is exactly
+// representable this is the usual approximation, only the deltas need the extra precision.
+pub fn compute_ref_orbit(max_iter: u32, c_ref: Complex64) -> Vec<Complex64> {
+    let mut orbit = vec![Complex64{re: 0.0, im: 0.0}, c_ref];
+    let mut z = c_ref;
+
+    while orbit.len() < (max_iter as usize) + 2 {
+        z = c_ref + (z * z);
+        if z.norm_sqr() > 4.0 {
+            break;
+        }
+        orbit.push(z);
+    }
+
+    orbit
+}
+
+// Number of iterations of the point c_ref + c_delta (like mandel_iter), but only the small difference
+// delta_n = z_n - Z_n to the reference orbit Z_n of c_ref is iterated:
+//
+//     delta_(n+1) = 2 * Z_n * delta_n + delta_n^2 + c_delta
+//
+// c_delta can be far below the precision of c_ref (deep zoom, beyond ~1e14), the deltas keep it.
+// When z_n gets smaller than delta_n or the reference orbit ends, the iteration continues from the start
+// of the reference orbit with delta = z_n (rebasing), so any reference point gives the right result.
+// With c_delta = 0 the result is exactly the one of mandel_iter(max_iter, c_ref).
+pub fn mandel_iter_perturbation(max_iter: u32, c_delta: Complex64, ref_orbit: &[Complex64]) -> u32 {
+    // z = ref_orbit[n] + delta, starting from z_0 = 0
+    let mut n = 0;
+    let mut delta = Complex64{re: 0.0, im: 0.0};
+
+    let step = |n: &mut usize, delta: &mut Complex64| -> Complex64 {
+        *delta = (ref_orbit[*n] * *delta * 2.0) + (*delta * *delta) + c_delta;
+        *n += 1;
+        let z = ref_orbit[*n] + *delta;
+
+        if ((*n + 1) >= ref_orbit.len()) || (z.norm_sqr() < delta.norm_sqr()) {
+            *delta = z;
+            *n = 0;
+        }
+
+        z
+    };
+
+    // The first step z_1 = c is not counted, like in mandel_iter_z0
+    let mut z = step(&mut n, &mut delta);
+    let mut iter = 0;
+
+    while (z.norm_sqr() <= 4.0) && (iter < max_iter) {
+        z = step(&mut n, &mut delta);
+        iter = iter + 1;
+    }
+
+    iter
+}
+
+// Color of a point outside of the set with the given number of iterations
+#[inline]
+pub fn escape_color(img_value: u32) -> (u8, u8, u8) {
+    (255, ((img_value % 16) * 16) as u8, 0)
+}
+
+// Additional key value pairs that are written as comments into the PPM image ("# key: value")
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImageMetadata {
+    pub pairs: Vec<(String, String)>
+}
+
+impl ImageMetadata {
+    pub fn new() -> ImageMetadata {
+        ImageMetadata::default()
+    }
+
+    // Builder style: ImageMetadata::new().add("method", "serial").add("num_threads", "4")
+    pub fn add(mut self, key: &str, value: &str) -> ImageMetadata {
+        self.pairs.push((key.to_string(), value.to_string()));
+        self
+    }
+}
+
+// Mix the bits of the given value, see http://xorshift.di.unimi.it/splitmix64.c
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+// Synthetic workload: does a pseudo random number of dummy iterations.
+// The number only depends on the seed and the pixel index, so every method does exactly
+// the same total work. Returns the number of iterations done (at most max_iter).
+pub fn synthetic_iter(max_iter: u32, distribution: SyntheticDistribution, seed: u64, pixel_index: u64) -> u32 {
+    // Uniform random number in [0, 1)
+    let random = ((splitmix64(seed ^ splitmix64(pixel_index)) >> 11) as f64) / ((1u64 << 53) as f64);
+
+    let num_of_iter = match distribution {
+        SyntheticDistribution::Uniform => (random * ((max_iter as f64) + 1.0)) as u32,
+        // Log uniform in [1, max_iter + 1), this is approximately zipf with exponent one
+        SyntheticDistribution::Zipf => ((((max_iter as f64) + 1.0).powf(random)) as u32) - 1
+    };
+
+    // The same work as in mandel_iter, but for a point that never escapes
+    let c = Complex64{re: -1.0, im: 0.0};
+    let mut z = c;
+    let mut iter = 0;
+
+    while iter < num_of_iter {
+        z = c + (z * z);
+        iter = iter + 1;
+    }
+
+    // Use z, so the loop can't be optimized away
+    if z.norm_sqr() > 4.0 { max_iter } else { iter }
+}
+
+// The point in the complex plane of the given pixel
+#[inline]
+pub fn pixel_to_complex(mandel_config: &MandelConfig, x: u32, y: u32) -> Complex64 {
+    Complex64{re: mandel_config.re1 + ((x as f64) * mandel_config.x_step()),
+              im: mandel_config.row_to_im(y)}
+}
+
+// With use_symmetry: copy the computed rows to the rows of the other half (see MandelConfig::mirror_source_row).
+// The methods skip these rows, so this must be called after each of them. The image is in the layout of the methods.
+pub fn mirror_rows(mandel_config: &MandelConfig, image: &mut [u32]) {
+    let run_len = mandel_config.run_len();
+
+    for y in 0..mandel_config.img_size {
+        if let Some(source_row) = mandel_config.mirror_source_row(y) {
+            for x in (0..mandel_config.img_size).step_by(run_len) {
+                let start = mandel_config.buffer_index(x, source_row);
+                image.copy_within(start..(start + run_len), mandel_config.buffer_index(x, y));
+            }
+        }
+    }
+}
+
+// Reorder an image buffer in the layout of the methods (--layout) row by row, nothing to do for rows
+pub fn to_rows(mandel_config: &MandelConfig, image: &mut [u32]) {
+    if mandel_config.layout == BufferLayout::Rows {
+        return;
+    }
+
+    let buffer = image.to_vec();
+    let run_len = mandel_config.run_len();
+
+    for (n, run) in buffer.chunks(run_len).enumerate() {
+        let (x, y) = mandel_config.buffer_pixel(n * run_len);
+        let start = mandel_config.pixel_index(x, y);
+        image[start..(start + run_len)].copy_from_slice(run);
+    }
+}
+
+// Compute the value of one pixel, depending on the workload.
+// The FPU mode of --detect_denormals is set by the jobs of the methods, see denormal::FlushDenormals
+#[inline]
+pub fn render_pixel(mandel_config: &MandelConfig, x: u32, y: u32) -> u32 {
+    match mandel_config.workload {
+        Workload::Fractal => match mandel_config.interior_results {
+            Some(ref interior_results) => {
+                match mandel_iter_full(mandel_config.max_iter, mandel_config.z0(), pixel_to_complex(mandel_config, x, y)) {
+                    PixelResult::Escaped{iter} => iter,
+                    result => {
+                        interior_results.store(mandel_config.pixel_index(x, y), result);
+                        mandel_config.max_iter
+                    }
+                }
+            },
+            None => mandel_iter_z0(mandel_config.max_iter, mandel_config.z0(), pixel_to_complex(mandel_config, x, y))
+        },
+        Workload::Synthetic => synthetic_iter(mandel_config.max_iter, mandel_config.synthetic_distribution,
+            mandel_config.synthetic_seed, ((y as u64) * (mandel_config.img_size as u64)) + (x as u64)),
+        Workload::Phoenix => phoenix_iter(mandel_config.max_iter, pixel_to_complex(mandel_config, x, y),
+            mandel_config.phoenix_p, mandel_config.phoenix_q)
+    }
+}
+
+// Compute the pixels x = 0 .. row.len() of row y. The methods use this for whole rows,
+// so the coordinate math is only in pixel_to_complex.
+#[inline]
+pub fn render_row(mandel_config: &MandelConfig, y: u32, row: &mut [u32]) {
+    render_row_from(mandel_config, 0, y, row);
+}
+
+// Compute the line-th img_size values of the image buffer of the methods: row line for --layout=rows,
+// one row of each of img_size / 64 tiles for tiles64. Rows that are mirrored with use_symmetry are skipped.
+#[inline]
+pub fn render_line(mandel_config: &MandelConfig, line: u32, values: &mut [u32]) {
+    let start = (line as usize) * (mandel_config.img_size as usize);
+    let run_len = mandel_config.run_len();
+
+    for (n, run) in values.chunks_mut(run_len).enumerate() {
+        let (x0, y) = mandel_config.buffer_pixel(start + (n * run_len));
+        if mandel_config.mirror_source_row(y).is_none() {
+            render_row_from(mandel_config, x0, y, run);
+        }
+    }
+}
+
+// Compute the pixels x = x0 .. x0 + row.len() of row y (a part of a row, see mandel_method::rect)
+#[inline]
+pub fn render_row_from(mandel_config: &MandelConfig, x0: u32, y: u32, row: &mut [u32]) {
+    for (x, value) in row.iter_mut().enumerate() {
+        update_pixel(mandel_config, x0 + (x as u32), y, value);
+    }
+}
+
+// Compute pixel (x, y) into value, unless value is already exact with previous_max_iter (--refine_from):
+// a pixel that escaped after n < previous_max_iter iterations escapes after n iterations with every higher max_iter.
+// The pixels that reached previous_max_iter are iterated again from the start.
+#[inline]
+pub fn update_pixel(mandel_config: &MandelConfig, x: u32, y: u32, value: &mut u32) {
+    if mandel_config.previous_max_iter.map_or(true, |previous_max_iter| *value >= previous_max_iter) {
+        *value = render_pixel(mandel_config, x, y);
+    }
+}
+
+// Color of one pixel: coloring_method outside of the set, interior_coloring inside
+#[cfg(not(target_arch = "wasm32"))]
+#[inline]
+pub fn pixel_color(mandel_config: &MandelConfig, coloring: &ImageColoring, x: u32, y: u32, img_value: u32) -> (u8, u8, u8) {
+    if img_value != mandel_config.max_iter {
+        coloring.color(mandel_config, x, y, img_value)
+    } else if mandel_config.interior_coloring == InteriorColoring::Black || mandel_config.workload != Workload::Fractal {
+        (0, 0, 0)
+    } else {
+        // The mirrored rows are copied after the render, their results are stored in the source row
+        let stored = mandel_config.interior_results.as_ref().and_then(|interior_results|
+            interior_results.get(mandel_config.pixel_index(x, mandel_config.mirror_source_row(y).unwrap_or(y))));
+
+        // Pixels that weren't rendered with the results (tile cache, incremental, resumed rows, ...) are iterated again
+        let result = stored.unwrap_or_else(||
+            mandel_iter_full(mandel_config.max_iter, mandel_config.z0(), pixel_to_complex(mandel_config, x, y)));
+        interior_color(mandel_config.interior_coloring, result, mandel_config.x_step())
+    }
+}
+
+// Map the iteration counts to RGB values, three bytes per pixel row by row. The rows are mapped in parallel.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn map_colors(mandel_config: &MandelConfig, image: &[u32]) -> Vec<u8> {
+    let coloring = ImageColoring::new(mandel_config, image);
+    let mut rgb = vec![0; 3 * mandel_config.num_of_pixels()];
+
+    rgb.par_chunks_mut(3 * (mandel_config.img_size as usize)).enumerate().for_each(|(y, row)| {
+        let y = y as u32;
+
+        for (x, pixel) in row.chunks_mut(3).enumerate() {
+            let x = x as u32;
+            let (red, green, blue) = pixel_color(mandel_config, &coloring, x, y, image[mandel_config.pixel_index(x, y)]);
+            pixel.copy_from_slice(&[red, green, blue]);
+        }
+    });
+
+    rgb
+}
+
+// The colors of --cost_map (see coloring::cost_color), three bytes per pixel row by row
+#[cfg(not(target_arch = "wasm32"))]
+pub fn map_cost_colors(mandel_config: &MandelConfig, image: &[u32]) -> Vec<u8> {
+    let mut rgb = vec![0; 3 * image.len()];
+
+    rgb.par_chunks_mut(3).zip(image.par_iter()).for_each(|(pixel, &iter)| {
+        let (red, green, blue) = cost_color(iter, mandel_config.max_iter);
+        pixel.copy_from_slice(&[red, green, blue]);
+    });
+
+    rgb
+}
+
+// Number of rows of an ASCII image that are formatted in parallel before they are written
+#[cfg(not(target_arch = "wasm32"))]
+const ASCII_BLOCK_ROWS: usize = 64;
+
+// Time of the two phases of write_image
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ImageWriteTimes {
+    // Iteration counts to colors (map_colors)
+    pub map_ms: f64,
+    // Formatting and writing the file (formatting only with --compress)
+    pub write_ms: f64,
+    // Compressing and writing the file, 0 without --compress
+    pub compress_ms: f64
+}
+
+// Maximum number of iterations of the pre-pass of complexity_map
+pub const COMPLEXITY_MAX_ITER: u32 = 256;
+
+// Estimated work per tile of coarse_factor x coarse_factor pixels, for adaptive scheduling.
+// Renders one pixel per tile (its center) with at most COMPLEXITY_MAX_ITER iterations, so the result is
+// an image of ceil(img_size / coarse_factor) x ceil(img_size / coarse_factor) iteration counts, row by row.
+// The last tile of a row or column is smaller if coarse_factor doesn't divide img_size.
+pub fn complexity_map(mandel_config: &MandelConfig, coarse_factor: u32) -> Vec<u32> {
+    let coarse_factor = coarse_factor.max(1);
+    let num_of_tiles = (mandel_config.img_size + coarse_factor - 1) / coarse_factor;
+    let config = MandelConfig{max_iter: mandel_config.max_iter.min(COMPLEXITY_MAX_ITER), .. mandel_config.clone()};
+    let center = |tile: u32| ((tile * coarse_factor) + (coarse_factor / 2)).min(mandel_config.img_size - 1);
+
+    let mut estimates = Vec::with_capacity((num_of_tiles as usize) * (num_of_tiles as usize));
+
+    for tile_y in 0..num_of_tiles {
+        for tile_x in 0..num_of_tiles {
+            estimates.push(render_pixel(&config, center(tile_x), center(tile_y)));
+        }
+    }
+
+    estimates
+}
+
+// The name of the file that write_image writes: with --compress .gz is appended (unless it is there already)
+#[cfg(not(target_arch = "wasm32"))]
+pub fn image_file_name(file_name: &str, mandel_config: &MandelConfig) -> String {
+    if mandel_config.compress && !file_name.ends_with(".gz") {
+        format!("{}.gz", file_name)
+    } else {
+        file_name.to_string()
+    }
+}
+
+// Write calculated mandelbrot set as PPM image, ASCII (P3) or binary (P6) with --output_format=ppm_binary.
+// Add run time information and the metadata as comments.
+//
+// With --compress the image is written to image_file_name(file_name) as gzip. The PPM is formatted in memory first,
+// so that the compression is measured separately (compress_ms). The compressed file is written under a temporary name
+// and renamed when it is complete, an error never leaves a truncated .gz file that looks valid.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_image(file_name: &str, mandel_config: &MandelConfig, time_in_ms: f64, metadata: &ImageMetadata,
+    image: &[u32]) -> std::result::Result<ImageWriteTimes, MandelError> {
+    write_mapped_image(file_name, mandel_config, time_in_ms, metadata, || map_colors(mandel_config, image))
+}
+
+// Write the cost of each pixel of an image instead of its colors (--cost_map): gray by the number of iterations,
+// see coloring::cost_color. The file is written like write_image.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_cost_map(file_name: &str, mandel_config: &MandelConfig, time_in_ms: f64, metadata: &ImageMetadata,
+    image: &[u32]) -> std::result::Result<ImageWriteTimes, MandelError> {
+    write_mapped_image(file_name, mandel_config, time_in_ms, metadata, || map_cost_colors(mandel_config, image))
+}
+
+// write_image with the colors of map_rgb
+#[cfg(not(target_arch = "wasm32"))]
+fn write_mapped_image<F: FnOnce() -> Vec<u8>>(file_name: &str, mandel_config: &MandelConfig, time_in_ms: f64,
+    metadata: &ImageMetadata, map_rgb: F) -> std::result::Result<ImageWriteTimes, MandelError> {
+    let start_time = precise_time_ns();
+    let rgb = map_rgb();
+    let map_time = precise_time_ns();
+
+    let file_name = image_file_name(file_name, mandel_config);
+    let io_error = |e| MandelError::io(&file_name, e);
+
+    if mandel_config.compress {
+        let mut ppm = Vec::new();
+        try!(write_ppm(&mut ppm, mandel_config, time_in_ms, metadata, &rgb).map_err(&io_error));
+        let format_time = precise_time_ns();
+
+        try!(write_gzip(&file_name, &ppm, mandel_config.compress_level).map_err(&io_error));
+        let end_time = precise_time_ns();
+
+        if mandel_config.verify_output {
+            try!(verify_image(&file_name, mandel_config));
+        }
+
+        return Ok(ImageWriteTimes{
+            map_ms: ((map_time - start_time) as f64) / (1000.0 * 1000.0),
+            write_ms: ((format_time - map_time) as f64) / (1000.0 * 1000.0),
+            compress_ms: ((end_time - format_time) as f64) / (1000.0 * 1000.0)
+        });
+    }
+
+    let mut buffer = BufWriter::new(try!(File::create(&file_name).map_err(&io_error)));
+    try!(write_ppm(&mut buffer, mandel_config, time_in_ms, metadata, &rgb).map_err(&io_error));
+
+    // Flush explicitly, dropping the BufWriter would silently ignore errors
+    try!(buffer.flush().map_err(&io_error));
+
+    let end_time = precise_time_ns();
+
+    if mandel_config.verify_output {
+        try!(verify_image(&file_name, mandel_config));
+    }
+
+    Ok(ImageWriteTimes{
+        map_ms: ((map_time - start_time) as f64) / (1000.0 * 1000.0),
+        write_ms: ((end_time - map_time) as f64) / (1000.0 * 1000.0),
+        compress_ms: 0.0
+    })
+}
+
+// Write the header (with the comments) and the pixels of the PPM image of write_image
+#[cfg(not(target_arch = "wasm32"))]
+fn write_ppm<W: Write>(buffer: &mut W, mandel_config: &MandelConfig, time_in_ms: f64, metadata: &ImageMetadata, rgb: &[u8]) -> io::Result<()> {
+    let binary = mandel_config.output_format == OutputFormat::PpmBinary;
+
+    try!(buffer.write_all(if binary { b"P6\n" } else { b"P3\n" }));
+    try!(write!(buffer, "# mandelbrot, max_iter: {}\n", mandel_config.max_iter));
+    if let Some(ref run_id) = mandel_config.run_id {
+        try!(write!(buffer, "# run_id: {}\n", run_id));
+    }
+    if mandel_config.has_z0() {
+        try!(write!(buffer, "# z0: {} {}\n", mandel_config.z0_re, mandel_config.z0_im));
+    }
+    if mandel_config.detect_denormals && denormal::FLUSH_SUPPORTED {
+        try!(write!(buffer, "# flush_denormals: ftz daz\n"));
+    }
+    if mandel_config.write_metadata {
+        try!(write!(buffer, "# computation time: {} ms\n", time_in_ms));
+    }
+    for &(ref key, ref value) in &metadata.pairs {
+        // A line break would end the comment and break the image
+        try!(write!(buffer, "# {}: {}\n", key.replace(['\n', '\r'], " "), value.replace(['\n', '\r'], " ")));
+    }
+    try!(write!(buffer, "{0} {0}\n", mandel_config.img_size));
+    try!(buffer.write_all(b"255\n"));
+    write_ppm_pixels(buffer, rgb, mandel_config.img_size, binary)
+}
+
+// Compress data with gzip into file_name. It is written to <file_name>.tmp first and renamed after finish(),
+// which writes the end of the gzip stream. On an error the temporary file is removed.
+#[cfg(not(target_arch = "wasm32"))]
+fn write_gzip(file_name: &str, data: &[u8], level: u32) -> io::Result<()> {
+    let tmp_file_name = format!("{}.tmp", file_name);
+
+    let result = File::create(&tmp_file_name).and_then(|file| {
+        let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::new(level));
+        try!(encoder.write_all(data));
+        // finish() only flushes the encoder, the BufWriter must be flushed explicitly
+        try!(encoder.finish()).flush()
+    }).and_then(|_| fs::rename(&tmp_file_name, file_name));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_file_name);
+    }
+
+    result
+}
+
+// Write the pixels of a PPM image after the header: binary or as ASCII lines (one per image row).
+// The ASCII lines are formatted in parallel, ASCII_BLOCK_ROWS rows at a time.
+#[cfg(not(target_arch = "wasm32"))]
+fn write_ppm_pixels<W: Write>(buffer: &mut W, rgb: &[u8], width: u32, binary: bool) -> io::Result<()> {
+    if binary {
+        return buffer.write_all(rgb);
+    }
+
+    let row_len = 3 * (width as usize);
+
+    for block in rgb.chunks(ASCII_BLOCK_ROWS * row_len) {
+        let lines: Vec<Vec<u8>> = block.par_chunks(row_len).map(|row| {
+            let mut line = Vec::with_capacity(4 * row.len() + 1);
+            for pixel in row.chunks(3) {
+                // Writing into a Vec can't fail
+                write!(line, "{} {} {} ", pixel[0], pixel[1], pixel[2]).unwrap();
+            }
+            line.push(b'\n');
+            line
+        }).collect();
+
+        for line in &lines {
+            try!(buffer.write_all(line));
+        }
+    }
+
+    Ok(())
+}
+
+// Height of the strip of --palette_preview
+pub const PALETTE_PREVIEW_HEIGHT: u32 = 64;
+
+// Write the palette of the configuration (max_iter, color_cycle, color_offset, color_scale) as PPM image of
+// img_size x PALETTE_PREVIEW_HEIGHT pixels, see Palette::preview_colors. Binary with --output_format=ppm_binary.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_palette_preview(file_name: &str, mandel_config: &MandelConfig) -> io::Result<()> {
+    let row: Vec<u8> = Palette::from_config(mandel_config).preview_colors(mandel_config.img_size).iter()
+        .flat_map(|&(red, green, blue)| vec![red, green, blue]).collect();
+    let rgb = row.repeat(PALETTE_PREVIEW_HEIGHT as usize);
+
+    let binary = mandel_config.output_format == OutputFormat::PpmBinary;
+    let mut buffer = BufWriter::new(try!(File::create(file_name)));
+
+    try!(buffer.write(if binary { b"P6\n" } else { b"P3\n" }));
+    try!(write!(buffer, "# mandelbrot palette preview, max_iter: {}, color_cycle: {}, color_offset: {}, color_scale: {}\n",
+        mandel_config.max_iter, mandel_config.color_cycle.map_or("default".to_string(), |cycle| cycle.to_string()),
+        mandel_config.color_offset, mandel_config.color_scale));
+    try!(write!(buffer, "{} {}\n255\n", mandel_config.img_size, PALETTE_PREVIEW_HEIGHT));
+    try!(write_ppm_pixels(&mut buffer, &rgb, mandel_config.img_size, binary));
+
+    buffer.flush()
+}
+
+// Read back a written PPM image and check the magic bytes, the declared dimensions
+// and that the file contains a color value for every pixel.
+#[cfg(not(target_arch = "wasm32"))]
+fn verify_image(file_name: &str, mandel_config: &MandelConfig) -> std::result::Result<(), MandelError> {
+    if mandel_config.output_format == OutputFormat::PpmBinary {
+        return verify_binary_image(file_name, mandel_config);
+    }
+
+    let content = String::from_utf8_lossy(&try!(ppm::read_file(file_name).map_err(|e| MandelError::io(file_name, e)))).into_owned();
+
+    let corrupt = |message: String| Err(MandelError::CorruptOutput{path: file_name.to_string(), message: message});
+
+    let mut tokens = content.lines()
+        .filter(|line| !line.starts_with('#'))
+        .flat_map(|line| line.split_whitespace());
+
+    match tokens.next() {
+        Some("P3") => {},
+        Some(magic) => return corrupt(format!("wrong magic bytes: '{}'", magic)),
+        None => return corrupt("empty file".to_string())
+    }
+
+    let mut header = Vec::new();
+    for _ in 0..3 {
+        match tokens.next().and_then(|token| token.parse::<u32>().ok()) {
+            Some(value) => header.push(value),
+            None => return corrupt("incomplete header".to_string())
+        }
+    }
+
+    if header[0] != mandel_config.img_size || header[1] != mandel_config.img_size {
+        return corrupt(format!("dimensions are {} x {}, expected {2} x {2}", header[0], header[1], mandel_config.img_size));
+    }
+
+    let expected_values = 3 * (mandel_config.img_size as usize) * (mandel_config.img_size as usize);
+    let num_of_values = tokens.count();
+
+    if num_of_values != expected_values {
+        return corrupt(format!("found {} color values, expected {}", num_of_values, expected_values));
+    }
+
+    Ok(())
+}
+
+// Like verify_image for binary images, read_ppm checks that there are enough bytes
+#[cfg(not(target_arch = "wasm32"))]
+fn verify_binary_image(file_name: &str, mandel_config: &MandelConfig) -> std::result::Result<(), MandelError> {
+    let corrupt = |message: String| MandelError::CorruptOutput{path: file_name.to_string(), message: message};
+    let ppm_image = try!(ppm::read_ppm(file_name).map_err(|e| corrupt(e.to_string())));
+
+    if ppm_image.width != mandel_config.img_size || ppm_image.height != mandel_config.img_size {
+        return Err(corrupt(format!("dimensions are {} x {}, expected {2} x {2}",
+            ppm_image.width, ppm_image.height, mandel_config.img_size)));
+    }
+
+    Ok(())
+}
+
+// Header of the benchmark results, change it when the columns change
+#[cfg(not(target_arch = "wasm32"))]
+const BENCHMARK_HEADER: [&'static str; 12] = ["num_threads", "mean_ms", "min_ms", "max_ms", "stddev_ms", "workload", "pin_threads", "run_id", "rss_delta_mb", "layout", "access", "runs"];
+
+// Append the benchmark result of one method to the CSV file plot/<method>.csv
+// The header is written only if the file is new. rss_delta_mb is empty without --print_memory,
+// runs is the number of measured runs (it differs from num_of_runs with --adaptive_runs).
+// A file with a different header (older version) is renamed to plot/<method>.csv.old first.
+#[cfg(not(target_arch = "wasm32"))]
+fn write_benchmark_result(method: &str, num_threads: u32, time_in_ms: f64, min_time: f64, max_time: f64, std_dev: f64,
+    workload: &str, pin_threads: bool, run_id: &str, rss_delta_mb: Option<f64>, layout: BufferLayout, access: AccessOrder, runs: u32)
+    -> std::result::Result<(), MandelError> {
+    let file_name = format!("plot{}{}.csv", std::path::MAIN_SEPARATOR, method);
+
+    append_benchmark_result(&file_name, num_threads, time_in_ms, min_time, max_time, std_dev, workload, pin_threads, run_id, rss_delta_mb,
+        layout, access, runs)
+        .map_err(|e| MandelError::io(&file_name, e))
+}
+
+// How long append_benchmark_result waits for another process that appends to the same file
+#[cfg(not(target_arch = "wasm32"))]
+const BENCHMARK_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Run f while holding the lock file <file_name>.lock. Several processes (for example a sweep script that starts
+// runs with different thread counts at once) append to the same file, the lock keeps their lines apart.
+// The lock file is kept, deleting it would let two processes lock different files of the same name.
+// A lock that is held by another process is tried again with a growing pause.
+#[cfg(not(target_arch = "wasm32"))]
+fn with_file_lock<T, F: FnOnce() -> Result<T>>(file_name: &str, f: F) -> Result<T> {
+    let lock_file_name = format!("{}.lock", file_name);
+    let lock_file = try!(OpenOptions::new().write(true).create(true).truncate(false).open(&lock_file_name));
+    let start_time = Instant::now();
+    let mut pause = Duration::from_millis(1);
+
+    loop {
+        match lock_file.try_lock() {
+            Ok(()) => break,
+            Err(TryLockError::WouldBlock) if start_time.elapsed() < BENCHMARK_LOCK_TIMEOUT => {
+                thread::sleep(pause);
+                pause = (pause * 2).min(Duration::from_millis(100));
+            },
+            Err(TryLockError::WouldBlock) => return Err(io::Error::new(io::ErrorKind::TimedOut,
+                format!("'{}' is still locked by another process after {} s", lock_file_name, BENCHMARK_LOCK_TIMEOUT.as_secs()))),
+            Err(TryLockError::Error(e)) => return Err(e)
+        }
+    }
+
+    let result = f();
+    // Closing the file releases the lock, too
+    let _ = lock_file.unlock();
+    result
+}
+
+// The directory of the file is created if it doesn't exist (also if another process creates it at the same time).
+// The line (and the header for a new file) is written with a single write while holding the lock of the file.
+#[cfg(not(target_arch = "wasm32"))]
+fn append_benchmark_result(file_name: &str, num_threads: u32,
+     time_in_ms: f64, min_time: f64, max_time: f64, std_dev: f64, workload: &str, pin_threads: bool, run_id: &str,
+     rss_delta_mb: Option<f64>, layout: BufferLayout, access: AccessOrder, runs: u32) -> Result<()> {
+
+    // Check if the output folder ("plot") is available:
+    if let Some(dir) = Path::new(file_name).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        if !dir.exists() {
+            // If not, create it!
+            println!("Folder '{}' does not exist, creating it...", dir.display());
+            try!(fs::create_dir_all(dir));
+        }
+    }
+
+    with_file_lock(file_name, || {
+        if let Ok(mut old_file) = File::open(file_name) {
+            let mut content = String::new();
+            try!(old_file.read_to_string(&mut content));
+
+            if content.lines().next().map_or(false, |header| header != BENCHMARK_HEADER.join(",")) {
+                println!("'{}' has an old header, renaming it to '{}.old'", file_name, file_name);
+                try!(fs::rename(&file_name, format!("{}.old", file_name)));
+            }
+        }
+
+        let mut file = try!(
+            OpenOptions::new()
+                .write(true)
+                .append(true)
+                .create(true)
+                .open(file_name));
+
+        let is_new_file = try!(file.metadata()).len() == 0;
+
+        let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(Vec::new());
+
+        if is_new_file {
+            try!(writer.write_record(&BENCHMARK_HEADER));
+        }
+
+        try!(writer.write_record(&[num_threads.to_string(), time_in_ms.to_string(),
+            min_time.to_string(), max_time.to_string(), std_dev.to_string(), workload.to_string(), pin_threads.to_string(), run_id.to_string(),
+            rss_delta_mb.map_or(String::new(), |mb| format!("{:.3}", mb)), layout.to_string(), access.to_string(), runs.to_string()]));
+
+        let line = try!(writer.into_inner().map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string())));
+        file.write_all(&line)
+    })
+}
+
+// Part of the file names if the iteration doesn't start from 0, for example "_z0_0.5_-0.25"
+#[cfg(not(target_arch = "wasm32"))]
+fn z0_suffix(mandel_config: &MandelConfig) -> String {
+    if mandel_config.has_z0() {
+        format!("_z0_{}_{}", mandel_config.z0_re, mandel_config.z0_im)
+    } else {
+        String::new()
+    }
+}
+
+// Part of the file names of a tile (--tile), for example "_tile_0_1" for row 0, column 1
+#[cfg(not(target_arch = "wasm32"))]
+fn tile_suffix(mandel_config: &MandelConfig) -> String {
+    mandel_config.tile.map_or(String::new(), |tile| format!("_tile_{}_{}", tile.row, tile.col))
+}
+
+// A new run id: date and time of the start and a random suffix, so that two runs started
+// in the same second still get different ids. For example: 2016_10_01__12_00_00_3f2a
+#[cfg(not(target_arch = "wasm32"))]
+pub fn generate_run_id() -> String {
+    let suffix = splitmix64(precise_time_ns() ^ ((std::process::id() as u64) << 32)) & 0xffff;
+    format!("{}_{:04x}", now().strftime("%Y_%m_%d__%H_%M_%S").unwrap(), suffix)
+}
+
+// Times of the repetitive runs of one method in ms
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Copy, Clone, Debug)]
+pub struct RunTimes {
+    pub mean_ms: f64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    // Sample standard deviation, zero for a single run
+    pub stddev_ms: f64,
+    // The largest change of the resident memory from before to after a run in bytes, None if get_rss_bytes doesn't know it
+    pub rss_delta_bytes: Option<i64>,
+    // Number of measured runs, with --adaptive_runs between num_of_runs and max_runs
+    pub num_of_runs: u32,
+    // Relative half width of the 95% confidence interval of the mean that was reached, None for a single run
+    pub ci: Option<f64>
+}
+
+// The stopping rule of time_runs: true if no more run is needed after the runs with the given times (in ms),
+// elapsed_ms is the time since the first run started.
+// With --adaptive_runs the runs are repeated until the confidence interval of the mean is within ci or max_runs
+// is reached. With --method_timeout_ms no run is started that would probably end after the timeout
+// (the time so far plus the slowest run), so the extra runs never make a method time out.
+pub fn enough_runs(mandel_config: &MandelConfig, times: &[f64], elapsed_ms: f64) -> bool {
+    let (min_runs, max_runs) = if mandel_config.adaptive_runs {
+        (mandel_config.num_of_runs.max(2), mandel_config.max_runs.max(mandel_config.num_of_runs))
+    } else {
+        (mandel_config.num_of_runs, mandel_config.num_of_runs)
+    };
+    let num_of_runs = times.len() as u32;
+
+    if num_of_runs >= max_runs {
+        return true;
+    }
+
+    if num_of_runs < min_runs {
+        return false;
+    }
+
+    let ci = confidence::confidence_interval(times).map(|interval| interval.relative_width());
+    if ci.map_or(false, |ci| ci <= mandel_config.ci) {
+        return true;
+    }
+
+    let max_time = times.iter().cloned().fold(0.0, f64::max);
+    mandel_config.method_timeout_ms.map_or(false, |timeout_ms| elapsed_ms + max_time > timeout_ms as f64)
+}
+
+// Run the method num_of_runs times and measure each run, see enough_runs for --adaptive_runs.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn time_runs(mandel_func: &Fn(&MandelConfig, &mut [u32], &AtomicBool) -> (), mandel_config: &MandelConfig, image: &mut [u32],
+    cancel: &AtomicBool) -> RunTimes {
+    let mut repetitive_times = Vec::new();
+    let mut min_time = std::f64::MAX;
+    let mut max_time = 0.0;
+    let mut rss_delta_bytes: Option<i64> = None;
+
+    let first_start_time = precise_time_ns();
+
+    while !enough_runs(mandel_config, &repetitive_times, ((precise_time_ns() - first_start_time) as f64) / (1000.0 * 1000.0)) {
+        // The times of a cancelled method are not used
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+
+        // Read outside of the measured time
+        let rss_before = get_rss_bytes();
+        let start_time = precise_time_ns();
+
+        mandel_func(mandel_config, image, cancel);
+        mirror_rows(mandel_config, image);
+
+        let end_time = precise_time_ns();
+
+        if let (Some(before), Some(after)) = (rss_before, get_rss_bytes()) {
+            let delta = (after as i64) - (before as i64);
+            rss_delta_bytes = Some(rss_delta_bytes.map_or(delta, |largest| largest.max(delta)));
+        }
+        let total_time_in_ms = ((end_time - start_time) as f64) / (1000.0 * 1000.0);
+
+        if total_time_in_ms > max_time {
+            max_time = total_time_in_ms;
+        }
+
+        if total_time_in_ms < min_time {
+            min_time = total_time_in_ms;
+        }
+
+        repetitive_times.push(total_time_in_ms);
+    }
+
+    let num_of_runs = repetitive_times.len();
+
+    let mean_time = repetitive_times.iter().fold(0.0, |sum, t| sum + t) /
+        (num_of_runs.max(1) as f64);
+
+    let std_dev = if num_of_runs > 1 {
+        (repetitive_times.iter().fold(0.0, |sum, t| sum + ((t - mean_time) * (t - mean_time))) /
+            ((num_of_runs - 1) as f64)).sqrt()
+    } else {
+        0.0
+    };
+
+    RunTimes{
+        mean_ms: mean_time,
+        min_ms: min_time,
+        max_ms: max_time,
+        stddev_ms: std_dev,
+        rss_delta_bytes: rss_delta_bytes,
+        num_of_runs: num_of_runs as u32,
+        ci: confidence::confidence_interval(&repetitive_times).map(|interval| interval.relative_width())
+    }
+}
+
+// Like time_runs, but cancels the method after timeout and returns None (--method_timeout_ms).
+//
+// The method runs in its own thread with its own buffer and its own rayon thread pool with num_threads
+// threads. After the timeout the cancel flag is set, the methods check it after each row and stop.
+// The thread isn't waited for, so a method that hangs is abandoned: it still owns its buffer and its pool,
+// so it can neither write into the image nor block the methods that run after it.
+// The buffer is copied into the image only if the method has finished.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn time_runs_with_timeout(method: &str, mandel_func: fn(&MandelConfig, &mut [u32], &AtomicBool), mandel_config: &MandelConfig,
+    image: &mut [u32], timeout: Duration) -> Option<RunTimes> {
+    let (sender, receiver) = mpsc::channel();
+    let config = mandel_config.clone();
+    let mut buffer = vec![0; image.len()];
+    let cancel = Arc::new(AtomicBool::new(false));
+    let thread_cancel = cancel.clone();
+
+    let spawned = thread::Builder::new().name(format!("watchdog_{}", method)).spawn(move || {
+        let pool = rayon::ThreadPoolBuilder::from(&config).build()
+            .unwrap_or_else(|e| panic!("Rayon error: can't create thread pool with {} threads: {}", config.num_threads, e));
+        let times = pool.install(|| time_runs(&mandel_func, &config, &mut buffer, &thread_cancel));
+
+        // After a time out nobody is waiting anymore, the buffer is simply dropped
+        let _ = sender.send((times, buffer));
+    });
+
+    if let Err(e) = spawned {
+        panic!("Can't start thread for method '{}': {}", method, e);
+    }
+
+    match receiver.recv_timeout(timeout) {
+        Ok((times, buffer)) => {
+            image.copy_from_slice(&buffer);
+            Some(times)
+        }
+        Err(RecvTimeoutError::Timeout) => {
+            cancel.store(true, Ordering::Relaxed);
+            None
+        }
+        Err(RecvTimeoutError::Disconnected) => panic!("Method '{}' panicked", method)
+    }
+}
+
+// Prepares and runs one version of the mandelbrot set calculation.
+// The run id is used for the file names and the benchmark results.
+// With --method_timeout_ms a method that doesn't finish in time is cancelled and reported with timed_out set,
+// nothing is written for it. Returns an error if an output file can't be written or is corrupt.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn do_run(method: &str, mandel_func: fn(&MandelConfig, &mut [u32], &AtomicBool),
+    mandel_config: &MandelConfig, image: &mut [u32], run_id: &str) -> std::result::Result<BenchmarkResult, MandelError> {
+    // The period and distance of the interior pixels are stored in the measured runs, for --interior_coloring
+    let mandel_config = &mandel_config.with_interior_results();
+
+    let times = match mandel_config.method_timeout_ms {
+        Some(timeout_ms) => match time_runs_with_timeout(method, mandel_func, mandel_config, image, Duration::from_millis(timeout_ms)) {
+            Some(times) => times,
+            None => {
+                println!("{}", paint(&format!("Warning: method {} TIMED OUT after {} ms, cancelled it", method, timeout_ms),
+                    Style::Bad, use_color(mandel_config.color)));
+
+                return Ok(BenchmarkResult{
+                    method: method.to_string(),
+                    num_threads: mandel_config.num_threads,
+                    mean_ms: timeout_ms as f64,
+                    min_ms: timeout_ms as f64,
+                    max_ms: timeout_ms as f64,
+                    stddev_ms: 0.0,
+                    timed_out: true
+                });
+            }
+        },
+        None => time_runs(&mandel_func, mandel_config, image, &AtomicBool::new(false))
+    };
+    let (mean_time, min_time, max_time, std_dev) = (times.mean_ms, times.min_ms, times.max_ms, times.stddev_ms);
+
+    // All outputs are row by row, outside of the measured time
+    to_rows(mandel_config, image);
+
+    println!("Time taken for this run ({}): {:.5} ms", method, mean_time);
+
+    if mandel_config.adaptive_runs {
+        let reached = times.ci.map_or(false, |ci| ci <= mandel_config.ci);
+        println!("Adaptive runs ({}): {} runs, 95% confidence interval: +-{} of the mean{}", method, times.num_of_runs,
+            times.ci.map_or("-".to_string(), |ci| format!("{:.2}%", ci * 100.0)),
+            if reached { "" } else { " (--ci not reached)" });
+    }
+
+    let rss_delta_mb = times.rss_delta_bytes.map(|bytes| (bytes as f64) / (1024.0 * 1024.0));
+    if let Some(rss_delta_mb) = rss_delta_mb {
+        println!("Peak RSS delta for this run: {:.3} MB", rss_delta_mb);
+    }
+
+    try!(write_benchmark_result(&method, mandel_config.num_threads, mean_time, min_time, max_time, std_dev,
+        &mandel_config.workload_name(), mandel_config.pins_threads(), run_id, if mandel_config.print_memory { rss_delta_mb } else { None },
+        mandel_config.layout, mandel_config.normalize_access, times.num_of_runs));
+
+    if !mandel_config.no_ppm {
+        let file_name = format!("{}_{}{}{}.{}", method, run_id, z0_suffix(mandel_config), tile_suffix(mandel_config), mandel_config.output_format.extension());
+
+        match mandel_config.output_format {
+            OutputFormat::Ppm | OutputFormat::PpmBinary => {
+                let mut metadata = if mandel_config.write_metadata {
+                    ImageMetadata::new()
+                        .add("method", method)
+                        .add("date", &now().rfc3339().to_string())
+                        .add("num_threads", &mandel_config.num_threads.to_string())
+                        .add("compiler", compiler_version)
+                        .add("access", &mandel_config.normalize_access.to_string())
+                        .add("tile", &mandel_config.tile.map_or("none".to_string(), |tile| tile.to_string()))
+                } else {
+                    ImageMetadata::new()
+                };
+
+                // The levels are needed to render the same colors again (--levels), so they are always stored
+                if let Some((low, high)) = image_levels(mandel_config, image) {
+                    if mandel_config.auto_levels {
+                        println!("Auto levels ({}): {}..{}", method, low, high);
+                    }
+                    metadata = metadata.add("levels", &format!("{}..{}", low, high));
+                } else if mandel_config.auto_levels {
+                    println!("Auto levels ({}): no points outside of the set, palette unchanged", method);
+                }
+
+                let times = try!(write_image(&file_name, &mandel_config, mean_time, &metadata, &image));
+                if mandel_config.compress {
+                    println!("Image written ({}): color mapping: {:.5} ms, formatting: {:.5} ms, compression: {:.5} ms",
+                        method, times.map_ms, times.write_ms, times.compress_ms);
+                } else {
+                    println!("Image written ({}): color mapping: {:.5} ms, writing: {:.5} ms",
+                        method, times.map_ms, times.write_ms);
+                }
+            },
+            OutputFormat::Exr => try!(write_image_exr(&file_name, &mandel_config, &image)
+                .map_err(|e| MandelError::io(&file_name, e))),
+            OutputFormat::Webp => try!(write_image_webp(&file_name, &mandel_config, &image, mandel_config.webp_quality)
+                .map_err(|e| MandelError::io(&file_name, e))),
+            OutputFormat::Svg => {
+                let levels = if mandel_config.contours.is_empty() { vec![mandel_config.max_iter] } else { mandel_config.contours.clone() };
+                let contours = extract_contour_levels(mandel_config, image, &levels);
+                println!("Contours ({}): {} paths at {} levels", method,
+                    contours.iter().map(|&(_, ref paths)| paths.len()).sum::<usize>(), levels.len());
+
+                try!(write_contour_svg(&file_name, mandel_config, method, &contours)
+                    .map_err(|e| MandelError::io(&file_name, e)));
+            },
+            OutputFormat::Obj => {
+                let field = height_field(mandel_config, image);
+                try!(write_obj(&file_name, mandel_config, method, &field)
+                    .map_err(|e| MandelError::io(&file_name, e)));
+                println!("Height field written ({}): {} vertices, {} triangles, mesh_step: {}", method,
+                    field.vertices.len(), field.num_of_triangles(), mesh_step(mandel_config));
+            }
+        }
+    }
+
+    // The same iteration counts, so all methods write the same cost map (the last one stays)
+    if let Some(ref file_name) = mandel_config.cost_map {
+        let metadata = ImageMetadata::new().add("method", method).add("cost_map", "log(1 + iter) / log(1 + max_iter)");
+        try!(write_cost_map(file_name, &mandel_config, mean_time, &metadata, &image));
+        println!("Cost map written ({}): {}", method, image_file_name(file_name, mandel_config));
+    }
+
+    if let Some(ref file_name) = mandel_config.power_spectrum {
+        let spectrum = spectrum::power_spectrum_2d(image, mandel_config.img_size);
+        try!(spectrum::write_power_spectrum(file_name, &spectrum, mandel_config.img_size)
+            .map_err(|e| MandelError::io(file_name, e)));
+        println!("Power spectrum written ({}): {} rings to {}", method, spectrum.len(), file_name);
+    }
+
+    if mandel_config.stats {
+        let image_stats = stats::analyze_image(image, mandel_config.img_size as usize, mandel_config.max_iter);
+        print!("Statistics ({}):\n{}", method, image_stats);
+
+        let file_name = format!("{}_{}{}{}_stats.json", method, run_id, z0_suffix(mandel_config), tile_suffix(mandel_config));
+        let json = serde_json::to_string_pretty(&image_stats.to_json()).unwrap();
+        try!(fs::write(&file_name, json + "\n").map_err(|e| MandelError::io(&file_name, e)));
+    }
+
+    if mandel_config.raw_output {
+        let file_name = format!("{}_{}{}{}.mraw", method, run_id, z0_suffix(mandel_config), tile_suffix(mandel_config));
+
+        try!(write_raw(&file_name, &mandel_config, &image).map_err(|e| MandelError::io(&file_name, e)));
+    }
+
+    if let Some(threshold) = mandel_config.extract_contour {
+        let file_name = format!("{}_{}{}{}_contour.svg", method, run_id, z0_suffix(mandel_config), tile_suffix(mandel_config));
+        let contours = extract_contour(mandel_config, image, threshold);
+        println!("Contour ({}): {} paths at iter {}", method, contours.len(), threshold);
+
+        try!(write_contour_svg(&file_name, mandel_config, method, &[(threshold, contours)])
+            .map_err(|e| MandelError::io(&file_name, e)));
+    }
+
+    if mandel_config.fractal_dimension {
+        let points: Vec<(f64, f64)> = extract_contour(mandel_config, image, mandel_config.max_iter).into_iter().flatten().collect();
+        println!("Fractal dimension ({}): {:.4} (box counting of {} boundary points)", method,
+            estimate_fractal_dimension(&points), points.len());
+    }
+
+    let result = BenchmarkResult{
+        method: method.to_string(),
+        num_threads: mandel_config.num_threads,
+        mean_ms: mean_time,
+        min_ms: min_time,
+        max_ms: max_time,
+        stddev_ms: std_dev,
+        timed_out: false
+    };
+
+    if let Some(ref db_path) = mandel_config.bench_db {
+        // The CSV file has been written already, so the result is not lost
+        if let Err(e) = write_benchmark_result_sqlite(db_path, method, mandel_config, &result) {
+            println!("Can't write benchmark result to database '{}': {}", db_path, e);
+        }
+    }
+
+    Ok(result)
+}
+
+// Run one method with each configuration (do_run), for example the lines of a CSV jobs file.
+// All runs share one run id with the number of the configuration as suffix (<run_id>_<n>), so the images don't
+// overwrite each other. Configurations that fail validation or whose outputs can't be written are skipped
+// with a warning and have no result.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn batch_render(configs: &[MandelConfig], method: &str, mandel_func: fn(&MandelConfig, &mut [u32], &AtomicBool)) -> Vec<BenchmarkResult> {
+    let run_id = generate_run_id();
+
+    configs.iter().enumerate().filter_map(|(n, mandel_config)| {
+        if let Err(e) = mandel_config.validate() {
+            println!("Warning: skipping configuration {}: {}", n + 1, e);
+            return None;
+        }
+
+        let mut image = vec![0; mandel_config.num_of_pixels()];
+        match do_run(method, mandel_func, mandel_config, &mut image, &format!("{}_{}", run_id, n + 1)) {
+            Ok(result) => Some(result),
+            Err(e) => {
+                println!("Warning: configuration {}: {}", n + 1, e);
+                None
+            }
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn origin_never_escapes() {
+        assert_eq!(mandel_iter(100, Complex64{re: 0.0, im: 0.0}), 100);
+        assert_eq!(mandel_iter(1, Complex64{re: 0.0, im: 0.0}), 1);
+    }
+
+    #[test]
+    fn far_point_escapes_immediately() {
+        assert_eq!(mandel_iter(100, Complex64{re: 3.0, im: 0.0}), 0);
+        assert_eq!(mandel_iter(100, Complex64{re: 0.0, im: -3.0}), 0);
+    }
+
+    #[test]
+    fn orbit_has_iteration_count_plus_one_points() {
+        let zero = Complex64{re: 0.0, im: 0.0};
+        assert_eq!(orbit(zero, 3), vec![zero; 4]);
+        assert_eq!(orbit(Complex64{re: -1.0, im: 0.0}, 3).iter().map(|z| z.re).collect::<Vec<_>>(), vec![-1.0, 0.0, -1.0, 0.0]);
+
+        for &(re, im) in &[(1.0, 0.0), (-0.75, 0.1), (0.3, 0.5), (3.0, 0.0), (2.0, 0.0)] {
+            let c = Complex64{re: re, im: im};
+            let z0 = Complex64{re: 0.2, im: -0.1};
+            assert_eq!(orbit(c, 50).len(), (mandel_iter(50, c) as usize) + 1, "c = {}", c);
+            assert_eq!(orbit_z0(50, z0, c).len(), (mandel_iter_z0(50, z0, c) as usize) + 1, "c = {}", c);
+        }
+    }
+
+    #[test]
+    fn perturbation_without_delta_is_exact() {
+        for &(re, im) in &[(0.0, 0.0), (-0.75, 0.1), (0.3, 0.5), (-2.0, 0.0), (3.0, 0.0), (-0.1, 0.9)] {
+            let c = Complex64{re: re, im: im};
+            let orbit = compute_ref_orbit(500, c);
+            assert!(orbit.len() >= 2 && orbit.len() <= 502);
+            assert_eq!(mandel_iter_perturbation(500, Complex64{re: 0.0, im: 0.0}, &orbit), mandel_iter(500, c), "c = {}", c);
+        }
+    }
+
+    #[test]
+    fn perturbation_matches_direct_iteration() {
+        // A reference inside the set and one that escapes after a few iterations (needs rebasing)
+        for &c_ref in &[Complex64{re: -0.75, im: 0.1}, Complex64{re: 0.5, im: 0.5}] {
+            let orbit = compute_ref_orbit(256, c_ref);
+            let mut num_of_equal = 0;
+
+            for y in 0..64 {
+                for x in 0..64 {
+                    let c = Complex64{re: -2.0 + ((x as f64) * 3.0 / 64.0), im: -1.5 + ((y as f64) * 3.0 / 64.0)};
+                    if mandel_iter_perturbation(256, c - c_ref, &orbit) == mandel_iter(256, c) {
+                        num_of_equal += 1;
+                    }
+                }
+            }
+
+            // Different rounding: only a few points close to the boundary may differ
+            assert!(num_of_equal >= (64 * 64 * 99) / 100, "c_ref = {}: {} of 4096 equal", c_ref, num_of_equal);
+        }
+    }
+
+    #[test]
+    fn radius_two_is_inside() {
+        // |c| == 2 is not escaped yet, the next step goes to 6
+        assert_eq!(mandel_iter(100, Complex64{re: 2.0, im: 0.0}), 1);
+        // -2 is the tip of the set: -2 -> 2 -> 2 -> ...
+        assert_eq!(mandel_iter(100, Complex64{re: -2.0, im: 0.0}), 100);
+    }
+
+    #[test]
+    fn period_two_bulb() {
+        // c = -1 oscillates between -1 and 0
+        assert_eq!(mandel_iter(1000, Complex64{re: -1.0, im: 0.0}), 1000);
+    }
+
+    #[test]
+    fn likely_interior_points_never_escape() {
+        // The points of a grid over the whole set that are claimed to be inside
+        let mut num_of_interior = 0;
+        for y in 0..200 {
+            for x in 0..250 {
+                let c = Complex64{re: -2.0 + ((x as f64) * 0.01), im: -1.0 + ((y as f64) * 0.01)};
+                if is_likely_interior(c) {
+                    assert_eq!(mandel_iter(2000, c), 2000, "{}", c);
+                    num_of_interior += 1;
+                }
+            }
+        }
+        assert!(num_of_interior > 10000, "{}", num_of_interior);
+
+        // The edges of the circles of the bulbs
+        for &(re, im, radius) in &PERIOD_BULBS {
+            for n in 0..64 {
+                let angle = (n as f64) * std::f64::consts::PI / 32.0;
+                let c = Complex64{re: re + (0.999 * radius * angle.cos()), im: im + (0.999 * radius * angle.sin())};
+                assert!(is_likely_interior(c));
+                assert_eq!(mandel_iter(10000, c), 10000, "{}", c);
+            }
+        }
+
+        // The cusp, the tip of the main bulb and the period 4 bulb on the real axis
+        assert!(is_inside_main_cardioid(Complex64{re: 0.25, im: 0.0}));
+        assert!(is_inside_main_bulb(Complex64{re: -1.25, im: 0.0}));
+        assert!(is_likely_interior(Complex64{re: -1.3, im: 0.0}));
+        assert!(!is_likely_interior(Complex64{re: 0.26, im: 0.0}));
+        assert!(!is_likely_interior(Complex64{re: -1.75, im: 0.0}));
+    }
+
+    #[test]
+    fn right_and_top_edge_not_sampled() {
+        let default = MandelConfig::default();
+        let config = MandelConfig{img_size: 16,
+            .. default};
+        let last = (config.img_size - 1) as f64;
+
+        assert_eq!(config.re1 + (0.0 * config.x_step()), config.re1);
+        assert!((config.re1 + (last * config.x_step()) - (config.re2 - config.x_step())).abs() < 1e-12);
+        assert!((config.img1 + (last * config.y_step()) - (config.img2 - config.y_step())).abs() < 1e-12);
+        assert!(config.re1 + (last * config.x_step()) < config.re2);
+        assert!(config.img1 + (last * config.y_step()) < config.img2);
+    }
+
+    #[test]
+    fn verify_detects_truncated_image() {
+        let default = MandelConfig::default();
+        let config = MandelConfig{img_size: 8, max_iter: 16,
+            verify_output: true,
+            .. default};
+        let file_name = std::env::temp_dir().join("mandel_util_verify_test.ppm");
+        let file_name = file_name.to_str().unwrap();
+
+        write_image(file_name, &config, 0.0, &ImageMetadata::new(), &vec![3; 64]).unwrap();
+
+        let content = fs::read(file_name).unwrap();
+        fs::write(file_name, &content[..(content.len() - 20)]).unwrap();
+
+        match verify_image(file_name, &config) {
+            Err(MandelError::CorruptOutput{..}) => {},
+            _ => panic!("truncated image not detected")
+        }
+
+        fs::remove_file(file_name).unwrap();
+    }
+
+    #[test]
+    fn compressed_image_equals_uncompressed() {
+        use flate2::read::GzDecoder;
+
+        let default = MandelConfig::default();
+        let config = MandelConfig{img_size: 16, max_iter: 64,
+            .. default};
+        let image: Vec<u32> = (0..256).map(|n| n % 65).collect();
+        let file_name = std::env::temp_dir().join("mandel_util_compress_test.ppm");
+        let file_name = file_name.to_str().unwrap();
+        let metadata = ImageMetadata::new().add("method", "serial");
+
+        for &output_format in &[OutputFormat::Ppm, OutputFormat::PpmBinary] {
+            let config = MandelConfig{output_format: output_format, .. config.clone()};
+            write_image(file_name, &config, 1.5, &metadata, &image).unwrap();
+            let uncompressed = fs::read(file_name).unwrap();
+
+            for &level in &[0, 1, 9] {
+                let compressed_config = MandelConfig{compress: true, compress_level: level, verify_output: true, .. config.clone()};
+                let compressed_file_name = image_file_name(file_name, &compressed_config);
+                assert_eq!(compressed_file_name, format!("{}.gz", file_name));
+                assert_eq!(image_file_name(&compressed_file_name, &compressed_config), compressed_file_name);
+
+                let times = write_image(file_name, &compressed_config, 1.5, &metadata, &image).unwrap();
+                assert!(times.compress_ms > 0.0);
+
+                let mut content = Vec::new();
+                GzDecoder::new(&fs::read(&compressed_file_name).unwrap()[..]).read_to_end(&mut content).unwrap();
+                assert!(content == uncompressed, "{:?}, level {}", output_format, level);
+                assert!(!Path::new(&format!("{}.tmp", compressed_file_name)).exists());
+
+                fs::remove_file(&compressed_file_name).unwrap();
+            }
+        }
+
+        fs::remove_file(file_name).unwrap();
+
+        assert!(MandelConfig{compress_level: 10, .. config.clone()}.validate().is_err());
+        assert!(MandelConfig{compress: true, output_format: OutputFormat::Exr, .. config}.validate().is_err());
+    }
+
+    #[test]
+    fn failed_compression_leaves_no_file() {
+        let dir = std::env::temp_dir().join("mandel_util_compress_missing_dir");
+        let _ = fs::remove_dir_all(&dir);
+        let file_name = dir.join("image.ppm.gz");
+
+        assert!(write_gzip(file_name.to_str().unwrap(), b"P3", 6).is_err());
+        assert!(!dir.exists());
+    }
+
+    // The single threaded writer before map_colors, pixel by pixel
+    fn write_image_reference(file_name: &str, mandel_config: &MandelConfig, image: &[u32]) {
+        let palette = Palette::from_config(mandel_config);
+        let mut buffer = BufWriter::new(File::create(file_name).unwrap());
+
+        write!(buffer, "P3\n# mandelbrot, max_iter: {}\n{1} {1}\n255\n", mandel_config.max_iter, mandel_config.img_size).unwrap();
+
+        for y in 0..mandel_config.img_size {
+            for x in 0..mandel_config.img_size {
+                let img_value = image[mandel_config.pixel_index(x, y)];
+                if img_value == mandel_config.max_iter {
+                    if mandel_config.interior_coloring == InteriorColoring::Black {
+                        buffer.write(b"0 0 0 ").unwrap();
+                    } else {
+                        let result = mandel_iter_full(mandel_config.max_iter, mandel_config.z0(), pixel_to_complex(mandel_config, x, y));
+                        let (red, green, blue) = interior_color(mandel_config.interior_coloring, result, mandel_config.x_step());
+                        write!(buffer, "{} {} {} ", red, green, blue).unwrap();
+                    }
+                } else {
+                    let (red, green, blue) = palette.color(img_value);
+                    write!(buffer, "{} {} {} ", red, green, blue).unwrap();
+                }
+            }
+            buffer.write(b"\n").unwrap();
+        }
+    }
+
+    #[test]
+    fn palette_preview_ends_are_first_and_last_entry() {
+        let file_name = std::env::temp_dir().join("mandel_util_palette_preview_test.ppm");
+        let file_name = file_name.to_str().unwrap();
+
+        for &output_format in &[OutputFormat::Ppm, OutputFormat::PpmBinary] {
+            let config = MandelConfig{img_size: 32, max_iter: 256, color_cycle: Some(1), output_format: output_format, .. MandelConfig::default()};
+            write_palette_preview(file_name, &config).unwrap();
+
+            let preview = ppm::read_ppm(file_name).unwrap();
+            assert_eq!((preview.width, preview.height), (32, PALETTE_PREVIEW_HEIGHT));
+
+            let column = |x: usize| {
+                let pixel = &preview.data[(3 * x)..(3 * x) + 3];
+                (pixel[0] as u8, pixel[1] as u8, pixel[2] as u8)
+            };
+            assert_eq!(column(0), escape_color(0));
+            assert_eq!(column(31), escape_color(palette::PALETTE_LEN - 1));
+            // Every row is the same
+            assert!(preview.data.chunks(3 * 32).all(|row| row == &preview.data[..3 * 32]));
+        }
+    }
+
+    #[test]
+    fn render_row_equals_render_pixel() {
+        let default = MandelConfig::default();
+        let config = MandelConfig{img_size: 12, max_iter: 64,
+            .. default};
+
+        for &flip_y in &[false, true] {
+            let config = MandelConfig{flip_y: flip_y, .. config.clone()};
+
+            for y in 0..12 {
+                let mut row = vec![0; 12];
+                render_row(&config, y, &mut row);
+                assert_eq!(row, (0..12).map(|x| render_pixel(&config, x, y)).collect::<Vec<_>>());
+
+                // A part of the row starting at x0
+                let mut part = vec![0; 5];
+                render_row_from(&config, 4, y, &mut part);
+                assert_eq!(part[..], row[4..9]);
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_writer_matches_single_threaded() {
+        let default = MandelConfig::default();
+        // 70 rows: more than one block of ASCII_BLOCK_ROWS
+        let config = MandelConfig{img_size: 70, max_iter: 64,
+            .. default};
+        let configs = [
+            config.clone(),
+            MandelConfig{interior_coloring: InteriorColoring::Period, flip_y: true, .. config.clone()},
+            MandelConfig{interior_coloring: InteriorColoring::Distance, use_symmetry: true, .. config.clone()},
+            MandelConfig{color_cycle: Some(3), color_offset: 5, color_scale: ColorScale::Log, .. config.clone()}
+        ];
+
+        let dir = std::env::temp_dir();
+        let file_name = dir.join("mandel_util_parallel_writer_test.ppm");
+        let file_name = file_name.to_str().unwrap();
+        let reference_file_name = dir.join("mandel_util_parallel_writer_reference.ppm");
+        let reference_file_name = reference_file_name.to_str().unwrap();
+
+        for config in &configs {
+            // The interior results are stored while the image is rendered, the mirrored rows are copied
+            let config = &config.with_interior_results();
+            let mut image = vec![0; config.num_of_pixels()];
+            for (line, values) in image.chunks_mut(config.img_size as usize).enumerate() {
+                render_line(config, line as u32, values);
+            }
+            mirror_rows(config, &mut image);
+
+            write_image(file_name, config, 0.0, &ImageMetadata::new(), &image).unwrap();
+            write_image_reference(reference_file_name, config, &image);
+            assert!(fs::read(file_name).unwrap() == fs::read(reference_file_name).unwrap());
+
+            // Binary: same colors
+            let binary_config = MandelConfig{output_format: OutputFormat::PpmBinary, verify_output: true, .. config.clone()};
+            write_image(file_name, &binary_config, 0.0, &ImageMetadata::new(), &image).unwrap();
+            let binary = ppm::read_ppm(file_name).unwrap();
+            assert!(binary.data == ppm::read_ppm(reference_file_name).unwrap().data);
+        }
+
+        fs::remove_file(file_name).unwrap();
+        fs::remove_file(reference_file_name).unwrap();
+    }
+
+    fn hang(_: &MandelConfig, _: &mut [u32], _: &AtomicBool) {
+        loop {
+            thread::sleep(Duration::from_secs(1000));
+        }
+    }
+
+    static STOPPED: AtomicBool = AtomicBool::new(false);
+
+    fn wait_for_cancel(_: &MandelConfig, _: &mut [u32], cancel: &AtomicBool) {
+        while !cancel.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        STOPPED.store(true, Ordering::Relaxed);
+    }
+
+    fn fill(_: &MandelConfig, image: &mut [u32], _: &AtomicBool) {
+        for pixel in image.iter_mut() {
+            *pixel = 7;
+        }
+    }
+
+    #[test]
+    fn method_timeout() {
+        let config = MandelConfig{img_size: 4, num_threads: 1, num_of_runs: 2, .. MandelConfig::default()};
+        let mut image = vec![1; 16];
+
+        assert!(time_runs_with_timeout("hang", hang, &config, &mut image, Duration::from_millis(100)).is_none());
+        assert!(image.iter().all(|&pixel| pixel == 1));
+
+        // A method that checks the flag stops after the timeout
+        assert!(time_runs_with_timeout("wait_for_cancel", wait_for_cancel, &config, &mut image, Duration::from_millis(10)).is_none());
+        for _ in 0..1000 {
+            if STOPPED.load(Ordering::Relaxed) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(STOPPED.load(Ordering::Relaxed));
+
+        assert!(time_runs_with_timeout("fill", fill, &config, &mut image, Duration::from_secs(60)).is_some());
+        assert!(image.iter().all(|&pixel| pixel == 7));
+    }
+
+    #[test]
+    fn concurrent_benchmark_results() {
+        let dir = std::env::temp_dir().join(format!("mandel_concurrent_plot_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let file_name = dir.join("rayon_join.csv").to_str().unwrap().to_string();
+
+        // All writers create the directory at the same time
+        thread::scope(|scope| {
+            for writer in 0..8 {
+                let file_name = &file_name;
+                scope.spawn(move || for n in 0..50 {
+                    append_benchmark_result(file_name, writer, (n as f64) + 0.25, 1.0, 2.0, 0.5, "fractal", false,
+                        &format!("run_{}_{}", writer, n), None, BufferLayout::Rows, AccessOrder::Native, 1).unwrap();
+                });
+            }
+        });
+
+        let content = fs::read_to_string(&file_name).unwrap();
+        let mut lines = content.lines();
+        assert_eq!(lines.next(), Some(BENCHMARK_HEADER.join(",").as_str()));
+
+        let mut run_ids: Vec<&str> = lines.map(|line| {
+            let values: Vec<&str> = line.split(',').collect();
+            assert_eq!(values.len(), BENCHMARK_HEADER.len(), "{}", line);
+            assert_eq!(&values[values.len() - 4..], &["", "rows", "native", "1"], "{}", line);
+            values[7]
+        }).collect();
+        run_ids.sort();
+        run_ids.dedup();
+        assert_eq!(run_ids.len(), 8 * 50);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn thread_pool_from_config() {
+        let config = MandelConfig{num_threads: 3, .. MandelConfig::default()};
+        let pool = rayon::ThreadPoolBuilder::from(&config).build().unwrap();
+
+        assert_eq!(pool.current_num_threads(), 3);
+        assert_eq!(pool.install(rayon::current_num_threads), 3);
+    }
+
+    fn sleep_5ms(_: &MandelConfig, _: &mut [u32], _: &AtomicBool) {
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    #[test]
+    fn adaptive_runs_stop() {
+        let config = MandelConfig{num_of_runs: 1, adaptive_runs: true, max_runs: 4, ci: 0.5, .. MandelConfig::default()};
+
+        // At least two runs for a confidence interval, then it's narrow enough
+        assert!(!enough_runs(&config, &[], 0.0));
+        assert!(!enough_runs(&config, &[5.0], 5.0));
+        assert!(enough_runs(&config, &[5.0, 5.1], 10.1));
+
+        // Too wide, all max_runs runs
+        assert!(!enough_runs(&config, &[1.0, 9.0], 10.0));
+        assert!(!enough_runs(&config, &[1.0, 9.0, 1.0], 11.0));
+        assert!(enough_runs(&config, &[1.0, 9.0, 1.0, 9.0], 20.0));
+
+        // No run is started that would end after the method timeout: elapsed time plus the slowest run
+        let config = MandelConfig{ci: 1e-12, max_runs: 100, method_timeout_ms: Some(30), .. config};
+        assert!(!enough_runs(&config, &[5.0, 6.0], 20.0));
+        assert!(enough_runs(&config, &[5.0, 6.0], 25.0));
+        // But always the minimum number of runs
+        assert!(!enough_runs(&config, &[50.0], 50.0));
+
+        // Without adaptive_runs exactly num_of_runs
+        let config = MandelConfig{adaptive_runs: false, num_of_runs: 3, .. config};
+        assert!(!enough_runs(&config, &[5.0, 5.0], 10.0));
+        assert!(enough_runs(&config, &[1.0, 9.0, 1.0], 11.0));
+    }
+
+    #[test]
+    fn adaptive_runs_end_before_the_timeout() {
+        let config = MandelConfig{img_size: 4, num_of_runs: 1, adaptive_runs: true, max_runs: 100, ci: 1e-12,
+            method_timeout_ms: Some(30), .. MandelConfig::default()};
+        let mut image = vec![0; 16];
+
+        // Each run takes at least 5 ms, so at most 6 runs fit into 30 ms
+        let times = time_runs(&sleep_5ms, &config, &mut image, &AtomicBool::new(false));
+        assert!(times.num_of_runs >= 2 && times.num_of_runs <= 6, "{}", times.num_of_runs);
+    }
+
+    #[test]
+    fn complexity_map_tiles() {
+        let default = MandelConfig::default();
+        let config = MandelConfig{img_size: 10, max_iter: 1000,
+            .. default};
+
+        // 3 x 3 tiles, the last ones only one pixel wide
+        let estimates = complexity_map(&config, 4);
+        assert_eq!(estimates.len(), 9);
+        assert!(estimates.iter().all(|&estimate| estimate <= COMPLEXITY_MAX_ITER));
+
+        // Tile (1, 1) samples pixel (6, 6): c = -0.2 - 0.6i is inside of the set
+        assert_eq!(estimates[4], COMPLEXITY_MAX_ITER);
+        assert_eq!(estimates[8], render_pixel(&config, 9, 9));
+
+        // Factor 1 is the whole image with fewer iterations
+        let low = MandelConfig{max_iter: COMPLEXITY_MAX_ITER, .. config.clone()};
+        let full: Vec<u32> = (0..100).map(|n| { let (x, y) = low.pixel_at(n); render_pixel(&low, x, y) }).collect();
+        assert_eq!(complexity_map(&config, 1), full);
+    }
+
+    #[test]
+    fn metadata_as_comments() {
+        let default = MandelConfig::default();
+        let config = MandelConfig{img_size: 4, max_iter: 16,
+            verify_output: true,
+            .. default};
+        let file_name = std::env::temp_dir().join("mandel_util_metadata_test.ppm");
+        let file_name = file_name.to_str().unwrap();
+        let metadata = ImageMetadata::new().add("method", "serial").add("note", "two\nlines");
+
+        write_image(file_name, &config, 0.0, &metadata, &vec![3; 16]).unwrap();
+
+        let content = fs::read_to_string(file_name).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines[0], "P3");
+        assert!(lines.contains(&"# method: serial"));
+        assert!(lines.contains(&"# note: two lines"));
+
+        fs::remove_file(file_name).unwrap();
+    }
+
+    #[test]
+    fn synthetic_iter_is_deterministic() {
+        for distribution in &[SyntheticDistribution::Uniform, SyntheticDistribution::Zipf] {
+            for pixel_index in 0..100 {
+                let iter = synthetic_iter(1000, *distribution, 42, pixel_index);
+                assert!(iter <= 1000);
+                assert_eq!(iter, synthetic_iter(1000, *distribution, 42, pixel_index));
+            }
+        }
+    }
+
+    #[test]
+    fn synthetic_distributions() {
+        let total = |distribution, seed| (0..10000).fold(0, |sum, pixel_index|
+            sum + (synthetic_iter(1000, distribution, seed, pixel_index) as u64));
+
+        let uniform = total(SyntheticDistribution::Uniform, 1);
+        let zipf = total(SyntheticDistribution::Zipf, 1);
+
+        // Mean of uniform is 500, mean of log uniform is 1000 / ln(1001) - 1 ~ 144
+        assert!(uniform > 4800000 && uniform < 5200000);
+        assert!(zipf > 1200000 && zipf < 1700000);
+        assert!(total(SyntheticDistribution::Uniform, 2) != uniform);
+    }
+
+    #[test]
+    fn config_string_round_trip() {
+        let config: MandelConfig = "re1=-0.75;re2=-0.7;img1=0.1;img2=0.15;max_iter=8192;img_size=512;num_threads=4"
+            .parse().unwrap();
+
+        assert_eq!(config.re1, -0.75);
+        assert_eq!(config.img2, 0.15);
+        assert_eq!(config.max_iter, 8192);
+        assert_eq!(config.img_size, 512);
+        assert_eq!(config.num_threads, 4);
+        assert!((config.x_step() - (0.05 / 512.0)).abs() < 1e-15);
+
+        let parsed: MandelConfig = config.to_string().parse().unwrap();
+
+        assert!((parsed.re1 - config.re1).abs() < 1e-15);
+        assert!((parsed.re2 - config.re2).abs() < 1e-15);
+        assert!((parsed.img1 - config.img1).abs() < 1e-15);
+        assert!((parsed.img2 - config.img2).abs() < 1e-15);
+        assert!((parsed.x_step() - config.x_step()).abs() < 1e-15);
+        assert!((parsed.y_step() - config.y_step()).abs() < 1e-15);
+        assert_eq!(parsed.max_iter, config.max_iter);
+        assert_eq!(parsed.img_size, config.img_size);
+        assert_eq!(parsed.num_threads, config.num_threads);
+    }
+
+    #[test]
+    fn config_string_errors() {
+        let default: MandelConfig = "".parse().unwrap();
+        assert_eq!(default.img_size, MandelConfig::default().img_size);
+
+        assert!("max_iter=12;foo=1".parse::<MandelConfig>().is_err());
+        assert!("max_iter".parse::<MandelConfig>().is_err());
+        assert_eq!("re1=abc".parse::<MandelConfig>().err(),
+            Some(ConfigError::ParseError("invalid value for 're1': 'abc'".to_string())));
+    }
+
+    #[test]
+    fn config_file_keeps_other_values() {
+        let file_name = std::env::temp_dir().join("mandel_util_config_file_test.txt");
+        fs::write(&file_name, "# seahorse valley\nre1 = -0.76; re2 = -0.72\n\nmax_iter=512 # more details\n").unwrap();
+
+        let base = MandelConfig{img_size: 256, num_threads: 8, .. MandelConfig::default()};
+        let config = read_config_file(file_name.to_str().unwrap(), &base).unwrap();
+
+        assert_eq!((config.re1, config.re2, config.max_iter), (-0.76, -0.72, 512));
+        assert_eq!((config.img1, config.img_size, config.num_threads), (base.img1, 256, 8));
+        assert!((config.x_step() - (0.04 / 256.0)).abs() < 1e-15);
+
+        fs::write(&file_name, "max_iter=512\ncolor=red\n").unwrap();
+        assert!(read_config_file(file_name.to_str().unwrap(), &base).is_err());
+
+        let _ = fs::remove_file(&file_name);
+        assert!(read_config_file(file_name.to_str().unwrap(), &base).is_err());
+    }
+
+    #[test]
+    fn watch_needs_a_config_file() {
+        let config = MandelConfig{watch: true, .. MandelConfig::default()};
+        assert!(config.validate().is_err());
+
+        let config = MandelConfig{config_file: Some("view.conf".to_string()), .. config};
+        assert!(config.validate().is_ok());
+        assert!(MandelConfig{benchmark_matrix: true, .. config}.validate().is_err());
+    }
+
+    #[test]
+    fn adaptive_runs_limits() {
+        let config = MandelConfig{adaptive_runs: true, .. MandelConfig::default()};
+        assert!(config.validate().is_ok());
+        assert!(MandelConfig{ci: 0.0, .. config.clone()}.validate().is_err());
+        assert!(MandelConfig{ci: 1.5, .. config.clone()}.validate().is_err());
+        assert!(MandelConfig{max_runs: 1, num_of_runs: 1, .. config.clone()}.validate().is_err());
+        assert!(MandelConfig{max_runs: 4, num_of_runs: 5, .. config}.validate().is_err());
+        // Without adaptive_runs they are not used
+        assert!(MandelConfig{max_runs: 1, .. MandelConfig::default()}.validate().is_ok());
+    }
+
+    #[test]
+    fn num_threads_sweep_range() {
+        let config = MandelConfig{num_threads_sweep: Some((1, 16, 1)), .. MandelConfig::default()};
+        assert!(config.validate().is_ok());
+        assert!(MandelConfig{num_threads_sweep: Some((0, 16, 1)), .. config.clone()}.validate().is_err());
+        assert!(MandelConfig{num_threads_sweep: Some((8, 4, 1)), .. config.clone()}.validate().is_err());
+        assert!(MandelConfig{num_threads_sweep: Some((1, 16, 0)), .. config.clone()}.validate().is_err());
+        assert!(MandelConfig{benchmark_matrix: true, .. config}.validate().is_err());
+    }
+
+    #[test]
+    fn coordinator_needs_the_fractal() {
+        let config = MandelConfig{coordinator: vec!["localhost:7878".to_string()], .. MandelConfig::default()};
+        assert!(config.validate().is_ok());
+        assert!(MandelConfig{workload: Workload::Synthetic, .. config.clone()}.validate().is_err());
+        assert!(MandelConfig{worker: Some("0.0.0.0:7878".to_string()), .. config}.validate().is_err());
+    }
+
+    #[test]
+    fn numa_init_without_timeout() {
+        let config = MandelConfig{numa_init: true, .. MandelConfig::default()};
+        assert!(config.validate().is_ok());
+        assert!(MandelConfig{method_timeout_ms: Some(1000), .. config}.validate().is_err());
+    }
+
+    #[test]
+    fn config_eq_tolerates_rounding() {
+        // Deep zoom, the parser reads the corners, here re2 and img2 follow from the step size
+        let (re1, re2, img1, img2) = (-0.743643887037151, -0.743643887037141, 0.131825904205330, 0.131825904205340);
+        let parsed: MandelConfig = format!("re1={};re2={};img1={};img2={};img_size=7", re1, re2, img1, img2).parse().unwrap();
+        let built = MandelConfig{re1: re1, img1: img1, img_size: 7, .. MandelConfig::default()}
+            .with_pixel_size((re2 - re1) * (1.0 / 7.0));
+
+        assert_eq!(parsed, built);
+
+        // Real differences are still found, also in the tiny steps
+        assert!(parsed != parsed.with_pixel_size(parsed.x_step() * 1.1));
+        assert!(parsed != MandelConfig{re1: re1 + 1e-13, .. parsed.clone()});
+        assert!(parsed != MandelConfig{max_iter: parsed.max_iter + 1, .. parsed.clone()});
+        assert!(parsed != MandelConfig{z0_re: 1e-300, .. parsed.clone()});
+    }
+
+    // The classic loop that starts from z = c, before z0 was added
+    fn classic_mandel_iter(max_iter: u32, c: Complex64) -> u32 {
+        let mut z = c;
+        let mut iter = 0;
+
+        while (z.norm_sqr() <= 4.0) && (iter < max_iter) {
+            z = c + (z * z);
+            iter = iter + 1;
+        }
+
+        iter
+    }
+
+    #[test]
+    fn phoenix_iterations() {
+        let origin = Complex64{re: 0.0, im: 0.0};
+
+        // p = -0.5, q = 0: julia set of c = -0.5, 0 stays bounded, 2 escapes after one step (z1 = 3.5)
+        assert_eq!(phoenix_iter(100, origin, -0.5, 0.0), 100);
+        assert_eq!(phoenix_iter(100, Complex64{re: 2.0, im: 0.0}, -0.5, 0.0), 1);
+        assert_eq!(phoenix_iter(100, Complex64{re: 2.5, im: 0.0}, -0.5, 0.0), 0);
+
+        // z1 = 1.5 (z_{-1} = 0), z2 = 2.75 escapes, with q = -1: z2 = 2.75 - z1 = 1.75 doesn't
+        let pixel = Complex64{re: 1.0, im: 0.0};
+        assert_eq!(phoenix_iter(3, pixel, 0.5, 0.0), 2);
+        assert_eq!(phoenix_iter(3, pixel, 0.5, -1.0), 3);
+
+        let config = MandelConfig{workload: Workload::Phoenix, phoenix_q: 0.25, .. MandelConfig::default()};
+        assert_eq!(render_pixel(&config, 10, 20), phoenix_iter(config.max_iter, pixel_to_complex(&config, 10, 20), -0.5, 0.25));
+        assert_eq!(config.workload_name(), "phoenix:-0.5:0.25");
+    }
+
+    #[test]
+    fn zero_z0_reproduces_classic_images() {
+        let config = MandelConfig{img_size: 64, max_iter: 256, .. MandelConfig::default()};
+        assert!(!config.has_z0());
+
+        for y in 0..64 {
+            for x in 0..64 {
+                assert_eq!(render_pixel(&config, x, y), classic_mandel_iter(256, pixel_to_complex(&config, x, y)));
+            }
+        }
+    }
+
+    #[test]
+    fn nonzero_z0() {
+        let origin = Complex64{re: 0.0, im: 0.0};
+
+        // z1 = 0.25, then z converges to 0
+        assert_eq!(mandel_iter_z0(100, Complex64{re: 0.5, im: 0.0}, origin), 100);
+        // z1 = 4 escapes immediately
+        assert_eq!(mandel_iter_z0(100, Complex64{re: 2.0, im: 0.0}, origin), 0);
+
+        let config: MandelConfig = "z0_re=0.5;z0_im=-0.25".parse().unwrap();
+        assert!(config.has_z0());
+        assert_eq!(z0_suffix(&config), "_z0_0.5_-0.25");
+        assert!(config.to_string().ends_with(";z0_re=0.5;z0_im=-0.25"));
+        assert_eq!(z0_suffix(&MandelConfig::default()), "");
+    }
+
+    #[test]
+    fn tiles_have_their_own_suffix() {
+        let config = MandelConfig{tile: Some(Tile{row: 1, col: 3, of_n: 4}), .. MandelConfig::default()};
+        assert_eq!(tile_suffix(&config), "_tile_1_3");
+        assert_eq!(tile_suffix(&MandelConfig::default()), "");
+    }
+
+    #[test]
+    fn img_size_must_be_power_of_two() {
+        assert!(MandelConfig::default().validate().is_ok());
+        assert!(MandelConfig{img_size: 1, .. MandelConfig::default()}.validate().is_ok());
+
+        assert_eq!(MandelConfig{img_size: 1000, .. MandelConfig::default()}.validate(),
+            Err(ConfigError::InvalidValue("img_size (1000) must be a power of two, for example 512 or 1024".to_string())));
+        assert_eq!(MandelConfig{img_size: 0, .. MandelConfig::default()}.validate(),
+            Err(ConfigError::InvalidValue("img_size (0) must be a power of two, for example 1".to_string())));
+        assert_eq!(nearest_powers_of_two(3_000_000_000), (Some(2_147_483_648), None));
+
+        assert!(MandelConfig{re1: 1.0, re2: 1.0, .. MandelConfig::default()}.validate().is_err());
+        assert!(MandelConfig{num_threads: 0, .. MandelConfig::default()}.validate().is_err());
+
+        let svg = MandelConfig{output_format: OutputFormat::Svg, contours: vec![100, 4096], .. MandelConfig::default()};
+        assert!(svg.validate().is_ok());
+        assert!(MandelConfig{contours: vec![100, 0], .. svg.clone()}.validate().is_err());
+        assert!(MandelConfig{contours: vec![4097], .. svg.clone()}.validate().is_err());
+        assert!(MandelConfig{output_format: OutputFormat::Ppm, .. svg}.validate().is_err());
+
+        assert!(MandelConfig{rayon_min_len: 64, rayon_max_len: 64, .. MandelConfig::default()}.validate().is_ok());
+        assert!(MandelConfig{rayon_min_len: 0, .. MandelConfig::default()}.validate().is_err());
+        assert!(MandelConfig{rayon_min_len: 65, rayon_max_len: 64, .. MandelConfig::default()}.validate().is_err());
+
+        let webp = MandelConfig{output_format: OutputFormat::Webp, img_size: 8192, .. MandelConfig::default()};
+        assert!(webp.validate().is_ok());
+        assert!(MandelConfig{img_size: 16384, .. webp.clone()}.validate().is_err());
+        assert!(MandelConfig{webp_quality: 100.5, .. webp.clone()}.validate().is_err());
+        assert!(MandelConfig{webp_quality: -1.0, .. webp}.validate().is_err());
+
+        let obj = MandelConfig{output_format: OutputFormat::Obj, mesh_step: Some(4), .. MandelConfig::default()};
+        assert!(obj.validate().is_ok());
+        assert!(MandelConfig{mesh_step: Some(0), .. obj.clone()}.validate().is_err());
+        assert!(MandelConfig{mesh_height: ::std::f64::NAN, .. obj.clone()}.validate().is_err());
+        assert!(MandelConfig{output_format: OutputFormat::Ppm, .. obj}.validate().is_err());
+
+        let matrix = MandelConfig{benchmark_matrix: true, size_list: vec![512, 1024], iter_list: vec![100, 1000], .. MandelConfig::default()};
+        assert!(matrix.validate().is_ok());
+        assert!(MandelConfig{size_list: vec![512, 1000], .. matrix.clone()}.validate().is_err());
+        assert!(MandelConfig{iter_list: vec![0], .. matrix.clone()}.validate().is_err());
+        assert!(MandelConfig{benchmark_matrix: false, .. matrix}.validate().is_err());
+
+        assert_eq!(parse_levels("12..407"), Some((12, 407)));
+        assert!(parse_levels("12").is_none() && parse_levels("12..x").is_none());
+        assert!(MandelConfig{levels: Some((12, 4095)), .. MandelConfig::default()}.validate().is_ok());
+        assert!(MandelConfig{levels: Some((12, 4096)), .. MandelConfig::default()}.validate().is_err());
+        assert!(MandelConfig{levels: Some((13, 12)), .. MandelConfig::default()}.validate().is_err());
+        assert!(MandelConfig{levels: Some((12, 13)), auto_levels: true, .. MandelConfig::default()}.validate().is_err());
+        assert!(MandelConfig{stripe_density: 0.0, .. MandelConfig::default()}.validate().is_err());
+        assert!(MandelConfig{stripe_density: std::f64::NAN, .. MandelConfig::default()}.validate().is_err());
+
+        let rows = MandelConfig{normalize_access: AccessOrder::Rows, .. MandelConfig::default()};
+        assert!(rows.validate().is_ok());
+        assert!(MandelConfig{layout: BufferLayout::Tiles64, .. rows}.validate().is_err());
+    }
+
+    #[test]
+    fn pixel_indices_beyond_u32() {
+        // 131072 * 131072 pixels: y * img_size overflows u32 from row 32768 on
+        let config = MandelConfig{img_size: 131072, max_iter: 1, .. MandelConfig::default()};
+        assert!(config.validate().is_ok());
+
+        let index = config.pixel_index(5, 40000);
+        assert_eq!(index as u64, (40000 * 131072) + 5);
+        assert!(index as u64 > (u32::MAX as u64));
+        assert_eq!(config.pixel_at(index), (5, 40000));
+        assert_eq!(config.pixel_at(config.num_of_pixels() - 1), (131071, 131071));
+
+        assert_eq!(image_memory(&config), 1 << 36);
+        assert_eq!(image_memory(&MandelConfig{compare_parallel_outputs: true, .. config.clone()}), 1 << 37);
+
+        // Render line 32800 of the image buffer like the methods do, it starts at index 32800 * 131072.
+        // Around 0 - 2i the points of the first rows escape with max_iter 1, the others don't.
+        let config = MandelConfig{re1: -0.01, re2: 0.01, img1: -3.0, img2: 1.0, flip_y: true, .. config};
+
+        for &layout in &[BufferLayout::Rows, BufferLayout::Tiles64] {
+            let config = MandelConfig{layout: layout, .. config.clone()};
+            let mut values = vec![7; 131072];
+            render_line(&config, 32800, &mut values);
+
+            // With tiles64 the line is in the tile row of the rows 32768 .. 32831, tiles 1024 .. 1055
+            let expected: Vec<u32> = (0..131072).map(|n| {
+                let (x, y) = match layout {
+                    BufferLayout::Rows => (n, 32800),
+                    BufferLayout::Tiles64 => ((64 * (1024 + (n / 4096))) + (n % 64), 32768 + ((n % 4096) / 64))
+                };
+                mandel_iter_z0(1, config.z0(), pixel_to_complex(&config, x, y))
+            }).collect();
+
+            assert!(values == expected, "{:?}", layout);
+            assert!(layout == BufferLayout::Rows || (expected.contains(&0) && expected.contains(&1)));
+        }
+    }
+
+    #[test]
+    fn tiled_buffer_indices() {
+        let config = MandelConfig{img_size: 256, layout: BufferLayout::Tiles64, .. MandelConfig::default()};
+        assert_eq!((config.tile_size(), config.run_len()), (64, 64));
+
+        // Pixel (70, 1) is pixel (6, 1) of the second tile
+        assert_eq!(config.buffer_index(70, 1), (64 * 64) + 64 + 6);
+        // The first pixel of the second tile row
+        assert_eq!(config.buffer_index(0, 64), 4 * 64 * 64);
+        assert!((0..config.num_of_pixels()).all(|index| {
+            let (x, y) = config.buffer_pixel(index);
+            config.buffer_index(x, y) == index
+        }));
+        assert_eq!(config.buffer_pixel(config.num_of_pixels() - 1), (255, 255));
+
+        // Smaller images are a single tile, the same as rows
+        let small = MandelConfig{img_size: 16, .. config.clone()};
+        assert_eq!(small.buffer_index(3, 5), small.pixel_index(3, 5));
+
+        // Each value of the tiled buffer is its own row-major index after to_rows
+        let mut image: Vec<u32> = (0..config.num_of_pixels()).map(|index| {
+            let (x, y) = config.buffer_pixel(index);
+            config.pixel_index(x, y) as u32
+        }).collect();
+        to_rows(&config, &mut image);
+        assert!(image.iter().enumerate().all(|(index, &value)| value as usize == index));
+
+        // render_line computes the pixels of the runs of the line
+        let mut line = vec![0; 256];
+        render_line(&config, 65, &mut line);
+        let (x0, y) = config.buffer_pixel(65 * 256);
+        // Offset 65 * 256 is row 4 of tile 4, the first tile of the second tile row
+        assert_eq!((x0, y), (0, 68));
+        assert_eq!(line[1], render_pixel(&config, 1, 68));
+        // The second run of 64 values is the next row of the same tile
+        assert_eq!(config.buffer_pixel((65 * 256) + 65), (1, 69));
+        assert_eq!(line[65], render_pixel(&config, 1, 69));
+
+        assert_eq!("tiles64".parse::<BufferLayout>(), Ok(BufferLayout::Tiles64));
+        assert_eq!(BufferLayout::Rows.to_string(), "rows");
+        assert!("tiles".parse::<BufferLayout>().is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn rss_grows_with_touched_memory() {
+        let before = get_rss_bytes().unwrap();
+        assert!(before > 0);
+
+        // 64 MB that are written, so they are resident
+        let buffer = vec![1u8; 64 * 1024 * 1024];
+        let after = get_rss_bytes().unwrap();
+        assert!(after >= before + (32 * 1024 * 1024), "{} -> {}", before, after);
+        assert_eq!(buffer[buffer.len() - 1], 1);
+    }
+
+    #[test]
+    fn mirror_source_rows() {
+        let config = MandelConfig{img_size: 8, use_symmetry: true, .. MandelConfig::default()};
+        assert!(config.validate().is_ok());
+
+        // Rows from the top: 4 .. 6 mirror 0 .. 2, 3 is the real axis and 7 is img1
+        let sources: Vec<_> = (0..8).map(|y| config.mirror_source_row(y)).collect();
+        assert_eq!(sources, vec![None, None, None, None, Some(2), Some(1), Some(0), None]);
+        assert_eq!(config.row_to_im(3), 0.0);
+
+        // Row 0 is img1, 4 the real axis
+        let flipped = MandelConfig{flip_y: true, .. config.clone()};
+        let sources: Vec<_> = (0..8).map(|y| flipped.mirror_source_row(y)).collect();
+        assert_eq!(sources, vec![None, None, None, None, None, Some(3), Some(2), Some(1)]);
+        assert_eq!(flipped.row_to_im(4), 0.0);
+
+        // Odd sizes have no center row
+        let odd = MandelConfig{img_size: 7, .. config.clone()};
+        let sources: Vec<_> = (0..7).map(|y| odd.mirror_source_row(y)).collect();
+        assert_eq!(sources, vec![None, None, None, Some(2), Some(1), Some(0), None]);
+
+        assert_eq!(MandelConfig{use_symmetry: false, .. config.clone()}.mirror_source_row(0), None);
+
+        // With inclusive_edges img2 is sampled: row 2 (im -0.5) mirrors row 1 (im 0.5), no real axis row
+        let inclusive = MandelConfig{img_size: 4, inclusive_edges: true, .. config.clone()};
+        let sources: Vec<_> = (0..4).map(|y| inclusive.mirror_source_row(y)).collect();
+        assert_eq!(sources, vec![None, None, Some(1), Some(0)]);
+        assert_eq!(inclusive.row_to_im(2), -inclusive.row_to_im(1));
+
+        let flipped = MandelConfig{flip_y: true, .. inclusive.clone()};
+        let sources: Vec<_> = (0..4).map(|y| flipped.mirror_source_row(y)).collect();
+        assert_eq!(sources, vec![None, None, Some(1), Some(0)]);
+
+        // Odd sizes sample the real axis
+        let odd = MandelConfig{img_size: 5, .. inclusive.clone()};
+        let sources: Vec<_> = (0..5).map(|y| odd.mirror_source_row(y)).collect();
+        assert_eq!(sources, vec![None, None, None, Some(1), Some(0)]);
+        assert_eq!(odd.row_to_im(2), 0.0);
+    }
+
+    #[test]
+    fn use_symmetry_needs_symmetric_region() {
+        let config = MandelConfig{img1: -1.0, use_symmetry: true, .. MandelConfig::default()};
+        assert!(config.validate().is_err());
+        assert!(MandelConfig{use_symmetry: false, .. config.clone()}.validate().is_ok());
+
+        let synthetic = MandelConfig{workload: Workload::Synthetic, use_symmetry: true, .. MandelConfig::default()};
+        assert!(synthetic.validate().is_err());
+    }
+
+    #[test]
+    fn auto_max_iter_grows_with_zoom() {
+        assert_eq!(MandelConfig::default().zoom(), 1.0);
+        assert_eq!(MandelConfig{re1: -1.0, re2: -0.5, img1: 0.0, img2: 1.0, .. MandelConfig::default()}.zoom(), 3.0);
+
+        assert_eq!(auto_max_iter(1.0), 256);
+        assert_eq!(auto_max_iter(1e10), 65536);
+        assert_eq!(auto_max_iter(0.1), 256);
+        assert_eq!(auto_max_iter(1e20), 65536);
+
+        let zooms = [1.0, 10.0, 1e3, 1e6, 1e9];
+        assert!(zooms.windows(2).all(|pair| auto_max_iter(pair[0]) < auto_max_iter(pair[1])));
+    }
+
+    quickcheck! {
+        fn never_exceeds_max_iter(max_iter: u16, re: f64, im: f64) -> bool {
+            let max_iter = max_iter as u32;
+            mandel_iter(max_iter, Complex64{re: re, im: im}) <= max_iter
+        }
+
+        fn monotone_in_max_iter(max_iter1: u16, max_iter2: u16, re: f64, im: f64) -> bool {
+            let (low, high) = if max_iter1 < max_iter2 { (max_iter1, max_iter2) } else { (max_iter2, max_iter1) };
+            let c = Complex64{re: re, im: im};
+            mandel_iter(low as u32, c) <= mandel_iter(high as u32, c)
+        }
+
+        fn symmetric_under_conjugation(max_iter: u16, re: f64, im: f64) -> bool {
+            let c = Complex64{re: re, im: im};
+            mandel_iter(max_iter as u32, c) == mandel_iter(max_iter as u32, c.conj())
+        }
     }
 }
@@ -0,0 +1,159 @@
+// Benchmark matrix (--benchmark_matrix): the selected methods are run for every combination of
+// --size_list and --iter_list with the region of the main options.
+//
+// All results go into one CSV file (plot/matrix_<run_id>.csv, one line per method and combination)
+// and into the JSON report (report_<run_id>.json), together with the combinations that were skipped
+// because their estimated time is longer than --skip_slow.
+
+// External modules
+use serde_json::Value;
+
+// Rust modules
+use std::fs::{self, File};
+use std::io::{BufWriter, Result};
+use std::path::Path;
+
+// Internal modules
+use {BenchmarkResult, MandelConfig};
+
+// Columns of the matrix CSV file
+pub const MATRIX_HEADER: [&'static str; 8] = ["method", "num_threads", "img_size", "max_iter", "mean_ms", "min_ms", "max_ms", "stddev_ms"];
+
+// The result of one method for one combination
+#[derive(Clone, Debug)]
+pub struct MatrixEntry {
+    pub img_size: u32,
+    pub max_iter: u32,
+    pub result: BenchmarkResult
+}
+
+// A method that was not run for one combination because of --skip_slow
+#[derive(Clone, Debug)]
+pub struct SkippedEntry {
+    pub img_size: u32,
+    pub max_iter: u32,
+    pub method: String,
+    // Estimated time of all runs in seconds
+    pub estimate_s: f64
+}
+
+// The image sizes of the matrix, img_size if --size_list is not given
+pub fn size_list(mandel_config: &MandelConfig) -> Vec<u32> {
+    if mandel_config.size_list.is_empty() { vec![mandel_config.img_size] } else { mandel_config.size_list.clone() }
+}
+
+// The max_iter values of the matrix, max_iter if --iter_list is not given
+pub fn iter_list(mandel_config: &MandelConfig) -> Vec<u32> {
+    if mandel_config.iter_list.is_empty() { vec![mandel_config.max_iter] } else { mandel_config.iter_list.clone() }
+}
+
+// The configuration of one combination: same region, the steps fit the image size.
+// The matrix only measures, no images are written.
+pub fn combination_config(mandel_config: &MandelConfig, img_size: u32, max_iter: u32) -> MandelConfig {
+    MandelConfig{
+        img_size: img_size,
+        max_iter: max_iter,
+        no_ppm: true,
+        .. mandel_config.clone()
+    }
+}
+
+// Write all results into one CSV file, the directory is created if it doesn't exist
+pub fn write_matrix_csv(file_name: &str, entries: &[MatrixEntry]) -> Result<()> {
+    if let Some(dir) = Path::new(file_name).parent() {
+        try!(fs::create_dir_all(dir));
+    }
+
+    let mut writer = ::csv::Writer::from_writer(BufWriter::new(try!(File::create(file_name))));
+    try!(writer.write_record(&MATRIX_HEADER));
+
+    for entry in entries {
+        let result = &entry.result;
+        try!(writer.write_record(&[result.method.clone(), result.num_threads.to_string(), entry.img_size.to_string(),
+            entry.max_iter.to_string(), result.mean_ms.to_string(), result.min_ms.to_string(), result.max_ms.to_string(),
+            result.stddev_ms.to_string()]));
+    }
+
+    try!(writer.flush());
+    Ok(())
+}
+
+// The JSON report of the whole matrix, write it with estimate::write_report
+pub fn matrix_json(run_id: &str, mandel_config: &MandelConfig, entries: &[MatrixEntry], skipped: &[SkippedEntry]) -> Value {
+    let results: Vec<Value> = entries.iter().map(|entry| json!({
+        "method": entry.result.method,
+        "num_threads": entry.result.num_threads,
+        "img_size": entry.img_size,
+        "max_iter": entry.max_iter,
+        "mean_ms": entry.result.mean_ms,
+        "min_ms": entry.result.min_ms,
+        "max_ms": entry.result.max_ms,
+        "stddev_ms": entry.result.stddev_ms,
+        "timed_out": entry.result.timed_out
+    })).collect();
+
+    let skipped: Vec<Value> = skipped.iter().map(|skipped| json!({
+        "method": skipped.method,
+        "img_size": skipped.img_size,
+        "max_iter": skipped.max_iter,
+        "estimate_s": skipped.estimate_s
+    })).collect();
+
+    json!({
+        "run_id": run_id,
+        "size_list": size_list(mandel_config),
+        "iter_list": iter_list(mandel_config),
+        "skip_slow_s": mandel_config.skip_slow,
+        "results": results,
+        "skipped": skipped
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    fn entry(method: &str, img_size: u32, max_iter: u32) -> MatrixEntry {
+        MatrixEntry{
+            img_size: img_size,
+            max_iter: max_iter,
+            result: BenchmarkResult{method: method.to_string(), num_threads: 4, mean_ms: 2.5, min_ms: 2.0, max_ms: 3.0, stddev_ms: 0.5, timed_out: false}
+        }
+    }
+
+    #[test]
+    fn combinations_keep_the_region() {
+        let config = MandelConfig{size_list: vec![512, 1024], iter_list: vec![100], benchmark_matrix: true, .. MandelConfig::default()};
+        assert_eq!((size_list(&config), iter_list(&config)), (vec![512, 1024], vec![100]));
+        assert_eq!((size_list(&MandelConfig::default()), iter_list(&MandelConfig::default())), (vec![2048], vec![4096]));
+
+        let combination = combination_config(&config, 512, 100);
+        assert_eq!((combination.img_size, combination.max_iter, combination.re1, combination.img2), (512, 100, config.re1, config.img2));
+        assert_eq!(combination.x_step() * 512.0, config.re2 - config.re1);
+        assert!(combination.no_ppm);
+        assert!(combination.validate().is_ok());
+    }
+
+    #[test]
+    fn csv_and_report_contain_all_entries() {
+        let file_name = temp_dir().join("mandel_matrix_test").join("matrix.csv");
+        let file_name = file_name.to_str().unwrap();
+        let entries = [entry("serial", 512, 100), entry("rayon_join", 1024, 100)];
+
+        write_matrix_csv(file_name, &entries).unwrap();
+        let content = fs::read_to_string(file_name).unwrap();
+        assert_eq!(content.lines().collect::<Vec<_>>(), vec![
+            "method,num_threads,img_size,max_iter,mean_ms,min_ms,max_ms,stddev_ms",
+            "serial,4,512,100,2.5,2,3,0.5",
+            "rayon_join,4,1024,100,2.5,2,3,0.5"]);
+
+        let config = MandelConfig{size_list: vec![512, 1024], iter_list: vec![100], benchmark_matrix: true, .. MandelConfig::default()};
+        let skipped = [SkippedEntry{img_size: 1024, max_iter: 100, method: "serial".to_string(), estimate_s: 90.0}];
+        let report = matrix_json("run", &config, &entries, &skipped);
+        assert_eq!(report["results"].as_array().unwrap().len(), 2);
+        assert_eq!(report["results"][1]["img_size"], 1024);
+        assert_eq!(report["skipped"][0]["method"], "serial");
+        assert_eq!(report["size_list"], json!([512, 1024]));
+    }
+}
@@ -0,0 +1,256 @@
+// The iteration counts as a 3D height field (--output_format=obj), for rendering the escape time surface
+// in Blender and other 3D tools.
+//
+// One vertex per sampled pixel at (x, y, height): x and y are the position in the image between 0 and 1
+// (y grows with the imaginary part like in the complex plane), the height is iter / max_iter or with --mesh_log
+// log(1 + iter) / log(1 + max_iter), times --mesh_height. The points of the set are a plateau at --mesh_height.
+// Each cell of the grid are two triangles, counter-clockwise seen from above.
+//
+// Every pixel of a large image would be a huge mesh (an image of 8192 x 8192 pixels has 134 million triangles),
+// so only every --mesh_step'th pixel is sampled. Without --mesh_step the step is chosen so that the mesh has at most
+// MESH_MAX_SIDE vertices per side. The last row and column are always sampled, so the mesh covers the whole image.
+
+// Rust modules
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::File;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::{self, BufWriter, Write};
+
+// Internal modules
+use MandelConfig;
+
+// Most vertices per side of the mesh without --mesh_step: about one million vertices, two million triangles
+pub const MESH_MAX_SIDE: u32 = 1025;
+
+// Distance of the sampled pixels: --mesh_step or the smallest one that keeps the mesh within MESH_MAX_SIDE
+pub fn mesh_step(mandel_config: &MandelConfig) -> u32 {
+    mandel_config.mesh_step.unwrap_or_else(|| {
+        let intervals = mandel_config.img_size.saturating_sub(1);
+        let max_intervals = MESH_MAX_SIDE - 1;
+        ((intervals + max_intervals - 1) / max_intervals).max(1)
+    })
+}
+
+// The sampled pixels of a row or column: 0, step, 2 * step, ... and always the last pixel
+pub fn sample_positions(img_size: u32, step: u32) -> Vec<u32> {
+    let mut positions: Vec<u32> = (0..img_size).step_by(step.max(1) as usize).collect();
+
+    if img_size > 0 && positions.last() != Some(&(img_size - 1)) {
+        positions.push(img_size - 1);
+    }
+
+    positions
+}
+
+// Height of a pixel between 0 (escapes immediately) and 1 (inside of the set) before --mesh_height
+pub fn height_of(mandel_config: &MandelConfig, iter: u32) -> f64 {
+    let iter = iter.min(mandel_config.max_iter) as f64;
+    let max_iter = mandel_config.max_iter as f64;
+
+    if mandel_config.mesh_log {
+        (1.0 + iter).ln() / (1.0 + max_iter).ln()
+    } else {
+        iter / max_iter
+    }
+}
+
+// The vertices of the mesh row by row, width x height of them
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeightField {
+    pub width: usize,
+    pub height: usize,
+    pub vertices: Vec<[f64; 3]>
+}
+
+impl HeightField {
+    pub fn num_of_triangles(&self) -> usize {
+        2 * self.width.saturating_sub(1) * self.height.saturating_sub(1)
+    }
+
+    fn vertex(&self, column: usize, row: usize) -> [f64; 3] {
+        self.vertices[(row * self.width) + column]
+    }
+
+    // Slope of the surface between the two neighbours (one of them is the vertex itself at the border)
+    fn slope(a: [f64; 3], b: [f64; 3], axis: usize) -> f64 {
+        let distance = b[axis] - a[axis];
+        if distance == 0.0 { 0.0 } else { (b[2] - a[2]) / distance }
+    }
+
+    // Normal of each vertex from the central differences of the heights, one-sided at the border
+    pub fn normals(&self) -> Vec<[f64; 3]> {
+        let mut normals = Vec::with_capacity(self.vertices.len());
+
+        for row in 0..self.height {
+            for column in 0..self.width {
+                let dx = HeightField::slope(self.vertex(column.saturating_sub(1), row),
+                    self.vertex((column + 1).min(self.width - 1), row), 0);
+                let dy = HeightField::slope(self.vertex(column, row.saturating_sub(1)),
+                    self.vertex(column, (row + 1).min(self.height - 1)), 1);
+
+                let length = (dx * dx + dy * dy + 1.0).sqrt();
+                normals.push([-dx / length, -dy / length, 1.0 / length]);
+            }
+        }
+
+        normals
+    }
+
+    // The triangles as indices of the vertices, counter-clockwise seen from above
+    pub fn triangles(&self) -> Vec<[usize; 3]> {
+        let mut triangles = Vec::with_capacity(self.num_of_triangles());
+
+        for row in 0..self.height.saturating_sub(1) {
+            for column in 0..self.width.saturating_sub(1) {
+                let lower_left = (row * self.width) + column;
+                let upper_left = lower_left + self.width;
+
+                triangles.push([lower_left, lower_left + 1, upper_left + 1]);
+                triangles.push([lower_left, upper_left + 1, upper_left]);
+            }
+        }
+
+        triangles
+    }
+}
+
+// The height field of an image (row by row, see to_rows) with the step of mesh_step
+pub fn height_field(mandel_config: &MandelConfig, image: &[u32]) -> HeightField {
+    let positions = sample_positions(mandel_config.img_size, mesh_step(mandel_config));
+    let img_size = mandel_config.img_size as f64;
+    let mut vertices = Vec::with_capacity(positions.len() * positions.len());
+
+    for &y in &positions {
+        for &x in &positions {
+            let iter = image[mandel_config.pixel_index(x, y)];
+            vertices.push([(x as f64) / img_size, (y as f64) / img_size,
+                mandel_config.mesh_height * height_of(mandel_config, iter)]);
+        }
+    }
+
+    HeightField{width: positions.len(), height: positions.len(), vertices: vertices}
+}
+
+// Write the height field as Wavefront OBJ with vertex normals, the indices of OBJ start at 1
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_obj(file_name: &str, mandel_config: &MandelConfig, method: &str, field: &HeightField) -> io::Result<()> {
+    let mut buffer = BufWriter::new(File::create(file_name)?);
+
+    writeln!(buffer, "# mandelbrot height field, method: {}, mesh_step: {}, {}", method, mesh_step(mandel_config), mandel_config)?;
+    writeln!(buffer, "# {} vertices, {} triangles", field.vertices.len(), field.num_of_triangles())?;
+    writeln!(buffer, "o mandelbrot")?;
+
+    for vertex in &field.vertices {
+        writeln!(buffer, "v {} {} {}", vertex[0], vertex[1], vertex[2])?;
+    }
+
+    for normal in field.normals() {
+        writeln!(buffer, "vn {} {} {}", normal[0], normal[1], normal[2])?;
+    }
+
+    for triangle in field.triangles() {
+        writeln!(buffer, "f {0}//{0} {1}//{1} {2}//{2}", triangle[0] + 1, triangle[1] + 1, triangle[2] + 1)?;
+    }
+
+    // Flush explicitly, dropping the BufWriter would silently ignore errors
+    buffer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn config(img_size: u32, mesh_step: Option<u32>) -> MandelConfig {
+        MandelConfig{img_size: img_size, max_iter: 100, mesh_step: mesh_step, mesh_height: 0.5, .. MandelConfig::default()}
+    }
+
+    #[test]
+    fn decimation() {
+        assert_eq!(sample_positions(8, 1), vec![0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(sample_positions(8, 3), vec![0, 3, 6, 7]);
+        assert_eq!(sample_positions(8, 7), vec![0, 7]);
+        assert_eq!(sample_positions(8, 100), vec![0, 7]);
+        assert_eq!(sample_positions(1, 4), vec![0]);
+
+        assert_eq!(mesh_step(&config(1024, None)), 1);
+        assert_eq!(mesh_step(&config(2048, None)), 2);
+        assert_eq!(mesh_step(&config(8192, None)), 8);
+        assert_eq!(mesh_step(&config(8192, Some(3))), 3);
+        assert!(sample_positions(8192, mesh_step(&config(8192, None))).len() as u32 <= MESH_MAX_SIDE);
+    }
+
+    #[test]
+    fn heights() {
+        let linear = config(4, None);
+        let log = MandelConfig{mesh_log: true, .. linear.clone()};
+
+        assert_eq!(height_of(&linear, 0), 0.0);
+        assert_eq!(height_of(&linear, 25), 0.25);
+        assert_eq!(height_of(&linear, 100), 1.0);
+        assert_eq!(height_of(&log, 0), 0.0);
+        assert_eq!(height_of(&log, 100), 1.0);
+        assert!(height_of(&log, 25) > 0.6);
+
+        // 4 x 4 pixels, the right half is inside of the set
+        let image: Vec<u32> = (0..16).map(|index| if index % 4 >= 2 { 100 } else { 0 }).collect();
+        let field = height_field(&linear, &image);
+        assert_eq!((field.width, field.height), (4, 4));
+        assert_eq!(field.vertex(1, 2), [0.25, 0.5, 0.0]);
+        assert_eq!(field.vertex(3, 3), [0.75, 0.75, 0.5]);
+    }
+
+    #[test]
+    fn normals_point_up_and_away_from_the_slope() {
+        let flat = height_field(&config(4, None), &[7; 16]);
+        assert!(flat.normals().iter().all(|&normal| normal == [0.0, 0.0, 1.0]));
+
+        // Rising in x: the normals lean to -x
+        let image: Vec<u32> = (0..16).map(|index| (index % 4) * 10).collect();
+        for normal in height_field(&config(4, None), &image).normals() {
+            assert!(normal[0] < 0.0 && normal[1] == 0.0 && normal[2] > 0.0);
+            assert!((normal[0] * normal[0] + normal[2] * normal[2] - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn triangles_are_counter_clockwise() {
+        let field = height_field(&config(3, None), &[0; 9]);
+        let triangles = field.triangles();
+        assert_eq!(triangles.len(), field.num_of_triangles());
+        assert_eq!(triangles.len(), 8);
+
+        for triangle in triangles {
+            let (a, b, c) = (field.vertices[triangle[0]], field.vertices[triangle[1]], field.vertices[triangle[2]]);
+            let z = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+            assert!(z > 0.0);
+        }
+    }
+
+    #[test]
+    fn obj_has_the_vertices_and_faces() {
+        let file_name = env::temp_dir().join(format!("mandel_mesh_{}.obj", ::std::process::id()));
+        let file_name = file_name.to_str().unwrap();
+        let config = config(16, Some(4));
+        let image: Vec<u32> = (0..256).map(|index| index % 101).collect();
+
+        // Pixels 0, 4, 8, 12 and 15 per side
+        write_obj(file_name, &config, "serial", &height_field(&config, &image)).unwrap();
+        let obj = fs::read_to_string(file_name).unwrap();
+        fs::remove_file(file_name).unwrap();
+
+        let count = |prefix: &str| obj.lines().filter(|line| line.starts_with(prefix)).count();
+        assert_eq!(count("v "), 25);
+        assert_eq!(count("vn "), 25);
+        assert_eq!(count("f "), 32);
+
+        // All faces refer to existing vertices
+        for face in obj.lines().filter(|line| line.starts_with("f ")) {
+            for corner in face.split_whitespace().skip(1) {
+                let index: usize = corner.split("//").next().unwrap().parse().unwrap();
+                assert!(index >= 1 && index <= 25, "{}", face);
+            }
+        }
+    }
+}
@@ -0,0 +1,310 @@
+// Colors of the points outside of the set (--color_cycle, --color_offset, --color_scale).
+//
+// The iteration count is first mapped by the scale function, then the range 0 .. max_iter
+// is spread over color_cycle repetitions of the palette and the palette is rotated by
+// color_offset. Without color_cycle one repetition covers PALETTE_LEN iterations, which is
+// the classic mapping of escape_color(iter % 16).
+//
+// With levels (--auto_levels, --levels) only the iteration counts low ..= high are spread over the palette,
+// as if they were 0 ..= high - low and max_iter was high - low + 1. The ones outside are clamped to low or high.
+
+// Rust modules
+use std::fmt;
+use std::str::FromStr;
+
+// Internal modules
+use {escape_color, MandelConfig};
+
+// Number of colors in the palette, see escape_color
+pub const PALETTE_LEN: u32 = 16;
+
+// How the iteration counts are mapped into the palette domain
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ColorScale {
+    Linear,
+    // ln(1 + iter): more colors for the low iteration counts far away from the set
+    Log,
+    Sqrt
+}
+
+impl ColorScale {
+    pub fn apply(&self, iter: f64) -> f64 {
+        match *self {
+            ColorScale::Linear => iter,
+            ColorScale::Log => iter.ln_1p(),
+            ColorScale::Sqrt => iter.sqrt()
+        }
+    }
+}
+
+impl fmt::Display for ColorScale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            ColorScale::Linear => "linear",
+            ColorScale::Log => "log",
+            ColorScale::Sqrt => "sqrt"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ColorScale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColorScale, String> {
+        match s {
+            "linear" => Ok(ColorScale::Linear),
+            "log" => Ok(ColorScale::Log),
+            "sqrt" => Ok(ColorScale::Sqrt),
+            other => Err(format!("Unknown color scale: '{}', must be log, linear or sqrt", other))
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Palette {
+    max_iter: u32,
+    scale: ColorScale,
+    offset: u32,
+    // Palette entries per scaled iteration
+    factor: f64,
+    // The lowest and the highest iteration count of the palette range, None for 0 .. max_iter
+    levels: Option<(u32, u32)>
+}
+
+impl Palette {
+    // A color_cycle larger than max_iter leaves less than one iteration per repetition
+    // (with the linear scale all iterations would get the same color), it falls back to the classic mapping.
+    pub fn new(max_iter: u32, color_cycle: Option<u32>, color_offset: u32, color_scale: ColorScale) -> Palette {
+        Palette::with_range(max_iter, max_iter, None, color_cycle, color_offset, color_scale)
+    }
+
+    // The palette range covers the iteration counts low ..= high (low <= high) instead of 0 .. max_iter,
+    // color_cycle is the number of repetitions in this range. For low == high all exterior points get the same color.
+    pub fn with_levels(max_iter: u32, (low, high): (u32, u32), color_cycle: Option<u32>, color_offset: u32, color_scale: ColorScale) -> Palette {
+        Palette::with_range(max_iter, (high - low) + 1, Some((low, high)), color_cycle, color_offset, color_scale)
+    }
+
+    // range is the number of iteration counts of the palette range, at least 1
+    fn with_range(max_iter: u32, range: u32, levels: Option<(u32, u32)>, color_cycle: Option<u32>, color_offset: u32,
+        color_scale: ColorScale) -> Palette {
+        let num_of_cycles = match color_cycle {
+            Some(color_cycle) if color_cycle <= range => color_cycle as f64,
+            _ => (range as f64) / (PALETTE_LEN as f64)
+        };
+
+        Palette {
+            max_iter: max_iter,
+            scale: color_scale,
+            offset: color_offset % PALETTE_LEN,
+            // Never 0 / 0: validate doesn't allow max_iter 0, levels have at least one iteration count
+            // and all scales are positive for 1
+            factor: (num_of_cycles * (PALETTE_LEN as f64)) / color_scale.apply(range as f64),
+            levels: levels
+        }
+    }
+
+    pub fn from_config(mandel_config: &MandelConfig) -> Palette {
+        Palette::new(mandel_config.max_iter, mandel_config.color_cycle, mandel_config.color_offset, mandel_config.color_scale)
+    }
+
+    // The palette of the configuration over the iteration counts low .. high (see coloring::image_levels)
+    pub fn from_config_with_levels(mandel_config: &MandelConfig, levels: (u32, u32)) -> Palette {
+        Palette::with_levels(mandel_config.max_iter, levels, mandel_config.color_cycle, mandel_config.color_offset, mandel_config.color_scale)
+    }
+
+    // Position of an iteration count in the palette range: clamped to low ..= high and shifted to 0
+    fn level(&self, iter: f64) -> f64 {
+        match self.levels {
+            Some((low, high)) => iter.max(low as f64).min(high as f64) - (low as f64),
+            None => iter
+        }
+    }
+
+    // Palette entry (0 .. PALETTE_LEN) of a point that escaped after iter iterations
+    pub fn index(&self, iter: u32) -> u32 {
+        let position = (self.scale.apply(self.level(iter as f64)) * self.factor) as u64;
+        ((position + (self.offset as u64)) % (PALETTE_LEN as u64)) as u32
+    }
+
+    // Color of a pixel, the inside of the set (iter == max_iter) stays black
+    pub fn color(&self, iter: u32) -> (u8, u8, u8) {
+        if iter >= self.max_iter {
+            (0, 0, 0)
+        } else {
+            escape_color(self.index(iter))
+        }
+    }
+
+    // Color of a continuous iteration count (--coloring=smooth): the palette entries of the two neighbouring
+    // iteration counts are blended. For an integer mu this is color(mu), the inside of the set stays black.
+    pub fn color_smooth(&self, mu: f64) -> (u8, u8, u8) {
+        if mu >= (self.max_iter as f64) {
+            return (0, 0, 0);
+        }
+
+        let position = (self.scale.apply(self.level(mu.max(0.0))) * self.factor) + (self.offset as f64);
+        let index = position.floor() as u64;
+        let t = position - position.floor();
+        let (r1, g1, b1) = escape_color((index % (PALETTE_LEN as u64)) as u32);
+        let (r2, g2, b2) = escape_color(((index + 1) % (PALETTE_LEN as u64)) as u32);
+        let blend = |a: u8, b: u8| ((a as f64) + (((b as f64) - (a as f64)) * t)).round() as u8;
+
+        (blend(r1, r2), blend(g1, g2), blend(b1, b2))
+    }
+
+    // One color per column of a preview strip (--palette_preview): the columns sweep the iterations
+    // 0 .. max_iter - 1 from left to right, so the first and the last column are the colors of 0 and max_iter - 1
+    pub fn preview_colors(&self, width: u32) -> Vec<(u8, u8, u8)> {
+        let last_iter = (self.max_iter - 1) as u64;
+        let last_x = (width.max(2) - 1) as u64;
+
+        (0..width).map(|x| self.color((((x as u64) * last_iter) / last_x) as u32)).collect()
+    }
+
+    // The first iteration count that gets each palette entry, None if no iteration count gets it
+    pub fn first_iters(&self) -> Vec<Option<u32>> {
+        let mut first_iters = vec![None; PALETTE_LEN as usize];
+
+        for iter in 0..self.max_iter {
+            let entry = &mut first_iters[self.index(iter) as usize];
+            if entry.is_none() {
+                *entry = Some(iter);
+            }
+        }
+
+        first_iters
+    }
+}
+
+// The palette entries with their colors and the first iteration count that gets them
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "Palette: {} colors, max_iter: {}, scale: {}, offset: {}", PALETTE_LEN, self.max_iter, self.scale, self.offset));
+
+        for (index, first_iter) in self.first_iters().iter().enumerate() {
+            let (red, green, blue) = escape_color(index as u32);
+            match *first_iter {
+                Some(iter) => try!(writeln!(f, "{:>4}: {:>3} {:>3} {:>3}, from iter {}", index, red, green, blue, iter)),
+                None => try!(writeln!(f, "{:>4}: {:>3} {:>3} {:>3}, not used", index, red, green, blue))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_is_classic_mapping() {
+        for &max_iter in &[1, 7, 16, 100, 4096] {
+            let palette = Palette::new(max_iter, None, 0, ColorScale::Linear);
+            for iter in 0..max_iter {
+                assert_eq!(palette.color(iter), escape_color(iter % 16), "iter {} of {}", iter, max_iter);
+            }
+        }
+    }
+
+    #[test]
+    fn mapping() {
+        // (iter, max_iter, color_cycle, color_offset, color_scale, expected index)
+        let cases = [
+            (0, 256, Some(1), 0, ColorScale::Linear, 0),
+            (128, 256, Some(1), 0, ColorScale::Linear, 8),
+            (255, 256, Some(1), 0, ColorScale::Linear, 15),
+            (128, 256, Some(2), 0, ColorScale::Linear, 0),
+            (128, 256, Some(1), 3, ColorScale::Linear, 11),
+            (255, 256, Some(1), 17, ColorScale::Linear, 0),
+            (5, 16, None, 20, ColorScale::Linear, 9),
+            // sqrt(64) / sqrt(256) = 1 / 2
+            (64, 256, Some(1), 0, ColorScale::Sqrt, 8),
+            // ln(15) / ln(256) = 0.488
+            (14, 255, Some(1), 0, ColorScale::Log, 7),
+            (0, 1, Some(1), 0, ColorScale::Log, 0),
+            (0, 1, None, 5, ColorScale::Sqrt, 5),
+            // Larger than max_iter: classic mapping
+            (3, 10, Some(11), 0, ColorScale::Linear, 3),
+            (3, 10, Some(10), 0, ColorScale::Linear, 0),
+        ];
+
+        for &(iter, max_iter, color_cycle, color_offset, color_scale, expected) in &cases {
+            let palette = Palette::new(max_iter, color_cycle, color_offset, color_scale);
+            assert_eq!(palette.index(iter), expected, "iter {}, max_iter {}, {:?}, {}, {}",
+                iter, max_iter, color_cycle, color_offset, color_scale);
+        }
+    }
+
+    #[test]
+    fn levels_clamp_and_stretch() {
+        // One repetition over 100 ..= 163: 4 iterations per palette entry
+        let palette = Palette::with_levels(1000, (100, 163), Some(1), 0, ColorScale::Linear);
+        let expected = [(0, 0), (100, 0), (104, 1), (131, 7), (163, 15), (500, 15), (999, 15)];
+        for &(iter, index) in &expected {
+            assert_eq!(palette.index(iter), index, "iter {}", iter);
+        }
+        assert_eq!(palette.color(1000), (0, 0, 0));
+        assert_eq!(palette.color_smooth(50.5), palette.color(100));
+
+        // Without color_cycle one palette entry per iteration from low on
+        let palette = Palette::with_levels(1000, (100, 163), None, 0, ColorScale::Linear);
+        assert_eq!(palette.index(117), 1);
+
+        // All exterior points with the same iteration count
+        let palette = Palette::with_levels(1000, (42, 42), Some(1), 3, ColorScale::Log);
+        assert!([0, 42, 999].iter().all(|&iter| palette.index(iter) == 3));
+    }
+
+    #[test]
+    fn interior_stays_black() {
+        for &color_scale in &[ColorScale::Linear, ColorScale::Log, ColorScale::Sqrt] {
+            for &max_iter in &[1, 2, 1000] {
+                let palette = Palette::new(max_iter, Some(3), 7, color_scale);
+                assert_eq!(palette.color(max_iter), (0, 0, 0));
+                assert!(palette.color(max_iter - 1) != (0, 0, 0));
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_colors_blend_the_neighbours() {
+        let palette = Palette::new(1000, None, 0, ColorScale::Linear);
+        assert_eq!(palette.color_smooth(3.0), palette.color(3));
+        // Green is 16 * index: halfway between 3 (48) and 4 (64)
+        assert_eq!(palette.color_smooth(3.5), (255, 56, 0));
+        // 15 blends into 0
+        assert_eq!(palette.color_smooth(15.5).1, 120);
+        assert_eq!(palette.color_smooth(1000.0), (0, 0, 0));
+    }
+
+    #[test]
+    fn preview_sweeps_the_palette() {
+        // One repetition: the first and the last column are the first and the last palette entry
+        let palette = Palette::new(256, Some(1), 0, ColorScale::Linear);
+        let colors = palette.preview_colors(512);
+        assert_eq!(colors.len(), 512);
+        assert_eq!((colors[0], colors[511]), (escape_color(0), escape_color(PALETTE_LEN - 1)));
+        assert_eq!(palette.first_iters(), (0..PALETTE_LEN).map(|index| Some(index * 16)).collect::<Vec<_>>());
+
+        // The offset rotates both ends
+        let palette = Palette::new(256, Some(1), 3, ColorScale::Linear);
+        assert_eq!(palette.preview_colors(100)[0], escape_color(3));
+        assert_eq!(palette.preview_colors(100)[99], escape_color(2));
+
+        // Fewer iterations than palette entries
+        let palette = Palette::new(4, None, 0, ColorScale::Linear);
+        assert_eq!(palette.preview_colors(1), vec![escape_color(0)]);
+        assert_eq!(palette.first_iters()[4], None);
+        assert!(palette.to_string().contains("   4: 255  64   0, not used"));
+    }
+
+    #[test]
+    fn color_scale_round_trip() {
+        for &color_scale in &[ColorScale::Linear, ColorScale::Log, ColorScale::Sqrt] {
+            assert_eq!(color_scale.to_string().parse::<ColorScale>(), Ok(color_scale));
+        }
+        assert!("cubic".parse::<ColorScale>().is_err());
+    }
+}
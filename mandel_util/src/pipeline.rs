@@ -0,0 +1,279 @@
+// A render pipeline of three exchangeable stages:
+//
+// - CoordSource: the point of the complex plane of each pixel
+// - IterEngine: the iteration count of a point
+// - ColorMapper: the color of an iteration count
+//
+// For example a julia set with histogram coloring:
+//
+//     let pipeline = Pipeline::new(PixelGrid::from_config(&config), Julia{max_iter: 256, c: c}, PaletteColors::from_config(&config));
+//     pipeline.render(&config, &mut image);
+//     let rgb = pipeline.with_colors(HistogramColors::new(&image, 256)).map_colors(&config, &image);
+//
+// The stages are Send + Sync, so the rows can be rendered in parallel (render_parallel).
+// The methods of mandel_method don't use it, they call render_row directly (see render_pixel for the workloads).
+// Colorings that need the point and not only the iteration count (smooth, distance, orbit_trap) are in coloring.
+
+// External modules
+use num_complex::Complex64;
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+
+// Internal modules
+use super::{escape_color, mandel_iter_z0, phoenix_iter, pixel_to_complex, MandelConfig};
+use coloring::{cost_color, cumulative_histogram, ramp};
+use palette::Palette;
+
+// The point of the complex plane of pixel (x, y)
+pub trait CoordSource: Send + Sync {
+    fn coord(&self, x: u32, y: u32) -> Complex64;
+}
+
+// The iteration count of point c
+pub trait IterEngine: Send + Sync {
+    fn iterate(&self, c: Complex64) -> u32;
+}
+
+// The color of an iteration count, iter == max_iter is inside of the set
+pub trait ColorMapper: Send + Sync {
+    fn color(&self, iter: u32, max_iter: u32) -> (u8, u8, u8);
+}
+
+// The pixel grid of a configuration: region, img_size and flip_y (see pixel_to_complex)
+#[derive(Clone, Debug)]
+pub struct PixelGrid {
+    config: MandelConfig
+}
+
+impl PixelGrid {
+    pub fn from_config(mandel_config: &MandelConfig) -> PixelGrid {
+        PixelGrid{config: mandel_config.clone()}
+    }
+}
+
+impl CoordSource for PixelGrid {
+    fn coord(&self, x: u32, y: u32) -> Complex64 {
+        pixel_to_complex(&self.config, x, y)
+    }
+}
+
+// The mandelbrot set, the iteration starts at z0 (see mandel_iter_z0)
+#[derive(Copy, Clone, Debug)]
+pub struct Mandelbrot {
+    pub max_iter: u32,
+    pub z0: Complex64
+}
+
+impl IterEngine for Mandelbrot {
+    fn iterate(&self, c: Complex64) -> u32 {
+        mandel_iter_z0(self.max_iter, self.z0, c)
+    }
+}
+
+// The julia set of c: the iteration z = z * z + c starts at the point
+#[derive(Copy, Clone, Debug)]
+pub struct Julia {
+    pub max_iter: u32,
+    pub c: Complex64
+}
+
+impl IterEngine for Julia {
+    fn iterate(&self, point: Complex64) -> u32 {
+        let mut z = point;
+        let mut iter = 0;
+
+        while (z.norm_sqr() <= 4.0) && (iter < self.max_iter) {
+            z = self.c + (z * z);
+            iter = iter + 1;
+        }
+
+        iter
+    }
+}
+
+// The phoenix fractal (see phoenix_iter)
+#[derive(Copy, Clone, Debug)]
+pub struct Phoenix {
+    pub max_iter: u32,
+    pub p: f64,
+    pub q: f64
+}
+
+impl IterEngine for Phoenix {
+    fn iterate(&self, point: Complex64) -> u32 {
+        phoenix_iter(self.max_iter, point, self.p, self.q)
+    }
+}
+
+// The classic colors: escape_color(iter % 16), black inside
+#[derive(Copy, Clone, Debug)]
+pub struct EscapeColors;
+
+impl ColorMapper for EscapeColors {
+    fn color(&self, iter: u32, max_iter: u32) -> (u8, u8, u8) {
+        if iter >= max_iter { (0, 0, 0) } else { escape_color(iter % 16) }
+    }
+}
+
+// The palette of --color_cycle, --color_offset and --color_scale (see palette)
+#[derive(Clone, Debug)]
+pub struct PaletteColors {
+    palette: Palette
+}
+
+impl PaletteColors {
+    pub fn new(palette: Palette) -> PaletteColors {
+        PaletteColors{palette: palette}
+    }
+
+    pub fn from_config(mandel_config: &MandelConfig) -> PaletteColors {
+        PaletteColors::new(Palette::from_config(mandel_config))
+    }
+}
+
+impl ColorMapper for PaletteColors {
+    fn color(&self, iter: u32, max_iter: u32) -> (u8, u8, u8) {
+        if iter >= max_iter { (0, 0, 0) } else { self.palette.color(iter) }
+    }
+}
+
+// Histogram equalization of a rendered image: every color gets the same number of exterior pixels
+// (like --coloring=histogram)
+#[derive(Clone, Debug)]
+pub struct HistogramColors {
+    cumulative: Vec<f64>
+}
+
+impl HistogramColors {
+    pub fn new(image: &[u32], max_iter: u32) -> HistogramColors {
+        HistogramColors{cumulative: cumulative_histogram(image, max_iter)}
+    }
+}
+
+impl ColorMapper for HistogramColors {
+    fn color(&self, iter: u32, max_iter: u32) -> (u8, u8, u8) {
+        match self.cumulative.get(iter as usize) {
+            Some(&fraction) if iter < max_iter => ramp(fraction),
+            _ => (0, 0, 0)
+        }
+    }
+}
+
+// The cost of each pixel (see cost_color), the interior is white
+#[derive(Copy, Clone, Debug)]
+pub struct CostColors;
+
+impl ColorMapper for CostColors {
+    fn color(&self, iter: u32, max_iter: u32) -> (u8, u8, u8) {
+        cost_color(iter, max_iter)
+    }
+}
+
+pub struct Pipeline<C: CoordSource, E: IterEngine, M: ColorMapper> {
+    pub coords: C,
+    pub engine: E,
+    pub colors: M
+}
+
+impl<C: CoordSource, E: IterEngine, M: ColorMapper> Pipeline<C, E, M> {
+    pub fn new(coords: C, engine: E, colors: M) -> Pipeline<C, E, M> {
+        Pipeline{coords: coords, engine: engine, colors: colors}
+    }
+
+    // The same coordinates and iteration with other colors
+    pub fn with_colors<N: ColorMapper>(self, colors: N) -> Pipeline<C, E, N> {
+        Pipeline{coords: self.coords, engine: self.engine, colors: colors}
+    }
+
+    // The iteration counts of row y
+    pub fn render_row(&self, y: u32, row: &mut [u32]) {
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = self.engine.iterate(self.coords.coord(x as u32, y));
+        }
+    }
+
+    // The iteration counts of all img_size x img_size pixels of the configuration, row by row
+    pub fn render(&self, mandel_config: &MandelConfig, image: &mut [u32]) {
+        for (y, row) in image.chunks_mut(mandel_config.img_size as usize).enumerate() {
+            self.render_row(y as u32, row);
+        }
+    }
+
+    // Like render, the rows are computed in parallel
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn render_parallel(&self, mandel_config: &MandelConfig, image: &mut [u32]) {
+        image.par_chunks_mut(mandel_config.img_size as usize).enumerate().for_each(|(y, row)| self.render_row(y as u32, row));
+    }
+
+    // The RGB values of the iteration counts, three bytes per pixel (max_iter of the configuration)
+    pub fn map_colors(&self, mandel_config: &MandelConfig, image: &[u32]) -> Vec<u8> {
+        let mut rgb = Vec::with_capacity(3 * image.len());
+
+        for &iter in image {
+            let (red, green, blue) = self.colors.color(iter, mandel_config.max_iter);
+            rgb.extend_from_slice(&[red, green, blue]);
+        }
+
+        rgb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::render_row;
+
+    fn small_config() -> MandelConfig {
+        let default = MandelConfig::default();
+
+        MandelConfig{img_size: 32, max_iter: 100,
+            .. default}
+    }
+
+    #[test]
+    fn mandelbrot_pipeline_is_render_row() {
+        let config = small_config();
+        let mut expected = vec![0; config.num_of_pixels()];
+        for (y, row) in expected.chunks_mut(32).enumerate() {
+            render_row(&config, y as u32, row);
+        }
+
+        let pipeline = Pipeline::new(PixelGrid::from_config(&config), Mandelbrot{max_iter: 100, z0: config.z0()}, EscapeColors);
+        let mut image = vec![0; config.num_of_pixels()];
+        pipeline.render(&config, &mut image);
+        assert_eq!(image, expected);
+
+        let mut parallel = vec![0; config.num_of_pixels()];
+        pipeline.render_parallel(&config, &mut parallel);
+        assert_eq!(parallel, expected);
+
+        // The classic colors are the ones of the default palette
+        assert_eq!(pipeline.map_colors(&config, &image),
+            pipeline.with_colors(PaletteColors::from_config(&config)).map_colors(&config, &image));
+    }
+
+    #[test]
+    fn julia_with_histogram_colors() {
+        let config = MandelConfig{re1: -1.5, re2: 1.5, img1: -1.5, img2: 1.5, .. small_config()};
+
+        // c = 0: the julia set is the unit circle
+        let julia = Julia{max_iter: 100, c: Complex64{re: 0.0, im: 0.0}};
+        assert_eq!(julia.iterate(Complex64{re: 0.5, im: 0.5}), 100);
+        assert_eq!(julia.iterate(Complex64{re: 1.5, im: 0.0}), 1);
+        assert_eq!(Phoenix{max_iter: 100, p: 0.0, q: 0.0}.iterate(Complex64{re: 1.5, im: 0.0}), 1);
+
+        let pipeline = Pipeline::new(PixelGrid::from_config(&config), Julia{max_iter: 100, c: Complex64{re: -0.8, im: 0.156}}, EscapeColors);
+        let mut image = vec![0; config.num_of_pixels()];
+        pipeline.render(&config, &mut image);
+        assert!(image.contains(&0) && image.contains(&100));
+
+        // The pixels with the most iterations outside of the set are white, inside is black
+        let histogram = HistogramColors::new(&image, 100);
+        let most = image.iter().cloned().filter(|&iter| iter < 100).max().unwrap();
+        let rgb = pipeline.with_colors(histogram).map_colors(&config, &image);
+        let index = image.iter().position(|&iter| iter == most).unwrap();
+        assert_eq!(&rgb[(3 * index)..(3 * index) + 3], &[255, 255, 255]);
+        let index = image.iter().position(|&iter| iter == 100).unwrap();
+        assert_eq!(&rgb[(3 * index)..(3 * index) + 3], &[0, 0, 0]);
+    }
+}
@@ -0,0 +1,119 @@
+// Read PPM images, ASCII (P3) and binary (P6).
+// See http://netpbm.sourceforge.net/doc/ppm.html
+//
+// Files whose name ends with .gz (written with --compress) are decompressed while reading.
+
+// External modules
+use flate2::read::GzDecoder;
+
+// Rust modules
+use std::fs::File;
+use std::io::prelude::Read;
+use std::io::{Error, ErrorKind, Result};
+
+// The content of a PPM image, three color values (red, green, blue) per pixel
+pub struct PpmImage {
+    pub width: u32,
+    pub height: u32,
+    pub max_value: u32,
+    pub data: Vec<u32>
+}
+
+fn invalid_data(file_name: &str, message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("'{}': {}", file_name, message))
+}
+
+// Return the next whitespace separated token of the header, skipping comments.
+// pos is moved behind the token.
+fn next_token<'a>(content: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    loop {
+        while *pos < content.len() && (content[*pos] as char).is_whitespace() {
+            *pos += 1;
+        }
+
+        if *pos < content.len() && content[*pos] == b'#' {
+            while *pos < content.len() && content[*pos] != b'\n' {
+                *pos += 1;
+            }
+        } else {
+            break;
+        }
+    }
+
+    let start = *pos;
+
+    while *pos < content.len() && !(content[*pos] as char).is_whitespace() {
+        *pos += 1;
+    }
+
+    if start == *pos { None } else { Some(&content[start..*pos]) }
+}
+
+fn next_number(file_name: &str, content: &[u8], pos: &mut usize) -> Result<u32> {
+    next_token(content, pos)
+        .and_then(|token| String::from_utf8_lossy(token).parse::<u32>().ok())
+        .ok_or_else(|| invalid_data(file_name, "invalid or missing number"))
+}
+
+// The content of a file, decompressed if the name ends with .gz
+pub fn read_file(file_name: &str) -> Result<Vec<u8>> {
+    let file = try!(File::open(file_name));
+    let mut content = Vec::new();
+
+    if file_name.ends_with(".gz") {
+        try!(GzDecoder::new(file).read_to_end(&mut content));
+    } else {
+        try!((&file).read_to_end(&mut content));
+    }
+
+    Ok(content)
+}
+
+// Read a P3 or P6 PPM image
+pub fn read_ppm(file_name: &str) -> Result<PpmImage> {
+    let content = try!(read_file(file_name));
+
+    let mut pos = 0;
+
+    let binary = match next_token(&content, &mut pos) {
+        Some(b"P3") => false,
+        Some(b"P6") => true,
+        _ => return Err(invalid_data(file_name, "not a P3 or P6 PPM image"))
+    };
+
+    let width = try!(next_number(file_name, &content, &mut pos));
+    let height = try!(next_number(file_name, &content, &mut pos));
+    let max_value = try!(next_number(file_name, &content, &mut pos));
+
+    if max_value == 0 || max_value > 65535 {
+        return Err(invalid_data(file_name, "invalid maximum color value"));
+    }
+
+    let num_of_values = 3 * (width as usize) * (height as usize);
+    let mut data = Vec::with_capacity(num_of_values);
+
+    if binary {
+        // Exactly one whitespace character between header and pixel data
+        pos += 1;
+        let bytes_per_value = if max_value < 256 { 1 } else { 2 };
+
+        if content.len() < pos + (num_of_values * bytes_per_value) {
+            return Err(invalid_data(file_name, "not enough pixel data"));
+        }
+
+        for value in content[pos..(pos + (num_of_values * bytes_per_value))].chunks(bytes_per_value) {
+            data.push(if bytes_per_value == 1 { value[0] as u32 } else { ((value[0] as u32) << 8) | (value[1] as u32) });
+        }
+    } else {
+        for _ in 0..num_of_values {
+            data.push(try!(next_number(file_name, &content, &mut pos)));
+        }
+    }
+
+    Ok(PpmImage{
+        width: width,
+        height: height,
+        max_value: max_value,
+        data: data
+    })
+}
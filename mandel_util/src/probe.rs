@@ -0,0 +1,187 @@
+// Everything about a single point, for debugging a wrong pixel (sub command probe).
+//
+// probe --x=X --y=Y takes a pixel of the image given by the main options,
+// probe --probe_point=RE,IM takes the point itself and finds its pixel if it is inside of the region.
+// orbit --export_orbit=RE,IM writes the whole orbit of a point as CSV (write_orbit_csv).
+
+// External modules
+use num_complex::Complex64;
+
+// Rust modules
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::Write;
+use std::io::{BufWriter, Result};
+
+// Internal modules
+use {mandel_iter_z0, orbit_z0, pixel_to_complex, render_pixel, MandelConfig, Workload};
+
+pub struct Probe {
+    // None if the point is outside of the image
+    pub pixel: Option<(u32, u32)>,
+    pub c: Complex64,
+    pub iter: u32,
+    pub max_iter: u32,
+    // z1, z2, ... (see mandel_iter_z0), empty for the synthetic workload
+    pub orbit: Vec<Complex64>
+}
+
+// The pixel whose cell contains c, the inverse of pixel_to_complex
+pub fn complex_to_pixel(mandel_config: &MandelConfig, c: Complex64) -> Option<(u32, u32)> {
+    let x = ((c.re - mandel_config.re1) / mandel_config.x_step()).floor();
+    let row = ((c.im - mandel_config.img1) / mandel_config.y_step()).floor();
+    let img_size = mandel_config.img_size as f64;
+
+    if x < 0.0 || x >= img_size || row < 0.0 || row >= img_size {
+        return None;
+    }
+
+    // See MandelConfig::row_to_im
+    let y = if mandel_config.flip_y { row as u32 } else { mandel_config.img_size - 1 - (row as u32) };
+    Some((x as u32, y))
+}
+
+// The first max_len points of the orbit of c, it stops after the first point with |z| > 2
+pub fn orbit(z0: Complex64, c: Complex64, max_len: usize) -> Vec<Complex64> {
+    if max_len == 0 {
+        return Vec::new();
+    }
+
+    orbit_z0((max_len - 1).min(u32::MAX as usize) as u32, z0, c)
+}
+
+// Write the orbit of c as CSV: one line per point with iteration, Re(z), Im(z), |z|, starting with z0 as iteration 0.
+// The last line is a comment that says if the point escaped or max_iter was reached.
+pub fn write_orbit_csv(file_name: &str, max_iter: u32, z0: Complex64, c: Complex64) -> Result<()> {
+    let points = orbit_z0(max_iter, z0, c);
+    let mut buffer = BufWriter::new(try!(File::create(file_name)));
+
+    try!(write!(buffer, "iteration,re,im,abs\n"));
+    for (n, z) in Some(z0).iter().chain(points.iter()).enumerate() {
+        try!(write!(buffer, "{},{},{},{}\n", n, z.re, z.im, z.norm_sqr().sqrt()));
+    }
+
+    // mandel_iter_z0 doesn't count the first step z0 -> z1
+    let iter = (points.len() - 1) as u32;
+    if iter < max_iter {
+        try!(write!(buffer, "# escaped after {} iterations (c = {} {:+}i)\n", iter, c.re, c.im));
+    } else {
+        try!(write!(buffer, "# max_iter {} reached, didn't escape (c = {} {:+}i)\n", max_iter, c.re, c.im));
+    }
+
+    buffer.flush()
+}
+
+// Probe the pixel (x, y), the iteration count is the same as in the image
+pub fn probe_pixel(mandel_config: &MandelConfig, x: u32, y: u32, orbit_len: usize) -> Probe {
+    let c = pixel_to_complex(mandel_config, x, y);
+
+    Probe{
+        pixel: Some((x, y)),
+        c: c,
+        iter: render_pixel(mandel_config, x, y),
+        max_iter: mandel_config.max_iter,
+        orbit: if mandel_config.workload == Workload::Fractal { orbit(mandel_config.z0(), c, orbit_len) } else { Vec::new() }
+    }
+}
+
+// Probe the point c, always with the fractal workload
+pub fn probe_point(mandel_config: &MandelConfig, c: Complex64, orbit_len: usize) -> Probe {
+    Probe{
+        pixel: complex_to_pixel(mandel_config, c),
+        c: c,
+        iter: mandel_iter_z0(mandel_config.max_iter, mandel_config.z0(), c),
+        max_iter: mandel_config.max_iter,
+        orbit: orbit(mandel_config.z0(), c, orbit_len)
+    }
+}
+
+impl fmt::Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.pixel {
+            Some((x, y)) => try!(writeln!(f, "Pixel: ({}, {})", x, y)),
+            None => try!(writeln!(f, "Pixel: outside of the image"))
+        }
+        try!(writeln!(f, "Point: {} {:+}i", self.c.re, self.c.im));
+        try!(writeln!(f, "Iterations: {} of {}{}", self.iter, self.max_iter,
+            if self.iter >= self.max_iter { " (inside of the set)" } else { "" }));
+
+        for (n, z) in self.orbit.iter().enumerate() {
+            try!(writeln!(f, "  z{}: {} {:+}i, |z|: {}", n + 1, z.re, z.im, z.norm_sqr().sqrt()));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_config(flip_y: bool) -> MandelConfig {
+        let default = MandelConfig::default();
+
+        MandelConfig{
+            img_size: 16,
+            max_iter: 64,
+            flip_y: flip_y,
+            .. default
+        }
+    }
+
+    #[test]
+    fn orbit_stops_after_escape() {
+        let zero = Complex64{re: 0.0, im: 0.0};
+
+        assert_eq!(orbit(zero, Complex64{re: 1.0, im: 0.0}, 10),
+            vec![Complex64{re: 1.0, im: 0.0}, Complex64{re: 2.0, im: 0.0}, Complex64{re: 5.0, im: 0.0}]);
+        // Period 2 cycle, never escapes
+        assert_eq!(orbit(zero, Complex64{re: -1.0, im: 0.0}, 4).iter().map(|z| z.re).collect::<Vec<_>>(), vec![-1.0, 0.0, -1.0, 0.0]);
+    }
+
+    #[test]
+    fn orbit_csv_of_known_orbits() {
+        let zero = Complex64{re: 0.0, im: 0.0};
+        let file_name = ::std::env::temp_dir().join(format!("mandel_orbit_test_{}.csv", ::std::process::id()));
+        let file_name = file_name.to_str().unwrap();
+        let lines = || ::std::fs::read_to_string(file_name).unwrap().lines().map(|line| line.to_string()).collect::<Vec<_>>();
+
+        // c = 0 stays at 0
+        write_orbit_csv(file_name, 3, zero, zero).unwrap();
+        assert_eq!(lines(), vec!["iteration,re,im,abs", "0,0,0,0", "1,0,0,0", "2,0,0,0", "3,0,0,0", "4,0,0,0",
+            "# max_iter 3 reached, didn't escape (c = 0 +0i)"]);
+
+        // c = -1: period 2
+        write_orbit_csv(file_name, 4, zero, Complex64{re: -1.0, im: 0.0}).unwrap();
+        assert_eq!(lines(), vec!["iteration,re,im,abs", "0,0,0,0", "1,-1,0,1", "2,0,0,0", "3,-1,0,1", "4,0,0,0", "5,-1,0,1",
+            "# max_iter 4 reached, didn't escape (c = -1 +0i)"]);
+
+        // c = 1: 1, 2, 5 escapes after 2 iterations like mandel_iter
+        write_orbit_csv(file_name, 10, zero, Complex64{re: 1.0, im: 0.0}).unwrap();
+        assert_eq!(lines(), vec!["iteration,re,im,abs", "0,0,0,0", "1,1,0,1", "2,2,0,2", "3,5,0,5",
+            "# escaped after 2 iterations (c = 1 +0i)"]);
+
+        ::std::fs::remove_file(file_name).unwrap();
+    }
+
+    #[test]
+    fn probe_agrees_with_image() {
+        for &flip_y in &[false, true] {
+            let config = small_config(flip_y);
+
+            for y in 0..16 {
+                for x in 0..16 {
+                    let probe = probe_pixel(&config, x, y, 0);
+                    assert_eq!(probe.iter, render_pixel(&config, x, y));
+
+                    // The center of the cell belongs to the same pixel
+                    let center = Complex64{re: probe.c.re + (config.x_step() / 2.0), im: probe.c.im + (config.y_step() / 2.0)};
+                    let point = probe_point(&config, center, 0);
+                    assert_eq!(point.pixel, Some((x, y)), "flip_y: {}", flip_y);
+                }
+            }
+
+            assert_eq!(probe_point(&config, Complex64{re: 1.5, im: 0.0}, 0).pixel, None);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+// Random but interesting regions for fuzzing the methods (--random_view=SEED).
+//
+// The same seed always gives the same region, so a mismatch found with
+// --random_view=SEED --compare_parallel_outputs can be reproduced from the seed alone.
+// A candidate region is only taken if a coarse probe (complexity_map) finds pixels inside
+// and outside of the set, regions that are completely inside or outside test nothing.
+
+// Internal modules
+use {complexity_map, splitmix64, MandelConfig, COMPLEXITY_MAX_ITER};
+
+// Give up after this many candidates
+pub const MAX_ATTEMPTS: u32 = 1000;
+
+// The probe has PROBE_TILES x PROBE_TILES pixels
+const PROBE_TILES: u32 = 16;
+
+// Width of the region between 3.0 (the whole set) and 3.0 * 10^-MAX_ZOOM_EXPONENT
+const MAX_ZOOM_EXPONENT: f64 = 6.0;
+
+// Sequence of pseudo random numbers, see splitmix64
+pub struct SplitMix64 {
+    state: u64
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> SplitMix64 {
+        SplitMix64{state: seed}
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        // splitmix64 adds the increment itself
+        let value = splitmix64(self.state);
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        value
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        ((self.next_u64() >> 11) as f64) / ((1u64 << 53) as f64)
+    }
+}
+
+// The configuration with the region set to (re1, im1) .. (re1 + width, im1 + width)
+fn with_region(mandel_config: &MandelConfig, re1: f64, img1: f64, width: f64) -> MandelConfig {
+    MandelConfig{
+        re1: re1,
+        re2: re1 + width,
+        img1: img1,
+        img2: img1 + width,
+        .. mandel_config.clone()
+    }
+}
+
+// True if the coarse probe has pixels inside and outside of the set
+pub fn is_interesting(mandel_config: &MandelConfig) -> bool {
+    let coarse_factor = (mandel_config.img_size / PROBE_TILES).max(1);
+    let probe = complexity_map(mandel_config, coarse_factor);
+    let interior = mandel_config.max_iter.min(COMPLEXITY_MAX_ITER);
+
+    probe.iter().any(|&iter| iter == interior) && probe.iter().any(|&iter| iter < interior)
+}
+
+// The configuration with the random region of the seed, None if no interesting region was found.
+// The center is uniform in the rectangle around the set, the zoom is log uniform.
+pub fn random_view(mandel_config: &MandelConfig, seed: u64) -> Option<MandelConfig> {
+    let mut random = SplitMix64::new(seed);
+
+    for _ in 0..MAX_ATTEMPTS {
+        let center_re = -2.0 + (2.5 * random.next_f64());
+        let center_im = -1.25 + (2.5 * random.next_f64());
+        let width = 3.0 * 10.0f64.powf(-MAX_ZOOM_EXPONENT * random.next_f64());
+
+        let config = with_region(mandel_config, center_re - (width / 2.0), center_im - (width / 2.0), width);
+
+        if is_interesting(&config) {
+            return Some(config);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_config() -> MandelConfig {
+        MandelConfig{img_size: 64, max_iter: 512, .. MandelConfig::default()}
+    }
+
+    #[test]
+    fn same_seed_same_view() {
+        for seed in 0..20 {
+            let a = random_view(&small_config(), seed).unwrap();
+            let b = random_view(&small_config(), seed).unwrap();
+
+            assert_eq!((a.re1, a.re2, a.img1, a.img2), (b.re1, b.re2, b.img1, b.img2));
+            assert!(a.validate().is_ok());
+            assert!(is_interesting(&a));
+        }
+
+        let a = random_view(&small_config(), 1).unwrap();
+        let b = random_view(&small_config(), 2).unwrap();
+        assert!(a.re1 != b.re1);
+    }
+
+    #[test]
+    fn boring_views_are_rejected() {
+        // Completely outside and completely inside of the main cardioid
+        assert!(!is_interesting(&with_region(&small_config(), 1.0, 1.0, 0.5)));
+        assert!(!is_interesting(&with_region(&small_config(), -0.2, -0.1, 0.2)));
+        assert!(is_interesting(&with_region(&small_config(), -2.0, -1.5, 3.0)));
+    }
+
+    #[test]
+    fn splitmix64_sequence() {
+        // First values of the reference implementation for seed 0
+        let mut random = SplitMix64::new(0);
+        assert_eq!(random.next_u64(), 0xE220A8397B1DCDAF);
+        assert_eq!(random.next_u64(), 0x6E789E6AA1B965F4);
+
+        assert!((0..1000).map(|_| random.next_f64()).all(|value| value >= 0.0 && value < 1.0));
+    }
+}
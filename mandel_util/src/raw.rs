@@ -0,0 +1,277 @@
+// Raw dump of the iteration counts, lossless unlike the PPM image.
+//
+// File layout (all values are u32 little endian):
+// magic "MRAW", version, width, height, max_iter, completed_rows, then width * height iteration counts row by row.
+// Only the rows 0 .. completed_rows are computed, the others are zero (see PartialRaw).
+// Version 1 has no completed_rows, all rows are computed.
+//
+// A long render with --resume=FILE writes the finished rows into the file in place and updates completed_rows
+// after each row, so a render that was interrupted (crash, kill) continues with the first missing row.
+
+// Rust modules
+use std::fs::{File, OpenOptions};
+use std::io::prelude::{Read, Write};
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Result, Seek, SeekFrom};
+
+// Internal modules
+use MandelConfig;
+
+const RAW_MAGIC: &'static [u8; 4] = b"MRAW";
+const RAW_VERSION: u32 = 2;
+
+// Position of completed_rows and of the first iteration count in a file of RAW_VERSION
+const COMPLETED_ROWS_OFFSET: u64 = 20;
+const DATA_OFFSET: u64 = 24;
+
+// The content of a raw dump
+pub struct RawImage {
+    pub width: u32,
+    pub height: u32,
+    pub max_iter: u32,
+    pub data: Vec<u32>
+}
+
+pub fn write_u32<W: Write>(writer: &mut W, value: u32) -> Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+pub fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut bytes = [0; 4];
+    try!(reader.read_exact(&mut bytes));
+    Ok(u32::from_le_bytes(bytes))
+}
+
+// Write the iteration counts of a square image as raw dump
+pub fn write_raw(file_name: &str, mandel_config: &MandelConfig, image: &[u32]) -> Result<()> {
+    let mut buffer = BufWriter::new(try!(File::create(file_name)));
+
+    try!(buffer.write_all(RAW_MAGIC));
+    try!(write_u32(&mut buffer, RAW_VERSION));
+    try!(write_u32(&mut buffer, mandel_config.img_size));
+    try!(write_u32(&mut buffer, mandel_config.img_size));
+    try!(write_u32(&mut buffer, mandel_config.max_iter));
+    try!(write_u32(&mut buffer, mandel_config.img_size));
+
+    for value in image {
+        try!(write_u32(&mut buffer, *value));
+    }
+
+    buffer.flush()
+}
+
+// The header of a raw dump
+struct RawHeader {
+    width: u32,
+    height: u32,
+    max_iter: u32,
+    completed_rows: u32,
+    // Position of the first iteration count
+    data_offset: u64
+}
+
+fn read_header<R: Read>(reader: &mut R, file_name: &str) -> Result<RawHeader> {
+    let mut magic = [0; 4];
+    try!(reader.read_exact(&mut magic));
+    if &magic != RAW_MAGIC {
+        return Err(Error::new(ErrorKind::InvalidData, format!("'{}' is not a raw dump", file_name)));
+    }
+
+    let version = try!(read_u32(reader));
+    if version != 1 && version != RAW_VERSION {
+        return Err(Error::new(ErrorKind::InvalidData, format!("'{}': unsupported raw dump version {}", file_name, version)));
+    }
+
+    let width = try!(read_u32(reader));
+    let height = try!(read_u32(reader));
+    let max_iter = try!(read_u32(reader));
+    let (completed_rows, data_offset) = if version == 1 { (height, COMPLETED_ROWS_OFFSET) } else { (try!(read_u32(reader)), DATA_OFFSET) };
+
+    if completed_rows > height {
+        return Err(Error::new(ErrorKind::InvalidData, format!("'{}': {} completed rows, but only {} rows", file_name, completed_rows, height)));
+    }
+
+    Ok(RawHeader{width: width, height: height, max_iter: max_iter, completed_rows: completed_rows, data_offset: data_offset})
+}
+
+// Read a complete raw dump written by write_raw or PartialRaw
+pub fn read_raw(file_name: &str) -> Result<RawImage> {
+    let mut reader = BufReader::new(try!(File::open(file_name)));
+    let header = try!(read_header(&mut reader, file_name));
+    let (width, height, max_iter) = (header.width, header.height, header.max_iter);
+
+    if header.completed_rows < height {
+        return Err(Error::new(ErrorKind::InvalidData, format!("'{}' is not complete: {} of {} rows, continue it with --resume",
+            file_name, header.completed_rows, height)));
+    }
+
+    let num_of_pixels = (width as usize) * (height as usize);
+    let mut data = Vec::with_capacity(num_of_pixels);
+
+    for _ in 0..num_of_pixels {
+        data.push(try!(read_u32(&mut reader)));
+    }
+
+    Ok(RawImage{
+        width: width,
+        height: height,
+        max_iter: max_iter,
+        data: data
+    })
+}
+
+// A raw dump that is written row by row in place (--resume), the rows are added in order.
+// A new file has the full size right away, the rows that are not computed yet are zero.
+pub struct PartialRaw {
+    file: File,
+    img_size: u32,
+    completed_rows: u32,
+    data_offset: u64
+}
+
+impl PartialRaw {
+    // Open the dump to continue it or create a new one. An existing dump must have the img_size and max_iter
+    // of the configuration, the region is not stored (like for --refine it must be given with the same options).
+    pub fn open(file_name: &str, mandel_config: &MandelConfig) -> Result<PartialRaw> {
+        let mut file = try!(OpenOptions::new().read(true).write(true).create(true).truncate(false).open(file_name));
+        let img_size = mandel_config.img_size;
+        let data_len = 4 * (img_size as u64) * (img_size as u64);
+
+        if try!(file.metadata()).len() == 0 {
+            let mut header = Vec::new();
+            header.extend_from_slice(RAW_MAGIC);
+            for value in &[RAW_VERSION, img_size, img_size, mandel_config.max_iter, 0] {
+                try!(write_u32(&mut header, *value));
+            }
+            try!(file.write_all(&header));
+            try!(file.set_len(DATA_OFFSET + data_len));
+
+            return Ok(PartialRaw{file: file, img_size: img_size, completed_rows: 0, data_offset: DATA_OFFSET});
+        }
+
+        let header = try!(read_header(&mut BufReader::new(&mut file), file_name));
+
+        if header.width != img_size || header.height != img_size || header.max_iter != mandel_config.max_iter {
+            return Err(Error::new(ErrorKind::InvalidData, format!("'{}' belongs to a different configuration: {} x {} pixels, max_iter: {}",
+                file_name, header.width, header.height, header.max_iter)));
+        }
+
+        if try!(file.metadata()).len() != header.data_offset + data_len {
+            return Err(Error::new(ErrorKind::InvalidData, format!("'{}' has the wrong size for {} x {} pixels", file_name, img_size, img_size)));
+        }
+
+        Ok(PartialRaw{file: file, img_size: img_size, completed_rows: header.completed_rows, data_offset: header.data_offset})
+    }
+
+    pub fn completed_rows(&self) -> u32 {
+        self.completed_rows
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.completed_rows == self.img_size
+    }
+
+    // Write the next rows (from completed_rows on), completed_rows is updated after each row.
+    // Rows past the end of the image are an error.
+    pub fn write_rows(&mut self, rows: &[u32]) -> Result<()> {
+        let row_len = self.img_size as usize;
+        if rows.len() % row_len != 0 || (self.completed_rows as usize) + rows.len() / row_len > row_len {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("{} values are no rows after row {} of {}",
+                rows.len(), self.completed_rows, self.img_size)));
+        }
+
+        for row in rows.chunks(row_len) {
+            let mut bytes = Vec::with_capacity(4 * row_len);
+            for value in row {
+                try!(write_u32(&mut bytes, *value));
+            }
+
+            try!(self.file.seek(SeekFrom::Start(self.data_offset + (bytes.len() as u64) * (self.completed_rows as u64))));
+            try!(self.file.write_all(&bytes));
+
+            // Only after the row is written, so completed_rows never counts a missing row
+            try!(self.file.seek(SeekFrom::Start(COMPLETED_ROWS_OFFSET)));
+            try!(write_u32(&mut self.file, self.completed_rows + 1));
+            self.completed_rows += 1;
+        }
+
+        Ok(())
+    }
+
+    // All iteration counts row by row, the rows that are not computed yet are zero
+    pub fn read_image(&mut self) -> Result<Vec<u32>> {
+        try!(self.file.seek(SeekFrom::Start(self.data_offset)));
+        let mut reader = BufReader::new(&mut self.file);
+        let num_of_pixels = (self.img_size as usize) * (self.img_size as usize);
+        let mut data = Vec::with_capacity(num_of_pixels);
+
+        for _ in 0..num_of_pixels {
+            data.push(try!(read_u32(&mut reader)));
+        }
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn temp_file(name: &str) -> String {
+        let file_name = env::temp_dir().join(format!("{}_{}.mraw", name, ::std::process::id())).to_str().unwrap().to_string();
+        let _ = fs::remove_file(&file_name);
+        file_name
+    }
+
+    #[test]
+    fn continue_partial_dump() {
+        let config = MandelConfig{img_size: 4, max_iter: 10, .. MandelConfig::default()};
+        let image: Vec<u32> = (0..16).collect();
+        let file_name = temp_file("mandel_partial");
+
+        {
+            let mut raw = PartialRaw::open(&file_name, &config).unwrap();
+            assert_eq!(raw.completed_rows(), 0);
+            raw.write_rows(&image[..4]).unwrap();
+            raw.write_rows(&image[4..8]).unwrap();
+        }
+
+        // Half done: read_raw refuses it, the next open continues after row 2
+        assert_eq!(read_raw(&file_name).err().unwrap().kind(), ErrorKind::InvalidData);
+        let mut raw = PartialRaw::open(&file_name, &config).unwrap();
+        assert_eq!(raw.completed_rows(), 2);
+        assert_eq!(&raw.read_image().unwrap()[..], &[&image[..8], &[0; 8][..]].concat()[..]);
+
+        assert!(raw.write_rows(&image[..3]).is_err());
+        assert!(raw.write_rows(&image[..12]).is_err());
+        raw.write_rows(&image[8..]).unwrap();
+        assert!(raw.is_complete());
+        drop(raw);
+
+        assert_eq!(read_raw(&file_name).unwrap().data, image);
+        assert!(PartialRaw::open(&file_name, &MandelConfig{max_iter: 20, .. config.clone()}).is_err());
+        assert!(PartialRaw::open(&file_name, &MandelConfig{img_size: 8, .. config}).is_err());
+
+        fs::remove_file(&file_name).unwrap();
+    }
+
+    #[test]
+    fn read_version_one() {
+        let file_name = temp_file("mandel_raw_v1");
+        let mut bytes = RAW_MAGIC.to_vec();
+        for value in &[1, 2, 2, 7, 3, 4, 5, 6] {
+            write_u32(&mut bytes, *value).unwrap();
+        }
+        fs::write(&file_name, &bytes).unwrap();
+
+        let raw = read_raw(&file_name).unwrap();
+        assert_eq!((raw.width, raw.height, raw.max_iter, raw.data), (2, 2, 7, vec![3, 4, 5, 6]));
+
+        // Complete, there is nothing left to resume
+        let config = MandelConfig{img_size: 2, max_iter: 7, .. MandelConfig::default()};
+        assert!(PartialRaw::open(&file_name, &config).unwrap().is_complete());
+
+        fs::remove_file(&file_name).unwrap();
+    }
+}
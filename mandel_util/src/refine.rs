@@ -0,0 +1,163 @@
+// Re-render a rectangle of an existing raw dump with a higher max_iter (--refine).
+//
+// The raw dump doesn't store the region of the complex plane, so it must be given with the same options
+// (--re1, --re2, --img1, --img2, --flip_y, ...) as for the original render, only img_size is taken from the dump.
+//
+// The pixels outside of the rectangle are copied unchanged from the base dump. For them the max_iter
+// of the base dump still means "inside of the set", display_values takes care of that for the image.
+
+// Rust modules
+use std::fmt;
+use std::io;
+
+// A rectangle of pixels: x0 .. x1 and y0 .. y1, x1 and y1 are not included
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct PixelRect {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32
+}
+
+impl PixelRect {
+    pub fn width(&self) -> u32 {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> u32 {
+        self.y1 - self.y0
+    }
+
+    pub fn num_of_pixels(&self) -> usize {
+        (self.width() as usize) * (self.height() as usize)
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x0 && x < self.x1 && y >= self.y0 && y < self.y1
+    }
+
+    // Check that the rectangle is not empty and lies inside of an image of the given size
+    pub fn check(&self, width: u32, height: u32) -> io::Result<()> {
+        if self.x0 >= self.x1 || self.y0 >= self.y1 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("empty rectangle: {}, x0 must be less than x1 and y0 less than y1", self)));
+        }
+
+        if self.x1 > width || self.y1 > height {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("rectangle {} is outside of the image ({} x {})", self, width, height)));
+        }
+
+        Ok(())
+    }
+}
+
+// Same form as the --refine option: "X0,Y0,X1,Y1"
+impl fmt::Display for PixelRect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.x0, self.y0, self.x1, self.y1)
+    }
+}
+
+// Parameters of --refine, --refine_iter and --base
+#[derive(Clone, PartialEq, Debug)]
+pub struct RefineJob {
+    pub rect: PixelRect,
+    // max_iter inside of the rectangle
+    pub max_iter: u32,
+    // File name of the raw dump
+    pub base: String
+}
+
+// Parse the rectangle of --refine, for example "100,200,300,400"
+pub fn parse_rect(rect: &str) -> io::Result<PixelRect> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("invalid rectangle: '{}', expected X0,Y0,X1,Y1", rect));
+    let parts: Vec<&str> = rect.split(',').collect();
+
+    if parts.len() != 4 {
+        return Err(invalid());
+    }
+
+    let mut values = [0; 4];
+    for (value, part) in values.iter_mut().zip(parts) {
+        *value = try!(part.trim().parse::<u32>().map_err(|_| invalid()));
+    }
+
+    Ok(PixelRect{x0: values[0], y0: values[1], x1: values[2], y1: values[3]})
+}
+
+// Copy the values of the rectangle (row by row, rect.width() values per row) into the image.
+// All other pixels of the image stay as they are.
+pub fn merge_rect(image: &mut [u32], width: u32, rect: &PixelRect, values: &[u32]) {
+    for (row, y) in values.chunks(rect.width() as usize).zip(rect.y0..rect.y1) {
+        let start = ((y as usize) * (width as usize)) + (rect.x0 as usize);
+        image[start..(start + row.len())].copy_from_slice(row);
+    }
+}
+
+// Values of the merged image for the PPM output with max_iter: pixels outside of the rectangle
+// that reached base_max_iter are inside of the set as well.
+pub fn display_values(image: &[u32], width: u32, rect: &PixelRect, base_max_iter: u32, max_iter: u32) -> Vec<u32> {
+    image.iter().enumerate().map(|(n, &value)| {
+        let (x, y) = ((n % (width as usize)) as u32, (n / (width as usize)) as u32);
+        if value == base_max_iter && !rect.contains(x, y) { max_iter } else { value }
+    }).collect()
+}
+
+// Output file of the refined image: "dump.mraw" -> "dump_refined.<extension>"
+pub fn refined_file_name(base: &str, extension: &str) -> String {
+    let stem = if base.ends_with(".mraw") { &base[..(base.len() - 5)] } else { base };
+    format!("{}_refined.{}", stem, extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_check_rect() {
+        let rect = parse_rect("1, 2,5,4").unwrap();
+
+        assert_eq!(rect, PixelRect{x0: 1, y0: 2, x1: 5, y1: 4});
+        assert_eq!(rect.to_string(), "1,2,5,4");
+        assert_eq!((rect.width(), rect.height(), rect.num_of_pixels()), (4, 2, 8));
+        assert!(rect.contains(1, 2) && rect.contains(4, 3));
+        assert!(!rect.contains(5, 3) && !rect.contains(1, 4) && !rect.contains(0, 2));
+
+        assert!(rect.check(5, 4).is_ok());
+        assert!(rect.check(4, 4).is_err());
+        assert!(rect.check(5, 3).is_err());
+        assert!(parse_rect("1,2,1,4").unwrap().check(8, 8).is_err());
+        assert!(parse_rect("1,2,3").is_err());
+        assert!(parse_rect("1,2,3,-4").is_err());
+    }
+
+    #[test]
+    fn merge_keeps_other_pixels() {
+        let mut image: Vec<u32> = (0..20).collect();
+        let rect = PixelRect{x0: 1, y0: 2, x1: 3, y1: 4};
+
+        merge_rect(&mut image, 5, &rect, &[100, 101, 102, 103]);
+
+        assert_eq!(image, vec![
+            0, 1, 2, 3, 4,
+            5, 6, 7, 8, 9,
+            10, 100, 101, 13, 14,
+            15, 102, 103, 18, 19]);
+    }
+
+    #[test]
+    fn old_interior_stays_inside() {
+        let rect = PixelRect{x0: 0, y0: 0, x1: 1, y1: 1};
+
+        // The first pixel is refined and escapes at 64, the last one was inside with the base max_iter
+        assert_eq!(display_values(&[64, 5, 6, 64], 2, &rect, 64, 1024), vec![64, 5, 6, 1024]);
+    }
+
+    #[test]
+    fn output_file_names() {
+        assert_eq!(refined_file_name("serial_run.mraw", "ppm"), "serial_run_refined.ppm");
+        assert_eq!(refined_file_name("dir/dump.mraw", "mraw"), "dir/dump_refined.mraw");
+        assert_eq!(refined_file_name("dump", "ppm"), "dump_refined.ppm");
+    }
+}
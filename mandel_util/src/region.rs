@@ -0,0 +1,529 @@
+// Regions of the complex plane and zoom paths between them, used by the "zoom" sub command.
+//
+// For a smooth zoom the center moves linearly while the size changes exponentially,
+// so every frame zooms in by the same factor.
+//
+// NestedRegions are the levels of a zoom into one point, each one twice as deep as the one before.
+//
+// Tile is one part of a large image that is rendered by itself (--tile), the tiles are put together afterwards.
+
+// Rust modules
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+// External modules
+use num_complex::Complex64;
+
+// Internal modules
+use super::{mandel_iter_z0, MandelConfig};
+#[cfg(not(target_arch = "wasm32"))]
+use super::{auto_max_iter, render_row, write_image, ImageMetadata};
+#[cfg(not(target_arch = "wasm32"))]
+use denormal::FlushDenormals;
+#[cfg(not(target_arch = "wasm32"))]
+use error::MandelError;
+
+// The visible part of the complex plane
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct MandelbrotRegion {
+    pub re1: f64,
+    pub re2: f64,
+    pub img1: f64,
+    pub img2: f64
+}
+
+impl MandelbrotRegion {
+    // The region of the given configuration
+    pub fn from_config(mandel_config: &MandelConfig) -> MandelbrotRegion {
+        MandelbrotRegion{
+            re1: mandel_config.re1,
+            re2: mandel_config.re2,
+            img1: mandel_config.img1,
+            img2: mandel_config.img2
+        }
+    }
+
+    // Region with the given center and size
+    pub fn from_center(center: (f64, f64), width: f64, height: f64) -> MandelbrotRegion {
+        MandelbrotRegion{
+            re1: center.0 - (width / 2.0),
+            re2: center.0 + (width / 2.0),
+            img1: center.1 - (height / 2.0),
+            img2: center.1 + (height / 2.0)
+        }
+    }
+
+    // Region with the given center where the pixels of the configuration are pixel_size apart
+    pub fn from_pixel_size(center: (f64, f64), pixel_size: f64, mandel_config: &MandelConfig) -> MandelbrotRegion {
+        let size = pixel_size * mandel_config.step_divisor();
+        MandelbrotRegion::from_center(center, size, size)
+    }
+
+    pub fn center(&self) -> (f64, f64) {
+        ((self.re1 + self.re2) / 2.0, (self.img1 + self.img2) / 2.0)
+    }
+
+    pub fn width(&self) -> f64 {
+        self.re2 - self.re1
+    }
+
+    pub fn height(&self) -> f64 {
+        self.img2 - self.img1
+    }
+
+    // Region with the same center, factor times smaller
+    pub fn zoom_in(&self, factor: f64) -> MandelbrotRegion {
+        MandelbrotRegion::from_center(self.center(), self.width() / factor, self.height() / factor)
+    }
+
+    // Copy of the configuration that shows this region, the step sizes follow from the region
+    pub fn apply_to(&self, mandel_config: &MandelConfig) -> MandelConfig {
+        MandelConfig{
+            re1: self.re1,
+            re2: self.re2,
+            img1: self.img1,
+            img2: self.img2,
+            .. mandel_config.clone()
+        }
+    }
+}
+
+// Tile row, col of a grid of of_n x of_n tiles, row 0 is the top of the image (--tile=ROW,COL,OF_N).
+// The region of the configuration is the whole image of of_n * img_size pixels per side, each tile has img_size pixels.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Tile {
+    pub row: u32,
+    pub col: u32,
+    pub of_n: u32
+}
+
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{},{}", self.row, self.col, self.of_n)
+    }
+}
+
+impl FromStr for Tile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Tile, String> {
+        let values: Vec<Option<u32>> = s.split(',').map(|value| value.trim().parse::<u32>().ok()).collect();
+
+        match values.as_slice() {
+            [Some(row), Some(col), Some(of_n)] if row < of_n && col < of_n => Ok(Tile{row: *row, col: *col, of_n: *of_n}),
+            _ => Err(format!("Unknown tile: '{}', must be ROW,COL,OF_N with ROW and COL less than OF_N", s))
+        }
+    }
+}
+
+// The point sampled by index k of num_of_steps steps between start and end. The index is scaled first,
+// so that the edges of neighbouring tiles are exactly the same float, and the last edge is exactly end.
+fn grid_edge(start: f64, end: f64, k: u32, num_of_steps: u32) -> f64 {
+    if k == num_of_steps { end } else { start + ((end - start) * ((k as f64) / (num_of_steps as f64))) }
+}
+
+impl Tile {
+    // The region of the tile. Its first pixel samples the pixel of the whole image where the tile starts.
+    // The half-open tile ends where the next one starts (re2 of a tile is re1 of its right neighbour),
+    // with inclusive_edges the tile ends at its own last pixel and the next one starts one step later.
+    // Either way the tiles sample the points of a single image of of_n * img_size pixels, no point is left out or
+    // sampled twice. Row 0 is the top (img2) unless flip_y is set, like the rows of the image.
+    pub fn region(&self, mandel_config: &MandelConfig) -> MandelbrotRegion {
+        let size = mandel_config.img_size;
+        let total = size * self.of_n;
+        let (num_of_steps, last) = if mandel_config.inclusive_edges { (total - 1, size - 1) } else { (total, size) };
+
+        // Index of the first pixel of the tile, counted from re1 and from img1
+        let x0 = self.col * size;
+        let y0 = if mandel_config.flip_y { self.row * size } else { total - ((self.row + 1) * size) };
+
+        MandelbrotRegion{
+            re1: grid_edge(mandel_config.re1, mandel_config.re2, x0, num_of_steps),
+            re2: grid_edge(mandel_config.re1, mandel_config.re2, x0 + last, num_of_steps),
+            img1: grid_edge(mandel_config.img1, mandel_config.img2, y0, num_of_steps),
+            img2: grid_edge(mandel_config.img1, mandel_config.img2, y0 + last, num_of_steps)
+        }
+    }
+
+    // Copy of the configuration that renders only this tile
+    pub fn apply_to(&self, mandel_config: &MandelConfig) -> MandelConfig {
+        MandelConfig{
+            tile: Some(*self),
+            .. self.region(mandel_config).apply_to(mandel_config)
+        }
+    }
+}
+
+// Region between start (t = 0.0) and end (t = 1.0).
+// The center is interpolated linearly, the width and height exponentially.
+pub fn interpolate_regions(start: &MandelbrotRegion, end: &MandelbrotRegion, t: f64) -> MandelbrotRegion {
+    let (start_re, start_im) = start.center();
+    let (end_re, end_im) = end.center();
+
+    let center = (start_re + ((end_re - start_re) * t), start_im + ((end_im - start_im) * t));
+    let width = start.width() * (end.width() / start.width()).powf(t);
+    let height = start.height() * (end.height() / start.height()).powf(t);
+
+    MandelbrotRegion::from_center(center, width, height)
+}
+
+// All frames of a zoom from start into target_center, the last frame is final_zoom_factor times smaller.
+// The first frame is start and the last frame is centered on target_center.
+pub fn zoom_path(start: &MandelbrotRegion, target_center: (f64, f64), final_zoom_factor: f64, num_frames: u32) -> Vec<MandelbrotRegion> {
+    let end = MandelbrotRegion::from_center(target_center,
+        start.width() / final_zoom_factor, start.height() / final_zoom_factor);
+
+    match num_frames {
+        0 => Vec::new(),
+        1 => vec![*start],
+        _ => (0..num_frames)
+            .map(|frame| interpolate_regions(start, &end, (frame as f64) / ((num_frames - 1) as f64)))
+            .collect()
+    }
+}
+
+// The levels of nested_regions
+pub struct NestedRegions {
+    // Level 0: the default region moved to the center
+    start: MandelbrotRegion,
+    config: MandelConfig,
+    max_zoom: f64,
+    level: u32,
+    num_levels: u32
+}
+
+impl Iterator for NestedRegions {
+    type Item = MandelConfig;
+
+    fn next(&mut self) -> Option<MandelConfig> {
+        // Each level from the start, so that the rounding errors don't add up
+        let zoom = 2.0f64.powi(self.level as i32);
+        if self.level >= self.num_levels || zoom > self.max_zoom {
+            return None;
+        }
+
+        self.level += 1;
+        let config = MandelConfig{max_iter: self.config.max_iter.saturating_mul(self.level), .. self.config.clone()};
+
+        Some(self.start.zoom_in(zoom).apply_to(&config))
+    }
+}
+
+// Configurations of a zoom into center: the first one is the default region (zoom 1) centered on center,
+// each further one is zoomed in twice as far. max_iter grows with the level to keep the details: max_iter, 2 * max_iter, ...
+// Stops after num_levels levels or before the zoom would exceed max_zoom.
+pub fn nested_regions(center: Complex64, max_zoom: f64, num_levels: u32, img_size: u32, max_iter: u32) -> NestedRegions {
+    let default = MandelConfig::default();
+    let config = MandelConfig{img_size: img_size, max_iter: max_iter, .. default.clone()};
+    let default_region = MandelbrotRegion::from_config(&default);
+
+    NestedRegions{
+        start: MandelbrotRegion::from_center((center.re, center.im), default_region.width(), default_region.height()),
+        config: config,
+        max_zoom: max_zoom,
+        level: 0,
+        num_levels: num_levels
+    }
+}
+
+// Result of sample_region, the fractions add up to 1.0 (both are 0.0 without samples)
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct RegionSample {
+    pub interior_fraction: f64,
+    pub exterior_fraction: f64,
+    // Mean number of iterations of the exterior points, 0.0 if there are none
+    pub mean_exterior_iter: f64
+}
+
+// Quick estimate of the content of the region before the full render (--auto_check):
+// iterates a uniform grid of samples x samples points, each in the center of its cell.
+// Always computes the mandelbrot set (with z0 of the configuration), even for the synthetic workload.
+pub fn sample_region(mandel_config: &MandelConfig, samples: u32) -> RegionSample {
+    let region = MandelbrotRegion::from_config(mandel_config);
+    let mut num_of_interior = 0u64;
+    let mut num_of_exterior = 0u64;
+    let mut exterior_iter_sum = 0u64;
+
+    for j in 0..samples {
+        for i in 0..samples {
+            let c = Complex64{
+                re: region.re1 + (((i as f64) + 0.5) * region.width() / (samples as f64)),
+                im: region.img1 + (((j as f64) + 0.5) * region.height() / (samples as f64))
+            };
+            let iter = mandel_iter_z0(mandel_config.max_iter, mandel_config.z0(), c);
+
+            if iter >= mandel_config.max_iter {
+                num_of_interior += 1;
+            } else {
+                num_of_exterior += 1;
+                exterior_iter_sum += iter as u64;
+            }
+        }
+    }
+
+    let num_of_samples = (num_of_interior + num_of_exterior).max(1) as f64;
+
+    RegionSample{
+        interior_fraction: (num_of_interior as f64) / num_of_samples,
+        exterior_fraction: (num_of_exterior as f64) / num_of_samples,
+        mean_exterior_iter: if num_of_exterior > 0 { (exterior_iter_sum as f64) / (num_of_exterior as f64) } else { 0.0 }
+    }
+}
+
+// Render all frames of the zoom path (single threaded) and write them as zoom_0000.ppm, zoom_0001.ppm, ...
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run_zoom(mandel_config: &MandelConfig, target_center: (f64, f64), final_zoom_factor: f64, num_frames: u32) -> Result<(), MandelError> {
+    let path = zoom_path(&MandelbrotRegion::from_config(mandel_config), target_center, final_zoom_factor, num_frames);
+    let mut image = vec![0; (mandel_config.img_size as usize) * (mandel_config.img_size as usize)];
+    let _denormals = FlushDenormals::new(mandel_config);
+
+    for (frame, region) in path.iter().enumerate() {
+        let mut frame_config = region.apply_to(mandel_config).with_interior_results();
+
+        // Deeper frames need more iterations
+        if frame_config.max_iter_auto {
+            frame_config.max_iter = auto_max_iter(frame_config.zoom());
+        }
+
+        for (y, row) in image.chunks_mut(frame_config.img_size as usize).enumerate() {
+            render_row(&frame_config, y as u32, row);
+        }
+
+        let file_name = format!("zoom_{:04}.ppm", frame);
+        let metadata = ImageMetadata::new().add("frame", &format!("{} of {}", frame + 1, path.len()));
+        try!(write_image(&file_name, &frame_config, 0.0, &metadata, &image));
+        println!("Frame {} of {}: {}, re: {} .. {}, img: {} .. {}", frame + 1, path.len(), file_name,
+            region.re1, region.re2, region.img1, region.img2);
+    }
+
+    Ok(())
+}
+
+// Parse the target center of the zoom, for example "-0.75,0.1"
+pub fn parse_center(center: &str) -> io::Result<(f64, f64)> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("invalid center: '{}', expected RE,IM", center));
+    let parts: Vec<&str> = center.split(',').collect();
+
+    if parts.len() != 2 {
+        return Err(invalid());
+    }
+
+    match (parts[0].trim().parse::<f64>(), parts[1].trim().parse::<f64>()) {
+        (Ok(re), Ok(im)) => Ok((re, im)),
+        _ => Err(invalid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pixel_to_complex;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-12, "{} != {}", a, b);
+    }
+
+    fn config_of_size(img_size: u32, inclusive_edges: bool) -> MandelConfig {
+        let config = MandelConfig{img_size: img_size, max_iter: 256, inclusive_edges: inclusive_edges, .. MandelConfig::default()};
+        MandelbrotRegion::from_config(&config).apply_to(&config)
+    }
+
+    #[test]
+    fn tiles_assemble_to_the_whole_image() {
+        for &flip_y in &[false, true] {
+            let whole = MandelConfig{flip_y: flip_y, .. config_of_size(256, false)};
+            let mut expected = vec![0; whole.num_of_pixels()];
+            for (y, row) in expected.chunks_mut(256).enumerate() {
+                render_row(&whole, y as u32, row);
+            }
+
+            let mut assembled = vec![0; whole.num_of_pixels()];
+            for row in 0..2 {
+                for col in 0..2 {
+                    let tile = Tile{row: row, col: col, of_n: 2}.apply_to(&MandelConfig{img_size: 128, .. whole.clone()});
+                    let mut values = vec![0; 128];
+
+                    for y in 0..128 {
+                        render_row(&tile, y, &mut values);
+                        let start = ((((row * 128) + y) * 256) + (col * 128)) as usize;
+                        assembled[start .. start + 128].copy_from_slice(&values);
+                    }
+                }
+            }
+
+            assert!(assembled == expected, "flip_y: {}", flip_y);
+        }
+    }
+
+    #[test]
+    fn tile_edges_are_contiguous() {
+        let config = MandelConfig{re1: -0.7436, re2: -0.7426, img1: 0.1313, img2: 0.1323, .. config_of_size(64, false)};
+        let config = MandelbrotRegion::from_config(&config).apply_to(&config);
+        let tile = |row: u32, col: u32, config: &MandelConfig| Tile{row: row, col: col, of_n: 3}.apply_to(config);
+
+        // Half-open: a tile ends exactly where its neighbours start, the outer tiles end at the region
+        assert_eq!(tile(0, 0, &config).re2, tile(0, 1, &config).re1);
+        assert_eq!(tile(1, 0, &config).img1, tile(2, 0, &config).img2);
+        assert_eq!((tile(0, 0, &config).re1, tile(0, 2, &config).re2), (config.re1, config.re2));
+        assert_eq!((tile(2, 0, &config).img1, tile(0, 0, &config).img2), (config.img1, config.img2));
+        assert_close(tile(1, 1, &config).x_step() * 3.0, config.x_step());
+
+        // Inclusive: the last pixel of a tile is one step before the first one of the next tile
+        let inclusive = MandelConfig{inclusive_edges: true, .. config.clone()};
+        let (left, right) = (tile(0, 0, &inclusive), tile(0, 1, &inclusive));
+        let step = (inclusive.re2 - inclusive.re1) / ((3 * 64 - 1) as f64);
+        assert_close(left.x_step(), step);
+        assert_close(pixel_to_complex(&left, 63, 0).re + step, pixel_to_complex(&right, 0, 0).re);
+        assert_eq!(pixel_to_complex(&tile(0, 2, &inclusive), 63, 0).re, inclusive.re2);
+        assert_eq!(tile(0, 0, &inclusive).row_to_im(0), inclusive.img2);
+
+        assert_eq!("1,2,3".parse::<Tile>(), Ok(Tile{row: 1, col: 2, of_n: 3}));
+        assert!("1,3,3".parse::<Tile>().is_err());
+        assert!("1,2".parse::<Tile>().is_err());
+    }
+
+    #[test]
+    fn interpolation_end_points() {
+        let start = MandelbrotRegion{re1: -2.0, re2: 1.0, img1: -1.5, img2: 1.5};
+        let end = MandelbrotRegion::from_center((-0.75, 0.1), 0.03, 0.03);
+
+        assert_eq!(interpolate_regions(&start, &start, 0.5), start);
+
+        let first = interpolate_regions(&start, &end, 0.0);
+        let last = interpolate_regions(&start, &end, 1.0);
+
+        assert_close(first.re1, start.re1);
+        assert_close(first.img2, start.img2);
+        assert_close(last.re1, end.re1);
+        assert_close(last.img2, end.img2);
+    }
+
+    #[test]
+    fn zoom_is_exponential() {
+        let start = MandelbrotRegion{re1: -2.0, re2: 1.0, img1: -1.5, img2: 1.5};
+        let path = zoom_path(&start, (-0.75, 0.1), 1000.0, 4);
+
+        assert_eq!(path.len(), 4);
+        assert_eq!(path[0], start);
+        assert_close(path[3].width(), 0.003);
+        assert_close(path[3].center().0, -0.75);
+        assert_close(path[3].center().1, 0.1);
+
+        // Every frame zooms in by the same factor: 1000^(1/3) = 10
+        for frames in path.windows(2) {
+            assert_close(frames[0].width() / frames[1].width(), 10.0);
+        }
+
+        assert!(zoom_path(&start, (0.0, 0.0), 2.0, 0).is_empty());
+        assert_eq!(zoom_path(&start, (0.0, 0.0), 2.0, 1), vec![start]);
+    }
+
+    #[test]
+    fn nested_levels_zoom_in_twice_as_far() {
+        let center = Complex64::new(-0.743643887, 0.131825904);
+        let levels: Vec<MandelConfig> = nested_regions(center, 1e6, 5, 256, 200).collect();
+
+        assert_eq!(levels.len(), 5);
+        assert_eq!(levels.iter().map(|config| config.max_iter).collect::<Vec<u32>>(), vec![200, 400, 600, 800, 1000]);
+        assert_close(levels[0].re2 - levels[0].re1, 3.0);
+
+        for (n, config) in levels.iter().enumerate() {
+            let region = MandelbrotRegion::from_config(config);
+            assert_close(region.center().0, center.re);
+            assert_close(region.center().1, center.im);
+            assert_close(config.zoom(), 2.0f64.powi(n as i32));
+            assert_close(config.x_step(), region.width() / 256.0);
+            assert_eq!(config.img_size, 256);
+            assert!(config.validate().is_ok());
+        }
+
+        for pair in levels.windows(2) {
+            assert_close(pair[0].re2 - pair[0].re1, 2.0 * (pair[1].re2 - pair[1].re1));
+        }
+
+        // max_zoom stops before num_levels: zoom 1, 2, 4 and 8
+        assert_eq!(nested_regions(center, 10.0, 100, 256, 200).count(), 4);
+        assert_eq!(nested_regions(center, 0.5, 100, 256, 200).count(), 0);
+        assert_eq!(nested_regions(center, 1e6, 0, 256, 200).count(), 0);
+    }
+
+    #[test]
+    fn apply_recomputes_steps() {
+        let config = MandelConfig{img_size: 100, .. MandelConfig::default()};
+        let region = MandelbrotRegion::from_center((0.0, 0.0), 1.0, 2.0);
+        let region_config = region.apply_to(&config);
+
+        assert_close(region_config.x_step(), 0.01);
+        assert_close(region_config.y_step(), 0.02);
+        assert_eq!(MandelbrotRegion::from_config(&region_config), region);
+
+        let zoomed = region.zoom_in(4.0);
+        assert_eq!(zoomed.center(), region.center());
+        assert_close(zoomed.width(), 0.25);
+        assert_close(zoomed.height(), 0.5);
+    }
+
+    #[test]
+    fn pixel_size_gives_the_same_grid_as_the_corners() {
+        for &inclusive_edges in &[false, true] {
+            // 64 pixels 1/32 apart (65 pixels with inclusive_edges), centered at -0.25 + 0i
+            let size = if inclusive_edges { 65 } else { 64 };
+            let base = MandelConfig{img_size: size, inclusive_edges: inclusive_edges, .. MandelConfig::default()};
+            let corners = MandelConfig{re1: -1.25, re2: 0.75, img1: -1.0, img2: 1.0, .. base.clone()};
+            let from_corner = MandelConfig{re1: -1.25, img1: -1.0, .. base.clone()}.with_pixel_size(1.0 / 32.0);
+            let from_center = MandelbrotRegion::from_pixel_size((-0.25, 0.0), 1.0 / 32.0, &base).apply_to(&base);
+
+            assert_eq!(corners.x_step(), 1.0 / 32.0);
+            for y in 0..size {
+                for x in 0..size {
+                    let c = pixel_to_complex(&corners, x, y);
+                    assert_eq!(pixel_to_complex(&from_corner, x, y), c);
+                    assert_eq!(pixel_to_complex(&from_center, x, y), c);
+                }
+            }
+        }
+
+        // A step size that isn't a power of two: the grids differ only by rounding
+        let base = MandelConfig{img_size: 100, .. MandelConfig::default()};
+        let from_center = MandelbrotRegion::from_pixel_size((-0.7436, 0.1318), 1e-5, &base).apply_to(&base);
+        let corners = MandelConfig{re1: -0.7436 - 5e-4, re2: -0.7436 + 5e-4, img1: 0.1318 - 5e-4, img2: 0.1318 + 5e-4, .. base};
+        let from_corner = MandelConfig{re2: 0.0, img2: 0.0, .. corners.clone()}.with_pixel_size(1e-5);
+
+        assert_eq!(from_center, corners);
+        assert_eq!(from_corner, corners);
+        for &(x, y) in &[(0, 0), (99, 0), (37, 61), (99, 99)] {
+            let c = pixel_to_complex(&corners, x, y);
+            assert!((pixel_to_complex(&from_center, x, y) - c).norm() < 1e-5 * 1e-9);
+            assert!((pixel_to_complex(&from_corner, x, y) - c).norm() < 1e-5 * 1e-9);
+        }
+    }
+
+    #[test]
+    fn sample_interior_and_exterior() {
+        // Inside the main cardioid
+        let inside = MandelbrotRegion::from_center((-0.1, 0.0), 0.2, 0.2).apply_to(&MandelConfig{max_iter: 256, .. MandelConfig::default()});
+        let sample = sample_region(&inside, 8);
+        assert_eq!((sample.interior_fraction, sample.exterior_fraction, sample.mean_exterior_iter), (1.0, 0.0, 0.0));
+
+        // Far outside: everything escapes after the first iteration
+        let outside = MandelbrotRegion::from_center((10.0, 10.0), 1.0, 1.0).apply_to(&MandelConfig{max_iter: 256, .. MandelConfig::default()});
+        let sample = sample_region(&outside, 8);
+        assert_eq!((sample.interior_fraction, sample.exterior_fraction, sample.mean_exterior_iter), (0.0, 1.0, 0.0));
+
+        // The full view has both
+        let sample = sample_region(&MandelConfig{max_iter: 256, .. MandelConfig::default()}, 32);
+        assert!(sample.interior_fraction > 0.1 && sample.interior_fraction < 0.5, "{:?}", sample);
+        assert_close(sample.interior_fraction + sample.exterior_fraction, 1.0);
+        assert!(sample.mean_exterior_iter > 0.0);
+
+        assert_eq!(sample_region(&MandelConfig::default(), 0).interior_fraction, 0.0);
+    }
+
+    #[test]
+    fn parse_zoom_center() {
+        assert_eq!(parse_center("-0.75, 0.1").unwrap(), (-0.75, 0.1));
+        assert!(parse_center("-0.75").is_err());
+        assert!(parse_center("a,b").is_err());
+    }
+}
@@ -0,0 +1,191 @@
+// Schedule trace of the dynamic methods (--trace_schedule, --replay_schedule).
+//
+// The work-stealing methods (job_steal, job_steal_join, kirk_crossbeam) assign the jobs to the workers in a
+// different order in every run, so a mismatch with serial is hard to reproduce. With --trace_schedule=FILE they
+// write the jobs in the order they were started: the worker and the lines of each job (see
+// mandel_method::schedule for the recording). --replay_schedule=FILE computes exactly these jobs in this order
+// on one thread, so the schedule that showed the mismatch can be studied.
+//
+// The trace is a text file, one job per line after the header:
+//
+//     # schedule trace of mandel-rust, one job per line: worker,first_row,num_of_rows
+//     method: job_steal
+//     config: re1=-2;re2=1;img1=-1.5;img2=1.5;max_iter=4096;img_size=2048;num_threads=4
+//     0,0,1
+//     1,1,1
+//
+// The config line is the compact configuration of the traced run (see MandelConfig::apply_config).
+// Rows are lines of the image buffer like the jobs of the methods (see BufferLayout).
+
+// Rust modules
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+// First line of a trace file
+const SCHEDULE_HEADER: &'static str = "# schedule trace of mandel-rust, one job per line: worker,first_row,num_of_rows";
+
+// One job: the worker that computed it (numbered in the order of their first job) and its lines
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScheduleEntry {
+    pub worker: u32,
+    pub first_row: u32,
+    pub num_of_rows: u32
+}
+
+// The jobs of one run in the order they were started
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+    pub method: String,
+    // The compact configuration of the run
+    pub config: String,
+    pub entries: Vec<ScheduleEntry>
+}
+
+impl Schedule {
+    // Number of lines each worker computed, index is the worker
+    pub fn rows_per_worker(&self) -> Vec<u32> {
+        let mut rows = Vec::new();
+
+        for entry in &self.entries {
+            if rows.len() <= entry.worker as usize {
+                rows.resize(entry.worker as usize + 1, 0);
+            }
+            rows[entry.worker as usize] += entry.num_of_rows;
+        }
+
+        rows
+    }
+
+    // Ok if the jobs compute each of the num_of_rows lines exactly once
+    pub fn check(&self, num_of_rows: u32) -> Result<(), String> {
+        let mut computed = vec![false; num_of_rows as usize];
+
+        for entry in &self.entries {
+            for row in entry.first_row..entry.first_row.saturating_add(entry.num_of_rows) {
+                match computed.get_mut(row as usize) {
+                    Some(done) if *done => return Err(format!("row {} is computed twice", row)),
+                    Some(done) => *done = true,
+                    None => return Err(format!("row {} is outside of the image ({} rows)", row, num_of_rows))
+                }
+            }
+        }
+
+        match computed.iter().position(|&done| !done) {
+            Some(row) => Err(format!("row {} is not computed", row)),
+            None => Ok(())
+        }
+    }
+}
+
+// The trace file of a method: the method name is added before the extension, so the traces of the
+// methods of one run don't overwrite each other. For example: trace.txt -> trace_job_steal.txt
+pub fn trace_file_name(file_name: &str, method: &str) -> String {
+    match file_name.rfind('.') {
+        Some(dot) if !file_name[dot..].contains(::std::path::MAIN_SEPARATOR) =>
+            format!("{}_{}{}", &file_name[..dot], method, &file_name[dot..]),
+        _ => format!("{}_{}", file_name, method)
+    }
+}
+
+pub fn write_schedule(file_name: &str, schedule: &Schedule) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(file_name)?);
+
+    writeln!(writer, "{}", SCHEDULE_HEADER)?;
+    writeln!(writer, "method: {}", schedule.method)?;
+    writeln!(writer, "config: {}", schedule.config)?;
+
+    for entry in &schedule.entries {
+        writeln!(writer, "{},{},{}", entry.worker, entry.first_row, entry.num_of_rows)?;
+    }
+
+    writer.flush()
+}
+
+// Read a trace of write_schedule, an invalid line is an error of kind InvalidData
+pub fn read_schedule(file_name: &str) -> io::Result<Schedule> {
+    let invalid = |line_number: usize, line: &str| io::Error::new(io::ErrorKind::InvalidData,
+        format!("line {}: '{}' must be worker,first_row,num_of_rows", line_number, line));
+
+    let mut lines = BufReader::new(File::open(file_name)?).lines();
+    let mut header = |prefix: &str| -> io::Result<String> {
+        let line = lines.next().unwrap_or_else(|| Ok(String::new()))?;
+        if line.starts_with(prefix) {
+            Ok(line[prefix.len()..].trim().to_string())
+        } else {
+            Err(io::Error::new(io::ErrorKind::InvalidData, format!("not a schedule trace, '{}' expected", prefix.trim())))
+        }
+    };
+
+    header(SCHEDULE_HEADER)?;
+    let method = header("method:")?;
+    let config = header("config:")?;
+
+    let mut entries = Vec::new();
+    for (n, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let values: Vec<Option<u32>> = line.split(',').map(|value| value.trim().parse::<u32>().ok()).collect();
+        match values.as_slice() {
+            [Some(worker), Some(first_row), Some(num_of_rows)] =>
+                entries.push(ScheduleEntry{worker: *worker, first_row: *first_row, num_of_rows: *num_of_rows}),
+            _ => return Err(invalid(n + 4, &line))
+        }
+    }
+
+    Ok(Schedule{method: method, config: config, entries: entries})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn entry(worker: u32, first_row: u32, num_of_rows: u32) -> ScheduleEntry {
+        ScheduleEntry{worker: worker, first_row: first_row, num_of_rows: num_of_rows}
+    }
+
+    fn schedule() -> Schedule {
+        Schedule{method: "job_steal".to_string(), config: "re1=-2;re2=1;img_size=8".to_string(),
+            entries: vec![entry(0, 0, 2), entry(1, 4, 4), entry(0, 2, 2)]}
+    }
+
+    #[test]
+    fn schedule_round_trip() {
+        let file_name = env::temp_dir().join(format!("mandel_schedule_{}.txt", ::std::process::id()));
+        let file_name = file_name.to_str().unwrap();
+
+        write_schedule(file_name, &schedule()).unwrap();
+        assert_eq!(read_schedule(file_name).unwrap(), schedule());
+
+        fs::write(file_name, format!("{}\nmethod: kirk_crossbeam\nconfig: img_size=8\n0,0\n", SCHEDULE_HEADER)).unwrap();
+        assert_eq!(read_schedule(file_name).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        fs::write(file_name, "0,0,8\n").unwrap();
+        assert_eq!(read_schedule(file_name).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_file(file_name).unwrap();
+    }
+
+    #[test]
+    fn every_row_exactly_once() {
+        assert_eq!(schedule().check(8), Ok(()));
+        assert_eq!(schedule().rows_per_worker(), vec![4, 4]);
+
+        assert!(schedule().check(16).unwrap_err().contains("row 8 is not computed"));
+        assert!(schedule().check(6).unwrap_err().contains("row 6 is outside"));
+
+        let mut twice = schedule();
+        twice.entries.push(entry(2, 3, 1));
+        assert!(twice.check(8).unwrap_err().contains("row 3 is computed twice"));
+    }
+
+    #[test]
+    fn trace_file_names() {
+        assert_eq!(trace_file_name("trace.txt", "job_steal"), "trace_job_steal.txt");
+        assert_eq!(trace_file_name("trace", "kirk_crossbeam"), "trace_kirk_crossbeam");
+        assert_eq!(trace_file_name("traces.d/trace", "job_steal"), "traces.d/trace_job_steal");
+    }
+}
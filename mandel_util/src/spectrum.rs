@@ -0,0 +1,115 @@
+// Power spectrum of the iteration counts (--power_spectrum), for studying the scaling of the fractal:
+// the image is a 2D signal, its 2D DFT is averaged over rings of the same spatial frequency.
+//
+// The power of a frequency (kx, ky) is |F(kx, ky)|^2 / num_of_pixels, so the sum over all frequencies is the
+// sum of the squared iteration counts (Parseval). Ring r contains the frequencies with round(sqrt(kx^2 + ky^2)) = r
+// for r = 0 .. img_size / 2, the corners beyond img_size / 2 are left out because they don't form full rings.
+// The log-power of a ring is log10(1 + mean power), so an empty or flat ring is 0 and not -inf.
+// Ring 0 is the mean of the image (DC component). If the power falls off like frequency^-beta,
+// log_power against log10(frequency) is a line with the slope -beta.
+
+// External modules
+use rustfft::FftPlanner;
+use rustfft::num_complex::Complex;
+
+// Rust modules
+use std::fs::File;
+use std::io::{BufWriter, Result};
+
+// Columns of the CSV file of write_power_spectrum
+pub const SPECTRUM_HEADER: [&'static str; 3] = ["radius", "frequency", "log_power"];
+
+// The signed frequency of DFT index i of n values: 0, 1, .. n / 2, then the negative ones
+fn signed_frequency(i: usize, n: usize) -> f64 {
+    if i <= n / 2 { i as f64 } else { (i as f64) - (n as f64) }
+}
+
+// The radially averaged log-power spectrum of the img_size x img_size iteration counts of image,
+// img_size / 2 + 1 values (ring 0 .. img_size / 2). Rows first, then columns (both with rustfft).
+pub fn power_spectrum_2d(image: &[u32], img_size: u32) -> Vec<f64> {
+    let n = img_size as usize;
+    assert_eq!(image.len(), n * n, "power_spectrum_2d: the image must have img_size x img_size pixels");
+
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let fft = FftPlanner::new().plan_fft_forward(n);
+
+    // All rows in one call, process() transforms each chunk of n values
+    let mut rows: Vec<Complex<f64>> = image.iter().map(|&value| Complex::new(value as f64, 0.0)).collect();
+    fft.process(&mut rows);
+
+    let mut columns = vec![Complex::new(0.0, 0.0); n * n];
+    for y in 0..n {
+        for x in 0..n {
+            columns[(x * n) + y] = rows[(y * n) + x];
+        }
+    }
+    fft.process(&mut columns);
+
+    let num_of_rings = (n / 2) + 1;
+    let mut sums = vec![0.0; num_of_rings];
+    let mut counts = vec![0u64; num_of_rings];
+
+    for kx in 0..n {
+        for ky in 0..n {
+            let fx = signed_frequency(kx, n);
+            let fy = signed_frequency(ky, n);
+            let ring = ((fx * fx) + (fy * fy)).sqrt().round() as usize;
+
+            if ring < num_of_rings {
+                sums[ring] += columns[(kx * n) + ky].norm_sqr() / ((n * n) as f64);
+                counts[ring] += 1;
+            }
+        }
+    }
+
+    sums.iter().zip(counts.iter())
+        .map(|(&sum, &count)| (1.0 + (sum / (count.max(1) as f64))).log10())
+        .collect()
+}
+
+// Write the spectrum of power_spectrum_2d as CSV: ring, frequency in cycles per pixel (ring / img_size) and log-power
+pub fn write_power_spectrum(file_name: &str, spectrum: &[f64], img_size: u32) -> Result<()> {
+    let mut writer = ::csv::Writer::from_writer(BufWriter::new(try!(File::create(file_name))));
+    try!(writer.write_record(&SPECTRUM_HEADER));
+
+    for (radius, log_power) in spectrum.iter().enumerate() {
+        try!(writer.write_record(&[radius.to_string(), ((radius as f64) / (img_size as f64)).to_string(), log_power.to_string()]));
+    }
+
+    try!(writer.flush());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn flat_image_has_only_the_mean() {
+        let spectrum = power_spectrum_2d(&vec![10; 16 * 16], 16);
+
+        assert_eq!(spectrum.len(), 9);
+        // |F(0, 0)|^2 / 256 = (10 * 256)^2 / 256
+        assert!((spectrum[0] - (1.0 + 25600.0f64).log10()).abs() < 1.0e-9);
+        assert!(spectrum[1..].iter().all(|&log_power| log_power.abs() < 1.0e-9));
+    }
+
+    #[test]
+    fn stripes_peak_at_their_frequency() {
+        // 4 periods across 32 pixels, in x and in y
+        let size = 32;
+        let wave = |i: usize| (100.0 + (50.0 * ((2.0 * PI * 4.0 * (i as f64)) / (size as f64)).cos())).round() as u32;
+        let vertical: Vec<u32> = (0..(size * size)).map(|i| wave(i % size)).collect();
+        let horizontal: Vec<u32> = (0..(size * size)).map(|i| wave(i / size)).collect();
+
+        for image in &[vertical, horizontal] {
+            let spectrum = power_spectrum_2d(image, size as u32);
+            let peak = (1..spectrum.len()).max_by(|&a, &b| spectrum[a].partial_cmp(&spectrum[b]).unwrap()).unwrap();
+            assert_eq!(peak, 4);
+        }
+    }
+}
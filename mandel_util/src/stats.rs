@@ -0,0 +1,252 @@
+// Statistics of the escape times of a rendered image (--stats), helps to choose max_iter:
+// if hardly any exterior pixel escapes close to max_iter, a lower max_iter gives the same image.
+//
+// Pixels with max_iter iterations are interior pixels, all others are exterior pixels.
+//
+// The sums of the iteration counts are the work of the image (--cost_map shows it per pixel): the quadrants
+// with the most iterations are the ones that keep a thread busy when the image is split into a few large parts.
+
+// External modules
+#[cfg(not(target_arch = "wasm32"))]
+use serde_json::Value;
+
+// Rust modules
+use std::fmt;
+
+// Number of buckets of the histogram
+pub const NUM_OF_BUCKETS: usize = 20;
+
+// Names of the quadrants in quadrant_sums
+pub const QUADRANTS: [&str; 4] = ["top left", "top right", "bottom left", "bottom right"];
+
+// Width of the longest bar of the histogram in characters
+const BAR_WIDTH: u64 = 40;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageStats {
+    pub max_iter: u32,
+    pub num_of_pixels: u64,
+    pub num_of_interior: u64,
+    // Fraction of interior pixels, 0.0 for an empty image
+    pub interior_fraction: f64,
+    // Escape counts of the exterior pixels, all 0 if there are none.
+    // The median is the lower one for an even number of exterior pixels.
+    pub exterior_min: u32,
+    pub exterior_max: u32,
+    pub exterior_mean: f64,
+    pub exterior_median: u32,
+    // Number of exterior pixels per bucket, bucket n contains the escape counts
+    // n * max_iter / NUM_OF_BUCKETS .. (n + 1) * max_iter / NUM_OF_BUCKETS
+    pub histogram: [u64; NUM_OF_BUCKETS],
+    // Sum of the iteration counts of all pixels (at most max_iter per pixel)
+    pub iteration_sum: u64,
+    // The same per quadrant of the image, in the order of QUADRANTS.
+    // With an odd width or height the middle column is in the right half and the middle row in the bottom half.
+    pub quadrant_sums: [u64; 4]
+}
+
+// The bucket of an escape count (less than max_iter)
+fn bucket(value: u32, max_iter: u32) -> usize {
+    (((value as u64) * (NUM_OF_BUCKETS as u64)) / (max_iter as u64)) as usize
+}
+
+// First escape count of a bucket
+fn bucket_start(n: usize, max_iter: u32) -> u32 {
+    (((n as u64) * (max_iter as u64)) / (NUM_OF_BUCKETS as u64)) as u32
+}
+
+// Compute the statistics of the iteration counts of an image with rows of width pixels.
+// Values greater than max_iter (for example from a dump with a higher max_iter) count as interior.
+pub fn analyze_image(image: &[u32], width: usize, max_iter: u32) -> ImageStats {
+    let mut exterior: Vec<u32> = image.iter().cloned().filter(|&value| value < max_iter).collect();
+    let num_of_pixels = image.len() as u64;
+    let num_of_interior = num_of_pixels - (exterior.len() as u64);
+
+    let mut histogram = [0; NUM_OF_BUCKETS];
+    for &value in &exterior {
+        histogram[bucket(value, max_iter)] += 1;
+    }
+
+    let width = width.max(1);
+    let height = (image.len() + width - 1) / width;
+    let mut quadrant_sums = [0; 4];
+    for (y, row) in image.chunks(width).enumerate() {
+        for (x, &value) in row.iter().enumerate() {
+            let quadrant = (if y < height / 2 { 0 } else { 2 }) + (if x < width / 2 { 0 } else { 1 });
+            quadrant_sums[quadrant] += value.min(max_iter) as u64;
+        }
+    }
+
+    let (exterior_min, exterior_max, exterior_mean, exterior_median) = if exterior.is_empty() {
+        (0, 0, 0.0, 0)
+    } else {
+        let sum = exterior.iter().fold(0u64, |sum, &value| sum + (value as u64));
+        let mean = (sum as f64) / (exterior.len() as f64);
+        let min = *exterior.iter().min().unwrap();
+        let max = *exterior.iter().max().unwrap();
+        let middle = (exterior.len() - 1) / 2;
+        let median = *exterior.select_nth_unstable(middle).1;
+        (min, max, mean, median)
+    };
+
+    ImageStats{
+        max_iter: max_iter,
+        num_of_pixels: num_of_pixels,
+        num_of_interior: num_of_interior,
+        interior_fraction: if num_of_pixels > 0 { (num_of_interior as f64) / (num_of_pixels as f64) } else { 0.0 },
+        exterior_min: exterior_min,
+        exterior_max: exterior_max,
+        exterior_mean: exterior_mean,
+        exterior_median: exterior_median,
+        histogram: histogram,
+        iteration_sum: quadrant_sums.iter().sum(),
+        quadrant_sums: quadrant_sums
+    }
+}
+
+impl ImageStats {
+    // The same data for the JSON file of --stats
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn to_json(&self) -> Value {
+        let buckets: Vec<Value> = self.histogram.iter().enumerate().map(|(n, &count)| json!({
+            "start": bucket_start(n, self.max_iter),
+            "end": bucket_start(n + 1, self.max_iter),
+            "count": count
+        })).collect();
+
+        json!({
+            "max_iter": self.max_iter,
+            "num_of_pixels": self.num_of_pixels,
+            "num_of_interior": self.num_of_interior,
+            "interior_fraction": self.interior_fraction,
+            "exterior_min": self.exterior_min,
+            "exterior_max": self.exterior_max,
+            "exterior_mean": self.exterior_mean,
+            "exterior_median": self.exterior_median,
+            "histogram": buckets,
+            "iteration_sum": self.iteration_sum,
+            "quadrant_sums": {
+                "top_left": self.quadrant_sums[0],
+                "top_right": self.quadrant_sums[1],
+                "bottom_left": self.quadrant_sums[2],
+                "bottom_right": self.quadrant_sums[3]
+            }
+        })
+    }
+}
+
+// Summary and histogram of the exterior pixels as ASCII bar chart, for example:
+//
+//     interior: 25.00 % (4 of 16 pixels)
+//     exterior escape count: min: 0, max: 63, mean: 20.50, median: 12
+//     iterations: 502, top left: 128 (25.50 %), top right: 102 (20.32 %), bottom left: 200 (39.84 %), bottom right: 72 (14.34 %)
+//         0 ..     3 | ##########                                10 (83.33 %)
+//         3 ..     6 |                                            0 (0.00 %)
+impl fmt::Display for ImageStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "interior: {:.2} % ({} of {} pixels)", self.interior_fraction * 100.0,
+            self.num_of_interior, self.num_of_pixels));
+        try!(writeln!(f, "exterior escape count: min: {}, max: {}, mean: {:.2}, median: {}",
+            self.exterior_min, self.exterior_max, self.exterior_mean, self.exterior_median));
+
+        try!(write!(f, "iterations: {}", self.iteration_sum));
+        for (name, &sum) in QUADRANTS.iter().zip(self.quadrant_sums.iter()) {
+            let percent = if self.iteration_sum > 0 { ((sum as f64) * 100.0) / (self.iteration_sum as f64) } else { 0.0 };
+            try!(write!(f, ", {}: {} ({:.2} %)", name, sum, percent));
+        }
+        try!(writeln!(f));
+
+        let num_of_exterior = self.num_of_pixels - self.num_of_interior;
+        let largest = self.histogram.iter().cloned().max().unwrap_or(0).max(1);
+
+        for (n, &count) in self.histogram.iter().enumerate() {
+            let bar: String = (0..((count * BAR_WIDTH) / largest)).map(|_| '#').collect();
+            let percent = if num_of_exterior > 0 { ((count as f64) * 100.0) / (num_of_exterior as f64) } else { 0.0 };
+
+            try!(writeln!(f, "{:>9} .. {:>9} | {:<40} {} ({:.2} %)", bucket_start(n, self.max_iter),
+                bucket_start(n + 1, self.max_iter), bar, count, percent));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_of_synthetic_buffer() {
+        // 4 interior pixels, 6 exterior pixels with an even count for the median
+        let stats = analyze_image(&[100, 0, 5, 100, 50, 99, 100, 5, 7, 100], 10, 100);
+
+        assert_eq!((stats.num_of_pixels, stats.num_of_interior), (10, 4));
+        assert_eq!(stats.interior_fraction, 0.4);
+        assert_eq!((stats.exterior_min, stats.exterior_max, stats.exterior_median), (0, 99, 5));
+        assert_eq!(stats.exterior_mean, 166.0 / 6.0);
+    }
+
+    #[test]
+    fn histogram_buckets() {
+        // max_iter 100: each bucket is 5 escape counts wide
+        let stats = analyze_image(&[0, 4, 5, 50, 94, 95, 99, 100], 8, 100);
+
+        let mut expected = [0; NUM_OF_BUCKETS];
+        expected[0] = 2;
+        expected[1] = 1;
+        expected[10] = 1;
+        expected[18] = 1;
+        expected[19] = 2;
+        assert_eq!(stats.histogram, expected);
+        assert_eq!(stats.histogram.iter().sum::<u64>(), 7);
+
+        // max_iter smaller than the number of buckets: some buckets stay empty
+        let small = analyze_image(&[0, 1, 2], 3, 3);
+        assert_eq!(small.histogram.iter().sum::<u64>(), 3);
+        assert_eq!((small.histogram[0], small.histogram[6], small.histogram[13]), (1, 1, 1));
+    }
+
+    #[test]
+    fn all_interior_and_empty() {
+        let interior = analyze_image(&[64, 64, 70], 3, 64);
+        assert_eq!(interior.interior_fraction, 1.0);
+        assert_eq!((interior.exterior_min, interior.exterior_max, interior.exterior_mean), (0, 0, 0.0));
+        assert!(interior.to_string().contains("interior: 100.00 % (3 of 3 pixels)"));
+
+        let empty = analyze_image(&[], 1, 64);
+        assert_eq!((empty.num_of_pixels, empty.interior_fraction), (0, 0.0));
+    }
+
+    #[test]
+    fn report_and_json() {
+        let stats = analyze_image(&[0, 0, 0, 10, 20], 5, 20);
+        let report = stats.to_string();
+
+        assert_eq!(report.lines().count(), 3 + NUM_OF_BUCKETS);
+        // The largest bucket gets the full bar
+        assert!(report.contains(&format!("        0 ..         1 | {} 3 (75.00 %)", "#".repeat(40))));
+
+        let json = stats.to_json();
+        assert_eq!(json["interior_fraction"], 0.2);
+        assert_eq!(json["exterior_median"], 0);
+        assert_eq!(json["histogram"].as_array().unwrap().len(), NUM_OF_BUCKETS);
+        assert_eq!(json["histogram"][10]["start"], 10);
+        assert_eq!(json["histogram"][10]["count"], 1);
+        assert_eq!(json["iteration_sum"], 30);
+    }
+
+    #[test]
+    fn iteration_sums_per_quadrant() {
+        // 3 x 3 pixels: the middle row and column belong to the bottom and the right half
+        let stats = analyze_image(&[1, 2, 3,
+                                    4, 5, 6,
+                                    7, 8, 100], 3, 10);
+
+        assert_eq!(stats.quadrant_sums, [1, 2 + 3, 4 + 7, 5 + 6 + 8 + 10]);
+        assert_eq!(stats.iteration_sum, 46);
+        assert!(stats.to_string().contains("iterations: 46, top left: 1 (2.17 %), top right: 5 (10.87 %)"));
+
+        let json = stats.to_json();
+        assert_eq!(json["quadrant_sums"]["bottom_right"], 29);
+    }
+}
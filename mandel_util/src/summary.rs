@@ -0,0 +1,308 @@
+// Summary table at the end of a benchmark run: one row per method with the times and the speedup to serial.
+//
+// The table is built as cells first (summary_table) and styled when it is rendered, so the plain text can be
+// compared in tests. With color the fastest method is green, timeouts and methods that are slower than
+// serial are red. Color is only used on a terminal and if NO_COLOR is not set (--color=auto), see use_color.
+
+// Rust modules
+use std::env;
+use std::fmt;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+
+// Internal modules
+use BenchmarkResult;
+
+// ANSI escape sequences
+const GREEN: &'static str = "\x1b[32m";
+const RED: &'static str = "\x1b[31m";
+const RESET: &'static str = "\x1b[0m";
+
+// When to use colors on the console (--color)
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ColorChoice {
+    Always,
+    Never,
+    // Only if stdout is a terminal and NO_COLOR is not set
+    Auto
+}
+
+impl fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            ColorChoice::Always => "always",
+            ColorChoice::Never => "never",
+            ColorChoice::Auto => "auto"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColorChoice, String> {
+        match s {
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            "auto" => Ok(ColorChoice::Auto),
+            other => Err(format!("Unknown color choice: '{}', must be always, never or auto", other))
+        }
+    }
+}
+
+// True if the console output should be colored. See https://no-color.org: NO_COLOR with any non-empty value
+// disables the colors of auto.
+pub fn use_color(choice: ColorChoice) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").map_or(true, |value| value.is_empty())
+    }
+}
+
+// How a row (or a line) is highlighted
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Style {
+    Plain,
+    // The fastest method
+    Good,
+    // Timed out or slower than serial
+    Bad
+}
+
+// The text with the ANSI colors of the style, unchanged without color
+pub fn paint(text: &str, style: Style, color: bool) -> String {
+    match (style, color) {
+        (Style::Good, true) => format!("{}{}{}", GREEN, text, RESET),
+        (Style::Bad, true) => format!("{}{}{}", RED, text, RESET),
+        _ => text.to_string()
+    }
+}
+
+// Methods with a lower speedup are slower than serial (red). A speedup that is shown as 1.00x is not.
+pub const REGRESSION_SPEEDUP: f64 = 0.995;
+
+// Columns of the summary table
+pub const SUMMARY_HEADER: [&'static str; 7] = ["method", "threads", "mean_ms", "min_ms", "max_ms", "stddev_ms", "speedup"];
+
+// The cells of the summary table and the style of each row
+#[derive(Clone, Debug)]
+pub struct SummaryTable {
+    pub rows: Vec<(Vec<String>, Style)>
+}
+
+// The table of the results in the order they were run. The speedup is the mean time of serial divided by the
+// mean time of the method ("-" without a serial result). Timed out methods have no times.
+pub fn summary_table(results: &[BenchmarkResult]) -> SummaryTable {
+    let serial_ms = results.iter().find(|result| result.method == "serial" && !result.timed_out).map(|result| result.mean_ms);
+    let fastest = results.iter().filter(|result| !result.timed_out)
+        .min_by(|a, b| a.mean_ms.partial_cmp(&b.mean_ms).unwrap()).map(|result| result.method.clone());
+
+    let rows = results.iter().map(|result| {
+        if result.timed_out {
+            let cells = vec![result.method.clone(), result.num_threads.to_string(), "timed out".to_string(),
+                "-".to_string(), "-".to_string(), "-".to_string(), "-".to_string()];
+            return (cells, Style::Bad);
+        }
+
+        let speedup = serial_ms.map(|serial_ms| serial_ms / result.mean_ms);
+        let style = if fastest.as_ref() == Some(&result.method) {
+            Style::Good
+        } else if speedup.map_or(false, |speedup| speedup < REGRESSION_SPEEDUP) {
+            Style::Bad
+        } else {
+            Style::Plain
+        };
+
+        let cells = vec![result.method.clone(), result.num_threads.to_string(), format!("{:.3}", result.mean_ms),
+            format!("{:.3}", result.min_ms), format!("{:.3}", result.max_ms), format!("{:.3}", result.stddev_ms),
+            speedup.map_or("-".to_string(), |speedup| format!("{:.2}x", speedup))];
+        (cells, style)
+    }).collect();
+
+    SummaryTable{rows: rows}
+}
+
+impl SummaryTable {
+    // The table as text, one line per row after the header. The method is left aligned, the numbers right aligned.
+    // Only the rows are colored, the padding is computed without the escape sequences.
+    pub fn render(&self, color: bool) -> String {
+        let mut widths: Vec<usize> = SUMMARY_HEADER.iter().map(|name| name.len()).collect();
+        for &(ref cells, _) in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(cells) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        let line = |cells: &[String]| -> String {
+            cells.iter().zip(&widths).enumerate().map(|(n, (cell, &width))| {
+                if n == 0 { format!("{:<1$}", cell, width) } else { format!("{:>1$}", cell, width) }
+            }).collect::<Vec<_>>().join("  ")
+        };
+
+        let header: Vec<String> = SUMMARY_HEADER.iter().map(|name| name.to_string()).collect();
+        let mut text = line(&header) + "\n";
+
+        for &(ref cells, style) in &self.rows {
+            text += &paint(&line(cells), style, color);
+            text += "\n";
+        }
+
+        text
+    }
+}
+
+// Columns of the parallel efficiency table
+pub const EFFICIENCY_HEADER: [&'static str; 4] = ["method", "threads", "mean_ms", "efficiency"];
+
+// All results of a run (or of a thread sweep) for the parallel efficiency E(n) = T(1) / (n * T(n)),
+// where T(n) is the mean time of the method with n threads
+#[derive(Clone, Debug, Default)]
+pub struct BenchmarkSuite {
+    results: Vec<BenchmarkResult>
+}
+
+impl BenchmarkSuite {
+    pub fn add(&mut self, result: BenchmarkResult) {
+        self.results.push(result);
+    }
+
+    // T(1) is the method with one thread, or serial if the method was not run with one thread.
+    // None if one of the times is missing or timed out.
+    pub fn parallel_efficiency(&self, method: &str, num_threads: u32) -> Option<f64> {
+        let mean_ms = |method: &str, num_threads: Option<u32>| self.results.iter()
+            .find(|result| result.method == method && !result.timed_out && num_threads.map_or(true, |n| result.num_threads == n))
+            .map(|result| result.mean_ms);
+
+        let time_n = mean_ms(method, Some(num_threads))?;
+        let time_1 = mean_ms(method, Some(1)).or_else(|| mean_ms("serial", None))?;
+
+        if num_threads == 0 || time_n <= 0.0 {
+            return None;
+        }
+
+        Some(time_1 / (num_threads as f64 * time_n))
+    }
+
+    // The efficiency of each result as text, in the order they were added. serial doesn't use threads,
+    // it is only the reference T(1) and has no row. Empty if no efficiency can be computed.
+    pub fn summary_table(&self) -> String {
+        let rows: Vec<Vec<String>> = self.results.iter().filter(|result| result.method != "serial").filter_map(|result| {
+            self.parallel_efficiency(&result.method, result.num_threads).map(|efficiency|
+                vec![result.method.clone(), result.num_threads.to_string(), format!("{:.3}", result.mean_ms), format!("{:.2}", efficiency)])
+        }).collect();
+
+        if rows.is_empty() {
+            return String::new();
+        }
+
+        let mut widths: Vec<usize> = EFFICIENCY_HEADER.iter().map(|name| name.len()).collect();
+        for cells in &rows {
+            for (width, cell) in widths.iter_mut().zip(cells) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        let line = |cells: &[String]| -> String {
+            cells.iter().zip(&widths).enumerate().map(|(n, (cell, &width))| {
+                if n == 0 { format!("{:<1$}", cell, width) } else { format!("{:>1$}", cell, width) }
+            }).collect::<Vec<_>>().join("  ")
+        };
+
+        let header: Vec<String> = EFFICIENCY_HEADER.iter().map(|name| name.to_string()).collect();
+        rows.iter().fold(line(&header) + "\n", |text, cells| text + &line(cells) + "\n")
+    }
+
+    // Print the efficiency table, nothing if it's empty
+    pub fn print_summary_table(&self) {
+        let table = self.summary_table();
+
+        if !table.is_empty() {
+            print!("Parallel efficiency T(1) / (n * T(n)):\n{}", table);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(method: &str, mean_ms: f64, timed_out: bool) -> BenchmarkResult {
+        BenchmarkResult{method: method.to_string(), num_threads: 4, mean_ms: mean_ms, min_ms: mean_ms - 1.0,
+            max_ms: mean_ms + 1.0, stddev_ms: 0.5, timed_out: timed_out}
+    }
+
+    fn results() -> Vec<BenchmarkResult> {
+        vec![result("serial", 400.0, false), result("rayon_join", 100.0, false),
+             result("scoped_thread_pool", 125.0, false), result("job_steal", 500.0, false), result("kirk_crossbeam", 1000.0, true)]
+    }
+
+    #[test]
+    fn plain_table() {
+        assert_eq!(summary_table(&results()).render(false),
+            "method              threads    mean_ms   min_ms   max_ms  stddev_ms  speedup\n\
+             serial                    4    400.000  399.000  401.000      0.500    1.00x\n\
+             rayon_join                4    100.000   99.000  101.000      0.500    4.00x\n\
+             scoped_thread_pool        4    125.000  124.000  126.000      0.500    3.20x\n\
+             job_steal                 4    500.000  499.000  501.000      0.500    0.80x\n\
+             kirk_crossbeam            4  timed out        -        -          -        -\n");
+
+        // Without serial there is no speedup
+        assert!(summary_table(&results()[1..2]).render(false).ends_with("  -\n"));
+    }
+
+    #[test]
+    fn styles_of_the_rows() {
+        let table = summary_table(&results());
+        let styles: Vec<Style> = table.rows.iter().map(|&(_, style)| style).collect();
+        assert_eq!(styles, vec![Style::Plain, Style::Good, Style::Plain, Style::Bad, Style::Bad]);
+
+        let colored = table.render(true);
+        let lines: Vec<&str> = colored.lines().collect();
+        assert!(lines[2].starts_with("\x1b[32mrayon_join") && lines[2].ends_with("\x1b[0m"));
+        assert!(lines[4].starts_with("\x1b[31mjob_steal"));
+        assert!(!lines[0].contains('\x1b') && !lines[1].contains('\x1b'));
+
+        // 0.998x is shown as 1.00x, not slower than serial
+        let close = summary_table(&[result("serial", 400.0, false), result("rayon_join", 100.0, false), result("job_steal", 400.8, false)]);
+        assert_eq!(close.rows[2].1, Style::Plain);
+    }
+
+    #[test]
+    fn parallel_efficiency() {
+        let mut suite = BenchmarkSuite::default();
+        suite.add(BenchmarkResult{num_threads: 1, .. result("serial", 400.0, false)});
+        suite.add(BenchmarkResult{num_threads: 1, .. result("rayon_join", 380.0, false)});
+        suite.add(result("rayon_join", 100.0, false));
+        suite.add(result("job_steal", 200.0, false));
+        suite.add(result("kirk_crossbeam", 1000.0, true));
+
+        // rayon_join has its own time with one thread, job_steal uses serial
+        assert_eq!(suite.parallel_efficiency("rayon_join", 4), Some(0.95));
+        assert_eq!(suite.parallel_efficiency("job_steal", 4), Some(0.5));
+        assert_eq!(suite.parallel_efficiency("kirk_crossbeam", 4), None);
+        assert_eq!(suite.parallel_efficiency("rayon_join", 8), None);
+
+        assert_eq!(suite.summary_table(),
+            "method      threads  mean_ms  efficiency\n\
+             rayon_join        1  380.000        1.00\n\
+             rayon_join        4  100.000        0.95\n\
+             job_steal         4  200.000        0.50\n");
+
+        // Without serial and without one thread there is nothing to compare with
+        let mut suite = BenchmarkSuite::default();
+        suite.add(result("rayon_join", 100.0, false));
+        assert_eq!(suite.parallel_efficiency("rayon_join", 4), None);
+        assert_eq!(suite.summary_table(), "");
+    }
+
+    #[test]
+    fn color_choices() {
+        assert!(use_color(ColorChoice::Always));
+        assert!(!use_color(ColorChoice::Never));
+        assert_eq!("auto".parse::<ColorChoice>(), Ok(ColorChoice::Auto));
+        assert!("sometimes".parse::<ColorChoice>().is_err());
+        assert_eq!(paint("text", Style::Bad, false), "text");
+    }
+}
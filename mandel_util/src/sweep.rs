@@ -0,0 +1,70 @@
+// Thread sweep (--num_threads_sweep=START,END,STEP): the selected methods are run once for every number of
+// threads from START to END, the results are appended to the benchmark files like the ones of a normal run.
+// At the end the number of threads with the best mean time of each method is printed.
+
+// Internal modules
+use BenchmarkResult;
+
+// The numbers of threads of the sweep: START, START + STEP, ... up to END (included if it is reached)
+pub fn thread_counts(sweep: (u32, u32, u32)) -> Vec<u32> {
+    let (start, end, step) = sweep;
+    (start..end.saturating_add(1)).step_by(step.max(1) as usize).collect()
+}
+
+// START,END,STEP of --num_threads_sweep, None if it isn't three numbers
+pub fn parse_sweep(sweep: &str) -> Option<(u32, u32, u32)> {
+    let values: Vec<Option<u32>> = sweep.split(',').map(|value| value.trim().parse::<u32>().ok()).collect();
+
+    match values.as_slice() {
+        [Some(start), Some(end), Some(step)] => Some((*start, *end, *step)),
+        _ => None
+    }
+}
+
+// The result with the best mean time of each method, in the order of the first result of each method.
+// Timed out results are left out.
+pub fn best_results(results: &[BenchmarkResult]) -> Vec<BenchmarkResult> {
+    let mut best: Vec<BenchmarkResult> = Vec::new();
+
+    for result in results.iter().filter(|result| !result.timed_out) {
+        match best.iter_mut().find(|best| best.method == result.method) {
+            Some(best) => if result.mean_ms < best.mean_ms { *best = result.clone() },
+            None => best.push(result.clone())
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(method: &str, num_threads: u32, mean_ms: f64) -> BenchmarkResult {
+        BenchmarkResult{method: method.to_string(), num_threads: num_threads, mean_ms: mean_ms,
+            min_ms: mean_ms, max_ms: mean_ms, stddev_ms: 0.0, timed_out: false}
+    }
+
+    #[test]
+    fn sweep_thread_counts() {
+        assert_eq!(parse_sweep("1,16,1").map(thread_counts), Some((1..17).collect()));
+        assert_eq!(parse_sweep(" 2, 9, 3 ").map(thread_counts), Some(vec![2, 5, 8]));
+        assert_eq!(parse_sweep("4,4,1").map(thread_counts), Some(vec![4]));
+        assert_eq!(parse_sweep("1,16"), None);
+        assert_eq!(parse_sweep("1,16,1,1"), None);
+        assert_eq!(parse_sweep("1,x,1"), None);
+    }
+
+    #[test]
+    fn best_result_of_each_method() {
+        let timed_out = BenchmarkResult{timed_out: true, .. result("rayon_join", 8, 1.0)};
+        let results = vec![result("rayon_join", 1, 40.0), result("job_steal", 1, 42.0), result("rayon_join", 2, 21.0),
+                           result("job_steal", 2, 22.0), result("rayon_join", 4, 25.0), timed_out];
+
+        let best = best_results(&results);
+
+        assert_eq!(best.iter().map(|best| (best.method.as_str(), best.num_threads)).collect::<Vec<_>>(),
+            vec![("rayon_join", 2), ("job_steal", 2)]);
+        assert!(best_results(&[]).is_empty());
+    }
+}
@@ -0,0 +1,125 @@
+// Cache of computed images on disk (--tile_cache=DIR, --clear_cache).
+//
+// When only the colors change (--color_cycle, --color_scale, ...) the iteration counts stay the same,
+// so they are stored after each render and read back by the next run with the same parameters.
+// The file name is <region hash>_<img_size>_<max_iter>.bin, the content is a raw dump (see raw).
+// The region hash is a CRC32 of the four boundary floats, together with the other parameters that
+// change the iteration counts (z0, flip_y, inclusive_edges and the workload).
+
+// Rust modules
+use std::fs;
+use std::io::{ErrorKind, Result};
+use std::path::Path;
+
+// Internal modules
+use raw::{read_raw, write_raw};
+use {MandelConfig, Workload};
+
+// CRC-32 (IEEE 802.3, as used by zip and PNG), bitwise: the cache hashes only a few bytes
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+// Hash of all parameters that change the iteration counts, except img_size and max_iter (they are in the file name)
+pub fn region_hash(mandel_config: &MandelConfig) -> u32 {
+    let mut bytes = Vec::new();
+
+    for value in &[mandel_config.re1, mandel_config.re2, mandel_config.img1, mandel_config.img2] {
+        bytes.extend_from_slice(&value.to_bits().to_le_bytes());
+    }
+
+    // Not part of the region, but two images that only differ in them must not share a file.
+    // Nothing is added for the defaults, so that the hash of a plain region is the CRC32 of its four floats.
+    if mandel_config.has_z0() {
+        bytes.extend_from_slice(&mandel_config.z0_re.to_bits().to_le_bytes());
+        bytes.extend_from_slice(&mandel_config.z0_im.to_bits().to_le_bytes());
+    }
+    if mandel_config.flip_y {
+        bytes.extend_from_slice(b"flip_y");
+    }
+    if mandel_config.inclusive_edges {
+        bytes.extend_from_slice(b"inclusive_edges");
+    }
+    if mandel_config.workload != Workload::Fractal {
+        bytes.extend_from_slice(mandel_config.workload_name().as_bytes());
+    }
+
+    crc32(&bytes)
+}
+
+// The cache file of the configuration in the directory
+pub fn cache_file_name(cache_dir: &str, mandel_config: &MandelConfig) -> String {
+    Path::new(cache_dir).join(format!("{:08x}_{}_{}.bin", region_hash(mandel_config), mandel_config.img_size, mandel_config.max_iter))
+        .to_string_lossy().into_owned()
+}
+
+// The cached image, None if there is none (or it doesn't fit, for example after a hash collision)
+pub fn load(cache_dir: &str, mandel_config: &MandelConfig) -> Option<Vec<u32>> {
+    match read_raw(&cache_file_name(cache_dir, mandel_config)) {
+        Ok(raw) => if raw.width == mandel_config.img_size && raw.height == mandel_config.img_size && raw.max_iter == mandel_config.max_iter {
+            Some(raw.data)
+        } else {
+            None
+        },
+        Err(_) => None
+    }
+}
+
+// Store the image, the directory is created if it doesn't exist
+pub fn store(cache_dir: &str, mandel_config: &MandelConfig, image: &[u32]) -> Result<()> {
+    try!(fs::create_dir_all(cache_dir));
+    write_raw(&cache_file_name(cache_dir, mandel_config), mandel_config, image)
+}
+
+// Delete the cache directory with all files, a missing directory is not an error
+pub fn clear(cache_dir: &str) -> Result<()> {
+    match fs::remove_dir_all(cache_dir) {
+        Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        result => result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn store_load_clear() {
+        let cache_dir = temp_dir().join(format!("mandel_tile_cache_test_{}", ::std::process::id())).to_str().unwrap().to_string();
+        let config = MandelConfig{img_size: 4, max_iter: 20, .. MandelConfig::default()};
+        let image: Vec<u32> = (0..16).collect();
+
+        assert_eq!(load(&cache_dir, &config), None);
+        store(&cache_dir, &config, &image).unwrap();
+        assert_eq!(load(&cache_dir, &config), Some(image));
+
+        // Any parameter that changes the iteration counts misses
+        assert_eq!(load(&cache_dir, &MandelConfig{max_iter: 21, .. config.clone()}), None);
+        assert_eq!(load(&cache_dir, &MandelConfig{re1: -1.9, .. config.clone()}), None);
+        assert_eq!(load(&cache_dir, &MandelConfig{flip_y: true, .. config.clone()}), None);
+        assert_eq!(load(&cache_dir, &MandelConfig{z0_re: 0.1, .. config.clone()}), None);
+
+        // The colors don't matter
+        assert!(load(&cache_dir, &MandelConfig{color_offset: 3, .. config.clone()}).is_some());
+
+        clear(&cache_dir).unwrap();
+        assert!(!Path::new(&cache_dir).exists());
+        clear(&cache_dir).unwrap();
+    }
+}
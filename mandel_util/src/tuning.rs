@@ -0,0 +1,282 @@
+// Auto tuning (--auto_tune): find the fastest number of threads and chunk_rows for a method.
+//
+// A calibration run renders a small version of the image (at most 256 x 256 pixels and 1024 iterations)
+// for every combination of thread count and chunk_rows and picks the fastest one.
+// The calibration of one method stops after CALIBRATION_BUDGET_MS, the remaining combinations are skipped.
+//
+// The results are cached per CPU model in plot/autotune_<cpu_model>.json and reused by the next runs
+// unless --retune is given:
+//
+//     {"cpu_model": "...", "methods": {"rayon_join": {"num_threads": 8, "chunk_rows": 4, "time_ms": 1.5}}}
+
+// External modules
+use num_cpus;
+use serde_json::{self, Map, Value};
+use time::precise_time_ns;
+
+// Rust modules
+use std::fs::{self, File};
+use std::io::prelude::{Read, Write};
+use std::io;
+use std::path::Path;
+
+// Internal modules
+use super::MandelConfig;
+
+// Time limit for the calibration of one method
+pub const CALIBRATION_BUDGET_MS: f64 = 2000.0;
+
+const CALIBRATION_IMG_SIZE: u32 = 256;
+const CALIBRATION_MAX_ITER: u32 = 1024;
+const CHUNK_ROWS_CANDIDATES: [u32; 5] = [1, 2, 4, 8, 16];
+// Each combination is measured this many times, the fastest time counts
+const CALIBRATION_RUNS: u32 = 2;
+
+// Fastest combination of a calibration
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TuneResult {
+    pub num_threads: u32,
+    pub chunk_rows: u32,
+    // Time of the calibration image
+    pub time_ms: f64
+}
+
+impl TuneResult {
+    // The configuration for the full run
+    pub fn apply_to(&self, mandel_config: &MandelConfig) -> MandelConfig {
+        MandelConfig{num_threads: self.num_threads, chunk_rows: self.chunk_rows, .. mandel_config.clone()}
+    }
+}
+
+// Small version of the image for the calibration: same region, fewer pixels and iterations
+pub fn calibration_config(mandel_config: &MandelConfig) -> MandelConfig {
+    let img_size = mandel_config.img_size.min(CALIBRATION_IMG_SIZE);
+
+    MandelConfig{
+        img_size: img_size,
+        max_iter: mandel_config.max_iter.min(CALIBRATION_MAX_ITER),
+        num_of_runs: 1,
+        no_ppm: true,
+        raw_output: false,
+        .. mandel_config.clone()
+    }
+}
+
+// Upper limit of the thread count: all cores
+pub fn max_threads() -> u32 {
+    num_cpus::get() as u32
+}
+
+// Thread counts 1, 2, 4, ... up to max_threads, max_threads itself is always included
+pub fn thread_candidates(max_threads: u32) -> Vec<u32> {
+    let mut threads: Vec<u32> = (0..32).map(|n| 1 << n).take_while(|&n| n < max_threads).collect();
+    threads.push(max_threads.max(1));
+    threads
+}
+
+// All combinations of thread count and chunk_rows to try.
+// Without chunking only chunk_rows = 1 is used, chunks larger than the image are left out.
+pub fn candidates(max_threads: u32, img_size: u32, chunking: bool) -> Vec<(u32, u32)> {
+    let chunk_rows: Vec<u32> = if chunking {
+        CHUNK_ROWS_CANDIDATES.iter().cloned().filter(|&rows| rows == 1 || rows <= img_size).collect()
+    } else {
+        vec![1]
+    };
+
+    thread_candidates(max_threads).iter()
+        .flat_map(|&num_threads| chunk_rows.iter().map(move |&rows| (num_threads, rows)))
+        .collect()
+}
+
+// Measure each candidate with measure(num_threads, chunk_rows), which returns the time in ms,
+// and return the fastest one. Stops when the budget is used up.
+// Returns None if there are no candidates.
+pub fn calibrate<F>(candidates: &[(u32, u32)], budget_ms: f64, mut measure: F) -> Option<TuneResult>
+    where F: FnMut(u32, u32) -> f64 {
+    let start_time = precise_time_ns();
+    let mut best: Option<TuneResult> = None;
+
+    for (n, &(num_threads, chunk_rows)) in candidates.iter().enumerate() {
+        if (n > 0) && (elapsed_ms(start_time) > budget_ms) {
+            println!("Calibration time limit of {} ms reached, {} of {} combinations measured",
+                budget_ms, n, candidates.len());
+            break;
+        }
+
+        let time_ms = (0..CALIBRATION_RUNS).map(|_| measure(num_threads, chunk_rows)).fold(::std::f64::MAX, f64::min);
+
+        if best.map_or(true, |best| time_ms < best.time_ms) {
+            best = Some(TuneResult{num_threads: num_threads, chunk_rows: chunk_rows, time_ms: time_ms});
+        }
+    }
+
+    best
+}
+
+fn elapsed_ms(start_time: u64) -> f64 {
+    ((precise_time_ns() - start_time) as f64) / (1000.0 * 1000.0)
+}
+
+// Model name of the CPU from /proc/cpuinfo, "unknown" if it's not available
+pub fn cpu_model() -> String {
+    let mut content = String::new();
+
+    if File::open("/proc/cpuinfo").and_then(|mut file| file.read_to_string(&mut content)).is_ok() {
+        for line in content.lines() {
+            if line.starts_with("model name") {
+                if let Some(model) = line.splitn(2, ':').nth(1) {
+                    return model.trim().to_string();
+                }
+            }
+        }
+    }
+
+    "unknown".to_string()
+}
+
+// Only letters, digits and single underscores, so that the CPU model can be used in a file name
+fn file_name_part(text: &str) -> String {
+    let mut result = String::new();
+
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() {
+            result.push(c.to_ascii_lowercase());
+        } else if !result.is_empty() && !result.ends_with('_') {
+            result.push('_');
+        }
+    }
+
+    result.trim_end_matches('_').to_string()
+}
+
+// Cache file of this machine: plot/autotune_<cpu_model>.json
+pub fn cache_file_name() -> String {
+    format!("plot/autotune_{}.json", file_name_part(&cpu_model()))
+}
+
+fn read_json(file_name: &str) -> Option<Value> {
+    let mut content = String::new();
+
+    if File::open(file_name).and_then(|mut file| file.read_to_string(&mut content)).is_err() {
+        return None;
+    }
+
+    serde_json::from_str(&content).ok()
+}
+
+// Cached result of a method, None if there is none or the cache file is not valid
+pub fn read_cache(file_name: &str, method: &str) -> Option<TuneResult> {
+    let json = match read_json(file_name) {
+        Some(json) => json,
+        None => return None
+    };
+    let entry = &json["methods"][method];
+
+    match (entry["num_threads"].as_u64(), entry["chunk_rows"].as_u64(), entry["time_ms"].as_f64()) {
+        (Some(num_threads), Some(chunk_rows), Some(time_ms)) if num_threads > 0 && chunk_rows > 0 =>
+            Some(TuneResult{num_threads: num_threads as u32, chunk_rows: chunk_rows as u32, time_ms: time_ms}),
+        _ => None
+    }
+}
+
+// Add or replace the result of a method in the cache file, the other methods are kept
+pub fn write_cache(file_name: &str, method: &str, result: &TuneResult) -> io::Result<()> {
+    let mut json = match read_json(file_name) {
+        Some(Value::Object(json)) => json,
+        _ => Map::new()
+    };
+
+    json.insert("cpu_model".to_string(), Value::from(cpu_model()));
+
+    let mut entry = Map::new();
+    entry.insert("num_threads".to_string(), Value::from(result.num_threads));
+    entry.insert("chunk_rows".to_string(), Value::from(result.chunk_rows));
+    entry.insert("time_ms".to_string(), Value::from(result.time_ms));
+
+    let methods = json.entry("methods".to_string()).or_insert_with(|| Value::Object(Map::new()));
+    if !methods.is_object() {
+        *methods = Value::Object(Map::new());
+    }
+    methods.as_object_mut().unwrap().insert(method.to_string(), Value::Object(entry));
+
+    if let Some(dir) = Path::new(file_name).parent() {
+        if !dir.as_os_str().is_empty() {
+            try!(fs::create_dir_all(dir));
+        }
+    }
+
+    let content = try!(serde_json::to_string_pretty(&Value::Object(json)).map_err(|e| io::Error::new(io::ErrorKind::Other, e)));
+    let mut file = try!(File::create(file_name));
+    try!(file.write_all(content.as_bytes()));
+    file.write_all(b"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    #[test]
+    fn candidate_grid() {
+        assert_eq!(thread_candidates(1), vec![1]);
+        assert_eq!(thread_candidates(6), vec![1, 2, 4, 6]);
+        assert_eq!(thread_candidates(8), vec![1, 2, 4, 8]);
+
+        assert_eq!(candidates(2, 256, false), vec![(1, 1), (2, 1)]);
+        assert_eq!(candidates(1, 4, true), vec![(1, 1), (1, 2), (1, 4)]);
+        assert_eq!(candidates(2, 256, true).len(), 10);
+    }
+
+    #[test]
+    fn calibrate_picks_fastest() {
+        let candidates = candidates(4, 256, true);
+        let mut calls = 0;
+
+        let best = calibrate(&candidates, 10_000.0, |num_threads, chunk_rows| {
+            calls += 1;
+            ((num_threads as f64) - 2.0).abs() + ((chunk_rows as f64) - 4.0).abs() + 1.0
+        }).unwrap();
+
+        assert_eq!(calls, 2 * candidates.len());
+        assert_eq!((best.num_threads, best.chunk_rows, best.time_ms), (2, 4, 1.0));
+        assert!(calibrate(&[], 10_000.0, |_, _| 1.0).is_none());
+
+        // No budget: only the first combination is measured
+        let first = calibrate(&candidates, -1.0, |_, _| 1.0).unwrap();
+        assert_eq!((first.num_threads, first.chunk_rows), (1, 1));
+    }
+
+    #[test]
+    fn cache_round_trip() {
+        let file_name = temp_dir().join("mandel_autotune_test").join("autotune_test.json");
+        let file_name = file_name.to_str().unwrap();
+        let _ = fs::remove_file(file_name);
+
+        assert!(read_cache(file_name, "rayon_join").is_none());
+
+        let rayon = TuneResult{num_threads: 4, chunk_rows: 2, time_ms: 1.5};
+        let pool = TuneResult{num_threads: 8, chunk_rows: 1, time_ms: 2.5};
+        write_cache(file_name, "rayon_join", &rayon).unwrap();
+        write_cache(file_name, "scoped_thread_pool", &pool).unwrap();
+
+        assert_eq!(read_cache(file_name, "rayon_join"), Some(rayon));
+        assert_eq!(read_cache(file_name, "scoped_thread_pool"), Some(pool));
+        assert!(read_cache(file_name, "serial").is_none());
+
+        fs::write(file_name, "not json").unwrap();
+        assert!(read_cache(file_name, "rayon_join").is_none());
+
+        fs::remove_file(file_name).unwrap();
+    }
+
+    #[test]
+    fn calibration_is_small() {
+        let config = calibration_config(&MandelConfig::default());
+
+        assert_eq!(config.img_size, 256);
+        assert_eq!(config.max_iter, 1024);
+        assert_eq!(config.x_step(), 3.0 / 256.0);
+        assert!(config.no_ppm);
+        assert_eq!(file_name_part(" Intel(R) Xeon(R) CPU @ 2.20GHz "), "intel_r_xeon_r_cpu_2_20ghz");
+    }
+}
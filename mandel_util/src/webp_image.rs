@@ -0,0 +1,63 @@
+// WebP output (--output_format=webp): the same colors as the PPM images, lossy compressed with
+// the quality --webp_quality (0 .. 100). Much smaller files for sharing the images, not for analysis
+// (use --raw_output or --output_format=exr for that).
+
+// External modules
+use webp::Encoder;
+
+// Rust modules
+use std::fs::File;
+use std::io::prelude::Write;
+use std::io::Result;
+
+// Internal modules
+use {map_colors, MandelConfig};
+
+// Write the image with the colors of map_colors as lossy WebP
+pub fn write_image_webp(file_name: &str, mandel_config: &MandelConfig, image: &[u32], quality: f32) -> Result<()> {
+    let rgb = map_colors(mandel_config, image);
+    let webp = Encoder::from_rgb(&rgb, mandel_config.img_size, mandel_config.img_size).encode(quality);
+
+    let mut file = try!(File::create(file_name));
+    file.write_all(&webp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webp::Decoder;
+    use std::env::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn decoded_colors_are_close() {
+        let file_name = temp_dir().join("mandel_webp_test.webp");
+        let file_name = file_name.to_str().unwrap();
+        let config = MandelConfig{img_size: 16, max_iter: 64, .. MandelConfig::default()};
+        // Two flat halves: inside of the set (black) and one color
+        let image: Vec<u32> = (0..256).map(|n| if n < 128 { 64 } else { 5 }).collect();
+        let expected = map_colors(&config, &image);
+
+        let mut sizes = Vec::new();
+        for &quality in &[10.0, 100.0] {
+            write_image_webp(file_name, &config, &image, quality).unwrap();
+            let data = fs::read(file_name).unwrap();
+            sizes.push(data.len());
+
+            let decoded = Decoder::new(&data).decode().unwrap();
+            assert_eq!((decoded.width(), decoded.height(), decoded.is_alpha()), (16, 16, false));
+
+            if quality == 100.0 {
+                // Lossy, but flat areas away from the edge between them keep their color
+                for &pixel in &[0, 40, 215, 255] {
+                    for channel in 0..3 {
+                        let (a, b) = (decoded[(3 * pixel) + channel] as i32, expected[(3 * pixel) + channel] as i32);
+                        assert!((a - b).abs() <= 8, "pixel {}, channel {}: {} vs. {}", pixel, channel, a, b);
+                    }
+                }
+            }
+        }
+
+        assert!(sizes[0] <= sizes[1], "sizes: {:?}", sizes);
+    }
+}
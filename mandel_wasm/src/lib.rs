@@ -0,0 +1,135 @@
+// mandel-rust for JavaScript: renders RGB images in WebAssembly (wasm32-unknown-unknown).
+//
+// The computation is the same as in the program: mandel_util and mandel_method compile to wasm32,
+// only the command line, the file output and the OS thread pools are left out there.
+//
+// Build with wasm-pack:
+//
+//     cd mandel_wasm
+//     wasm-pack build --release --target web
+//
+// render_parallel uses rayon. Without the feature "threads" rayon has no worker threads on wasm32 and
+// computes everything in the calling thread. With it the rayon thread pool runs on Web Workers
+// (wasm-bindgen-rayon), this needs a nightly compiler and a std with atomics:
+//
+//     RUSTFLAGS='-C target-feature=+atomics,+bulk-memory' rustup run nightly \
+//         wasm-pack build --release --target web -- --features threads -Z build-std=panic_abort,std
+//
+// The page must be cross-origin isolated (SharedArrayBuffer) and call initThreadPool once before rendering.
+//
+// Example (JavaScript):
+//
+//     import init, { initThreadPool, render_parallel } from "./pkg/mandel_wasm.js";
+//     await init();
+//     await initThreadPool(navigator.hardwareConcurrency);   // only with the feature "threads"
+//     const rgb = render_parallel(-2.0, 1.0, -1.5, 1.5, 1024, 512);
+//
+// Written by Willi Kappler, grandor@gmx.de
+//
+// License: MIT
+
+// External crates
+extern crate num_complex;
+extern crate wasm_bindgen;
+#[cfg(all(feature = "threads", target_arch = "wasm32"))]
+extern crate wasm_bindgen_rayon;
+
+// Internal crates
+extern crate mandel_util;
+extern crate mandel_method;
+
+// External modules
+use num_complex::Complex64;
+use wasm_bindgen::prelude::*;
+#[cfg(all(feature = "threads", target_arch = "wasm32"))]
+pub use wasm_bindgen_rayon::init_thread_pool;
+
+// Rust modules
+use std::sync::atomic::AtomicBool;
+
+// Internal modules
+pub use mandel_util::mandel_iter;
+use mandel_util::{escape_color, MandelConfig};
+use mandel_method::{serial, rayon_join};
+
+// Iteration count of one point c = re + im * i, see mandel_util::mandel_iter
+#[wasm_bindgen]
+pub fn mandel_iter_at(re: f64, im: f64, max_iter: u32) -> u32 {
+    mandel_iter(max_iter, Complex64{re: re, im: im})
+}
+
+// The configuration of a render. Invalid parameters (for example img_size not a power of two)
+// throw a JavaScript exception.
+fn render_config(re1: f64, re2: f64, img1: f64, img2: f64, max_iter: u32, img_size: u32) -> MandelConfig {
+    let mandel_config = MandelConfig{
+        re1: re1,
+        re2: re2,
+        img1: img1,
+        img2: img2,
+        max_iter: max_iter,
+        img_size: img_size,
+        .. MandelConfig::default()
+    };
+
+    if let Err(e) = mandel_config.validate() {
+        wasm_bindgen::throw_str(&e.to_string());
+    }
+
+    mandel_config
+}
+
+// The iteration counts as RGB bytes (3 per pixel, row by row, row 0 is img2).
+// Same colors as the PPM images, the inside of the set is black.
+fn to_rgb(image: &[u32], max_iter: u32) -> Vec<u8> {
+    let mut rgb = Vec::with_capacity(3 * image.len());
+
+    for &count in image {
+        let (red, green, blue) = if count == max_iter { (0, 0, 0) } else { escape_color(count) };
+        rgb.extend_from_slice(&[red, green, blue]);
+    }
+
+    rgb
+}
+
+// Render the region into an image of img_size x img_size pixels in the calling thread (method serial)
+#[wasm_bindgen]
+pub fn render_serial(re1: f64, re2: f64, img1: f64, img2: f64, max_iter: u32, img_size: u32) -> Vec<u8> {
+    let mandel_config = render_config(re1, re2, img1, img2, max_iter, img_size);
+    let mut image = vec![0; mandel_config.num_of_pixels()];
+
+    serial(&mandel_config, &mut image, &AtomicBool::new(false));
+
+    to_rgb(&image, max_iter)
+}
+
+// Like render_serial, but with rayon (method rayon_join) on the thread pool of initThreadPool
+#[wasm_bindgen]
+pub fn render_parallel(re1: f64, re2: f64, img1: f64, img2: f64, max_iter: u32, img_size: u32) -> Vec<u8> {
+    let mandel_config = render_config(re1, re2, img1, img2, max_iter, img_size);
+    let mut image = vec![0; mandel_config.num_of_pixels()];
+
+    rayon_join(&mandel_config, &mut image, &AtomicBool::new(false));
+
+    to_rgb(&image, max_iter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serial_and_parallel_are_equal() {
+        let serial = render_serial(-2.0, 1.0, -1.5, 1.5, 64, 32);
+        let parallel = render_parallel(-2.0, 1.0, -1.5, 1.5, 64, 32);
+
+        assert_eq!(serial.len(), 3 * 32 * 32);
+        assert!(serial == parallel);
+
+        // Pixel (16, 16) is -0.5 (inside, black), pixel (0, 0) is -2 + 1.5i (escapes)
+        let pixel = |x: usize, y: usize| &serial[3 * ((y * 32) + x)..3 * ((y * 32) + x) + 3];
+        assert_eq!(mandel_iter_at(-0.5, 0.0, 64), 64);
+        assert_eq!(pixel(16, 16), &[0, 0, 0]);
+        let (red, green, blue) = escape_color(mandel_iter_at(-2.0, 1.5, 64));
+        assert_eq!(pixel(0, 0), &[red, green, blue]);
+    }
+}
@@ -1,7 +1,7 @@
 #!/bin/bash
 
 # remove old files:
-rm plot/*.txt
+rm plot/*.csv
 
 for i in $(seq 1 24)
 do
@@ -0,0 +1,16 @@
+#!/bin/bash
+
+# Task granularity of rayon_par_iter: --rayon_min_len for 2, 4, 8 and 16 threads and several image sizes.
+# The results are appended to plot/rayon_par_iter.csv, the run id tells the combinations apart.
+
+for img_size in 512 1024 2048
+do
+    for threads in 2 4 8 16
+    do
+        for min_len in 1 64 1024 $((img_size * 4)) $((img_size * 32))
+        do
+            cargo run --release -- --no_ppm --select_methods=rayon_par_iter --num_of_runs 10 --img_size $img_size \
+                --num_threads $threads --rayon_min_len $min_len --run_id "size_${img_size}_threads_${threads}_min_len_${min_len}"
+        done
+    done
+done
@@ -0,0 +1,1005 @@
+// Mandelbrot set in rust
+//
+// The application state of mandel-rust, used by main.rs.
+// Keeping it in a library makes it possible to test the application from tests/.
+//
+// Written by Willi Kappler, grandor@gmx.de
+//
+// License: MIT
+
+// External crates
+extern crate time;
+extern crate rayon;
+extern crate notify;
+
+// Internal crates
+extern crate mandel_util;
+extern crate mandel_method;
+
+// External modules
+use time::precise_time_ns;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use notify::event::ModifyKind;
+
+// Internal modules
+use mandel_util::{do_run, compiler_version, generate_run_id, image_file_name, mirror_rows, read_config_file, to_rows, write_image, ImageMetadata, MandelConfig, BenchmarkResult, ConfigError};
+use mandel_util::error::MandelError;
+use mandel_util::raw::{read_raw, write_raw, PartialRaw};
+use mandel_util::refine::{display_values, merge_rect, refined_file_name, PixelRect, RefineJob};
+use mandel_util::batch::{read_jobs, BatchJob};
+use mandel_util::incremental::IncrementalRenderer;
+use mandel_util::tuning;
+use mandel_util::tile_cache;
+use mandel_util::estimate::{estimate_runtime, report_json, write_report, SkippedMethod};
+use mandel_util::matrix::{self, MatrixEntry, SkippedEntry};
+use mandel_util::sweep;
+use mandel_util::distributed::{self, render_distributed};
+use mandel_util::schedule::read_schedule;
+use mandel_util::summary::{paint, summary_table, use_color, Style};
+use mandel_method::registry::{get_method, is_approximate, is_baseline, is_parallel, method_names, supports_chunking, supports_pinning, MandelFunc};
+use mandel_method::dispatch;
+use mandel_method::first_touch;
+use mandel_method::rect::{render_rect, RECT_METHOD_NAMES};
+use mandel_method::progressive::render_progressive;
+use mandel_method::schedule::replay;
+
+// Rust modules
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+// Environment variable for the tests of --compare_parallel_outputs: run_method changes one pixel of the output
+// of the method with this name after it has run (the written files are not affected)
+pub const BREAK_METHOD_VAR: &str = "MANDEL_BREAK_METHOD";
+
+// A pixel that differs from the reference image
+#[derive(Debug, PartialEq)]
+pub struct PixelMismatch {
+    pub x: u32,
+    pub y: u32,
+    pub expected: u32,
+    pub actual: u32,
+}
+
+// Compare an image pixel by pixel with the reference image
+pub fn find_mismatches(reference: &[u32], image: &[u32], img_size: u32) -> Vec<PixelMismatch> {
+    reference.iter().zip(image.iter()).enumerate()
+        .filter(|&(_, (expected, actual))| expected != actual)
+        .map(|(n, (&expected, &actual))| PixelMismatch {
+            x: (n % (img_size as usize)) as u32,
+            y: (n / (img_size as usize)) as u32,
+            expected,
+            actual,
+        })
+        .collect()
+}
+
+pub struct MandelbrotApp {
+    pub config: MandelConfig,
+    pub image: Vec<u32>,
+    pub results: Vec<BenchmarkResult>,
+    // Methods that were not run because of --skip_slow
+    pub skipped: Vec<SkippedMethod>,
+    // Identifies all outputs of this run, used for the file names and the benchmark results
+    pub run_id: String,
+}
+
+impl MandelbrotApp {
+    pub fn new(mut config: MandelConfig) -> Self {
+        // Generate the run id once and pass it to the individual runs
+        let run_id = config.run_id.clone().unwrap_or_else(generate_run_id);
+        config.run_id = Some(run_id.clone());
+
+        // vec! macro expects usize. With --numa_init each method gets a new buffer, see first_touch
+        let image = vec![0; config.num_of_pixels()];
+
+        MandelbrotApp {
+            config,
+            image,
+            results: Vec::new(),
+            skipped: Vec::new(),
+            run_id,
+        }
+    }
+
+    // Print the summary table of all results. The baseline methods don't compute the image,
+    // they are left out so that they are never highlighted as the fastest method.
+    pub fn print_summary(&self) {
+        let results: Vec<BenchmarkResult> = self.results.iter().filter(|result| !is_baseline(&result.method)).cloned().collect();
+
+        if !results.is_empty() {
+            print!("Summary:\n{}", summary_table(&results).render(use_color(self.config.color)));
+        }
+    }
+
+    // Names of the methods to run, see selected_methods
+    pub fn selected_methods(&self) -> Vec<String> {
+        selected_methods(&self.config)
+    }
+
+    // Run one method by name and keep its result. Returns an error if its outputs can't be written.
+    // Panics if there is no method with that name.
+    pub fn run_method(&mut self, name: &str) -> Result<BenchmarkResult, MandelError> {
+        let mandel_func = get_method(name).unwrap_or_else(|| panic!("Unknown method: '{}'", name));
+
+        if self.config.pins_threads() && !supports_pinning(name) {
+            println!("Method '{}' does not support --pin_threads or --cpu_affinity, threads are not pinned", name);
+        }
+
+        // Nothing to tune for serial
+        let result = if self.config.auto_tune && is_parallel(name) {
+            let config = self.tuned_config(name, mandel_func);
+
+            // The rayon methods use the tuned number of threads, too
+            match rayon::ThreadPoolBuilder::from(&config).build() {
+                Ok(pool) => pool.install(|| {
+                    self.first_touch(name, mandel_func, &config);
+                    do_run(name, mandel_func, &config, &mut self.image, &self.run_id)
+                })?,
+                Err(e) => panic!("Rayon error: can't create thread pool with {} threads: {}", config.num_threads, e)
+            }
+        } else {
+            let config = self.config.clone();
+            self.first_touch(name, mandel_func, &config);
+            do_run(name, mandel_func, &config, &mut self.image, &self.run_id)?
+        };
+        self.results.push(result.clone());
+
+        if env::var(BREAK_METHOD_VAR).is_ok_and(|broken| broken == name) {
+            self.image[0] = self.image[0].wrapping_add(1);
+        }
+
+        Ok(result)
+    }
+
+    // With --numa_init: a new image buffer whose pages are placed by the workers of the method (not measured)
+    fn first_touch(&mut self, name: &str, mandel_func: MandelFunc, config: &MandelConfig) {
+        if config.numa_init {
+            let time_in_ms = first_touch::first_touch(mandel_func, config, &mut self.image);
+            println!("Image buffer placed by the workers of {} in {:.5} ms (first touch)", name, time_in_ms);
+        }
+    }
+
+    // Configuration with the fastest num_threads and chunk_rows for the method (--auto_tune),
+    // from the cache or from a calibration run
+    fn tuned_config(&self, name: &str, mandel_func: MandelFunc) -> MandelConfig {
+        let cache_file = tuning::cache_file_name();
+        let cached = if self.config.retune { None } else { tuning::read_cache(&cache_file, name) };
+
+        let result = match cached {
+            Some(result) => {
+                println!("Auto tune {}: num_threads: {}, chunk_rows: {} (from {})", name, result.num_threads, result.chunk_rows, cache_file);
+                result
+            }
+            None => {
+                let calibration = tuning::calibration_config(&self.config);
+                let candidates = tuning::candidates(tuning::max_threads(), calibration.img_size, supports_chunking(name));
+                let mut image = vec![0; calibration.num_of_pixels()];
+
+                let result = tuning::calibrate(&candidates, tuning::CALIBRATION_BUDGET_MS, |num_threads, chunk_rows| {
+                    let config = MandelConfig { num_threads, chunk_rows, ..calibration.clone() };
+                    let pool = rayon::ThreadPoolBuilder::from(&config).build()
+                        .unwrap_or_else(|e| panic!("Rayon error: can't create thread pool with {} threads: {}", num_threads, e));
+
+                    let start_time = precise_time_ns();
+                    pool.install(|| mandel_func(&config, &mut image, &AtomicBool::new(false)));
+                    ((precise_time_ns() - start_time) as f64) / (1000.0 * 1000.0)
+                }).expect("No candidates for the calibration");
+
+                println!("Auto tune {}: num_threads: {}, chunk_rows: {} ({:.5} ms for the calibration image)",
+                    name, result.num_threads, result.chunk_rows, result.time_ms);
+
+                if let Err(e) = tuning::write_cache(&cache_file, name, &result) {
+                    println!("Can't write auto tune cache '{}': {}", cache_file, e);
+                }
+
+                result
+            }
+        };
+
+        result.apply_to(&self.config)
+    }
+
+    // Run all selected methods. All methods are run even if some of them differ from serial or time out,
+    // the error is the first of them (differences before timeouts). An output that can't be written stops the run.
+    pub fn run_all(&mut self) -> Result<(), MandelError> {
+        let version = env!("CARGO_PKG_VERSION");
+
+        println!("mandel-rust version: {}", version);
+        println!("Run id: {}", self.run_id);
+        if self.config.adaptive_runs {
+            println!("Number of repetitive runs: {} to {} (until the 95% confidence interval is within {}%)",
+                self.config.num_of_runs.max(2), self.config.max_runs, self.config.ci * 100.0);
+        } else {
+            println!("Number of repetitive runs: {}", self.config.num_of_runs);
+        }
+        println!("Rustc version: {}", compiler_version);
+
+        if let Some(thread_model) = self.config.thread_model {
+            if !dispatch::is_available(thread_model) {
+                return Err(MandelError::Config(ConfigError::InvalidValue(
+                    format!("thread model '{}' is not available in this build (tokio needs: cargo build --features tokio)", thread_model))));
+            }
+        }
+
+        let selected_methods = self.selected_methods();
+
+        check_methods(&selected_methods)?;
+
+        // Make sure this is only called once
+        let rayon_ok = init_rayon(&self.config);
+
+        // Output of the serial method, all other methods must compute exactly the same pixels
+        let reference = if self.config.compare_parallel_outputs {
+            if self.run_method("serial")?.timed_out {
+                println!("No reference output, the outputs are not compared with serial");
+                None
+            } else {
+                Some(self.image.clone())
+            }
+        } else {
+            None
+        };
+
+        let mut failed_methods = Vec::new();
+        let mut timed_out_methods = Vec::new();
+
+        for name in &selected_methods {
+            if name.starts_with("rayon") && !rayon_ok {
+                continue;
+            }
+
+            if reference.is_some() {
+                if name == "serial" {
+                    continue;
+                }
+
+                if is_baseline(name) {
+                    println!("Method '{}' doesn't compute the image, not compared with serial", name);
+                    continue;
+                }
+
+                // A method that misses some pixels must not pass because of the previous output
+                for pixel in self.image.iter_mut() {
+                    *pixel = u32::MAX;
+                }
+            }
+
+            if let Some(skip_slow) = self.config.skip_slow {
+                let estimate_s = estimate_runtime(&get_method(name).unwrap(), &self.config);
+
+                if estimate_s > skip_slow {
+                    println!("Skipping {}: estimated {:.1} s, more than --skip_slow={} s", name, estimate_s, skip_slow);
+                    self.skipped.push(SkippedMethod { method: name.clone(), estimate_s });
+                    continue;
+                }
+
+                println!("Estimated time of {}: {:.1} s", name, estimate_s);
+            }
+
+            if self.run_method(name)?.timed_out {
+                timed_out_methods.push(name.clone());
+                continue;
+            }
+
+            if let Some(ref reference) = reference {
+                let mismatches = find_mismatches(reference, &self.image, self.config.img_size);
+
+                if mismatches.is_empty() {
+                    println!("Output of {} matches serial", name);
+                } else if is_approximate(name) {
+                    // Rounding differs near the boundary of the set, this is not a failure
+                    println!("Output of {} differs from serial in {} pixels (approximate method)", name, mismatches.len());
+                } else {
+                    failed_methods.push(MandelError::Verification { method: name.clone(), mismatches: mismatches.len() });
+                    println!("Output of {} differs from serial in {} pixels", name, mismatches.len());
+
+                    for mismatch in mismatches.iter().take(10) {
+                        println!("    {}: x: {}, y: {}, serial: {}, {}: {}", name,
+                            mismatch.x, mismatch.y, mismatch.expected, name, mismatch.actual);
+                    }
+                }
+            }
+        }
+
+        self.print_summary();
+
+        if let Some(skip_slow) = self.config.skip_slow {
+            let file_name = format!("report_{}.json", self.run_id);
+            let report = report_json(&self.run_id, skip_slow, &self.results, &self.skipped);
+
+            match write_report(&file_name, &report) {
+                Ok(()) => println!("{} method(s) skipped, report written to '{}'", self.skipped.len(), file_name),
+                Err(e) => println!("Can't write report '{}': {}", file_name, e),
+            }
+        }
+
+        if !timed_out_methods.is_empty() {
+            println!("{}", paint(&format!("{} method(s) timed out: {}", timed_out_methods.len(), timed_out_methods.join(", ")),
+                Style::Bad, use_color(self.config.color)));
+        }
+
+        if !failed_methods.is_empty() {
+            println!("{} method(s) differ from serial", failed_methods.len());
+            return Err(failed_methods.remove(0));
+        }
+
+        match timed_out_methods.first() {
+            Some(method) => Err(MandelError::Timeout { method: method.clone() }),
+            None => Ok(())
+        }
+    }
+}
+
+// Names of the methods to run: the selected ones, serial and the methods of --thread_model or all of them
+pub fn selected_methods(config: &MandelConfig) -> Vec<String> {
+    if !config.select_methods.is_empty() {
+        config.select_methods.clone()
+    } else if let Some(thread_model) = config.thread_model {
+        let mut names = vec!["serial".to_string()];
+        names.extend(dispatch::method_names(thread_model).iter().map(|name| name.to_string()));
+        names
+    } else {
+        method_names().iter().map(|name| name.to_string()).collect()
+    }
+}
+
+// An error for the first name that is not a method
+fn check_methods(methods: &[String]) -> Result<(), MandelError> {
+    match methods.iter().find(|name| get_method(name).is_none()) {
+        Some(name) => Err(MandelError::Config(ConfigError::InvalidValue(
+            format!("unknown method: '{}', available methods: {}", name, method_names().join(", "))))),
+        None => Ok(())
+    }
+}
+
+// Run the selected methods for all combinations of --size_list and --iter_list (--benchmark_matrix).
+// The estimated time of every combination is printed first, combinations that would take longer than
+// --skip_slow are skipped. Without --yes the user has to confirm, otherwise nothing is run.
+// Returns the results of all methods that were run, they are also written to plot/matrix_<run_id>.csv
+// and report_<run_id>.json.
+pub fn run_benchmark_matrix(config: &MandelConfig) -> Result<Vec<MatrixEntry>, MandelError> {
+    let run_id = config.run_id.clone().unwrap_or_else(generate_run_id);
+    let config = MandelConfig { run_id: Some(run_id.clone()), ..config.clone() };
+    let methods = selected_methods(&config);
+    let (sizes, iters) = (matrix::size_list(&config), matrix::iter_list(&config));
+
+    check_methods(&methods)?;
+
+    // Only once for all combinations, before the probes of the rayon methods start the global pool with the default size
+    let rayon_ok = init_rayon(&config);
+
+    // The probe doesn't depend on the image size (see estimate::probe_config), so one per max_iter and method is enough
+    let mut estimates = Vec::new();
+    let mut skipped = Vec::new();
+    let mut total_s = 0.0;
+
+    for &max_iter in &iters {
+        for name in &methods {
+            let probe_s = estimate_runtime(&get_method(name).unwrap(), &matrix::combination_config(&config, sizes[0], max_iter));
+
+            for &img_size in &sizes {
+                let scale = ((img_size as f64) / (sizes[0] as f64)).powi(2);
+                let estimate_s = probe_s * scale;
+
+                if config.skip_slow.is_some_and(|skip_slow| estimate_s > skip_slow) {
+                    skipped.push(SkippedEntry { img_size, max_iter, method: name.clone(), estimate_s });
+                } else {
+                    total_s += estimate_s;
+                    estimates.push((img_size, max_iter, name.clone()));
+                }
+            }
+        }
+    }
+
+    println!("Benchmark matrix: {} method(s), img_size: {:?}, max_iter: {:?}, estimated time: {:.1} s",
+        methods.len(), sizes, iters, total_s);
+    for skipped in &skipped {
+        println!("Skipping {} with img_size {} and max_iter {}: estimated {:.1} s, more than --skip_slow", skipped.method,
+            skipped.img_size, skipped.max_iter, skipped.estimate_s);
+    }
+
+    if !config.yes && !confirm("Start the benchmark matrix? [y/N] ") {
+        println!("Benchmark matrix cancelled");
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+
+    for &img_size in &sizes {
+        // A new image buffer for each size
+        let mut app = MandelbrotApp::new(matrix::combination_config(&config, img_size, iters[0]));
+
+        for &max_iter in &iters {
+            app.config = matrix::combination_config(&config, img_size, max_iter);
+            println!("img_size: {}, max_iter: {}", img_size, max_iter);
+
+            for name in &methods {
+                if (name.starts_with("rayon") && !rayon_ok) || !estimates.contains(&(img_size, max_iter, name.clone())) {
+                    continue;
+                }
+
+                let result = app.run_method(name)?;
+                entries.push(MatrixEntry { img_size, max_iter, result });
+            }
+        }
+    }
+
+    let csv_file = format!("plot{}matrix_{}.csv", std::path::MAIN_SEPARATOR, run_id);
+    matrix::write_matrix_csv(&csv_file, &entries).map_err(|e| MandelError::io(&csv_file, e))?;
+    let report_file = format!("report_{}.json", run_id);
+    write_report(&report_file, &matrix::matrix_json(&run_id, &config, &entries, &skipped)).map_err(|e| MandelError::io(&report_file, e))?;
+    println!("Benchmark matrix written to '{}' and '{}'", csv_file, report_file);
+
+    Ok(entries)
+}
+
+// Run the selected methods with every number of threads of --num_threads_sweep, see mandel_util::sweep.
+// Methods that don't use threads (serial) are run only once. Each number of threads has a rayon pool of its own.
+// Returns the results of all runs, the best result of each method is printed at the end.
+pub fn run_num_threads_sweep(config: &MandelConfig) -> Result<Vec<BenchmarkResult>, MandelError> {
+    let thread_counts = sweep::thread_counts(config.num_threads_sweep.expect("run_num_threads_sweep needs a sweep"));
+    let methods = selected_methods(config);
+
+    check_methods(&methods)?;
+
+    let mut app = MandelbrotApp::new(config.clone());
+    println!("Run id: {}", app.run_id);
+    println!("Thread sweep: {} method(s), num_threads: {:?}", methods.len(), thread_counts);
+
+    for (n, &num_threads) in thread_counts.iter().enumerate() {
+        println!("num_threads: {}", num_threads);
+        app.config.num_threads = num_threads;
+
+        let pool = rayon::ThreadPoolBuilder::from(&app.config).build()
+            .unwrap_or_else(|e| panic!("Rayon error: can't create thread pool with {} threads: {}", num_threads, e));
+
+        for name in methods.iter().filter(|&name| n == 0 || is_parallel(name)) {
+            pool.install(|| app.run_method(name))?;
+        }
+    }
+
+    println!("Best number of threads:");
+    for best in sweep::best_results(&app.results).iter().filter(|best| is_parallel(&best.method)) {
+        println!("    {}: num_threads: {}, mean: {:.5} ms", best.method, best.num_threads, best.mean_ms);
+    }
+
+    Ok(app.results)
+}
+
+// Ask a yes / no question on the terminal, anything but y or yes (also no input at all) is no
+fn confirm(question: &str) -> bool {
+    print!("{}", question);
+    let _ = io::stdout().flush();
+
+    let mut answer = String::new();
+    match io::stdin().lock().read_line(&mut answer) {
+        Ok(_) => ["y", "yes"].contains(&answer.trim().to_lowercase().as_str()),
+        Err(_) => false
+    }
+}
+
+// Set up the global rayon thread pool, returns false if that failed.
+// The global pool can only be set up once, later calls (the renders of run_watch) return the result of the first one.
+fn init_rayon(config: &MandelConfig) -> bool {
+    static RAYON_OK: OnceLock<bool> = OnceLock::new();
+
+    *RAYON_OK.get_or_init(|| match rayon::ThreadPoolBuilder::from(config).build_global() {
+        Ok(_) => true,
+        Err(e) => {
+            println!("Rayon error: set number of threads failed: {}", e);
+            false
+        }
+    })
+}
+
+// Outcome of a batch run
+#[derive(Debug, PartialEq)]
+pub struct BatchSummary {
+    pub num_of_jobs: usize,
+    pub num_of_failed_jobs: usize,
+}
+
+// Render all jobs of a jobs file one after another, see mandel_util::batch.
+// Jobs with invalid parameters or I/O errors are reported and skipped.
+// Returns an error only if the jobs file itself can't be read.
+pub fn run_batch(jobs_file: &str, base_config: &MandelConfig) -> Result<BatchSummary, MandelError> {
+    let run_id = base_config.run_id.clone().unwrap_or_else(generate_run_id);
+    println!("Run id: {}", run_id);
+
+    let base_config = &MandelConfig { run_id: Some(run_id), ..base_config.clone() };
+    let jobs = read_jobs(jobs_file, base_config)?;
+    let num_of_jobs = jobs.len();
+    let mut num_of_failed_jobs = 0;
+
+    // The thread pools are set up once for all jobs
+    init_rayon(base_config);
+
+    let batch_start_time = precise_time_ns();
+
+    for (n, job) in jobs.into_iter().enumerate() {
+        let mut job = match job {
+            Ok(job) => job,
+            Err(e) => {
+                println!("Job {} of {}: skipped: {}", n + 1, num_of_jobs, e);
+                num_of_failed_jobs += 1;
+                continue;
+            }
+        };
+
+        let mandel_func = match get_method(&job.method) {
+            Some(mandel_func) => mandel_func,
+            None => {
+                println!("Job {} of {}: skipped: unknown method: '{}', available methods: {}",
+                    n + 1, num_of_jobs, job.method, method_names().join(", "));
+                num_of_failed_jobs += 1;
+                continue;
+            }
+        };
+
+        // Images from the tile cache and the incremental renderer have no interior results, the writer computes them
+        job.config = job.config.with_interior_results();
+
+        let mut image = Vec::new();
+        let start_time = precise_time_ns();
+
+        let renderer = if job.config.incremental {
+            match render_incremental(&job) {
+                Ok(renderer) => Some(renderer),
+                Err(e) => {
+                    println!("Job {} of {}: error while rendering: {}", n + 1, num_of_jobs, e);
+                    num_of_failed_jobs += 1;
+                    continue;
+                }
+            }
+        } else {
+            match job.config.tile_cache.as_ref().and_then(|cache_dir| tile_cache::load(cache_dir, &job.config)) {
+                Some(cached) => {
+                    println!("Job {} of {}: iteration counts from the tile cache", n + 1, num_of_jobs);
+                    image = cached;
+                }
+                None => {
+                    image = vec![0; job.config.num_of_pixels()];
+                    mandel_func(&job.config, &mut image, &AtomicBool::new(false));
+                    mirror_rows(&job.config, &mut image);
+                    to_rows(&job.config, &mut image);
+
+                    if let Some(ref cache_dir) = job.config.tile_cache {
+                        if let Err(e) = tile_cache::store(cache_dir, &job.config, &image) {
+                            println!("Job {} of {}: can't store the image in the tile cache '{}': {}", n + 1, num_of_jobs, cache_dir, e);
+                        }
+                    }
+                }
+            }
+            None
+        };
+
+        let time_in_ms = ((precise_time_ns() - start_time) as f64) / (1000.0 * 1000.0);
+        let image = match renderer {
+            Some(ref renderer) => renderer.image(),
+            None => &image
+        };
+
+        let metadata = ImageMetadata::new().add("method", &job.method).add("job", &(n + 1).to_string());
+
+        if let Err(e) = write_image(&job.file_name, &job.config, time_in_ms, &metadata, image) {
+            println!("Job {} of {}: error while writing image: '{}': {}", n + 1, num_of_jobs, job.file_name, e);
+            num_of_failed_jobs += 1;
+            continue;
+        }
+
+        println!("Job {} of {}: {} ({}, {} x {}, max_iter: {}): {:.5} ms", n + 1, num_of_jobs,
+            image_file_name(&job.file_name, &job.config), job.method, job.config.img_size, job.config.img_size, job.config.max_iter, time_in_ms);
+
+        // The output is complete, the progress file is not needed anymore
+        if let Some(renderer) = renderer {
+            if let Err(e) = renderer.finish() {
+                println!("Job {} of {}: can't remove progress file: {}", n + 1, num_of_jobs, e);
+            }
+        }
+    }
+
+    let total_time_in_ms = ((precise_time_ns() - batch_start_time) as f64) / (1000.0 * 1000.0);
+    println!("Batch finished: {} of {} jobs rendered, {} failed, total time: {:.5} ms",
+        num_of_jobs - num_of_failed_jobs, num_of_jobs, num_of_failed_jobs, total_time_in_ms);
+
+    Ok(BatchSummary {
+        num_of_jobs,
+        num_of_failed_jobs,
+    })
+}
+
+// Render a batch job with a progress file, continues an interrupted render of the same job
+fn render_incremental(job: &BatchJob) -> std::io::Result<IncrementalRenderer> {
+    let mut renderer = IncrementalRenderer::open(&job.file_name, &job.config)?;
+
+    if renderer.num_of_rows_done() > 0 {
+        println!("Continue '{}': {} of {} rows already done", job.file_name, renderer.num_of_rows_done(), job.config.img_size);
+    }
+
+    renderer.render(&job.config)?;
+
+    Ok(renderer)
+}
+
+// Re-render the rectangle of the raw dump job.base with job.max_iter, see mandel_util::refine.
+// The method is the first one of --select_methods (default: rayon_par_iter).
+// Writes the merged image as <base>_refined.mraw and <base>_refined.ppm.
+pub fn run_refine(job: &RefineJob, base_config: &MandelConfig) -> Result<(), MandelError> {
+    let invalid = |message: String| MandelError::Config(ConfigError::InvalidValue(message));
+
+    let base = read_raw(&job.base).map_err(|e| MandelError::io(&job.base, e))?;
+    if base.width != base.height {
+        return Err(invalid(format!("'{}' is not square: {} x {}", job.base, base.width, base.height)));
+    }
+    job.rect.check(base.width, base.height).map_err(|e| invalid(e.to_string()))?;
+
+    let method = base_config.select_methods.first().map_or("rayon_par_iter", |method| method.as_str());
+    if !RECT_METHOD_NAMES.contains(&method) {
+        return Err(invalid(format!("method '{}' can't refine, use one of: {}", method, RECT_METHOD_NAMES.join(", "))));
+    }
+
+    // Same region as the dump, only the size comes from the dump
+    let config = MandelConfig {
+        img_size: base.width,
+        max_iter: job.max_iter,
+        ..base_config.clone()
+    };
+
+    init_rayon(&config);
+
+    let mut values = vec![0; job.rect.num_of_pixels()];
+    let start_time = precise_time_ns();
+    render_rect(&config, method, &job.rect, &mut values);
+    let time_in_ms = ((precise_time_ns() - start_time) as f64) / (1000.0 * 1000.0);
+
+    let mut image = base.data;
+    merge_rect(&mut image, base.width, &job.rect, &values);
+
+    let raw_file_name = refined_file_name(&job.base, "mraw");
+    write_raw(&raw_file_name, &config, &image).map_err(|e| MandelError::io(&raw_file_name, e))?;
+
+    let rect = &job.rect;
+    let ppm_file_name = refined_file_name(&job.base, "ppm");
+    let metadata = ImageMetadata::new()
+        .add("base", &job.base)
+        .add("base_max_iter", &base.max_iter.to_string())
+        .add("refine", &rect.to_string())
+        .add("method", method);
+    write_image(&ppm_file_name, &config, time_in_ms, &metadata,
+        &display_values(&image, base.width, rect, base.max_iter, job.max_iter))?;
+
+    println!("Refined {} x {} pixels of '{}' (max_iter: {} -> {}, {}): {:.5} ms, written to '{}' and '{}'",
+        rect.width(), rect.height(), job.base, base.max_iter, job.max_iter, method, time_in_ms, raw_file_name, ppm_file_name);
+
+    Ok(())
+}
+
+// Rows per thread of one step of run_resume, computed together before they are written
+const RESUME_ROWS_PER_THREAD: u32 = 4;
+
+// Compute the missing rows of the raw dump --resume (see mandel_util::raw::PartialRaw), each finished row
+// is written into the dump right away. The method is the first one of --select_methods (default: rayon_par_iter).
+// When the dump is complete it is also written as <dump>.ppm.
+pub fn run_resume(config: &MandelConfig) -> Result<(), MandelError> {
+    // Only the rows that are computed now have stored results, the writer computes the others
+    let config = &config.with_interior_results();
+    let file_name = config.resume.as_ref().expect("run_resume needs a file name");
+    let invalid = |message: String| MandelError::Config(ConfigError::InvalidValue(message));
+
+    let method = config.select_methods.first().map_or("rayon_par_iter", |method| method.as_str());
+    if !RECT_METHOD_NAMES.contains(&method) {
+        return Err(invalid(format!("method '{}' can't resume, use one of: {}", method, RECT_METHOD_NAMES.join(", "))));
+    }
+
+    let mut raw = PartialRaw::open(file_name, config).map_err(|e| MandelError::io(file_name, e))?;
+    let first_row = raw.completed_rows();
+    if first_row > 0 {
+        println!("Resume '{}': {} of {} rows already done", file_name, first_row, config.img_size);
+    }
+
+    init_rayon(config);
+
+    let rows_per_step = config.num_threads.max(1) * RESUME_ROWS_PER_THREAD;
+    let start_time = precise_time_ns();
+
+    while !raw.is_complete() {
+        let y0 = raw.completed_rows();
+        let rect = PixelRect{x0: 0, y0, x1: config.img_size, y1: (y0 + rows_per_step).min(config.img_size)};
+        let mut rows = vec![0; rect.num_of_pixels()];
+
+        render_rect(config, method, &rect, &mut rows);
+        raw.write_rows(&rows).map_err(|e| MandelError::io(file_name, e))?;
+    }
+
+    let time_in_ms = ((precise_time_ns() - start_time) as f64) / (1000.0 * 1000.0);
+    let image = raw.read_image().map_err(|e| MandelError::io(file_name, e))?;
+
+    let ppm_file_name = Path::new(file_name).with_extension("ppm").to_string_lossy().into_owned();
+    let metadata = ImageMetadata::new()
+        .add("resume", file_name)
+        .add("method", method);
+    write_image(&ppm_file_name, config, time_in_ms, &metadata, &image)?;
+
+    println!("Resumed '{}' ({}): {} rows computed in {:.5} ms, written to '{}'",
+        file_name, method, config.img_size - first_row, time_in_ms, ppm_file_name);
+
+    Ok(())
+}
+
+// Compute the image of the raw dump --refine_from again with the higher max_iter of the configuration. The pixels of the
+// dump that escaped are exact and kept, only the ones that reached the max_iter of the dump are iterated again.
+// The method is the first one of --select_methods (default: rayon_par_iter), the size comes from the dump.
+// Writes the image as <dump>_refined.mraw and <dump>_refined.ppm.
+pub fn run_refine_from(config: &MandelConfig) -> Result<(), MandelError> {
+    let file_name = config.refine_from.as_ref().expect("run_refine_from needs a file name");
+    let invalid = |message: String| MandelError::Config(ConfigError::InvalidValue(message));
+
+    let previous = read_raw(file_name).map_err(|e| MandelError::io(file_name, e))?;
+    if previous.width != previous.height {
+        return Err(invalid(format!("'{}' is not square: {} x {}", file_name, previous.width, previous.height)));
+    }
+    if previous.max_iter > config.max_iter {
+        return Err(invalid(format!("max_iter ({}) must be at least the max_iter of '{}' ({})", config.max_iter, file_name, previous.max_iter)));
+    }
+
+    let method = config.select_methods.first().map_or("rayon_par_iter", |method| method.as_str());
+    if !RECT_METHOD_NAMES.contains(&method) {
+        return Err(invalid(format!("method '{}' can't refine, use one of: {}", method, RECT_METHOD_NAMES.join(", "))));
+    }
+
+    // Same region as the dump, only the size comes from the dump
+    // All interior pixels are iterated again, so their results are stored
+    let config = MandelConfig { img_size: previous.width, previous_max_iter: Some(previous.max_iter), ..config.clone() }
+        .with_interior_results();
+
+    init_rayon(&config);
+
+    let num_of_unresolved = previous.data.iter().filter(|&&value| value >= previous.max_iter).count();
+    let rect = PixelRect { x0: 0, y0: 0, x1: config.img_size, y1: config.img_size };
+    let mut image = previous.data;
+
+    let start_time = precise_time_ns();
+    render_rect(&config, method, &rect, &mut image);
+    let time_in_ms = ((precise_time_ns() - start_time) as f64) / (1000.0 * 1000.0);
+
+    let raw_file_name = refined_file_name(file_name, "mraw");
+    write_raw(&raw_file_name, &config, &image).map_err(|e| MandelError::io(&raw_file_name, e))?;
+
+    let ppm_file_name = refined_file_name(file_name, "ppm");
+    let metadata = ImageMetadata::new()
+        .add("refine_from", file_name)
+        .add("previous_max_iter", &previous.max_iter.to_string())
+        .add("method", method);
+    write_image(&ppm_file_name, &config, time_in_ms, &metadata, &image)?;
+
+    println!("Refined '{}' (max_iter: {} -> {}, {}): {} of {} pixels iterated again in {:.5} ms, written to '{}' and '{}'",
+        file_name, previous.max_iter, config.max_iter, method, num_of_unresolved, image.len(), time_in_ms, raw_file_name, ppm_file_name);
+
+    Ok(())
+}
+
+// Method of the workers if --method is not given
+pub const DEFAULT_WORKER_METHOD: &str = "rayon_join";
+
+// Compute the rows that coordinators ask for (--worker), see mandel_util::distributed.
+// Runs until the process is killed, returns an error if the address can't be used.
+pub fn run_worker(config: &MandelConfig) -> Result<(), MandelError> {
+    let address = config.worker.as_ref().expect("run_worker needs an address");
+    let listener = TcpListener::bind(address).map_err(|e| MandelError::io(address, e))?;
+
+    serve_worker(listener, config)
+}
+
+// Answer the coordinators that connect to the listener with the method and the number of threads of the configuration
+pub fn serve_worker(listener: TcpListener, config: &MandelConfig) -> Result<(), MandelError> {
+    let method = config.select_methods.first().map_or(DEFAULT_WORKER_METHOD, |method| method.as_str());
+    if !RECT_METHOD_NAMES.contains(&method) {
+        return Err(MandelError::Config(ConfigError::InvalidValue(
+            format!("method '{}' can't compute rows for a coordinator, use one of: {}", method, RECT_METHOD_NAMES.join(", ")))));
+    }
+
+    init_rayon(config);
+
+    let address = listener.local_addr().map_or_else(|_| "unknown".to_string(), |address| address.to_string());
+    println!("Worker listening on {}, method: {}, num_threads: {}", address, method, config.num_threads);
+
+    distributed::serve(listener, config, |job_config, rect, values| render_rect(job_config, method, rect, values))
+        .map_err(|e| MandelError::io(&address, e))
+}
+
+// Render the image on the workers of --coordinator and write distributed_<run_id>.ppm,
+// the rows of each worker are in its metadata
+pub fn run_coordinator(config: &MandelConfig) -> Result<(), MandelError> {
+    let run_id = config.run_id.clone().unwrap_or_else(generate_run_id);
+    let config = MandelConfig { run_id: Some(run_id.clone()), ..config.clone() };
+    let workers = config.coordinator.join(",");
+    println!("Run id: {}", run_id);
+
+    let start_time = precise_time_ns();
+    let render = render_distributed(&config, &config.coordinator).map_err(|e| MandelError::io(&workers, e))?;
+    let time_in_ms = ((precise_time_ns() - start_time) as f64) / (1000.0 * 1000.0);
+
+    let rows_per_worker = render.workers.iter()
+        .map(|worker| format!("{}: {}", worker.address, worker.num_of_rows))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let file_name = format!("distributed_{}.ppm", run_id);
+    let metadata = ImageMetadata::new()
+        .add("workers", &workers)
+        .add("rows", &rows_per_worker);
+    write_image(&file_name, &config, time_in_ms, &metadata, &render.image)?;
+
+    println!("Rendered on {} worker(s) in {:.5} ms (rows: {}), written to '{}'", render.workers.len(), time_in_ms, rows_per_worker, file_name);
+
+    Ok(())
+}
+
+// Compute the jobs of a --trace_schedule file in their order on one thread (see mandel_method::schedule::replay)
+// and compare the image with serial. The region, max_iter and img_size of the traced run are used.
+// Writes replay_<method>_<run_id>.ppm, returns a Verification error if the image differs from serial.
+pub fn run_replay(config: &MandelConfig) -> Result<(), MandelError> {
+    let file_name = config.replay_schedule.as_ref().expect("run_replay needs a schedule trace");
+    let schedule = read_schedule(file_name).map_err(|e| MandelError::io(file_name, e))?;
+
+    let run_id = config.run_id.clone().unwrap_or_else(generate_run_id);
+    let config = MandelConfig { run_id: Some(run_id.clone()), ..config.apply_config(&schedule.config)? }.with_interior_results();
+    config.validate()?;
+    schedule.check(config.img_size).map_err(|e| MandelError::Config(ConfigError::InvalidValue(
+        format!("schedule trace '{}' doesn't fit the image: {}", file_name, e))))?;
+
+    let rows_per_worker = schedule.rows_per_worker().iter().enumerate()
+        .map(|(worker, num_of_rows)| format!("{}: {}", worker, num_of_rows))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    println!("Run id: {}", run_id);
+    println!("Replaying {} jobs of {} from '{}' (rows per worker: {})", schedule.entries.len(), schedule.method, file_name, rows_per_worker);
+
+    let mut image = vec![0; config.num_of_pixels()];
+    let start_time = precise_time_ns();
+    replay(&config, &mut image, &schedule, &AtomicBool::new(false));
+    mirror_rows(&config, &mut image);
+    let time_in_ms = ((precise_time_ns() - start_time) as f64) / (1000.0 * 1000.0);
+
+    let mut reference = vec![0; config.num_of_pixels()];
+    get_method("serial").expect("serial is always available")(&config, &mut reference, &AtomicBool::new(false));
+    mirror_rows(&config, &mut reference);
+
+    to_rows(&config, &mut image);
+    to_rows(&config, &mut reference);
+
+    let replay_file = format!("replay_{}_{}.ppm", schedule.method, run_id);
+    let metadata = ImageMetadata::new()
+        .add("method", &schedule.method)
+        .add("schedule", file_name)
+        .add("rows", &rows_per_worker);
+    write_image(&replay_file, &config, time_in_ms, &metadata, &image)?;
+    println!("Replay written to '{}' ({:.5} ms)", replay_file, time_in_ms);
+
+    let mismatches = find_mismatches(&reference, &image, config.img_size);
+    if mismatches.is_empty() {
+        println!("Replay of {} matches serial", schedule.method);
+        return Ok(());
+    }
+
+    println!("Replay of {} differs from serial in {} pixels", schedule.method, mismatches.len());
+    for mismatch in mismatches.iter().take(10) {
+        println!("    x: {}, y: {}, serial: {}, replay: {}", mismatch.x, mismatch.y, mismatch.expected, mismatch.actual);
+    }
+
+    Err(MandelError::Verification { method: format!("replay of {}", schedule.method), mismatches: mismatches.len() })
+}
+
+// Render as good as possible within config.time_budget seconds, see mandel_method::progressive.
+// Writes time_budget_<run_id>.ppm, the effective max_iter of the pixels is in its metadata.
+pub fn run_time_budget(config: &MandelConfig) -> Result<(), MandelError> {
+    let time_budget = config.time_budget.expect("run_time_budget needs a time budget");
+    let run_id = config.run_id.clone().unwrap_or_else(generate_run_id);
+    let config = MandelConfig { run_id: Some(run_id.clone()), ..config.clone() };
+
+    init_rayon(&config);
+
+    let start_time = Instant::now();
+    let result = render_progressive(&config, start_time + Duration::from_secs_f64(time_budget));
+    let time_in_ms = start_time.elapsed().as_secs_f64() * 1000.0;
+
+    let effective_max_iter = result.pixels_per_limit().iter()
+        .map(|&(limit, num_of_pixels)| format!("{} ({} pixels)", limit, num_of_pixels))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let file_name = format!("time_budget_{}.ppm", run_id);
+    let metadata = ImageMetadata::new()
+        .add("time_budget", &format!("{} s", time_budget))
+        .add("passes", &result.num_of_passes.to_string())
+        .add("finished", &result.finished.to_string())
+        .add("effective_max_iter", &effective_max_iter);
+    write_image(&file_name, &config, time_in_ms, &metadata, &result.display_image(config.max_iter))?;
+
+    println!("Time budget of {} s: {} passes in {:.5} ms, {}, effective max_iter: {}, written to '{}'",
+        time_budget, result.num_of_passes, time_in_ms, if result.finished { "finished" } else { "not finished" },
+        effective_max_iter, file_name);
+
+    Ok(())
+}
+
+// Run all methods and then again whenever the file of --config_file changes (--watch), until the process is killed.
+// Changes within watch_debounce_ms are one change, editors often write a file in several steps.
+// The keys of the changed file replace the values of the configuration, the command line options included.
+// Errors of a render and invalid files are printed and the watch goes on, an error is returned only if the
+// file can't be watched.
+pub fn run_watch(config: &MandelConfig) -> Result<(), MandelError> {
+    let config_file = config.config_file.clone().expect("run_watch needs a config file");
+    let path = Path::new(&config_file);
+    // The directory is watched instead of the file, many editors replace the file when they save it
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new(".")
+    };
+    let watch_error = |e: notify::Error| MandelError::io(&config_file, io::Error::other(e));
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+    watcher.watch(dir, RecursiveMode::NonRecursive).map_err(watch_error)?;
+
+    // All renders write to the same files
+    let run_id = config.run_id.clone().unwrap_or_else(generate_run_id);
+    let config = MandelConfig { run_id: Some(run_id), ..config.clone() };
+    let debounce = Duration::from_millis(config.watch_debounce_ms);
+
+    render_watched(&config);
+
+    loop {
+        println!("Watching '{}' for changes, stop with Ctrl-C", config_file);
+
+        loop {
+            match receiver.recv() {
+                Ok(Ok(event)) => if is_config_change(&event, path) { break },
+                Ok(Err(e)) => println!("Error while watching '{}': {}", config_file, e),
+                // Only if the watcher is gone
+                Err(_) => return Ok(())
+            }
+        }
+
+        // Wait until the file has been quiet for the debounce time
+        while receiver.recv_timeout(debounce).is_ok() {}
+
+        match read_config_file(&config_file, &config).and_then(|changed| changed.validate().map(|_| changed)) {
+            Ok(changed) => {
+                println!("'{}' changed: {}", config_file, changed);
+                render_watched(&changed);
+            }
+            Err(e) => println!("Invalid configuration file: '{}': {}, the images are not changed", config_file, e)
+        }
+    }
+}
+
+// True if the event of the watched directory creates or writes the file
+pub fn is_config_change(event: &Event, file: &Path) -> bool {
+    let writes = matches!(event.kind, EventKind::Create(_) |
+        EventKind::Modify(ModifyKind::Data(_)) | EventKind::Modify(ModifyKind::Name(_)) | EventKind::Modify(ModifyKind::Any));
+
+    writes && event.paths.iter().any(|changed| changed.file_name() == file.file_name())
+}
+
+// One render of run_watch. The rayon methods run in a pool of their own, the global one
+// keeps the num_threads of the first render.
+fn render_watched(config: &MandelConfig) {
+    let mut app = MandelbrotApp::new(config.clone());
+
+    let result = match rayon::ThreadPoolBuilder::from(config).build() {
+        Ok(pool) => pool.install(|| app.run_all()),
+        Err(e) => panic!("Rayon error: can't create thread pool with {} threads: {}", config.num_threads, e)
+    };
+
+    if let Err(e) = result {
+        println!("Error: {}", e);
+    }
+}
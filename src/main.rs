@@ -11,20 +11,19 @@
 //
 //#![plugin(clippy)]
 
-// External crates
-extern crate time;
-extern crate rayon;
-
 // Internal crates
+extern crate mandel;
 extern crate mandel_util;
 extern crate mandel_method;
 
-// External modules
-use time::{now};
-
 // Internal modules
-use mandel_util::{parse_arguments, do_run, compiler_version};
-use mandel_method::*;
+use mandel::{MandelbrotApp, run_batch, run_benchmark_matrix, run_coordinator, run_num_threads_sweep, run_refine, run_refine_from, run_replay, run_resume, run_time_budget, run_watch, run_worker};
+use mandel_util::{parse_arguments, write_palette_preview};
+use mandel_util::palette::Palette;
+use mandel_util::summary::BenchmarkSuite;
+use mandel_util::tile_cache;
+use mandel_method::registry::is_baseline;
+use mandel_util::error::{MandelError, EXIT_FAILURE, EXIT_IO};
 
 fn main() {
     // For example run with:
@@ -35,42 +34,131 @@ fn main() {
     // cargo run --release -- --num_threads=2
     //
     // Note that the image size must be a power of two
+    //
+    // The exit code tells what went wrong, see mandel_util::error (and the end of --help)
+
+    let config = parse_arguments();
+
+    if config.clear_cache {
+        // validate makes sure that tile_cache is set
+        let cache_dir = config.tile_cache.as_ref().unwrap();
+        if let Err(e) = tile_cache::clear(cache_dir) {
+            println!("Error while deleting the tile cache '{}': {}", cache_dir, e);
+            std::process::exit(EXIT_IO);
+        }
+        println!("Tile cache '{}' deleted", cache_dir);
+        return;
+    }
 
-    let mandel_config = parse_arguments();
+    if let Some(ref preview_file) = config.palette_preview {
+        print!("{}", Palette::from_config(&config));
+        if let Err(e) = write_palette_preview(preview_file, &config) {
+            println!("Error while writing the palette preview '{}': {}", preview_file, e);
+            std::process::exit(EXIT_IO);
+        }
+        println!("Palette preview written to {}", preview_file);
+        return;
+    }
 
-    let version = env!("CARGO_PKG_VERSION");
+    if let Some(ref job) = config.refine {
+        if let Err(e) = run_refine(job, &config) {
+            exit_with(&format!("Error while refining '{}'", job.base), e);
+        }
+        return;
+    }
 
-    println!("mandel-rust version: {}", version);
-    println!("Number of repetitive runs: {}", mandel_config.num_of_runs);
-    println!("Rustc version: {}", compiler_version);
+    if let Some(ref file_name) = config.resume {
+        if let Err(e) = run_resume(&config) {
+            exit_with(&format!("Error while resuming '{}'", file_name), e);
+        }
+        return;
+    }
 
-    // Get current date and time once and pass it to the individual runs for the image filename.
-    let tm = now();
-    let tm = tm.strftime("%Y_%m_%d__%H_%M_%S").unwrap();
-    let time_now = format!("{}", &tm);
+    if let Some(ref file_name) = config.refine_from {
+        if let Err(e) = run_refine_from(&config) {
+            exit_with(&format!("Error while refining '{}'", file_name), e);
+        }
+        return;
+    }
 
-    // vec! macro expects usize
-    let mut image: Vec<u32> = vec![0; (mandel_config.img_size * mandel_config.img_size) as usize];
+    if config.time_budget.is_some() {
+        if let Err(e) = run_time_budget(&config) {
+            exit_with("Error while writing image", e);
+        }
+        return;
+    }
 
-    do_run("serial", &serial, &mandel_config, &mut image, &time_now);
+    if config.benchmark_matrix {
+        if let Err(e) = run_benchmark_matrix(&config) {
+            exit_with("Error in the benchmark matrix", e);
+        }
+        return;
+    }
 
-    do_run("scoped_thread_pool", &scoped_thread_pool_, &mandel_config, &mut image, &time_now);
+    // The results of the thread sweep or of the normal run, for the parallel efficiency at the end.
+    // The baseline methods don't compute the image and are left out.
+    let mut suite = BenchmarkSuite::default();
+
+    if config.num_threads_sweep.is_some() {
+        match run_num_threads_sweep(&config) {
+            Ok(results) => for result in results.into_iter().filter(|result| !is_baseline(&result.method)) { suite.add(result) },
+            Err(e) => exit_with("Error in the thread sweep", e)
+        }
+        suite.print_summary_table();
+        return;
+    }
 
-    // Make sure this is only called once
-    match rayon::initialize(rayon::Configuration::new().set_num_threads(mandel_config.num_threads as usize)) {
-        Ok(_) => {
-            do_run("rayon_join", &rayon_join, &mandel_config, &mut image, &time_now);
+    if config.worker.is_some() {
+        if let Err(e) = run_worker(&config) {
+            exit_with("Error in the worker", e);
+        }
+        return;
+    }
+
+    if !config.coordinator.is_empty() {
+        if let Err(e) = run_coordinator(&config) {
+            exit_with("Error in the distributed render", e);
+        }
+        return;
+    }
+
+    if let Some(ref trace_file) = config.replay_schedule {
+        if let Err(e) = run_replay(&config) {
+            exit_with(&format!("Error while replaying '{}'", trace_file), e);
+        }
+        return;
+    }
 
-            do_run("rayon_par_iter", &rayon_par_iter, &mandel_config, &mut image, &time_now);
-        },
-        Err(e) => println!("Rayon error: set number of threads failed: {}", e)
+    if let Some(ref jobs_file) = config.batch_file {
+        match run_batch(jobs_file, &config) {
+            Ok(summary) => if summary.num_of_failed_jobs > 0 { std::process::exit(EXIT_FAILURE) },
+            Err(e) => exit_with(&format!("Invalid jobs file: '{}'", jobs_file), e)
+        }
+        return;
     }
 
-    do_run("rust_scoped_pool", &rust_scoped_pool, &mandel_config, &mut image, &time_now);
+    if config.watch {
+        if let Err(e) = run_watch(&config) {
+            exit_with("Error", e);
+        }
+        return;
+    }
+
+    let mut app = MandelbrotApp::new(config);
+    let outcome = app.run_all();
 
-    do_run("job_steal", &job_steal, &mandel_config, &mut image, &time_now);
+    for result in app.results.drain(..).filter(|result| !is_baseline(&result.method)) {
+        suite.add(result);
+    }
+    suite.print_summary_table();
 
-    do_run("job_steal_join", &job_steal_join, &mandel_config, &mut image, &time_now);
+    if let Err(e) = outcome {
+        exit_with("Error", e);
+    }
+}
 
-    // do_run("kirk_crossbeam", &kirk_crossbeam, &mandel_config, &mut image, &time_now);
+// Print the error and exit with its exit code
+fn exit_with(context: &str, e: MandelError) -> ! {
+    println!("{}: {}", context, e);
+    std::process::exit(e.exit_code());
 }
@@ -0,0 +1,497 @@
+extern crate mandel;
+extern crate mandel_method;
+extern crate mandel_util;
+extern crate notify;
+
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Mutex, MutexGuard};
+
+use notify::event::{AccessKind, CreateKind, DataChange, ModifyKind};
+use notify::{Event, EventKind};
+
+use mandel::{find_mismatches, is_config_change, serve_worker, run_batch, run_benchmark_matrix, run_num_threads_sweep, run_refine, run_refine_from, run_resume, BatchSummary, MandelbrotApp, PixelMismatch};
+use mandel_method::serial;
+use mandel_util::raw::{read_raw, write_raw, PartialRaw};
+use mandel_util::refine::{PixelRect, RefineJob};
+use mandel_util::tile_cache;
+use mandel_util::distributed::render_distributed;
+use mandel_util::interior::InteriorColoring;
+use mandel_util::{batch_render, MandelConfig, ThreadModel};
+
+fn small_config() -> MandelConfig {
+    let default = MandelConfig::default();
+
+    MandelConfig {
+        img_size: 16,
+        max_iter: 64,
+        num_of_runs: 1,
+        no_ppm: true,
+        ..default
+    }
+}
+
+// The current directory is shared by all tests of this binary and they run in parallel:
+// a test that changes it holds this lock until it is done, so no other test changes it in between
+static CURRENT_DIR_LOCK: Mutex<()> = Mutex::new(());
+
+// Change to work_dir (it is created if needed), the benchmark results are written to plot/ in the current directory.
+// Keep the guard until the test has read its output files.
+fn enter_work_dir(work_dir: &Path) -> MutexGuard<'static, ()> {
+    // A failed test must not fail the others
+    let guard = CURRENT_DIR_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    std::fs::create_dir_all(work_dir).unwrap();
+    std::env::set_current_dir(work_dir).unwrap();
+    guard
+}
+
+#[test]
+fn run_method_records_result() {
+    let work_dir = std::env::temp_dir().join("mandel_app_test");
+    let _current_dir = enter_work_dir(&work_dir);
+
+    let mut app = MandelbrotApp::new(small_config());
+    assert_eq!(app.image.len(), 16 * 16);
+
+    let result = app.run_method("serial").unwrap();
+    assert_eq!(result.method, "serial");
+    assert_eq!(result.num_threads, 2);
+    let serial_image = app.image.clone();
+    assert!(serial_image.contains(&64));
+
+    app.image = vec![0; 16 * 16];
+    app.run_method("rayon_join").unwrap();
+    assert_eq!(app.image, serial_image);
+
+    assert_eq!(app.results.len(), 2);
+    assert!(work_dir.join("plot").join("serial.csv").exists());
+}
+
+#[test]
+fn thread_model_selects_methods() {
+    let app = MandelbrotApp::new(MandelConfig {
+        thread_model: Some(ThreadModel::Rayon),
+        ..small_config()
+    });
+    let mut expected = vec!["serial", "rayon_join", "rayon_join_dynamic", "rayon_par_iter", "rayon_par_iter_collect", "rayon_par_iter_no_div", "rayon_par_bridge", "rayon_adaptive_weighted", "rayon_perturbation"];
+    if cfg!(feature = "portable_simd") {
+        expected.push("rayon_portable_simd");
+    }
+    assert_eq!(app.selected_methods(), expected);
+
+    // --select_methods wins
+    let app = MandelbrotApp::new(MandelConfig {
+        thread_model: Some(ThreadModel::Os),
+        select_methods: vec!["rayon_join".to_string()],
+        ..small_config()
+    });
+    assert_eq!(app.selected_methods(), vec!["rayon_join"]);
+}
+
+#[test]
+fn run_id_in_all_outputs() {
+    // Same work dir as run_method_records_result, so the tests don't change it back and forth
+    let work_dir = std::env::temp_dir().join("mandel_app_test");
+    let _current_dir = enter_work_dir(&work_dir);
+
+    let config = MandelConfig {
+        no_ppm: false,
+        raw_output: true,
+        run_id: Some("test_run_id".to_string()),
+        ..small_config()
+    };
+
+    let mut app = MandelbrotApp::new(config);
+    assert_eq!(app.run_id, "test_run_id");
+    app.run_method("rust_scoped_pool").unwrap();
+
+    let image = std::fs::read_to_string(work_dir.join("rust_scoped_pool_test_run_id.ppm")).unwrap();
+    assert!(image.contains("# run_id: test_run_id\n"));
+    assert!(work_dir.join("rust_scoped_pool_test_run_id.mraw").exists());
+
+    let results = std::fs::read_to_string(work_dir.join("plot").join("rust_scoped_pool.csv")).unwrap();
+    assert!(results.starts_with("num_threads,mean_ms,min_ms,max_ms,stddev_ms,workload,pin_threads,run_id,rss_delta_mb,layout,access,runs\n"));
+    // Without --print_memory the RSS column is empty
+    assert!(results.lines().last().unwrap().ends_with(",test_run_id,,rows,native,1"));
+
+    let mut app = MandelbrotApp::new(MandelConfig {
+        print_memory: true,
+        run_id: Some("test_memory".to_string()),
+        ..small_config()
+    });
+    app.run_method("rust_scoped_pool").unwrap();
+    let results = std::fs::read_to_string(work_dir.join("plot").join("rust_scoped_pool.csv")).unwrap();
+    let rss_delta_mb = results.lines().last().unwrap().rsplit(',').nth(3).unwrap();
+    assert!(rss_delta_mb.parse::<f64>().is_ok(), "{}", rss_delta_mb);
+
+    // Without --run_id one is generated: date and time and 4 random hex digits, used in all outputs
+    let mut app = MandelbrotApp::new(MandelConfig { no_ppm: false, ..small_config() });
+    let generated = app.run_id.clone();
+    let (date_time, suffix) = generated.split_at("2016_10_01__12_00_00_".len());
+    assert_eq!(date_time.replace(|c: char| c.is_ascii_digit(), "d"), "dddd_dd_dd__dd_dd_dd_", "{}", generated);
+    assert!(suffix.len() == 4 && suffix.chars().all(|c| c.is_ascii_hexdigit()), "{}", generated);
+
+    app.run_method("rust_scoped_pool").unwrap();
+    let image = std::fs::read_to_string(work_dir.join(format!("rust_scoped_pool_{}.ppm", generated))).unwrap();
+    assert!(image.contains(&format!("# run_id: {}\n", generated)));
+    let results = std::fs::read_to_string(work_dir.join("plot").join("rust_scoped_pool.csv")).unwrap();
+    assert!(results.lines().last().unwrap().contains(&format!(",{},", generated)));
+}
+
+#[test]
+fn mismatches_have_coordinates() {
+    let reference = vec![1, 2, 3, 4, 5, 6];
+    let image = vec![1, 2, 3, 4, 7, 6];
+
+    assert!(find_mismatches(&reference, &reference, 3).is_empty());
+    assert_eq!(find_mismatches(&reference, &image, 3),
+        vec![PixelMismatch { x: 1, y: 1, expected: 5, actual: 7 }]);
+}
+
+#[test]
+fn watch_reacts_to_writes_of_the_file() {
+    let file = Path::new("views").join("view.conf");
+    let event = |kind: EventKind, name: &str| Event::new(kind).add_path(PathBuf::from("/home/user/views").join(name));
+
+    assert!(is_config_change(&event(EventKind::Create(CreateKind::File), "view.conf"), &file));
+    assert!(is_config_change(&event(EventKind::Modify(ModifyKind::Data(DataChange::Content)), "view.conf"), &file));
+    assert!(is_config_change(&event(EventKind::Modify(ModifyKind::Any), "view.conf"), &file));
+
+    // Other files of the directory and events that don't change the content
+    assert!(!is_config_change(&event(EventKind::Modify(ModifyKind::Data(DataChange::Content)), "other.conf"), &file));
+    assert!(!is_config_change(&event(EventKind::Access(AccessKind::Read), "view.conf"), &file));
+    assert!(!is_config_change(&event(EventKind::Modify(ModifyKind::Data(DataChange::Content)), ".view.conf.swp"), &file));
+}
+
+#[test]
+fn distributed_render_matches_serial() {
+    let default = MandelConfig::default();
+    let config = MandelConfig {
+        img_size: 256,
+        max_iter: 256,
+        ..default
+    };
+
+    // Two in-process workers with different methods
+    let workers: Vec<String> = ["serial", "rayon_join"].iter().map(|&method| {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let worker_config = MandelConfig { select_methods: vec![method.to_string()], ..MandelConfig::default() };
+
+        std::thread::spawn(move || serve_worker(listener, &worker_config));
+        address
+    }).collect();
+
+    let render = render_distributed(&config, &workers).unwrap();
+
+    let mut expected = vec![0; config.num_of_pixels()];
+    serial(&config, &mut expected, &AtomicBool::new(false));
+
+    assert!(find_mismatches(&expected, &render.image, config.img_size).is_empty());
+    assert_eq!(render.workers.iter().map(|worker| worker.num_of_rows).sum::<u32>(), 256);
+    assert!(render.workers.iter().all(|worker| worker.error.is_none()));
+}
+
+#[test]
+fn batch_skips_invalid_jobs() {
+    // Absolute paths, the current directory is changed by run_method_records_result
+    let batch_dir = std::env::temp_dir().join("mandel_batch_test");
+    std::fs::create_dir_all(&batch_dir).unwrap();
+
+    let output = |name: &str| batch_dir.join(name);
+    for name in &["first.ppm", "invalid.ppm", "third.ppm"] {
+        let _ = std::fs::remove_file(output(name));
+    }
+
+    let jobs = format!("[[job]]\nfile_name = {:?}\nmethod = \"serial\"\n\n\
+                        [[job]]\nfile_name = {:?}\nre1 = 1.0\nre2 = -1.0\n\n\
+                        [[job]]\nfile_name = {:?}\nre1 = -1.5\nre2 = -0.5\nimg_size = 8\ninterior_coloring = \"period\"\n",
+        output("first.ppm"), output("invalid.ppm"), output("third.ppm"));
+    let jobs_file = output("jobs.toml");
+    std::fs::write(&jobs_file, jobs).unwrap();
+
+    let summary = run_batch(jobs_file.to_str().unwrap(), &small_config()).unwrap();
+
+    assert_eq!(summary, BatchSummary { num_of_jobs: 3, num_of_failed_jobs: 1 });
+    assert!(output("first.ppm").exists());
+    assert!(!output("invalid.ppm").exists());
+    assert!(std::fs::read_to_string(output("third.ppm")).unwrap().contains("\n8 8\n"));
+
+    assert!(run_batch(output("missing.toml").to_str().unwrap(), &small_config()).is_err());
+}
+
+#[test]
+fn csv_batch_renders_each_line() {
+    let batch_dir = std::env::temp_dir().join("mandel_csv_batch_test");
+    std::fs::create_dir_all(&batch_dir).unwrap();
+
+    let output = |name: &str| batch_dir.join(name);
+    let jobs = format!("file_name,max_iter,img_size\n{},32,\n{},0,\n{},64,8\n",
+        output("iter_32.ppm").display(), output("iter_0.ppm").display(), output("size_8.ppm").display());
+    let jobs_file = output("jobs.csv");
+    std::fs::write(&jobs_file, jobs).unwrap();
+
+    let summary = run_batch(jobs_file.to_str().unwrap(), &small_config()).unwrap();
+
+    assert_eq!(summary, BatchSummary { num_of_jobs: 3, num_of_failed_jobs: 1 });
+    assert!(std::fs::read_to_string(output("iter_32.ppm")).unwrap().contains("\n16 16\n"));
+    assert!(!output("iter_0.ppm").exists());
+    assert!(std::fs::read_to_string(output("size_8.ppm")).unwrap().contains("\n8 8\n"));
+}
+
+#[test]
+fn batch_render_skips_invalid_configs() {
+    let work_dir = std::env::temp_dir().join("mandel_batch_render_test");
+    let _current_dir = enter_work_dir(&work_dir);
+
+    let configs = [
+        small_config(),
+        MandelConfig { max_iter: 0, ..small_config() },
+        MandelConfig { num_threads: 3, ..small_config() },
+    ];
+    let results = batch_render(&configs, "serial", serial);
+
+    assert_eq!(results.len(), 2);
+    assert_eq!((results[0].num_threads, results[1].num_threads), (2, 3));
+    assert!(results.iter().all(|result| result.method == "serial" && !result.timed_out));
+}
+
+#[test]
+fn incremental_batch_removes_progress_file() {
+    let batch_dir = std::env::temp_dir().join("mandel_incremental_batch_test");
+    std::fs::create_dir_all(&batch_dir).unwrap();
+
+    let output = batch_dir.join("incremental.ppm");
+    let progress = batch_dir.join("incremental.ppm.progress");
+    let _ = std::fs::remove_file(&progress);
+
+    let jobs_file = batch_dir.join("jobs.toml");
+    std::fs::write(&jobs_file, format!("[[job]]\nfile_name = {:?}\n", output)).unwrap();
+
+    let config = MandelConfig { incremental: true, ..small_config() };
+    let summary = run_batch(jobs_file.to_str().unwrap(), &config).unwrap();
+
+    assert_eq!(summary, BatchSummary { num_of_jobs: 1, num_of_failed_jobs: 0 });
+    assert!(output.exists());
+    assert!(!progress.exists());
+}
+
+#[test]
+fn benchmark_matrix_runs_all_combinations() {
+    let work_dir = std::env::temp_dir().join("mandel_matrix_app_test");
+    let _current_dir = enter_work_dir(&work_dir);
+
+    // Methods without rayon: the global rayon pool may already exist in this test process
+    let config = MandelConfig {
+        benchmark_matrix: true,
+        size_list: vec![8, 16],
+        iter_list: vec![16, 64],
+        select_methods: vec!["serial".to_string(), "rust_scoped_pool".to_string()],
+        run_id: Some("matrix_test".to_string()),
+        yes: true,
+        ..small_config()
+    };
+    let entries = run_benchmark_matrix(&config).unwrap();
+
+    let combinations: Vec<(u32, u32, &str)> = entries.iter().map(|entry| (entry.img_size, entry.max_iter, entry.result.method.as_str())).collect();
+    assert_eq!(combinations, vec![
+        (8, 16, "serial"), (8, 16, "rust_scoped_pool"), (8, 64, "serial"), (8, 64, "rust_scoped_pool"),
+        (16, 16, "serial"), (16, 16, "rust_scoped_pool"), (16, 64, "serial"), (16, 64, "rust_scoped_pool")]);
+
+    let csv = std::fs::read_to_string(work_dir.join("plot").join("matrix_matrix_test.csv")).unwrap();
+    assert_eq!(csv.lines().count(), 1 + 8);
+    let report = std::fs::read_to_string(work_dir.join("report_matrix_test.json")).unwrap();
+    assert!(report.contains("\"iter_list\": [\n    16,\n    64\n  ]"));
+
+    // Everything takes longer than 0 s: all combinations are skipped
+    let entries = run_benchmark_matrix(&MandelConfig { skip_slow: Some(0.0), ..config }).unwrap();
+    assert!(entries.is_empty());
+}
+
+#[test]
+fn num_threads_sweep_runs_serial_once() {
+    let work_dir = std::env::temp_dir().join("mandel_sweep_app_test");
+    let _current_dir = enter_work_dir(&work_dir);
+
+    let config = MandelConfig {
+        num_threads_sweep: Some((1, 5, 2)),
+        select_methods: vec!["serial".to_string(), "rayon_join".to_string(), "job_steal".to_string()],
+        ..small_config()
+    };
+    let results = run_num_threads_sweep(&config).unwrap();
+
+    let runs: Vec<(&str, u32)> = results.iter().map(|result| (result.method.as_str(), result.num_threads)).collect();
+    assert_eq!(runs, vec![("serial", 1), ("rayon_join", 1), ("job_steal", 1), ("rayon_join", 3), ("job_steal", 3),
+                          ("rayon_join", 5), ("job_steal", 5)]);
+    assert!(work_dir.join("plot").join("rayon_join.csv").exists());
+}
+
+#[test]
+fn batch_reuses_tile_cache() {
+    let batch_dir = std::env::temp_dir().join("mandel_tile_cache_batch_test");
+    let cache_dir = batch_dir.join("cache");
+    let _ = std::fs::remove_dir_all(&batch_dir);
+    std::fs::create_dir_all(&batch_dir).unwrap();
+
+    let output = batch_dir.join("cached.ppm");
+    let jobs_file = batch_dir.join("jobs.toml");
+    std::fs::write(&jobs_file, format!("[[job]]\nfile_name = {:?}\n", output)).unwrap();
+
+    let config = MandelConfig { tile_cache: Some(cache_dir.to_str().unwrap().to_string()), ..small_config() };
+    run_batch(jobs_file.to_str().unwrap(), &config).unwrap();
+
+    let cache_file = tile_cache::cache_file_name(cache_dir.to_str().unwrap(), &config);
+    assert!(std::path::Path::new(&cache_file).exists());
+    let computed = std::fs::read_to_string(&output).unwrap();
+
+    // The second run takes the image from the cache: all points inside of the set (black)
+    write_raw(&cache_file, &config, &vec![config.max_iter; config.num_of_pixels()]).unwrap();
+    run_batch(jobs_file.to_str().unwrap(), &config).unwrap();
+    let cached = std::fs::read_to_string(&output).unwrap();
+
+    assert!(computed != cached);
+    assert!(cached.split("\n255\n").nth(1).unwrap().split_whitespace().all(|value| value == "0"));
+}
+
+#[test]
+fn tile_cache_keeps_interior_coloring() {
+    let batch_dir = std::env::temp_dir().join("mandel_tile_cache_interior_test");
+    let cache_dir = batch_dir.join("cache");
+    let _ = std::fs::remove_dir_all(&batch_dir);
+    std::fs::create_dir_all(&batch_dir).unwrap();
+
+    let output = batch_dir.join("interior.ppm");
+    let jobs_file = batch_dir.join("jobs.toml");
+    std::fs::write(&jobs_file, format!("[[job]]\nfile_name = {:?}\n", output)).unwrap();
+
+    let config = MandelConfig {
+        tile_cache: Some(cache_dir.to_str().unwrap().to_string()),
+        interior_coloring: InteriorColoring::Period,
+        ..small_config()
+    };
+    run_batch(jobs_file.to_str().unwrap(), &config).unwrap();
+    assert!(std::path::Path::new(&tile_cache::cache_file_name(cache_dir.to_str().unwrap(), &config)).exists());
+    let computed = std::fs::read_to_string(&output).unwrap();
+
+    // The second run takes the counts from the cache, the inside must have the same colors
+    run_batch(jobs_file.to_str().unwrap(), &config).unwrap();
+    let cached = std::fs::read_to_string(&output).unwrap();
+
+    // Each run has its own run id in the header
+    let pixels = |image: &str| image.split("\n255\n").nth(1).unwrap().to_string();
+    assert_eq!(pixels(&computed), pixels(&cached));
+}
+
+#[test]
+fn refine_only_changes_the_rectangle() {
+    let refine_dir = std::env::temp_dir().join("mandel_refine_test");
+    std::fs::create_dir_all(&refine_dir).unwrap();
+
+    let base_file = refine_dir.join("base.mraw");
+    let base_config = small_config();
+    let mut base_image = vec![0; 16 * 16];
+    serial(&base_config, &mut base_image, &AtomicBool::new(false));
+    write_raw(base_file.to_str().unwrap(), &base_config, &base_image).unwrap();
+
+    let rect = PixelRect { x0: 3, y0: 5, x1: 11, y1: 9 };
+    let job = RefineJob { rect, max_iter: 1024, base: base_file.to_str().unwrap().to_string() };
+
+    // The image size comes from the dump, not from the configuration
+    let config = MandelConfig { img_size: 2048, select_methods: vec!["rayon_join".to_string()], ..small_config() };
+    run_refine(&job, &config).unwrap();
+
+    let refined = read_raw(refine_dir.join("base_refined.mraw").to_str().unwrap()).unwrap();
+    let mut full_image = vec![0; 16 * 16];
+    serial(&MandelConfig { max_iter: 1024, ..small_config() }, &mut full_image, &AtomicBool::new(false));
+
+    assert_eq!((refined.width, refined.height, refined.max_iter), (16, 16, 1024));
+    for y in 0..16 {
+        for x in 0..16 {
+            let n = (y * 16) + x;
+            let expected = if rect.contains(x, y) { full_image[n as usize] } else { base_image[n as usize] };
+            assert_eq!(refined.data[n as usize], expected, "pixel ({}, {})", x, y);
+        }
+    }
+    // The higher max_iter changes some pixels in the rectangle
+    assert!(refined.data != base_image);
+
+    let image = std::fs::read_to_string(refine_dir.join("base_refined.ppm")).unwrap();
+    assert!(image.contains("# refine: 3,5,11,9\n"));
+    assert!(image.contains("\n16 16\n"));
+
+    let outside = RefineJob { rect: PixelRect { x0: 3, y0: 5, x1: 17, y1: 9 }, ..job.clone() };
+    assert!(run_refine(&outside, &config).is_err());
+
+    let unknown_method = MandelConfig { select_methods: vec!["null_method".to_string()], ..config };
+    assert!(run_refine(&job, &unknown_method).is_err());
+}
+
+#[test]
+fn resume_continues_with_the_missing_rows() {
+    let resume_dir = std::env::temp_dir().join("mandel_resume_test");
+    std::fs::create_dir_all(&resume_dir).unwrap();
+    let dump = resume_dir.join("render.mraw");
+    let _ = std::fs::remove_file(&dump);
+
+    let config = MandelConfig {
+        resume: Some(dump.to_str().unwrap().to_string()),
+        select_methods: vec!["rayon_join".to_string()],
+        ..small_config()
+    };
+    let mut expected = vec![0; 16 * 16];
+    serial(&config, &mut expected, &AtomicBool::new(false));
+
+    // An interrupted render: 5 rows are done, the next ones are computed again
+    {
+        let mut raw = PartialRaw::open(dump.to_str().unwrap(), &config).unwrap();
+        raw.write_rows(&expected[..(5 * 16)]).unwrap();
+    }
+    assert!(read_raw(dump.to_str().unwrap()).is_err());
+
+    run_resume(&config).unwrap();
+    assert_eq!(read_raw(dump.to_str().unwrap()).unwrap().data, expected);
+
+    let image = std::fs::read_to_string(resume_dir.join("render.ppm")).unwrap();
+    assert!(image.contains("# method: rayon_join\n"));
+
+    // Complete dumps are only written as image again, other configurations are rejected
+    run_resume(&config).unwrap();
+    assert!(run_resume(&MandelConfig { max_iter: 100, ..config.clone() }).is_err());
+}
+
+#[test]
+fn refine_from_equals_a_full_render() {
+    let refine_dir = std::env::temp_dir().join("mandel_refine_from_test");
+    std::fs::create_dir_all(&refine_dir).unwrap();
+
+    let previous_file = refine_dir.join("previous.mraw");
+    let previous_config = small_config();
+    let mut previous_image = vec![0; 16 * 16];
+    serial(&previous_config, &mut previous_image, &AtomicBool::new(false));
+    write_raw(previous_file.to_str().unwrap(), &previous_config, &previous_image).unwrap();
+
+    // The image size comes from the dump, not from the configuration
+    let config = MandelConfig {
+        img_size: 2048,
+        max_iter: 1024,
+        refine_from: Some(previous_file.to_str().unwrap().to_string()),
+        select_methods: vec!["rayon_join".to_string()],
+        ..small_config()
+    };
+    run_refine_from(&config).unwrap();
+
+    let mut expected = vec![0; 16 * 16];
+    serial(&MandelConfig { max_iter: 1024, ..small_config() }, &mut expected, &AtomicBool::new(false));
+
+    let refined = read_raw(refine_dir.join("previous_refined.mraw").to_str().unwrap()).unwrap();
+    assert_eq!((refined.width, refined.height, refined.max_iter), (16, 16, 1024));
+    assert_eq!(refined.data, expected);
+    assert!(refined.data != previous_image);
+
+    let image = std::fs::read_to_string(refine_dir.join("previous_refined.ppm")).unwrap();
+    assert!(image.contains("# previous_max_iter: 64\n"));
+
+    // A lower max_iter can't be refined from the dump
+    assert!(run_refine_from(&MandelConfig { max_iter: 32, ..config }).is_err());
+}
@@ -0,0 +1,309 @@
+// Runs the mandel binary end-to-end and checks its output files and its exit codes (see mandel_util::error)
+
+extern crate mandel;
+extern crate mandel_util;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use mandel::BREAK_METHOD_VAR;
+use mandel_util::error::{EXIT_CONFIG, EXIT_IO, EXIT_SUCCESS, EXIT_VERIFICATION};
+use mandel_util::ppm::read_ppm;
+
+// An empty directory for each test, the benchmark results are written to plot/ in the current directory
+fn work_dir(name: &str) -> PathBuf {
+    let work_dir = std::env::temp_dir().join(format!("mandel_cli_test_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&work_dir);
+    fs::create_dir_all(&work_dir).unwrap();
+    work_dir
+}
+
+// A small image and a single run with a fixed run id, so the file names are known
+fn mandel(work_dir: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_mandel"));
+    command.current_dir(work_dir)
+        .env_remove(BREAK_METHOD_VAR)
+        .args(["--img_size=64", "--num_of_runs=1", "--run_id=cli"]);
+    command
+}
+
+fn run_mandel(name: &str, args: &[&str]) -> Output {
+    mandel(&work_dir(name)).args(args).output().unwrap()
+}
+
+// The errors are printed to stdout
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn ppm_files(work_dir: &Path) -> Vec<String> {
+    fs::read_dir(work_dir).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".ppm"))
+        .collect()
+}
+
+#[test]
+fn serial_run_writes_results_and_image() {
+    let work_dir = work_dir("serial");
+    let output = mandel(&work_dir).args(["--max_iter=64", "--method=serial"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_SUCCESS), "{}", stdout(&output));
+
+    // num_threads, mean_ms, min_ms, max_ms of the run
+    let results = fs::read_to_string(work_dir.join("plot").join("serial.csv")).unwrap();
+    let fields: Vec<&str> = results.lines().last().unwrap().split(',').collect();
+    for field in &fields[..4] {
+        assert!(field.parse::<f64>().is_ok(), "{}", results);
+    }
+    assert_eq!(fields[7], "cli");
+
+    assert_eq!(ppm_files(&work_dir), vec!["serial_cli.ppm"]);
+    let image = read_ppm(work_dir.join("serial_cli.ppm").to_str().unwrap()).unwrap();
+    assert_eq!((image.width, image.height), (64, 64));
+    assert_eq!(image.data.len(), 64 * 64 * 3);
+}
+
+#[test]
+fn no_ppm_suppresses_the_image() {
+    let work_dir = work_dir("no_ppm");
+    let output = mandel(&work_dir).args(["--max_iter=64", "--method=serial", "--no_ppm"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_SUCCESS), "{}", stdout(&output));
+
+    assert!(work_dir.join("plot").join("serial.csv").exists());
+    assert!(ppm_files(&work_dir).is_empty());
+}
+
+#[test]
+fn cost_map_next_to_the_image() {
+    let work_dir = work_dir("cost_map");
+    let output = mandel(&work_dir).args(["--max_iter=64", "--method=serial", "--cost_map=cost.ppm", "--stats"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_SUCCESS), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Cost map written (serial): cost.ppm"), "{}", stdout(&output));
+    assert!(stdout(&output).contains("iterations: "), "{}", stdout(&output));
+
+    let mut files = ppm_files(&work_dir);
+    files.sort();
+    assert_eq!(files, vec!["cost.ppm", "serial_cli.ppm"]);
+
+    // Gray pixels, the inside of the set is white
+    let cost = read_ppm(work_dir.join("cost.ppm").to_str().unwrap()).unwrap();
+    assert_eq!((cost.width, cost.height), (64, 64));
+    assert!(cost.data.chunks(3).all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2]));
+    assert!(cost.data.contains(&255));
+}
+
+#[test]
+fn auto_levels_are_stored_in_the_image() {
+    let work_dir = work_dir("auto_levels");
+    let output = mandel(&work_dir).args(["--max_iter=64", "--method=serial", "--auto_levels"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_SUCCESS), "{}", stdout(&output));
+
+    let printed = stdout(&output);
+    let levels = printed.lines().find(|line| line.starts_with("Auto levels (serial): ")).expect(&printed)
+        .trim_start_matches("Auto levels (serial): ").to_string();
+    let ppm = fs::read_to_string(work_dir.join("serial_cli.ppm")).unwrap();
+    assert!(ppm.contains(&format!("# levels: {}", levels)), "{}", levels);
+
+    // The stored levels render the same image
+    let fixed_dir = self::work_dir("fixed_levels");
+    let output = mandel(&fixed_dir).args(["--max_iter=64", "--method=serial", &format!("--levels={}", levels)]).output().unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_SUCCESS), "{}", stdout(&output));
+    assert_eq!(read_ppm(work_dir.join("serial_cli.ppm").to_str().unwrap()).unwrap().data,
+        read_ppm(fixed_dir.join("serial_cli.ppm").to_str().unwrap()).unwrap().data);
+}
+
+#[test]
+fn tiled_layout_writes_the_same_image() {
+    let rows_dir = work_dir("layout_rows");
+    let tiles_dir = work_dir("layout_tiles");
+
+    // 2 x 2 tiles, mandel() has a single one
+    for (work_dir, layout) in [(&rows_dir, "--layout=rows"), (&tiles_dir, "--layout=tiles64")] {
+        let output = Command::new(env!("CARGO_BIN_EXE_mandel")).current_dir(work_dir).env_remove(BREAK_METHOD_VAR)
+            .args(["--img_size=128", "--num_of_runs=1", "--run_id=cli", "--max_iter=64", "--method=rayon_join", layout])
+            .output().unwrap();
+        assert_eq!(output.status.code(), Some(EXIT_SUCCESS), "{}", stdout(&output));
+    }
+
+    assert_eq!(fs::read(rows_dir.join("rayon_join_cli.ppm")).unwrap(), fs::read(tiles_dir.join("rayon_join_cli.ppm")).unwrap());
+
+    let results = fs::read_to_string(tiles_dir.join("plot").join("rayon_join.csv")).unwrap();
+    assert!(results.lines().last().unwrap().ends_with(",tiles64,native,1"), "{}", results);
+}
+
+#[test]
+fn pixel_size_writes_the_same_image() {
+    let corners_dir = work_dir("pixel_size_corners");
+    let pixel_size_dir = work_dir("pixel_size_step");
+
+    // 64 pixels of 3/64 from -2.0, -1.5 is the default region -2.0 .. 1.0, -1.5 .. 1.5
+    let output = mandel(&corners_dir).args(["--max_iter=64", "--method=serial"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_SUCCESS), "{}", stdout(&output));
+    let output = mandel(&pixel_size_dir).args(["--max_iter=64", "--method=serial", "--re1=-2.0", "--img1=-1.5", "--pixel_size=0.046875"])
+        .output().unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_SUCCESS), "{}", stdout(&output));
+    assert!(stdout(&output).contains("re1: -2.00, re2: 1.00, img1: -1.50, img2: 1.50"), "{}", stdout(&output));
+
+    assert_eq!(fs::read(corners_dir.join("serial_cli.ppm")).unwrap(), fs::read(pixel_size_dir.join("serial_cli.ppm")).unwrap());
+}
+
+#[test]
+fn tiles_have_their_own_file_names() {
+    let work_dir = work_dir("tiles");
+
+    for tile in &["0,0,2", "0,1,2"] {
+        let output = mandel(&work_dir).args(["--max_iter=64", "--method=serial", "--inclusive_edges", &format!("--tile={}", tile)])
+            .output().unwrap();
+        assert_eq!(output.status.code(), Some(EXIT_SUCCESS), "{}", stdout(&output));
+    }
+
+    let mut files = ppm_files(&work_dir);
+    files.sort();
+    assert_eq!(files, vec!["serial_cli_tile_0_0.ppm", "serial_cli_tile_0_1.ppm"]);
+}
+
+#[test]
+fn traced_schedule_replays() {
+    let work_dir = work_dir("schedule");
+
+    let output = mandel(&work_dir).args(["--max_iter=64", "--select_methods=kirk_crossbeam", "--num_threads=3", "--no_ppm",
+        "--trace_schedule=trace.txt"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_SUCCESS), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Schedule trace written (kirk_crossbeam): trace_kirk_crossbeam.txt"), "{}", stdout(&output));
+
+    let output = mandel(&work_dir).arg("--replay_schedule=trace_kirk_crossbeam.txt").output().unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_SUCCESS), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Replay of kirk_crossbeam matches serial"), "{}", stdout(&output));
+    assert_eq!(ppm_files(&work_dir), vec!["replay_kirk_crossbeam_cli.ppm"]);
+
+    // Not a trace at all
+    let output = mandel(&work_dir).arg("--replay_schedule=replay_kirk_crossbeam_cli.ppm").output().unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_IO), "{}", stdout(&output));
+}
+
+#[test]
+fn watch_renders_again_after_a_change() {
+    let work_dir = work_dir("watch");
+    fs::write(work_dir.join("view.conf"), "max_iter=64\n").unwrap();
+    let image = work_dir.join("serial_cli.ppm");
+
+    let mut child = mandel(&work_dir)
+        .args(["--config_file=view.conf", "--watch", "--watch_debounce_ms=50", "--method=serial"])
+        .stdout(Stdio::null())
+        .spawn().unwrap();
+
+    // Polls until the image exists and is different from the previous one
+    let wait_for_image = |previous: Option<&Vec<u8>>| {
+        for _ in 0..300 {
+            if let Ok(content) = fs::read(&image) {
+                if previous != Some(&content) {
+                    return Some(content);
+                }
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        None
+    };
+
+    let first = wait_for_image(None);
+    // Gives the watcher time to start after the first render
+    thread::sleep(Duration::from_millis(500));
+    fs::write(work_dir.join("view.conf"), "max_iter=16\nre1=-1.5\n").unwrap();
+    let second = first.as_ref().and_then(|first| wait_for_image(Some(first)));
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(first.is_some(), "no image after the start");
+    assert!(second.is_some(), "no new image after the change");
+}
+
+#[test]
+fn invalid_options() {
+    let output = run_mandel("invalid_value", &["--max_iter=0"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+    assert!(stdout(&output).contains("Error: invalid configuration: max_iter must be greater than 0"), "{}", stdout(&output));
+
+    let output = run_mandel("invalid_coloring", &["--coloring=rainbow"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+
+    let output = run_mandel("invalid_layout", &["--layout=columns"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+
+    let output = run_mandel("tiled_rows_access", &["--layout=tiles64", "--normalize_access=rows"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+
+    let output = run_mandel("adaptive_ci", &["--adaptive_runs", "--ci=0"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+
+    let output = run_mandel("trace_and_replay", &["--trace_schedule=trace.txt", "--replay_schedule=trace.txt"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+
+    let output = run_mandel("tile_outside", &["--tile=2,0,2"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+
+    let output = run_mandel("negative_pixel_size", &["--pixel_size=-0.01"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+
+    let output = run_mandel("pixel_size_and_re2", &["--pixel_size=0.01", "--re2=1.0"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+
+    let output = run_mandel("mesh_step_without_obj", &["--mesh_step=4"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+
+    let output = run_mandel("refine_and_resume", &["--refine=0,0,1,1", "--refine_iter=10", "--base=base.mraw", "--resume=render.mraw"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+
+    let output = run_mandel("resume_and_refine_from", &["--resume=render.mraw", "--refine_from=base.mraw"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+
+    let output = run_mandel("watch_without_file", &["--watch"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+
+    let output = run_mandel("unknown_method", &["--max_iter=64", "--method=no_such_method"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+    assert!(stdout(&output).contains("unknown method: 'no_such_method'"), "{}", stdout(&output));
+
+    let output = run_mandel("method_twice", &["--method=serial", "--select_methods=serial"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+
+    // Rejected by clap
+    let output = run_mandel("unknown_option", &["--no_such_option"]);
+    assert_eq!(output.status.code(), Some(EXIT_CONFIG));
+}
+
+#[test]
+fn compare_catches_a_broken_method() {
+    let args = ["--max_iter=64", "--select_methods=serial,rayon_join", "--compare_parallel_outputs", "--no_ppm"];
+
+    let output = mandel(&work_dir("compare")).args(args).output().unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_SUCCESS), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Output of rayon_join matches serial"));
+
+    let output = mandel(&work_dir("compare_broken")).args(args).env(BREAK_METHOD_VAR, "rayon_join").output().unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_VERIFICATION), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Output of rayon_join differs from serial in 1 pixels"), "{}", stdout(&output));
+    // The times are still valid, the efficiency is printed before the exit
+    assert!(stdout(&output).contains("Parallel efficiency T(1) / (n * T(n)):"), "{}", stdout(&output));
+}
+
+#[test]
+fn unwritable_output_directory() {
+    // The directory plot/ can't be created over a file (permissions wouldn't stop root)
+    let work_dir = work_dir("unwritable");
+    fs::write(work_dir.join("plot"), "not a directory").unwrap();
+
+    let output = mandel(&work_dir).args(["--max_iter=64", "--method=serial", "--no_ppm"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_IO));
+    assert!(stdout(&output).contains("Error: I/O error: 'plot/serial.csv'"), "{}", stdout(&output));
+}
+
+#[test]
+fn help_lists_the_exit_codes() {
+    let output = Command::new(env!("CARGO_BIN_EXE_mandel")).arg("--help").output().unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_SUCCESS));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("EXIT CODES:"));
+}
@@ -0,0 +1,59 @@
+extern crate mandel_method;
+extern crate mandel_util;
+
+use std::sync::atomic::AtomicBool;
+
+use mandel_method::registry::MandelFunc;
+use mandel_method::{rayon_par_iter, rust_scoped_pool, scoped_thread_pool_, serial};
+use mandel_util::MandelConfig;
+
+fn tiny_config() -> MandelConfig {
+    let default = MandelConfig::default();
+
+    MandelConfig {
+        img_size: 16,
+        max_iter: 64,
+        ..default
+    }
+}
+
+fn render(config: &MandelConfig, method: MandelFunc) -> Vec<u32> {
+    let mut image = vec![0; config.num_of_pixels()];
+    method(config, &mut image, &AtomicBool::new(false));
+    image
+}
+
+fn assert_methods_match_serial(config: &MandelConfig) {
+    let expected = render(config, serial);
+
+    // The image must contain both inside and outside pixels, otherwise wrong coordinates would go unnoticed
+    assert!(expected.contains(&config.max_iter));
+    assert!(expected.iter().any(|&value| value < config.max_iter));
+
+    let methods: [(&str, MandelFunc); 3] = [
+        ("scoped_thread_pool_", scoped_thread_pool_),
+        ("rayon_par_iter", rayon_par_iter),
+        ("rust_scoped_pool", rust_scoped_pool),
+    ];
+
+    for &(name, method) in &methods {
+        let image = render(config, method);
+        let differing = expected.iter().zip(&image).filter(|&(a, b)| a != b).count();
+        assert_eq!(differing, 0, "method '{}' differs from serial in {} pixels", name, differing);
+    }
+}
+
+#[test]
+fn tiny_image_matches_serial() {
+    assert_methods_match_serial(&tiny_config());
+}
+
+#[test]
+fn tiny_image_matches_serial_with_more_threads_than_rows() {
+    let config = MandelConfig {
+        num_threads: 32,
+        ..tiny_config()
+    };
+
+    assert_methods_match_serial(&config);
+}